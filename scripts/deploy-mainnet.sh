@@ -2,9 +2,21 @@
 
 echo "🚀 Starting mainnet deployment..."
 
+# Principals to make SuperAdmin on install/upgrade, e.g. BOOTSTRAP_ADMINS="aaaaa-aa bbbbb-bb"
+BACKEND_ARGUMENT="(null)"
+if [ ! -z "$BOOTSTRAP_ADMINS" ]; then
+    ADMIN_PRINCIPALS=""
+    for principal in $BOOTSTRAP_ADMINS; do
+        ADMIN_PRINCIPALS="$ADMIN_PRINCIPALS principal \"$principal\";"
+    done
+    BACKEND_ARGUMENT="(opt record { admins = vec {$ADMIN_PRINCIPALS } })"
+    echo "👤 Bootstrapping admins: $BOOTSTRAP_ADMINS"
+fi
+
 # Deploy to mainnet
 echo "📦 Deploying canisters to mainnet..."
-dfx deploy --network ic
+dfx deploy --network ic backend --argument "$BACKEND_ARGUMENT"
+dfx deploy --network ic frontend
 
 # Get the mainnet canister ID
 CANISTER_ID=$(dfx canister --network ic id backend)
@@ -27,6 +27,9 @@ use crate::models::github::Issue;
 use crate::models::openchat_user::OpenChatUser;
 use crate::models::discord_user::DiscordUser;
 use crate::models::slack_user::SlackUser;
+pub use crate::models::platform_identity::PlatformIdentity;
+use crate::services::platform_identity_service::save_identity;
+use crate::services::schema_migration_service::{latest_schema_version, migrate_schema, set_schema_version};
 use crate::models::task::Task;
 use crate::models::{
     api_message::{ApiMessage, ApiMetadata}, chat::ChatMessage, stable_principal::StablePrincipal, user::User,
@@ -36,12 +39,16 @@ use crate::models::startup::{Startup, StartupStatus, StartupCohort, StartupActiv
 use crate::services::account_service::ConnectionStatus;
 use crate::services::account_service::{UserActivity, UserIdentifier,  UserIdentifier as AccountUserIdentifier};
 use crate::storage::memory::{
-    API_MESSAGES, CHAT_HISTORY, CONNECTED_ACCOUNTS, DASHBOARD_TOKENS, OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS, TASKS, USERS, WAITLIST, GITHUB_ISSUES,
+    API_MESSAGES, CHAT_HISTORY, CONNECTED_ACCOUNTS, DASHBOARD_TOKENS, TASKS, USERS, WAITLIST, GITHUB_ISSUES,
     STARTUPS, STARTUP_STATUSES, STARTUP_COHORTS, STARTUP_ACTIVITIES, ACCELERATORS, STARTUP_INVITES, ADMINS, USER_SUBSCRIPTIONS, USER_DAILY_USAGE,
-    PAYMENT_RECORDS, INVOICES, USER_ANALYTICS,
+    PAYMENT_RECORDS, INVOICES, USER_ANALYTICS, SUBSCRIPTION_BILLING, BILLING_SETTINGS, REFUNDS, PLANS, COUPONS,
+    COUPON_REDEMPTIONS, SEAT_SPONSORSHIPS, SEAT_ASSIGNMENTS, WEBHOOK_EVENTS, INVOICE_SEQUENCES, INVOICE_SETTINGS,
+    BOT_PRINCIPALS,
+    AUDIT_LOG,
+    AUDIT_ARCHIVE,
+    MERGED_RECORDS, QUOTA_POLICIES, QUOTA_BUCKETS,
 };
-use candid::Principal;
-use ic_cdk::storage::{stable_restore, stable_save};
+use candid::{CandidType, Deserialize, Principal};
 use crate::services::token_service::TokenValidationResult;
 use crate::services::accelerator_service::{AcceleratorSignUp, TeamMemberInviteWithId, UpdateTeamMemberRole, RemoveTeamMember, AcceleratorUpdateWithId, AcceleratorUpdate};
 use crate::models::accelerator::{Accelerator, TeamMember};
@@ -52,142 +59,96 @@ pub use crate::models::usage_service::{UsageStats, UserTier, UserSubscription};
 pub use crate::services::admin::{UserActivityReport, PaymentStats};
 pub use crate::services::settings_service::{update_display_name, get_display_name, get_user_profile, update_email};
 pub use crate::models::analytics::{AnalyticsSummary, UserAnalytics, AnalyticsChartData};
-use crate::models::admin::PlaygroundStats;
-use crate::migrations::{CurrentStableState, migrate_from_bytes};
+use crate::models::admin::{PlaygroundStats, Admin, AdminRole};
+use crate::services::admin::bootstrap_admins;
+use crate::migrations::{CurrentStableState, read_legacy_snapshot};
 use crate::services::payment_service::{InitializePaymentRequest, InitializePaymentResponse};
+use crate::services::subscription_service::{start_billing_timer, RenewalRunSummary};
+use crate::services::reconciliation_service::{start_reconciliation_timer, ReconciliationRunSummary};
+use crate::services::plan_service::seed_default_plans;
+use crate::models::subscription_billing::{SubscriptionBilling, BillingSettings};
 
 // Use the stable state from migrations module
 type StableState = CurrentStableState;
 
 // Payment API endpoints and types
-pub use crate::models::payment::{PaymentRecord, Invoice, TransactionDetails};
-pub use crate::payments::PaystackConfig;
-
-#[ic_cdk::pre_upgrade]
-fn pre_upgrade() {
-    let users = USERS.with(|users| users.borrow().iter().collect::<Vec<_>>());
-    let waitlist = WAITLIST.with(|w| w.borrow().iter().collect::<Vec<_>>());
-    let chat_history = CHAT_HISTORY.with(|h| h.borrow().iter().collect::<Vec<_>>());
-    let api_messages = API_MESSAGES.with(|m| m.borrow().iter().collect::<Vec<_>>());
-    let connected_accounts = CONNECTED_ACCOUNTS.with(|ca| ca.borrow().iter().collect::<Vec<_>>());
-    let tasks = TASKS.with(|t| t.borrow().iter().collect::<Vec<_>>());
-    let github_issues = GITHUB_ISSUES.with(|i| i.borrow().iter().collect::<Vec<_>>());
-    let openchat_users = OPENCHAT_USERS.with(|u| u.borrow().iter().collect::<Vec<_>>());
-    let slack_users = SLACK_USERS.with(|u| u.borrow().iter().collect::<Vec<_>>());
-    let discord_users = DISCORD_USERS.with(|u| u.borrow().iter().collect::<Vec<_>>());
-    let dashboard_tokens = DASHBOARD_TOKENS.with(|t| t.borrow().iter().collect::<Vec<_>>());
-    let accelerators = ACCELERATORS.with(|a| a.borrow().iter().collect::<Vec<_>>());
-    let startup_invites = STARTUP_INVITES.with(|i| i.borrow().iter().collect::<Vec<_>>());
-    let startups = STARTUPS.with(|s| s.borrow().iter().collect::<Vec<_>>());
-    let startup_statuses = STARTUP_STATUSES.with(|s| s.borrow().iter().collect::<Vec<_>>());
-    let startup_cohorts = STARTUP_COHORTS.with(|c| c.borrow().iter().collect::<Vec<_>>());
-    let startup_activities = STARTUP_ACTIVITIES.with(|a| a.borrow().iter().collect::<Vec<_>>());
-    let admins = ADMINS.with(|a| a.borrow().iter().collect::<Vec<_>>());
-    let user_subscriptions = USER_SUBSCRIPTIONS.with(|s| s.borrow().iter().collect::<Vec<_>>());
-    let user_daily_usage = USER_DAILY_USAGE.with(|u| u.borrow().iter().collect::<Vec<_>>());
-    let payment_records = PAYMENT_RECORDS.with(|p| p.borrow().iter().collect::<Vec<_>>());
-    let invoices = INVOICES.with(|i| i.borrow().iter().collect::<Vec<_>>());
-    let user_analytics = USER_ANALYTICS.with(|a| a.borrow().iter().collect::<Vec<_>>());
-
-    let state = StableState {
-        users,
-        waitlist,
-        chat_history,
-        api_messages,
-        connected_accounts,
-        tasks,
-        github_issues,
-        openchat_users,
-        slack_users,
-        discord_users,
-        dashboard_tokens,
-        accelerators,
-        startup_invites,
-        startups,
-        startup_statuses,
-        startup_cohorts,
-        startup_activities,
-        admins,
-        user_subscriptions,
-        user_daily_usage,
-        payment_records,
-        invoices,
-        user_analytics,
-    };
-
-    // Serialize with bincode for better performance and compatibility
-    let serialized = bincode::serialize(&state).expect("Failed to serialize state");
-    stable_save((serialized,)).expect("Failed to save stable state");
+pub use crate::models::payment::{PaymentRecord, Invoice, TransactionDetails, PaymentGateway, RefundRecord, InvoiceKind, Currency, InvoiceSettings};
+pub use crate::services::invoice_service::{InvoiceFormat, RenderedInvoice};
+pub use crate::models::plan::{Plan, Price};
+pub use crate::models::coupon::{Coupon, CouponRedemption};
+pub use crate::services::coupon_service::CouponQuote;
+pub use crate::models::sponsorship::{SeatSponsorship, SeatAssignment};
+pub use crate::services::sponsorship_service::{SeatPurchaseRequest, SeatUsage};
+pub use crate::models::webhook_event::{WebhookEvent, WebhookEventStatus, WebhookSource};
+pub use crate::payments::{PaystackConfig, StripeConfig, DarajaConfig, ProviderRoute};
+pub use crate::models::bot_principal::{BotPrincipal, BotPermission};
+pub use crate::services::bot_service::BotRegistration;
+pub use crate::models::platform::Platform;
+pub use crate::models::audit_log::{AuditEntry, AuditAction};
+pub use crate::services::audit_service::{AuditLogFilter, AuditLogPage};
+pub use crate::models::quota::{QuotaPolicy, BurstLimit, BotCost, QuotaLimit, QuotaRemaining, QuotaRejection};
+pub use crate::services::schema_migration_service::{MigrationStepReport, SchemaMigrationPlan};
+pub use crate::models::record::QuarantinedRecord;
+pub use crate::services::record_repair_service::{RecordScanReport, UndecodableRecord};
+pub use crate::services::snapshot_service::{SnapshotCursor, SnapshotEntry, SnapshotPage};
+use crate::services::audit_service::{digest, record_audit, start_audit_retention_timer};
+
+/// Canister arguments for install and upgrade
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct InitArgs {
+    /// Principals made SuperAdmin, e.g. to grant the first admin or restore lost access
+    pub admins: Vec<Principal>,
+}
+
+/// Applies `InitArgs::admins` and records each grant against the installer
+fn bootstrap_admins_from_args(args: Option<InitArgs>) {
+    let admins = args.unwrap_or_default().admins;
+    let now = ic_cdk::api::time();
+    bootstrap_admins(&admins, now);
+    for admin in &admins {
+        record_audit(ic_cdk::caller(), AuditAction::AdminBootstrapped, admin.to_text(), None, Some(digest(&AdminRole::SuperAdmin)), now);
+    }
+}
+
+#[ic_cdk::init]
+fn init(args: Option<InitArgs>) {
+    bootstrap_admins_from_args(args);
+    seed_default_plans(ic_cdk::api::time());
+    // A new canister starts at the current schema; there is nothing to migrate
+    set_schema_version(latest_schema_version());
+    start_billing_timer();
+    start_reconciliation_timer();
+    start_audit_retention_timer();
 }
 
 #[ic_cdk::post_upgrade]
-fn post_upgrade() {
-    let state = match stable_restore::<(Vec<u8>,)>() {
-        Ok((serialized,)) => {
-            match migrate_from_bytes(&serialized) {
-                Ok(state) => state,
-                Err(e) => {
-                    ic_cdk::println!("Failed to migrate state: {}", e);
-                    // Fallback to empty state with all required fields
-                    // Fallback to empty state
-                    StableState {
-                        users: vec![],
-                        waitlist: vec![],
-                        chat_history: vec![],
-                        api_messages: vec![],
-                        connected_accounts: vec![],
-                        tasks: vec![],
-                        github_issues: vec![],
-                        openchat_users: vec![],
-                        slack_users: vec![],
-                        discord_users: vec![],
-                        dashboard_tokens: vec![],
-                        accelerators: vec![],
-                        startup_invites: vec![],
-                        startups: vec![],
-                        startup_statuses: vec![],
-                        startup_cohorts: vec![],
-                        startup_activities: vec![],
-                        admins: vec![],
-                        user_subscriptions: vec![],
-                        user_daily_usage: vec![],
-                        payment_records: vec![],
-                        invoices: vec![],
-                        user_analytics: vec![],
-                    }
-                }
-            }
-        }
-        Err(_) => {
-            // No existing state, start fresh
-            StableState {
-                users: vec![],
-                waitlist: vec![],
-                chat_history: vec![],
-                api_messages: vec![],
-                connected_accounts: vec![],
-                tasks: vec![],
-                github_issues: vec![],
-                openchat_users: vec![],
-                slack_users: vec![],
-                discord_users: vec![],
-                dashboard_tokens: vec![],
-                accelerators: vec![],
-                startup_invites: vec![],
-                startups: vec![],
-                startup_statuses: vec![],
-                startup_cohorts: vec![],
-                startup_activities: vec![],
-                admins: vec![],
-                user_subscriptions: vec![],
-                user_daily_usage: vec![],
-                payment_records: vec![],
-                invoices: vec![],
-                user_analytics: vec![],
+fn post_upgrade(args: Option<InitArgs>) {
+    // The stable maps survive upgrades as they are. Only a canister coming
+    // from a release that still saved a snapshot has anything to restore.
+    if let Some(state) = read_legacy_snapshot() {
+        restore_legacy_snapshot(state);
+    }
+    // Trapping rolls the upgrade back rather than running on half-migrated maps
+    match migrate_schema() {
+        Ok(reports) => {
+            for report in reports {
+                ic_cdk::println!("Schema migration {}: {} ({} records)", report.version, report.description, report.records);
             }
         }
-    };
+        Err(e) => ic_cdk::trap(&format!("Schema migration failed: {}", e)),
+    }
+
+    // After any restore, so bootstrapped roles win over restored ones
+    bootstrap_admins_from_args(args);
 
+    // Timers are not persisted across upgrades
+    start_billing_timer();
+    start_reconciliation_timer();
+    start_audit_retention_timer();
+}
+
+/// Copies a legacy upgrade snapshot into the stable maps
+fn restore_legacy_snapshot(state: StableState) {
     // Restore users
     USERS.with(|u| {
         let mut u = u.borrow_mut();
@@ -244,29 +205,10 @@ fn post_upgrade() {
         }
     });
 
-    // Restore OpenChat users
-    OPENCHAT_USERS.with(|u| {
-        let mut u = u.borrow_mut();
-        for (k, v) in state.openchat_users {
-            u.insert(k, v);
-        }
-    });
-
-    // Restore Slack users
-    SLACK_USERS.with(|u| {
-        let mut u = u.borrow_mut();
-        for (k, v) in state.slack_users {
-            u.insert(k, v);
-        }
-    });
-
-    // Restore Discord users
-    DISCORD_USERS.with(|u| {
-        let mut u = u.borrow_mut();
-        for (k, v) in state.discord_users {
-            u.insert(k, v);
-        }
-    });
+    // Restore platform identities; save_identity rebuilds the by-principal index
+    for (_, identity) in state.platform_identities {
+        save_identity(identity);
+    }
 
     // Restore dashboard tokens
     DASHBOARD_TOKENS.with(|t| {
@@ -371,6 +313,137 @@ fn post_upgrade() {
             a.insert(k, v);
         }
     });
+
+    // Restore subscription billing
+    SUBSCRIPTION_BILLING.with(|b| {
+        let mut b = b.borrow_mut();
+        for (k, v) in state.subscription_billing {
+            b.insert(k, v);
+        }
+    });
+
+    // Restore billing settings
+    BILLING_SETTINGS.with(|s| {
+        let mut s = s.borrow_mut();
+        for (k, v) in state.billing_settings {
+            s.insert(k, v);
+        }
+    });
+
+    // Restore refunds
+    REFUNDS.with(|r| {
+        let mut r = r.borrow_mut();
+        for (k, v) in state.refunds {
+            r.insert(k, v);
+        }
+    });
+
+    // Restore price catalog
+    PLANS.with(|p| {
+        let mut p = p.borrow_mut();
+        for (k, v) in state.plans {
+            p.insert(k, v);
+        }
+    });
+
+    // Restore coupons
+    COUPONS.with(|c| {
+        let mut c = c.borrow_mut();
+        for (k, v) in state.coupons {
+            c.insert(k, v);
+        }
+    });
+
+    // Restore coupon redemptions
+    COUPON_REDEMPTIONS.with(|r| {
+        let mut r = r.borrow_mut();
+        for (k, v) in state.coupon_redemptions {
+            r.insert(k, v);
+        }
+    });
+
+    // Restore sponsored seats
+    SEAT_SPONSORSHIPS.with(|s| {
+        let mut s = s.borrow_mut();
+        for (k, v) in state.seat_sponsorships {
+            s.insert(k, v);
+        }
+    });
+
+    SEAT_ASSIGNMENTS.with(|a| {
+        let mut a = a.borrow_mut();
+        for (k, v) in state.seat_assignments {
+            a.insert(k, v);
+        }
+    });
+
+    // Restore webhook event log
+    WEBHOOK_EVENTS.with(|w| {
+        let mut w = w.borrow_mut();
+        for (k, v) in state.webhook_events {
+            w.insert(k, v);
+        }
+    });
+
+    // Restore invoice numbering and seller settings
+    INVOICE_SEQUENCES.with(|s| {
+        let mut s = s.borrow_mut();
+        for (k, v) in state.invoice_sequences {
+            s.insert(k, v);
+        }
+    });
+    INVOICE_SETTINGS.with(|s| {
+        let mut s = s.borrow_mut();
+        for (k, v) in state.invoice_settings {
+            s.insert(k, v);
+        }
+    });
+
+    // Restore bot principal registry
+    BOT_PRINCIPALS.with(|b| {
+        let mut b = b.borrow_mut();
+        for (k, v) in state.bot_principals {
+            b.insert(k, v);
+        }
+    });
+
+    // Restore audit log and its archive
+    AUDIT_LOG.with(|l| {
+        let mut l = l.borrow_mut();
+        for (k, v) in state.audit_log {
+            l.insert(k, v);
+        }
+    });
+
+    AUDIT_ARCHIVE.with(|a| {
+        let mut a = a.borrow_mut();
+        for (k, v) in state.audit_archive {
+            a.insert(k, v);
+        }
+    });
+
+    // Restore merged records
+    MERGED_RECORDS.with(|r| {
+        let mut r = r.borrow_mut();
+        for (k, v) in state.merged_records {
+            r.insert(k, v);
+        }
+    });
+
+    // Restore quota policies and burst buckets
+    QUOTA_POLICIES.with(|p| {
+        let mut p = p.borrow_mut();
+        for (k, v) in state.quota_policies {
+            p.insert(k, v);
+        }
+    });
+
+    QUOTA_BUCKETS.with(|b| {
+        let mut b = b.borrow_mut();
+        for (k, v) in state.quota_buckets {
+            b.insert(k, v);
+        }
+    });
 }
 
 
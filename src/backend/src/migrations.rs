@@ -1,14 +1,30 @@
 // Migration Logic for Canister State
-// This module contains all migration implementations between different versions of StableState
+// This module contains all migration implementations between different versions of StableState.
+// Released layouts V1-V4 saved a snapshot of every map in pre_upgrade; later
+// releases keep the stable maps in place and only read such a snapshot once,
+// when upgrading from one of those releases. Schema changes since then are steps
+// in services/schema_migration_service.rs, applied to the maps in place.
 
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use crate::models::{
     user::User, chat::ChatMessage, api_message::ApiMessage, connected_accounts::ConnectedAccounts,
     task::Task, github::Issue, openchat_user::OpenChatUser, slack_user::SlackUser,
-    discord_user::DiscordUser, dashboard_token::DashboardToken, accelerator::Accelerator,
-    startup_invite::StartupInvite, startup::Startup, admin::Admin, usage_service::UserSubscription,
-    payment::{PaymentRecord, Invoice}, analytics::AnalyticsDataPoint
+    discord_user::DiscordUser, dashboard_token::DashboardToken, accelerator::Accelerator, platform::Platform,
+    platform_identity::PlatformIdentity, merged_record::MergedRecord, quota::{QuotaPolicy, TokenBucket},
+    startup_invite::StartupInvite, startup::Startup, admin::{Admin, AdminRole}, usage_service::UserSubscription,
+    payment::{PaymentRecord, Invoice, InvoiceKind, InvoiceLineItem, InvoiceParty, InvoiceSettings, RefundRecord, Currency, PaymentStatus, PaymentChannel, PaymentMetadata, PaymentGateway},
+    analytics::AnalyticsDataPoint,
+    subscription_billing::{SubscriptionBilling, BillingSettings},
+    plan::Plan,
+    coupon::{Coupon, CouponRedemption},
+    sponsorship::{SeatSponsorship, SeatAssignment},
+    webhook_event::WebhookEvent,
+    bot_principal::BotPrincipal,
+    audit_log::AuditEntry,
 };
+use crate::services::plan_service::default_plans;
+use crate::storage::memory::IdentityKey;
 use crate::models::{
     stable_principal::StablePrincipal, stable_string::StableString, waitlist::WaitlistEntry
 };
@@ -27,14 +43,14 @@ pub struct StableStateV1 {
     pub openchat_users: Vec<(StableString, OpenChatUser)>,
     pub slack_users: Vec<(StableString, SlackUser)>,
     pub discord_users: Vec<(StableString, DiscordUser)>,
-    pub dashboard_tokens: Vec<(StableString, DashboardToken)>,
+    pub dashboard_tokens: Vec<(StableString, DashboardTokenV1)>,
     pub accelerators: Vec<(StablePrincipal, Accelerator)>,
     pub startup_invites: Vec<(StableString, StartupInvite)>,
     pub startups: Vec<(StableString, Startup)>,
     pub startup_statuses: Vec<(StableString, StartupStatus)>,
     pub startup_cohorts: Vec<(StableString, StartupCohort)>,
     pub startup_activities: Vec<((StableString, u64), StartupActivity)>,
-    pub admins: Vec<(StablePrincipal, Admin)>,
+    pub admins: Vec<(StablePrincipal, AdminV1)>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -49,19 +65,163 @@ pub struct StableStateV2 {
     pub openchat_users: Vec<(StableString, OpenChatUser)>,
     pub slack_users: Vec<(StableString, SlackUser)>,
     pub discord_users: Vec<(StableString, DiscordUser)>,
-    pub dashboard_tokens: Vec<(StableString, DashboardToken)>,
+    pub dashboard_tokens: Vec<(StableString, DashboardTokenV1)>,
     pub accelerators: Vec<(StablePrincipal, Accelerator)>,
     pub startup_invites: Vec<(StableString, StartupInvite)>,
     pub startups: Vec<(StableString, Startup)>,
     pub startup_statuses: Vec<(StableString, StartupStatus)>,
     pub startup_cohorts: Vec<(StableString, StartupCohort)>,
     pub startup_activities: Vec<((StableString, u64), StartupActivity)>,
-    pub admins: Vec<(StablePrincipal, Admin)>,
+    pub admins: Vec<(StablePrincipal, AdminV1)>,
     // NEW FIELDS IN V2:
     pub user_subscriptions: Vec<(StableString, UserSubscription)>,
     pub user_daily_usage: Vec<((StableString, u64), u32)>,
 }
 
+// PaymentRecord as stored up to V4, before multi-gateway support and coupons
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PaymentRecordV1 {
+    pub id: String,
+    pub user_id: String,
+    pub paystack_reference: String,
+    pub amount: u64,
+    pub currency: Currency,
+    pub email: String,
+    pub status: PaymentStatus,
+    pub payment_channel: Option<PaymentChannel>,
+    pub authorization_url: Option<String>,
+    pub access_code: Option<String>,
+    pub tier: String,
+    pub billing_period: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub paid_at: Option<u64>,
+    pub paystack_transaction_id: Option<u64>,
+    pub metadata: PaymentMetadata,
+}
+
+impl From<PaymentRecordV1> for PaymentRecord {
+    fn from(v1: PaymentRecordV1) -> Self {
+        PaymentRecord {
+            id: v1.id,
+            user_id: v1.user_id,
+            paystack_reference: v1.paystack_reference,
+            amount: v1.amount,
+            currency: v1.currency,
+            email: v1.email,
+            status: v1.status,
+            payment_channel: v1.payment_channel,
+            authorization_url: v1.authorization_url,
+            access_code: v1.access_code,
+            tier: v1.tier,
+            billing_period: v1.billing_period,
+            created_at: v1.created_at,
+            updated_at: v1.updated_at,
+            paid_at: v1.paid_at,
+            paystack_transaction_id: v1.paystack_transaction_id,
+            metadata: v1.metadata,
+            // All older payments went through Paystack and had no coupon
+            provider: PaymentGateway::Paystack,
+            provider_transaction_id: None,
+            discount: None,
+        }
+    }
+}
+
+// Invoice as stored up to V4, before credit notes, coupons, line items and tax
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InvoiceV1 {
+    pub id: String,
+    pub user_id: String,
+    pub payment_id: String,
+    pub amount: u64,
+    pub currency: Currency,
+    pub billing_period_start: u64,
+    pub billing_period_end: u64,
+    pub invoice_number: String,
+    pub paid: bool,
+    pub created_at: u64,
+}
+
+impl From<InvoiceV1> for Invoice {
+    fn from(v1: InvoiceV1) -> Self {
+        // Older invoices are undiscounted charges; they keep their number and
+        // get a single untaxed line
+        Invoice {
+            id: v1.id,
+            user_id: v1.user_id.clone(),
+            payment_id: v1.payment_id,
+            amount: v1.amount,
+            currency: v1.currency,
+            billing_period_start: v1.billing_period_start,
+            billing_period_end: v1.billing_period_end,
+            invoice_number: v1.invoice_number,
+            paid: v1.paid,
+            created_at: v1.created_at,
+            kind: InvoiceKind::Charge,
+            original_invoice_id: None,
+            discount: None,
+            line_items: vec![InvoiceLineItem {
+                description: "Infoundr subscription".to_string(),
+                quantity: 1,
+                unit_amount: v1.amount,
+                amount: v1.amount,
+            }],
+            net_amount: v1.amount,
+            tax_lines: vec![],
+            seller: InvoiceParty {
+                name: InvoiceSettings::default().seller_name,
+                ..Default::default()
+            },
+            buyer: InvoiceParty {
+                name: v1.user_id,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// Admin as stored up to V4, before roles
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AdminV1 {
+    pub principal_id: String,
+    pub created_at: u64,
+}
+
+
+impl From<AdminV1> for Admin {
+    fn from(v1: AdminV1) -> Self {
+        // Nobody was authorized from this list, so nobody gains access through it
+        Admin {
+            principal_id: v1.principal_id,
+            created_at: v1.created_at,
+            role: AdminRole::ReadOnly,
+        }
+    }
+}
+
+// Dashboard token as stored up to V4, before the platform was recorded
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DashboardTokenV1 {
+    pub token: Vec<u8>,
+    pub openchat_id: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+impl From<DashboardTokenV1> for DashboardToken {
+    fn from(v1: DashboardTokenV1) -> Self {
+        // The platform was never stored, so classify the ID the way it used to be
+        DashboardToken {
+            token: v1.token,
+            platform: Platform::guess_from_id(&v1.openchat_id),
+            platform_id: v1.openchat_id,
+            created_at: v1.created_at,
+            expires_at: v1.expires_at,
+        }
+    }
+}
+
 // V3: Added payment system (payment_records, invoices)
 #[derive(Serialize, Deserialize)]
 pub struct StableStateV3 {
@@ -75,23 +235,23 @@ pub struct StableStateV3 {
     pub openchat_users: Vec<(StableString, OpenChatUser)>,
     pub slack_users: Vec<(StableString, SlackUser)>,
     pub discord_users: Vec<(StableString, DiscordUser)>,
-    pub dashboard_tokens: Vec<(StableString, DashboardToken)>,
+    pub dashboard_tokens: Vec<(StableString, DashboardTokenV1)>,
     pub accelerators: Vec<(StablePrincipal, Accelerator)>,
     pub startup_invites: Vec<(StableString, StartupInvite)>,
     pub startups: Vec<(StableString, Startup)>,
     pub startup_statuses: Vec<(StableString, StartupStatus)>,
     pub startup_cohorts: Vec<(StableString, StartupCohort)>,
     pub startup_activities: Vec<((StableString, u64), StartupActivity)>,
-    pub admins: Vec<(StablePrincipal, Admin)>,
+    pub admins: Vec<(StablePrincipal, AdminV1)>,
     pub user_subscriptions: Vec<(StableString, UserSubscription)>,
     pub user_daily_usage: Vec<((StableString, u64), u32)>,
     // NEW FIELDS IN V3:
-    pub payment_records: Vec<(StableString, PaymentRecord)>,
-    pub invoices: Vec<(StableString, Invoice)>,
+    pub payment_records: Vec<(StableString, PaymentRecordV1)>,
+    pub invoices: Vec<(StableString, InvoiceV1)>,
 }
 
 // V4: Added analytics system
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct StableStateV4 {
     pub users: Vec<(StablePrincipal, User)>,
     pub waitlist: Vec<(StableString, WaitlistEntry)>,
@@ -103,6 +263,35 @@ pub struct StableStateV4 {
     pub openchat_users: Vec<(StableString, OpenChatUser)>,
     pub slack_users: Vec<(StableString, SlackUser)>,
     pub discord_users: Vec<(StableString, DiscordUser)>,
+    pub dashboard_tokens: Vec<(StableString, DashboardTokenV1)>,
+    pub accelerators: Vec<(StablePrincipal, Accelerator)>,
+    pub startup_invites: Vec<(StableString, StartupInvite)>,
+    pub startups: Vec<(StableString, Startup)>,
+    pub startup_statuses: Vec<(StableString, StartupStatus)>,
+    pub startup_cohorts: Vec<(StableString, StartupCohort)>,
+    pub startup_activities: Vec<((StableString, u64), StartupActivity)>,
+    pub admins: Vec<(StablePrincipal, AdminV1)>,
+    pub user_subscriptions: Vec<(StableString, UserSubscription)>,
+    pub user_daily_usage: Vec<((StableString, u64), u32)>,
+    pub payment_records: Vec<(StableString, PaymentRecordV1)>,
+    pub invoices: Vec<(StableString, InvoiceV1)>,
+    // NEW FIELDS IN V4:
+    pub user_analytics: Vec<((StableString, u64), AnalyticsDataPoint)>,
+}
+// Current stable state: the layout a legacy snapshot is restored from. The
+// layouts between V4 and this one never shipped, so V4 migrates straight to it.
+#[derive(Serialize, Deserialize, Default)]
+pub struct CurrentStableState {
+    pub users: Vec<(StablePrincipal, User)>,
+    pub waitlist: Vec<(StableString, WaitlistEntry)>,
+    pub chat_history: Vec<((StablePrincipal, u64), ChatMessage)>,
+    pub api_messages: Vec<((StableString, u64), ApiMessage)>,
+    pub connected_accounts: Vec<(StablePrincipal, ConnectedAccounts)>,
+    pub tasks: Vec<((StablePrincipal, StableString), Task)>,
+    pub github_issues: Vec<((StablePrincipal, StableString), Issue)>,
+    pub openchat_users: Vec<(StableString, OpenChatUser)>, // RETIRED: moved to platform_identities, always empty
+    pub slack_users: Vec<(StableString, SlackUser)>, // RETIRED: moved to platform_identities, always empty
+    pub discord_users: Vec<(StableString, DiscordUser)>, // RETIRED: moved to platform_identities, always empty
     pub dashboard_tokens: Vec<(StableString, DashboardToken)>,
     pub accelerators: Vec<(StablePrincipal, Accelerator)>,
     pub startup_invites: Vec<(StableString, StartupInvite)>,
@@ -115,12 +304,30 @@ pub struct StableStateV4 {
     pub user_daily_usage: Vec<((StableString, u64), u32)>,
     pub payment_records: Vec<(StableString, PaymentRecord)>,
     pub invoices: Vec<(StableString, Invoice)>,
-    // NEW FIELDS IN V4:
     pub user_analytics: Vec<((StableString, u64), AnalyticsDataPoint)>,
+    pub subscription_billing: Vec<(StableString, SubscriptionBilling)>,
+    pub billing_settings: Vec<(u8, BillingSettings)>,
+    pub refunds: Vec<(StableString, RefundRecord)>,
+    pub plans: Vec<(StableString, Plan)>,
+    pub coupons: Vec<(StableString, Coupon)>,
+    pub coupon_redemptions: Vec<(StableString, CouponRedemption)>,
+    pub seat_sponsorships: Vec<(StableString, SeatSponsorship)>,
+    pub seat_assignments: Vec<(StableString, SeatAssignment)>,
+    pub webhook_events: Vec<(StableString, WebhookEvent)>,
+    pub invoice_sequences: Vec<(StableString, u64)>,
+    pub invoice_settings: Vec<(u8, InvoiceSettings)>,
+    pub bot_principals: Vec<(StablePrincipal, BotPrincipal)>,
+    pub audit_log: Vec<(u64, AuditEntry)>,
+    pub audit_archive: Vec<(u64, AuditEntry)>,
+    pub platform_identities: Vec<(IdentityKey, PlatformIdentity)>,
+    pub merged_records: Vec<((IdentityKey, u64), MergedRecord)>,
+    pub quota_policies: Vec<(StableString, QuotaPolicy)>,
+    pub quota_buckets: Vec<(StableString, TokenBucket)>,
 }
 
-// Current stable state (latest version)
-pub type CurrentStableState = StableStateV4;
+/// Schema version of the last snapshot layout. Later schema changes are steps
+/// in the schema migration registry instead.
+pub const LAST_SNAPSHOT_VERSION: u32 = 19;
 
 // Migration implementations
 impl From<StableStateV1> for StableStateV2 {
@@ -212,6 +419,78 @@ impl From<StableStateV3> for StableStateV4 {
     }
 }
 
+impl From<StableStateV4> for CurrentStableState {
+    fn from(v4: StableStateV4) -> Self {
+        // Each platform's users become identities keyed by (platform, id)
+        let platform_identities = v4.openchat_users.into_iter().map(|(_, user)| PlatformIdentity::from(user))
+            .chain(v4.slack_users.into_iter().map(|(_, user)| PlatformIdentity::from(user)))
+            .chain(v4.discord_users.into_iter().map(|(_, user)| PlatformIdentity::from(user)))
+            .map(|identity| ((identity.platform, StableString::from(identity.external_id.clone())), identity))
+            .collect();
+
+        CurrentStableState {
+            users: v4.users,
+            waitlist: v4.waitlist,
+            chat_history: v4.chat_history,
+            api_messages: v4.api_messages,
+            connected_accounts: v4.connected_accounts,
+            tasks: v4.tasks,
+            github_issues: v4.github_issues,
+            openchat_users: vec![],
+            slack_users: vec![],
+            discord_users: vec![],
+            dashboard_tokens: v4
+                .dashboard_tokens
+                .into_iter()
+                .map(|(key, token)| (key, token.into()))
+                .collect(),
+            accelerators: v4.accelerators,
+            startup_invites: v4.startup_invites,
+            startups: v4.startups,
+            startup_statuses: v4.startup_statuses,
+            startup_cohorts: v4.startup_cohorts,
+            startup_activities: v4.startup_activities,
+            admins: v4.admins.into_iter().map(|(key, admin)| (key, admin.into())).collect(),
+            user_subscriptions: v4.user_subscriptions,
+            user_daily_usage: v4.user_daily_usage,
+            payment_records: v4
+                .payment_records
+                .into_iter()
+                .map(|(key, record)| (key, record.into()))
+                .collect(),
+            // Existing invoices keep their numbers; numbering restarts per year from here
+            invoices: v4
+                .invoices
+                .into_iter()
+                .map(|(key, invoice)| (key, invoice.into()))
+                .collect(),
+            user_analytics: v4.user_analytics,
+            subscription_billing: vec![],
+            billing_settings: vec![],
+            refunds: vec![],
+            // Prices used to be compiled in, start from the default catalog
+            plans: default_plans(0)
+                .into_iter()
+                .map(|plan| (StableString::from(plan.id.clone()), plan))
+                .collect(),
+            coupons: vec![],
+            coupon_redemptions: vec![],
+            seat_sponsorships: vec![],
+            seat_assignments: vec![],
+            webhook_events: vec![],
+            invoice_sequences: vec![],
+            invoice_settings: vec![],
+            bot_principals: vec![],
+            audit_log: vec![],
+            audit_archive: vec![],
+            platform_identities,
+            merged_records: vec![],
+            quota_policies: vec![],
+            quota_buckets: vec![],
+        }
+    }
+}
+
 // Chain migration from V1 to V3
 impl From<StableStateV1> for StableStateV3 {
     fn from(v1: StableStateV1) -> Self {
@@ -233,28 +512,70 @@ pub fn migrate_from_v1_to_v3(v1_data: StableStateV1) -> StableStateV3 {
     v1_data.into()
 }
 
-pub fn migrate_from_bytes(bytes: &[u8]) -> Result<CurrentStableState, String> {
-    // Try to deserialize as current version first
-    match bincode::deserialize::<CurrentStableState>(bytes) {
-        Ok(state) => Ok(state),
-        Err(_) => {
-            // Try V2 and migrate to V3
-            match bincode::deserialize::<StableStateV2>(bytes) {
-                Ok(v2_state) => {
-                    println!("Migrating from V2 to V3");
-                    Ok(migrate_from_v2_to_v3(v2_state).into())
-                }
-                Err(_) => {
-                    // Try V1 and migrate to V3
-                    match bincode::deserialize::<StableStateV1>(bytes) {
-                        Ok(v1_state) => {
-                            println!("Migrating from V1 to V3");
-                            Ok(migrate_from_v1_to_v3(v1_state).into())
-                        }
-                        Err(e) => Err(format!("Failed to deserialize state: {:?}", e))
-                    }
-                }
+type SnapshotMigration = fn(&[u8]) -> Option<CurrentStableState>;
+
+/// Snapshot layouts, newest first, with how each one reaches the current layout.
+/// Snapshots carry no version, so the first layout that decodes wins: bincode
+/// ignores trailing bytes, so an older layout could otherwise decode a prefix
+/// of a newer snapshot.
+const SNAPSHOT_LAYOUTS: &[(u32, SnapshotMigration)] = &[
+    (LAST_SNAPSHOT_VERSION, |bytes| decode::<CurrentStableState>(bytes)),
+    (4, |bytes| decode::<StableStateV4>(bytes).map(from_v4)),
+    (3, |bytes| decode::<StableStateV3>(bytes).map(from_v3)),
+    (2, |bytes| decode::<StableStateV2>(bytes).map(from_v2)),
+    (1, |bytes| decode::<StableStateV1>(bytes).map(from_v1)),
+];
+
+fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    bincode::deserialize(bytes).ok()
+}
+
+fn from_v4(state: StableStateV4) -> CurrentStableState { state.into() }
+fn from_v3(state: StableStateV3) -> CurrentStableState { from_v4(state.into()) }
+fn from_v2(state: StableStateV2) -> CurrentStableState { from_v3(migrate_from_v2_to_v3(state)) }
+fn from_v1(state: StableStateV1) -> CurrentStableState { from_v3(migrate_from_v1_to_v3(state)) }
+
+/// Decodes a snapshot in whichever layout it was saved and migrates it to the
+/// current one. Returns the layout version it was saved in.
+pub fn migrate_snapshot(bytes: &[u8]) -> Result<(u32, CurrentStableState), String> {
+    for (version, migrate) in SNAPSHOT_LAYOUTS {
+        if let Some(state) = migrate(bytes) {
+            if *version != LAST_SNAPSHOT_VERSION {
+                println!("Migrating from V{} to V{}", version, LAST_SNAPSHOT_VERSION);
             }
+            return Ok((*version, state));
         }
     }
+    Err(format!("Failed to deserialize state: no snapshot layout from V1 to V{} matches", LAST_SNAPSHOT_VERSION))
+}
+
+pub fn migrate_from_bytes(bytes: &[u8]) -> Result<CurrentStableState, String> {
+    migrate_snapshot(bytes).map(|(_, state)| state)
+}
+
+/// Magic the stable-structures memory manager writes at the start of stable memory
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+/// The snapshot a V1-V4 release saved in pre_upgrade, migrated to the current
+/// snapshot layout. It was written over the start of stable memory, so the
+/// memory manager's header is missing when there is one.
+///
+/// Traps when the snapshot can't be read: the upgrade then rolls back instead
+/// of the canister carrying on with empty maps.
+pub fn read_legacy_snapshot() -> Option<CurrentStableState> {
+    if ic_cdk::api::stable::stable_size() == 0 {
+        return None;
+    }
+    let mut magic = [0u8; 3];
+    ic_cdk::api::stable::stable_read(0, &mut magic);
+    if &magic == MEMORY_MANAGER_MAGIC {
+        return None;
+    }
+
+    let (bytes,) = ic_cdk::storage::stable_restore::<(Vec<u8>,)>()
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to read upgrade snapshot: {}", e)));
+    match migrate_from_bytes(&bytes) {
+        Ok(state) => Some(state),
+        Err(e) => ic_cdk::trap(&format!("Failed to migrate state: {}", e)),
+    }
 }
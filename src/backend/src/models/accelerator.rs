@@ -1,8 +1,7 @@
 use crate::models::stable_principal::StablePrincipal;
-use candid::{CandidType, Decode, Encode, Principal};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum Role {
@@ -63,17 +62,4 @@ pub enum ActivityType {
     Other(String),
 }
 
-impl Storable for Accelerator {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for Accelerator {
-    const MAX_SIZE: u32 = 8192; // Adjust as needed for blob data
-    const IS_FIXED_SIZE: bool = false;
-} 
\ No newline at end of file
+impl VersionedRecord for Accelerator {}
\ No newline at end of file
@@ -1,12 +1,57 @@
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Admin {
     pub principal_id: String,
     pub created_at: u64,
+    pub role: AdminRole,
+}
+
+/// What an admin may do, see `AdminRole::allows`
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AdminRole {
+    SuperAdmin,     // Everything, including admins, gateway credentials, the audit log and storage maintenance
+    Support,        // Users, accelerators and their usage; read-only billing
+    Billing,        // Payments, refunds, plans, coupons and billing settings
+    ReadOnly,       // Read access to users and billing
+}
+
+/// Permission declared by each admin endpoint
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    ViewAdmins,
+    ManageAdmins,
+    ViewUsers,
+    ManageUsers,
+    ViewBilling,
+    ManageBilling,
+    ManagePaymentConfig,
+    ViewAuditLog,
+    ViewStorage,
+    ManageStorage,
+}
+
+impl AdminRole {
+    pub fn allows(&self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            AdminRole::SuperAdmin => true,
+            AdminRole::Support => matches!(permission, ViewAdmins | ViewUsers | ManageUsers | ViewBilling),
+            AdminRole::Billing => matches!(permission, ViewAdmins | ViewBilling | ManageBilling),
+            AdminRole::ReadOnly => matches!(permission, ViewUsers | ViewBilling),
+        }
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => AdminRole::SuperAdmin,
+            1 => AdminRole::Support,
+            2 => AdminRole::Billing,
+            _ => AdminRole::ReadOnly,
+        }
+    }
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -16,26 +61,62 @@ pub struct PlaygroundStats {
     pub bot_usage: std::collections::HashMap<String, u32>,
 }
 
-impl Storable for Admin {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = vec![];
-        bytes.extend_from_slice(&self.principal_id.as_bytes());
-        bytes.extend_from_slice(&self.created_at.to_le_bytes());
-        Cow::Owned(bytes)
+/* ============================
+   Storable
+   ============================ */
+
+// Before the record envelope ADMINS had byte layouts of its own. Version 2:
+// ADMIN_FORMAT_V2, role, created_at (8 bytes LE), principal text. Records
+// written before roles existed are principal text followed by created_at;
+// their first byte is a letter or digit, never a version this type uses.
+// Schema migration step 23 rewrites both as enveloped records.
+const ADMIN_FORMAT_V2: u8 = 0x02;
+
+impl VersionedRecord for Admin {
+    const VERSION: u8 = 3;
+
+    fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String> {
+        match version {
+            ADMIN_FORMAT_V2 => Admin::from_v2_layout(payload),
+            _ => Err(format!("no upgrade from version {}", version)),
+        }
     }
+}
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let principal_id = String::from_utf8(bytes[..bytes.len() - 8].to_vec()).unwrap();
-        let created_at = u64::from_le_bytes(bytes[bytes.len() - 8..].try_into().unwrap());
-        Admin {
-            principal_id,
-            created_at,
+impl Admin {
+    /// Reads a record in either byte layout ADMINS used before the envelope
+    pub fn from_legacy_bytes(bytes: &[u8]) -> Result<Admin, String> {
+        if let Some((&ADMIN_FORMAT_V2, payload)) = bytes.split_first() {
+            return Admin::from_v2_layout(payload);
         }
+
+        // Admins were never authorized from this layout, so grant no more than read access
+        let Some(split) = bytes.len().checked_sub(8) else {
+            return Err(format!("Admin record of {} bytes is too short", bytes.len()));
+        };
+        Ok(Admin {
+            principal_id: principal_text(&bytes[..split])?,
+            created_at: le_u64(&bytes[split..])?,
+            role: AdminRole::ReadOnly,
+        })
     }
+
+    fn from_v2_layout(payload: &[u8]) -> Result<Admin, String> {
+        if payload.len() < 9 {
+            return Err(format!("Admin record of {} bytes is too short", payload.len() + 1));
+        }
+        Ok(Admin {
+            role: AdminRole::from_byte(payload[0]),
+            created_at: le_u64(&payload[1..9])?,
+            principal_id: principal_text(&payload[9..])?,
+        })
+    }
+}
+
+fn le_u64(bytes: &[u8]) -> Result<u64, String> {
+    bytes.try_into().map(u64::from_le_bytes).map_err(|_| "created_at is not 8 bytes".to_string())
 }
 
-// Add BoundedStorable implementation
-impl BoundedStorable for Admin {
-    const MAX_SIZE: u32 = 1024; // Adjust this value based on your needs
-    const IS_FIXED_SIZE: bool = false;
+fn principal_text(bytes: &[u8]) -> Result<String, String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| "principal is not UTF-8".to_string())
 }
@@ -1,7 +1,6 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
-use ic_stable_structures::storable::{Storable, BoundedStorable};
+use crate::models::record::VersionedRecord;
 
 /// Analytics data point for a specific day
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
@@ -53,18 +52,4 @@ pub struct AnalyticsSummary {
     pub chart_data: AnalyticsChartData,
 }
 
-impl Storable for AnalyticsDataPoint {
-    fn to_bytes(&self) -> Cow<'_, [u8]> {
-        let bytes = candid::encode_one(self).expect("Failed to encode AnalyticsDataPoint");
-        Cow::Owned(bytes)
-    }
-
-    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
-        candid::decode_one(&bytes).expect("Failed to decode AnalyticsDataPoint")
-    }
-}
-
-impl BoundedStorable for AnalyticsDataPoint {
-    const MAX_SIZE: u32 = 1024; // Adjust based on your needs
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for AnalyticsDataPoint {}
@@ -1,7 +1,6 @@
-use candid::{CandidType, Decode, Encode};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct ApiMessage {
@@ -24,17 +23,4 @@ pub struct ApiMetadata {
     pub direct_auth: Option<bool>,
 }
 
-impl Storable for ApiMessage {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for ApiMessage {
-    const MAX_SIZE: u32 = 4096; // Larger than ChatMessage since API responses can be longer
-    const IS_FIXED_SIZE: bool = false;
-} 
\ No newline at end of file
+impl VersionedRecord for ApiMessage {}
\ No newline at end of file
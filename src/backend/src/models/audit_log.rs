@@ -0,0 +1,70 @@
+// backend/src/models/audit_log.rs
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use crate::models::record::VersionedRecord;
+
+/// Longest target kept on an entry; longer ones are truncated
+pub const MAX_AUDIT_TARGET_LEN: usize = 256;
+
+/// Privileged or financial action recorded in the audit log
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum AuditAction {
+    // Admins and bots
+    AdminBootstrapped,
+    AdminAdded,
+    AdminRoleChanged,
+    AdminRemoved,
+    BotRegistered,
+    BotRotated,
+    BotRemoved,
+    // Accelerators and their teams
+    AcceleratorUpdated,
+    AcceleratorDeleted,
+    TeamMemberInvited,
+    TeamMemberRoleChanged,
+    TeamMemberRemoved,
+    StartupInviteRevoked,
+    // Startups
+    StartupCreated,
+    StartupUpdated,
+    StartupDeleted,
+    StartupStatusChanged,
+    StartupCohortChanged,
+    // Billing
+    UserTierChanged,
+    PaymentConfigChanged,
+    ProviderRouteChanged,
+    RefundIssued,
+    WebhookReprocessed,
+    PlanChanged,
+    CouponChanged,
+    BillingSettingsChanged,
+    InvoiceSettingsChanged,
+    // Account settings
+    ProfileUpdated,
+    // Usage
+    QuotaPolicyChanged,
+    // Storage
+    RecordsQuarantined,
+    SnapshotImported,
+}
+
+/// One append-only audit record. Digests are SHA-256 hex of the candid-encoded
+/// value before and after the change, see `audit_service::digest`.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub actor: Principal,
+    pub action: AuditAction,
+    pub target: String,
+    pub before_digest: Option<String>,   // None when the target was created
+    pub after_digest: Option<String>,    // None when the target was removed
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for AuditEntry {}
@@ -0,0 +1,40 @@
+// backend/src/models/bot_principal.rs
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use crate::models::platform::Platform;
+use crate::models::record::VersionedRecord;
+
+/// What a registered bot may do through the bot-facing API
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum BotPermission {
+    StoreMessages,      // store_api_message
+    ReadMessages,       // get_api_message_history and friends
+    ReadUsage,          // Usage stats, tier and subscription lookups
+    TrackUsage,         // api_increment_user_requests
+    UpgradeTier,        // api_upgrade_user_tier
+    IssueTokens,        // generate_dashboard_token, api_is_platform_id_linked
+}
+
+/// A trusted bot-backend principal, keyed by that principal
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct BotPrincipal {
+    pub principal: Principal,
+    pub platform: Platform,
+    pub name: String,
+    pub permissions: Vec<BotPermission>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl BotPrincipal {
+    pub fn allows(&self, permission: BotPermission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for BotPrincipal {}
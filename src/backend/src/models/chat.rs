@@ -1,8 +1,7 @@
-use candid::{CandidType, Decode, Encode, Principal};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
 use std::fmt;
+use crate::models::record::VersionedRecord;
 // use crate::models::stable_principal::StablePrincipal;
 // use ic_cdk::export::candid::Principal;
 
@@ -40,17 +39,4 @@ impl fmt::Display for BotType {
     }
 }
 
-impl Storable for ChatMessage {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for ChatMessage {
-    const MAX_SIZE: u32 = 2048;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for ChatMessage {}
@@ -1,7 +1,6 @@
-use candid::{CandidType, Decode, Deserialize, Encode};
+use candid::{CandidType, Deserialize};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct AsanaAccount {
@@ -23,18 +22,4 @@ pub struct ConnectedAccounts {
 }
 
 // Implement Storable for ConnectedAccounts
-impl Storable for ConnectedAccounts {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-// Implement BoundedStorable for ConnectedAccounts
-impl BoundedStorable for ConnectedAccounts {
-    const MAX_SIZE: u32 = 100000; // Adjust this value based on your needs
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for ConnectedAccounts {}
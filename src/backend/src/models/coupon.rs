@@ -0,0 +1,63 @@
+// backend/src/models/coupon.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::payment::Currency;
+use crate::models::record::VersionedRecord;
+
+/// How much a coupon takes off the catalog price
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum CouponDiscount {
+    Percentage(u32),                            // 1-100 percent
+    FixedAmount { amount: u64, currency: Currency }, // Smallest unit, only for payments in `currency`
+}
+
+/// Promo code redeemable at checkout
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Coupon {
+    pub code: String,                           // Stored upper case
+    pub description: String,
+    pub discount: CouponDiscount,
+    pub max_redemptions: Option<u32>,           // Across all users, None = unlimited
+    pub per_user_limit: Option<u32>,            // None = unlimited
+    pub expires_at: Option<u64>,
+    pub allowed_tiers: Vec<String>,             // Plan ids, empty = any plan
+    pub allowed_billing_periods: Vec<String>,   // Empty = any period
+    pub recurring: bool,                        // Discount also applies to automatic renewals
+    pub is_active: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Lifecycle of a coupon use
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum RedemptionStatus {
+    Reserved,   // Payment started, counts towards the limits
+    Redeemed,   // Payment succeeded
+    Released,   // Payment failed or was abandoned, no longer counts
+}
+
+/// One use of a coupon, keyed by payment reference
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct CouponRedemption {
+    pub code: String,
+    pub user_id: String,
+    pub payment_reference: String,
+    pub tier: String,
+    pub billing_period: String,
+    pub currency: Currency,
+    pub original_amount: u64,
+    pub discount_amount: u64,
+    pub status: RedemptionStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for Coupon {}
+
+impl VersionedRecord for CouponRedemption {}
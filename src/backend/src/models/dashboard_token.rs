@@ -1,27 +1,23 @@
-use candid::{CandidType, Decode, Deserialize, Encode};
+use candid::{CandidType, Deserialize};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::platform::Platform;
+use crate::migrations::DashboardTokenV1;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct DashboardToken {
     pub token: Vec<u8>,
-    pub openchat_id: String,
+    pub platform: Platform,
+    pub platform_id: String,
     pub created_at: u64,
     pub expires_at: u64,
 }
 
-impl Storable for DashboardToken {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+impl VersionedRecord for DashboardToken {
+    fn upgrade(_version: u8, payload: &[u8]) -> Result<Self, String> {
+        // Tokens issued before the platform was stored decode as DashboardTokenV1
+        candid::decode_one(payload)
+            .or_else(|_| candid::decode_one::<DashboardTokenV1>(payload).map(Into::into))
+            .map_err(|e| e.to_string())
     }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for DashboardToken {
-    const MAX_SIZE: u32 = 2048;
-    const IS_FIXED_SIZE: bool = false;
 }
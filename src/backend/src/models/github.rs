@@ -1,7 +1,6 @@
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Issue {
@@ -19,17 +18,4 @@ pub enum IssueStatus {
     Closed,
 }
 
-impl Storable for Issue {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
-    }
-}
-
-impl BoundedStorable for Issue {
-    const MAX_SIZE: u32 = 2048;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for Issue {}
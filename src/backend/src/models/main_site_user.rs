@@ -1,9 +1,9 @@
-use candid::{CandidType, Deserialize, Encode, Decode};
+use candid::{CandidType, Deserialize};
 use serde::Serialize;
-use ic_stable_structures::storable::{Storable, BoundedStorable};
-use std::borrow::Cow;
 use crate::models::stable_principal::StablePrincipal;
+use crate::models::record::VersionedRecord;
 
+/// Record layout of the retired MAIN_SITE_USERS map, only read to migrate it
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct MainSiteUser {
     pub main_site_id: String,
@@ -12,17 +12,4 @@ pub struct MainSiteUser {
     pub last_interaction: u64,
 }
 
-impl Storable for MainSiteUser {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for MainSiteUser {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for MainSiteUser {}
@@ -0,0 +1,49 @@
+// backend/src/models/merged_record.rs
+
+use crate::models::analytics::AnalyticsDataPoint;
+use crate::models::api_message::ApiMessage;
+use crate::models::chat::ChatMessage;
+use crate::models::github::Issue;
+use crate::models::task::Task;
+use crate::models::usage_service::UserSubscription;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use crate::models::record::VersionedRecord;
+
+/// A record moved from a platform identity to the site principal it was
+/// linked to, with its key before and after, or one dropped because the
+/// principal already held that key. Unlinking uses these to put the records
+/// back under their original keys.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum MergedRecord {
+    ChatMessage { from_seq: u64, seq: u64 },
+    Task { id: String },
+    Issue { id: String },
+    ApiMessage { from_id: String, id: String, timestamp: u64 },
+    DailyUsage { day: u64, count: u32 },
+    Analytics { day: u64, data: AnalyticsDataPoint },
+    /// `moved` as stored under the principal, and the subscription it replaced
+    Subscription { moved: UserSubscription, replaced: Option<UserSubscription> },
+    DroppedChatMessage { from_seq: u64, message: ChatMessage },
+    DroppedTask { id: String, task: Task },
+    DroppedIssue { id: String, issue: Issue },
+    DroppedApiMessage { id: String, timestamp: u64, message: ApiMessage },
+}
+
+impl MergedRecord {
+    pub fn is_dropped(&self) -> bool {
+        matches!(
+            self,
+            MergedRecord::DroppedChatMessage { .. }
+                | MergedRecord::DroppedTask { .. }
+                | MergedRecord::DroppedIssue { .. }
+                | MergedRecord::DroppedApiMessage { .. }
+        )
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for MergedRecord {}
@@ -19,3 +19,15 @@ pub mod usage_service;
 pub mod payment;
 pub mod analytics;
 pub mod main_site_user;
+pub mod subscription_billing;
+pub mod plan;
+pub mod coupon;
+pub mod sponsorship;
+pub mod webhook_event;
+pub mod bot_principal;
+pub mod audit_log;
+pub mod platform;
+pub mod platform_identity;
+pub mod merged_record;
+pub mod quota;
+pub mod record;
@@ -1,7 +1,6 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 /// Payment channels supported by Paystack
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -63,6 +62,27 @@ impl Currency {
             Currency::USD => "USD",
         }
     }
+
+    /// Parse an ISO currency code, case-insensitive
+    pub fn from_code(code: &str) -> Option<Currency> {
+        match code.to_uppercase().as_str() {
+            "NGN" => Some(Currency::NGN),
+            "GHS" => Some(Currency::GHS),
+            "ZAR" => Some(Currency::ZAR),
+            "KES" => Some(Currency::KES),
+            "USD" => Some(Currency::USD),
+            _ => None,
+        }
+    }
+}
+
+/// Payment gateways the canister can route a payment through
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PaymentGateway {
+    #[default]
+    Paystack,   // Cards and mobile money across Africa
+    Stripe,     // Stripe Checkout, international cards
+    Daraja,     // Safaricom M-Pesa STK push
 }
 
 /// Main payment record stored in stable memory
@@ -85,6 +105,18 @@ pub struct PaymentRecord {
     pub paid_at: Option<u64>,                // When payment was completed
     pub paystack_transaction_id: Option<u64>, // Paystack's internal transaction ID
     pub metadata: PaymentMetadata,           // Additional metadata
+    pub provider: PaymentGateway,            // Gateway that processed the payment
+    pub provider_transaction_id: Option<String>, // Stripe payment intent / M-Pesa receipt number
+    pub discount: Option<AppliedDiscount>,   // Coupon applied at checkout
+}
+
+/// Coupon discount applied to a payment (and copied to its invoice)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AppliedDiscount {
+    pub coupon_code: String,
+    pub original_amount: u64,                // Catalog price before the discount
+    pub discount_amount: u64,                // Amount taken off the catalog price
+    pub recurring: bool,                     // Renewals keep the discounted price
 }
 
 /// Metadata for payments
@@ -130,37 +162,113 @@ pub struct Invoice {
     pub invoice_number: String,
     pub paid: bool,
     pub created_at: u64,
+    pub kind: InvoiceKind,
+    pub original_invoice_id: Option<String>, // Invoice a credit note corrects
+    pub discount: Option<AppliedDiscount>,   // Coupon applied to the payment
+    pub line_items: Vec<InvoiceLineItem>,
+    pub net_amount: u64,                     // `amount` without tax
+    pub tax_lines: Vec<InvoiceTaxLine>,      // Included in `amount`
+    pub seller: InvoiceParty,
+    pub buyer: InvoiceParty,
 }
 
-// Storable implementations for PaymentRecord
-impl Storable for PaymentRecord {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).expect("Failed to encode PaymentRecord"))
-    }
+/// One billed item. Amounts include tax, like the catalog prices.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InvoiceLineItem {
+    pub description: String,
+    pub quantity: u32,
+    pub unit_amount: u64,
+    pub amount: u64,                         // quantity * unit_amount
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(bytes.as_ref()).expect("Failed to decode PaymentRecord")
-    }
+/// Tax contained in an invoice total, e.g. "VAT" at 750 basis points (7.5%)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InvoiceTaxLine {
+    pub name: String,
+    pub rate_bps: u32,
+    pub amount: u64,
 }
 
-impl BoundedStorable for PaymentRecord {
-    const MAX_SIZE: u32 = 8 * 1024; // 8KB per payment record
-    const IS_FIXED_SIZE: bool = false;
+/// Seller or buyer as printed on an invoice
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InvoiceParty {
+    pub name: String,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub country: Option<String>,             // ISO 3166-1 alpha-2
+    pub tax_id: Option<String>,              // VAT / TIN / KRA PIN
 }
 
-// Storable implementations for Invoice
-impl Storable for Invoice {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).expect("Failed to encode Invoice"))
-    }
+/// Seller details printed on new invoices
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InvoiceSettings {
+    pub seller_name: String,
+    pub seller_email: Option<String>,
+    pub seller_address: Option<String>,
+    pub tax_registrations: Vec<TaxRegistration>,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(bytes.as_ref()).expect("Failed to decode Invoice")
+/// Seller tax number in one country
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaxRegistration {
+    pub country: String,                     // ISO 3166-1 alpha-2
+    pub tax_id: String,
+}
+
+impl Default for InvoiceSettings {
+    fn default() -> Self {
+        InvoiceSettings {
+            seller_name: "Infoundr".to_string(),
+            seller_email: Some("billing@infoundr.com".to_string()),
+            seller_address: None,
+            tax_registrations: vec![],
+        }
     }
 }
 
-impl BoundedStorable for Invoice {
-    const MAX_SIZE: u32 = 4 * 1024; // 4KB per invoice
-    const IS_FIXED_SIZE: bool = false;
+/// Regular invoice or credit note
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum InvoiceKind {
+    #[default]
+    Charge,         // Issued for a successful payment
+    CreditNote,     // Issued for a refund, `amount` is the credited amount
 }
 
+/// Refund status
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum RefundStatus {
+    #[default]
+    Pending,        // Accepted by the gateway, waiting for it to settle
+    Processed,      // Money returned, credit note issued
+    Failed,         // Gateway rejected or failed the refund
+}
+
+/// A full or partial refund of a payment
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RefundRecord {
+    pub id: String,                          // "RF-{payment reference}-{n}"
+    pub payment_reference: String,
+    pub user_id: String,
+    pub amount: u64,                         // Smallest currency unit
+    pub currency: Currency,
+    pub reason: String,
+    pub status: RefundStatus,
+    pub provider: PaymentGateway,
+    pub provider_refund_id: Option<String>,
+    pub credit_note_id: Option<String>,      // Set once processed
+    pub requested_by: String,                // Admin principal
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// Storable implementations for PaymentRecord
+impl VersionedRecord for PaymentRecord {}
+
+// Storable implementations for Invoice
+impl VersionedRecord for Invoice {}
+
+impl VersionedRecord for InvoiceSettings {}
+
+// Storable implementations for RefundRecord
+impl VersionedRecord for RefundRecord {}
@@ -0,0 +1,47 @@
+// backend/src/models/plan.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::payment::Currency;
+use crate::models::usage_service::UserTier;
+use crate::models::record::VersionedRecord;
+
+/// A subscription plan in the price catalog.
+///
+/// `id` is what clients send as `tier` in `InitializePaymentRequest` and what
+/// payment records store, e.g. "Pro".
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub tier: UserTier,         // Tier granted once a price of this plan is paid
+    pub prices: Vec<Price>,
+    pub is_active: bool,        // Inactive plans cannot be bought, existing subscribers keep renewing
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Price of a plan for one billing period in one currency
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Price {
+    pub billing_period: String, // "monthly" or "yearly"
+    pub currency: Currency,
+    pub amount: u64,            // Smallest currency unit (kobo, cents, pesewas)
+}
+
+impl Plan {
+    /// Price for a billing period and currency, if the plan has one
+    pub fn price(&self, billing_period: &str, currency: &Currency) -> Option<&Price> {
+        self.prices
+            .iter()
+            .find(|price| price.billing_period == billing_period && &price.currency == currency)
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for Plan {}
@@ -0,0 +1,146 @@
+// backend/src/models/platform.rs
+
+use candid::CandidType;
+use ic_stable_structures::storable::{Bound, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Platform a bot user comes from, and the one a registered bot serves. New
+/// platforms only need a variant here:
+/// identities for every platform live in the same PLATFORM_IDENTITIES map.
+/// `Default` is only there because stable-structures tuple keys require it.
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    #[default]
+    OpenChat,
+    Slack,
+    Discord,
+    MainSite,
+    Telegram,
+    WhatsApp,
+    Playground,
+    // Read back from a stored key whose byte this release doesn't know, e.g.
+    // one written by a newer release. Never parsed from a name or served by a bot.
+    Unknown,
+}
+
+impl Platform {
+    /// Every platform, in declaration (and therefore key) order; not `Unknown`
+    pub const ALL: [Platform; 7] = [
+        Platform::OpenChat,
+        Platform::Slack,
+        Platform::Discord,
+        Platform::MainSite,
+        Platform::Telegram,
+        Platform::WhatsApp,
+        Platform::Playground,
+    ];
+
+    /// Guess the platform from the shape of an ID. Only for records written
+    /// before the platform was stored: Slack IDs may also start with 'W'.
+    pub fn guess_from_id(platform_id: &str) -> Self {
+        if platform_id.starts_with('U') {
+            Platform::Slack
+        } else if platform_id.chars().all(|c| c.is_numeric()) {
+            Platform::Discord
+        } else {
+            Platform::OpenChat
+        }
+    }
+
+    /// The lowercase name bots send, e.g. "slack"
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::OpenChat => "openchat",
+            Platform::Slack => "slack",
+            Platform::Discord => "discord",
+            Platform::MainSite => "mainsite",
+            Platform::Telegram => "telegram",
+            Platform::WhatsApp => "whatsapp",
+            Platform::Playground => "playground",
+            Platform::Unknown => "unknown",
+        }
+    }
+
+    /// The ID an unlinked account's API messages, usage and analytics are
+    /// recorded under. Telegram and WhatsApp IDs are bare numbers that could
+    /// clash with Discord IDs, so they carry their platform name, e.g.
+    /// "telegram:123456789".
+    pub fn user_id(self, external_id: &str) -> String {
+        match self {
+            Platform::Telegram | Platform::WhatsApp => format!("{}:{}", self.name(), external_id),
+            _ => external_id.to_string(),
+        }
+    }
+
+    /// The external ID a `user_id` of this platform would stand for
+    pub fn external_id(self, user_id: &str) -> Option<&str> {
+        match self {
+            Platform::Telegram | Platform::WhatsApp => user_id.strip_prefix(self.name())?.strip_prefix(':'),
+            _ => Some(user_id),
+        }
+    }
+
+    /// First byte of the principal that stands in for an unlinked user. OpenChat
+    /// IDs are principals already and have no tag; `Unknown` has one of its own
+    /// so its users never share a principal with anyone else's.
+    pub fn principal_tag(self) -> Option<u8> {
+        match self {
+            Platform::OpenChat => None,
+            Platform::Slack => Some(5),
+            Platform::Discord => Some(6),
+            Platform::MainSite => Some(8),
+            Platform::Telegram => Some(9),
+            Platform::WhatsApp => Some(10),
+            Platform::Playground => Some(7),
+            Platform::Unknown => Some(u8::MAX),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Platform::OpenChat => "OpenChat",
+            Platform::Slack => "Slack",
+            Platform::Discord => "Discord",
+            Platform::MainSite => "Main site",
+            Platform::Telegram => "Telegram",
+            Platform::WhatsApp => "WhatsApp",
+            Platform::Playground => "Playground",
+            Platform::Unknown => "Unknown platform",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Parses the lowercase names bots send, e.g. "slack"
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .into_iter()
+            .find(|platform| platform.name() == name)
+            .ok_or_else(|| format!("Unsupported platform: {}", name))
+    }
+}
+
+// Stored as its position in `ALL`, so variants may only ever be appended.
+// Any other byte reads as `Unknown`, which sorts after every known platform.
+impl Storable for Platform {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            Platform::Unknown => Cow::Owned(vec![u8::MAX]),
+            platform => Cow::Owned(vec![*platform as u8]),
+        }
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        bytes.first().and_then(|byte| Platform::ALL.get(*byte as usize)).copied().unwrap_or(Platform::Unknown)
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 1, is_fixed_size: true };
+}
@@ -0,0 +1,127 @@
+// backend/src/models/platform_identity.rs
+
+use crate::models::discord_user::DiscordUser;
+use crate::models::main_site_user::MainSiteUser;
+use crate::models::openchat_user::OpenChatUser;
+use crate::models::platform::Platform;
+use crate::models::slack_user::SlackUser;
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use crate::models::record::VersionedRecord;
+
+/// A user as a bot platform knows them, keyed by (platform, external_id).
+/// `site_principal` is set once they link their site account.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlatformIdentity {
+    pub platform: Platform,
+    pub external_id: String,
+    pub site_principal: Option<Principal>,
+    pub display_name: Option<String>,
+    pub workspace_id: Option<String>,    // Slack team or Discord guild
+    pub first_interaction: u64,
+    pub last_interaction: u64,
+}
+
+impl PlatformIdentity {
+    pub fn new(platform: Platform, external_id: String, now: u64) -> Self {
+        Self {
+            platform,
+            external_id,
+            site_principal: None,
+            display_name: None,
+            workspace_id: None,
+            first_interaction: now,
+            last_interaction: now,
+        }
+    }
+}
+
+/* ============================
+   Per-platform records
+   ============================ */
+
+// The per-platform tables this replaced, and the candid views built from it.
+// Slack and Discord never tracked interaction times; they migrate as 0.
+
+impl From<OpenChatUser> for PlatformIdentity {
+    fn from(user: OpenChatUser) -> Self {
+        Self {
+            site_principal: user.site_principal.map(|p| p.get()),
+            first_interaction: user.first_interaction,
+            last_interaction: user.last_interaction,
+            ..Self::new(Platform::OpenChat, user.openchat_id, 0)
+        }
+    }
+}
+
+impl From<SlackUser> for PlatformIdentity {
+    fn from(user: SlackUser) -> Self {
+        Self {
+            site_principal: user.site_principal.map(|p| p.get()),
+            display_name: user.display_name,
+            workspace_id: user.team_id,
+            ..Self::new(Platform::Slack, user.slack_id, 0)
+        }
+    }
+}
+
+impl From<DiscordUser> for PlatformIdentity {
+    fn from(user: DiscordUser) -> Self {
+        Self {
+            site_principal: user.site_principal.map(|p| p.get()),
+            display_name: user.username,
+            workspace_id: user.guild_id,
+            ..Self::new(Platform::Discord, user.discord_id, 0)
+        }
+    }
+}
+
+impl From<MainSiteUser> for PlatformIdentity {
+    fn from(user: MainSiteUser) -> Self {
+        Self {
+            site_principal: user.site_principal.map(|p| p.get()),
+            first_interaction: user.first_interaction,
+            last_interaction: user.last_interaction,
+            ..Self::new(Platform::MainSite, user.main_site_id, 0)
+        }
+    }
+}
+
+impl From<PlatformIdentity> for OpenChatUser {
+    fn from(identity: PlatformIdentity) -> Self {
+        Self {
+            openchat_id: identity.external_id,
+            site_principal: identity.site_principal.map(Into::into),
+            first_interaction: identity.first_interaction,
+            last_interaction: identity.last_interaction,
+        }
+    }
+}
+
+impl From<PlatformIdentity> for SlackUser {
+    fn from(identity: PlatformIdentity) -> Self {
+        Self {
+            slack_id: identity.external_id,
+            site_principal: identity.site_principal.map(Into::into),
+            display_name: identity.display_name,
+            team_id: identity.workspace_id,
+        }
+    }
+}
+
+impl From<PlatformIdentity> for DiscordUser {
+    fn from(identity: PlatformIdentity) -> Self {
+        Self {
+            discord_id: identity.external_id,
+            site_principal: identity.site_principal.map(Into::into),
+            username: identity.display_name,
+            guild_id: identity.workspace_id,
+        }
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for PlatformIdentity {}
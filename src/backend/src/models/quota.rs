@@ -0,0 +1,111 @@
+// backend/src/models/quota.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::models::usage_service::UserTier;
+use crate::models::record::VersionedRecord;
+
+/// Request limits for one tier, editable by admins at runtime.
+///
+/// Amounts are in request units: a request costs 1 unless its bot is listed
+/// in `bot_costs`. `None` means no limit of that kind.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct QuotaPolicy {
+    pub tier: UserTier,
+    pub burst: Option<BurstLimit>,
+    pub daily_limit: Option<u32>,   // Per UTC day
+    pub monthly_limit: Option<u32>, // Per UTC calendar month
+    pub bot_costs: Vec<BotCost>,
+    pub updated_at: u64,
+}
+
+/// Token bucket: holds up to `capacity` units and refills `refill_per_minute`
+/// units a minute, so short bursts pass but sustained load is throttled
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct BurstLimit {
+    pub capacity: u32,
+    pub refill_per_minute: u32,
+}
+
+/// What one request through a bot costs, e.g. 5 for a GitHub agent
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct BotCost {
+    pub bot_name: String,
+    pub cost: u32,
+}
+
+impl QuotaPolicy {
+    /// The limits that applied before policies were configurable: 20 requests
+    /// a day on Free, nothing on Pro
+    pub fn default_for(tier: UserTier) -> Self {
+        let daily_limit = match tier {
+            UserTier::Free => Some(20),
+            UserTier::Pro => None,
+        };
+        QuotaPolicy {
+            tier,
+            burst: None,
+            daily_limit,
+            monthly_limit: None,
+            bot_costs: Vec::new(),
+            updated_at: 0,
+        }
+    }
+
+    /// Request units a request through `bot_name` uses
+    pub fn cost(&self, bot_name: Option<&str>) -> u32 {
+        bot_name
+            .and_then(|name| self.bot_costs.iter().find(|bot| bot.bot_name == name))
+            .map_or(1, |bot| bot.cost)
+    }
+}
+
+/// Token bucket state of one account. Tokens are kept in thousandths so a
+/// bucket can refill by less than a whole unit between requests.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TokenBucket {
+    pub milli_tokens: u64,
+    pub updated_at: u64,
+}
+
+/// The limit a request ran into
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum QuotaLimit {
+    Burst,
+    Daily,
+    Monthly,
+}
+
+/// Request units left under each limit. `None` means unlimited.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct QuotaRemaining {
+    pub burst: Option<u32>,
+    pub daily: Option<u32>,
+    pub monthly: Option<u32>,
+}
+
+/// Why a request was refused and when it can be retried
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct QuotaRejection {
+    pub limit: QuotaLimit,
+    pub message: String,
+    pub cost: u32,
+    pub retry_after_secs: u64,
+    pub remaining: QuotaRemaining,
+}
+
+impl fmt::Display for QuotaRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Retry after {} seconds.", self.message, self.retry_after_secs)
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for QuotaPolicy {}
+
+impl VersionedRecord for TokenBucket {}
@@ -0,0 +1,97 @@
+// backend/src/models/record.rs
+//
+// Envelope for Candid-encoded stable map values: one version byte, then the
+// Candid payload. Values stored before the envelope are bare Candid, which
+// always starts with "DIDL", and are read as version 0. An enveloped record
+// never starts with "DIDL": its second byte is already the "D" of its payload.
+
+use candid::CandidType;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Version of records stored before the envelope
+pub const LEGACY_RECORD_VERSION: u8 = 0;
+
+const CANDID_MAGIC: &[u8; 4] = b"DIDL";
+
+/// A model stored in a stable map as an enveloped record
+pub trait VersionedRecord: CandidType + DeserializeOwned {
+    /// Version written by `encode_record`. Bump it when older payloads no longer
+    /// decode as the current type, e.g. after adding a non-optional field, and
+    /// read the old payload in `upgrade`.
+    const VERSION: u8 = 1;
+
+    /// Reads a payload written at an older `version`. By default the payload
+    /// decodes as the current type, which covers added optional fields.
+    fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String> {
+        let _ = version;
+        candid::decode_one(payload).map_err(|e| e.to_string())
+    }
+}
+
+pub fn encode_record<T: VersionedRecord>(record: &T) -> Vec<u8> {
+    let mut bytes = vec![T::VERSION];
+    bytes.extend(candid::encode_one(record).unwrap_or_else(|e| panic!("Failed to encode {}: {}", type_name::<T>(), e)));
+    bytes
+}
+
+/// Largest encoded record a map takes. Payloads that can outgrow it, such as
+/// logos and long responses, belong in the blob store.
+pub const MAX_RECORD_SIZE: usize = 64 * 1024;
+
+/// Rejects a record too large to store, before anything is written
+pub fn check_record_size<T: VersionedRecord>(record: &T) -> Result<(), String> {
+    let size = encode_record(record).len();
+    if size > MAX_RECORD_SIZE {
+        return Err(format!(
+            "{} record is {} bytes, over the {} byte limit",
+            type_name::<T>(),
+            size,
+            MAX_RECORD_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// The version a stored record was written at, and its payload
+pub fn split_record(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    if bytes.starts_with(CANDID_MAGIC) {
+        return Some((LEGACY_RECORD_VERSION, bytes));
+    }
+    bytes.split_first().map(|(version, payload)| (*version, payload))
+}
+
+/// Decodes an enveloped or legacy record, upgrading older payloads. Returns an
+/// error instead of panicking on anything it cannot read.
+pub fn decode_record<T: VersionedRecord>(bytes: &[u8]) -> Result<T, String> {
+    let Some((version, payload)) = split_record(bytes) else {
+        return Err(format!("Empty {} record", type_name::<T>()));
+    };
+    let decoded = if version == T::VERSION {
+        candid::decode_one(payload).map_err(|e| e.to_string())
+    } else if version < T::VERSION {
+        T::upgrade(version, payload)
+    } else {
+        Err(format!("written by a newer release than this one (version {})", T::VERSION))
+    };
+    decoded.map_err(|e| format!("Undecodable {} record, version {}: {}", type_name::<T>(), version, e))
+}
+
+fn type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// A stored value that could not be decoded, moved out of its map so reads
+/// of the map no longer skip it. Key and value are the raw stored bytes.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct QuarantinedRecord {
+    pub id: u64,
+    pub map: String,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub error: String,
+    pub quarantined_at: u64,
+}
+
+impl VersionedRecord for QuarantinedRecord {}
\ No newline at end of file
@@ -0,0 +1,53 @@
+// backend/src/models/sponsorship.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::payment::Currency;
+use crate::models::record::VersionedRecord;
+
+/// Lifecycle of a seat purchase
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum SponsorshipStatus {
+    Pending,    // Waiting for the payment
+    Active,     // Paid, seats can be assigned until `ends_at`
+    Cancelled,  // Payment failed or was fully refunded
+}
+
+/// Pro seats an accelerator bought for its founders, keyed by payment reference
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SeatSponsorship {
+    pub id: String,                     // Payment reference
+    pub accelerator_id: String,
+    pub cohort_id: Option<String>,      // Seats only for this cohort, None = any cohort
+    pub tier: String,                   // Plan id from the catalog
+    pub billing_period: String,
+    pub seats: u32,
+    pub amount: u64,                    // Total paid, smallest unit
+    pub currency: Currency,
+    pub status: SponsorshipStatus,
+    pub starts_at: Option<u64>,
+    pub ends_at: Option<u64>,
+    pub purchased_by: String,           // Team member principal
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// A seat given to a startup founder
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SeatAssignment {
+    pub sponsorship_id: String,
+    pub startup_id: String,
+    pub user_id: String,                // Founder principal
+    pub assigned_by: String,
+    pub assigned_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for SeatSponsorship {}
+
+impl VersionedRecord for SeatAssignment {}
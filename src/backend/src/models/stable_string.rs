@@ -1,5 +1,5 @@
 use candid::{CandidType, Decode, Encode};
-use ic_stable_structures::{BoundedStorable, Storable};
+use ic_stable_structures::storable::{Bound, Storable};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
@@ -44,11 +44,9 @@ impl Storable for StableString {
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
         Decode!(bytes.as_ref(), Self).unwrap()
     }
-}
 
-impl BoundedStorable for StableString {
-    const MAX_SIZE: u32 = 1024; // Maximum string length
-    const IS_FIXED_SIZE: bool = false;
+    // Used as a map key, and keys stay bounded so stored keys keep their layout
+    const BOUND: Bound = Bound::Bounded { max_size: 1024, is_fixed_size: false };
 }
 
 impl PartialEq for StableString {
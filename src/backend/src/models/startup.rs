@@ -1,8 +1,7 @@
 use crate::models::stable_principal::StablePrincipal;
-use candid::{CandidType, Decode, Encode};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Startup {
@@ -132,62 +131,10 @@ pub struct StartupStats {
 }
 
 // Storable implementations
-impl Storable for Startup {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+impl VersionedRecord for Startup {}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for Startup {
-    const MAX_SIZE: u32 = 4096;
-    const IS_FIXED_SIZE: bool = false;
-}
-
-impl Storable for StartupStatus {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for StartupStatus {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for StartupStatus {}
 
-impl Storable for StartupCohort {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for StartupCohort {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
-
-impl Storable for StartupActivity {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
+impl VersionedRecord for StartupCohort {}
 
-impl BoundedStorable for StartupActivity {
-    const MAX_SIZE: u32 = 2048;
-    const IS_FIXED_SIZE: bool = false;
-} 
\ No newline at end of file
+impl VersionedRecord for StartupActivity {}
\ No newline at end of file
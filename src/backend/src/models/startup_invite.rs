@@ -1,8 +1,7 @@
 use crate::models::stable_principal::StablePrincipal;
-use candid::{CandidType, Deserialize, Principal, Decode, Encode};
+use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq)]
 pub enum InviteType {
@@ -35,17 +34,4 @@ pub struct StartupInvite {
     pub registered_at: Option<u64>,
 }
 
-impl Storable for StartupInvite {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for StartupInvite {
-    const MAX_SIZE: u32 = 2048;
-    const IS_FIXED_SIZE: bool = false;
-}
\ No newline at end of file
+impl VersionedRecord for StartupInvite {}
\ No newline at end of file
@@ -0,0 +1,67 @@
+// backend/src/models/subscription_billing.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::payment::Currency;
+use crate::models::usage_service::UserTier;
+use crate::models::record::VersionedRecord;
+
+/// Lifecycle state of a paid subscription
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Default)]
+pub enum SubscriptionStatus {
+    #[default]
+    Active,     // Paid up, renews automatically before expiry
+    PastDue,    // Renewal charge failed, still Pro while inside the grace period
+    Cancelled,  // User cancelled, stays Pro until the current period ends
+    Expired,    // Period (and grace period) is over, user is back on Free
+}
+
+/// Billing state kept next to `UserSubscription` for automatic renewals
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct SubscriptionBilling {
+    pub user_id: String,
+    pub status: SubscriptionStatus,
+    pub tier: UserTier,
+    pub billing_period: String,                 // "monthly" or "yearly"
+    pub amount: u64,                            // Amount charged on each renewal (smallest unit)
+    pub currency: Currency,
+    pub email: String,
+    pub authorization_code: Option<String>,     // Reusable Paystack authorization from the last charge
+    pub current_period_end_ns: u64,
+    pub past_due_since_ns: Option<u64>,
+    pub renewal_attempts: u32,                  // Failed attempts since the last successful charge
+    pub last_attempt_at_ns: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_payment_reference: Option<String>,
+    pub cancelled_at_ns: Option<u64>,
+    pub updated_at_ns: u64,
+}
+
+/// Admin-tunable knobs for the renewal engine
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct BillingSettings {
+    pub grace_period_days: u32,         // How long a PastDue user keeps Pro after expiry
+    pub renew_before_hours: u32,        // How early before expiry the first charge is attempted
+    pub retry_interval_hours: u32,      // Wait between failed renewal attempts
+    pub max_renewal_attempts: u32,      // Stop charging after this many failures
+}
+
+impl Default for BillingSettings {
+    fn default() -> Self {
+        BillingSettings {
+            grace_period_days: 3,
+            renew_before_hours: 24,
+            retry_interval_hours: 24,
+            max_renewal_attempts: 4,
+        }
+    }
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for SubscriptionBilling {}
+
+impl VersionedRecord for BillingSettings {}
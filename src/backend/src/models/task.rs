@@ -1,7 +1,6 @@
-use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
-use ic_stable_structures::{BoundedStorable, Storable};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Task {
@@ -16,18 +15,4 @@ pub struct Task {
 }
 
 // Implement Storable for Task
-impl Storable for Task {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-// Implement BoundedStorable for Task
-impl BoundedStorable for Task {
-    const MAX_SIZE: u32 = 2048; // Adjust this value
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for Task {}
@@ -2,8 +2,7 @@
 
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
-use ic_stable_structures::storable::{Storable, BoundedStorable};
+use crate::models::record::VersionedRecord;
 
 /// Billing tiers
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
@@ -21,7 +20,7 @@ impl Default for UserTier {
 }
 
 /// Subscription state for a user
-#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
 pub struct UserSubscription {
     pub user_id: String,
     pub tier: UserTier,
@@ -54,28 +53,4 @@ pub struct UsageStats {
     pub day_bucket: u64,
     pub reset_time_rfc3339: String,
 }
-/* ============================
-   Storable / BoundedStorable
-   ============================
-
-   `StableBTreeMap` requires stored types to implement `Storable` (and
-   older versions required `BoundedStorable`). We serialize via Candid.
-   Adjust MAX_SIZE if you expect larger serialized payloads.
-*/
-
-impl Storable for UserSubscription {
-    fn to_bytes(&self) -> Cow<'_, [u8]> {
-        Cow::Owned(candid::encode_one(self).expect("Candid encode failed"))
-    }
-
-    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
-        candid::decode_one::<UserSubscription>(bytes.as_ref())
-            .expect("Candid decode failed for UserSubscription")
-    }
-}
-
-impl BoundedStorable for UserSubscription {
-    // e.g. 4KB; increase if you store lots of metadata in UserSubscription
-    const MAX_SIZE: u32 = 4 * 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
\ No newline at end of file
+impl VersionedRecord for UserSubscription {}
\ No newline at end of file
@@ -1,8 +1,7 @@
 use crate::models::stable_principal::StablePrincipal;
-use candid::{CandidType, Decode, Encode};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct User {
@@ -23,17 +22,4 @@ pub enum SubscriptionTier {
     Enterprise,
 }
 
-impl Storable for User {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for User {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for User {}
@@ -1,7 +1,6 @@
-use candid::{CandidType, Decode, Encode};
-use ic_stable_structures::{BoundedStorable, Storable};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use crate::models::record::VersionedRecord;
 
 #[derive(CandidType, Serialize, Deserialize, Clone)]
 pub struct WaitlistEntry {
@@ -18,17 +17,4 @@ pub enum WaitlistStatus {
     Rejected,
 }
 
-impl Storable for WaitlistEntry {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
-}
-
-impl BoundedStorable for WaitlistEntry {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
+impl VersionedRecord for WaitlistEntry {}
@@ -0,0 +1,43 @@
+// backend/src/models/webhook_event.rs
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::payment::PaymentGateway;
+use crate::models::record::VersionedRecord;
+
+/// Endpoint that received a webhook, and so the handler that replays it
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum WebhookSource {
+    PaystackWebhook,    // payment_webhook: Paystack charges and refunds
+    ProviderWebhook,    // payment_provider_webhook: any gateway's payment events
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum WebhookEventStatus {
+    Processing,
+    Processed,
+    Failed,
+}
+
+/// A verified webhook delivery, keyed by the gateway's event id (or a payload hash)
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub source: WebhookSource,
+    pub gateway: PaymentGateway,
+    pub event_type: String,
+    pub payload: Option<String>,        // None when too large to keep
+    pub status: WebhookEventStatus,
+    pub attempts: u32,
+    pub received_at: u64,
+    pub processed_at: Option<u64>,
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+/* ============================
+   Storable
+   ============================ */
+
+impl VersionedRecord for WebhookEvent {}
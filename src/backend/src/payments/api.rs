@@ -1,55 +1,85 @@
-/// Payment API endpoints
-/// 
-/// This module contains all the canister endpoints for payment operations
+//! Payment API endpoints
+//!
+//! This module contains all the canister endpoints for payment operations
 
 use crate::services::payment_service::{
-    initialize_payment, verify_payment, get_payment_history, get_payment, get_user_invoices,
+    initialize_payment, verify_payment, get_payment_history, get_payment, get_user_invoices, can_view_billing,
     InitializePaymentRequest, InitializePaymentResponse,
 };
-use crate::models::payment::{PaymentRecord, Invoice, TransactionDetails};
+use crate::services::webhook_service::{get_webhook_event, list_webhook_events};
+use crate::payments::webhook_handler::{receive_webhook, reprocess_webhook_event};
+use crate::models::webhook_event::{WebhookEvent, WebhookEventStatus, WebhookSource};
+use crate::services::refund_service::{refund_payment, get_payment_refunds};
+use crate::services::invoice_service::{can_view_invoice, get_invoice, render_invoice, InvoiceFormat, RenderedInvoice};
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest, record_audit};
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::auth::caller_is_authenticated;
+use crate::models::payment::{PaymentRecord, Invoice, TransactionDetails, PaymentGateway, RefundRecord};
 use crate::payments::{set_paystack_config, get_paystack_config, PaystackConfig};
+use crate::payments::{
+    set_stripe_config, get_stripe_config, StripeConfig, set_daraja_config, get_daraja_config,
+    DarajaConfig, set_provider_route, get_provider_routes, ProviderRoute,
+};
+use crate::payments::provider::{IcHttpTransport, PaymentProvider, Provider};
 
-/// Initialize a new payment (creates Paystack transaction)
-#[ic_cdk::update]
+/// Initialize a new payment (creates Paystack transaction) for the caller or their accelerator
+#[ic_cdk::update(guard = "caller_is_authenticated")]
 pub async fn payment_initialize(request: InitializePaymentRequest) -> Result<InitializePaymentResponse, String> {
+    if !can_view_billing(ic_cdk::caller(), &request.user_id) {
+        return Err("Unauthorized: Can only start payments for your own account".to_string());
+    }
     initialize_payment(request).await
 }
 
-/// Verify a payment after user completes it on Paystack
-#[ic_cdk::update]
+/// Verify a payment after user completes it on Paystack (owner, accelerator team or admin)
+#[ic_cdk::update(guard = "caller_is_authenticated")]
 pub async fn payment_verify(reference: String) -> Result<TransactionDetails, String> {
+    let payment = get_payment(reference.clone()).ok_or_else(|| "Payment record not found".to_string())?;
+    if !can_view_billing(ic_cdk::caller(), &payment.user_id) && require_permission(Permission::ViewBilling).is_err() {
+        return Err("Unauthorized: Not your payment".to_string());
+    }
     verify_payment(reference).await
 }
 
-/// Get payment history for a user
-#[ic_cdk::query]
-pub fn payment_get_history(user_id: String) -> Vec<PaymentRecord> {
-    get_payment_history(user_id)
+/// Get the caller's payment history
+#[ic_cdk::query(guard = "caller_is_authenticated")]
+pub fn payment_get_my_history() -> Vec<PaymentRecord> {
+    get_payment_history(ic_cdk::caller().to_text())
 }
 
-/// Get a specific payment by reference
-#[ic_cdk::query]
+/// Get a specific payment by reference (owner, accelerator team or admin)
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn payment_get(reference: String) -> Option<PaymentRecord> {
-    get_payment(reference)
+    get_payment(reference).filter(|payment| {
+        can_view_billing(ic_cdk::caller(), &payment.user_id) || require_permission(Permission::ViewBilling).is_ok()
+    })
 }
 
-/// Get all invoices for a user
-#[ic_cdk::query]
-pub fn payment_get_invoices(user_id: String) -> Vec<Invoice> {
-    get_user_invoices(user_id)
+/// Get the caller's invoices
+#[ic_cdk::query(guard = "caller_is_authenticated")]
+pub fn payment_get_my_invoices() -> Vec<Invoice> {
+    get_user_invoices(ic_cdk::caller().to_text())
+}
+
+/// Render an invoice or credit note as HTML or PDF (owner, accelerator team or admin)
+#[ic_cdk::query(guard = "caller_is_authenticated")]
+pub fn payment_render_invoice(invoice_id: String, format: InvoiceFormat) -> Result<RenderedInvoice, String> {
+    let invoice = get_invoice(&invoice_id)
+        .ok_or_else(|| format!("Invoice not found: {}", invoice_id))?;
+    if !can_view_invoice(ic_cdk::caller(), &invoice) && require_permission(Permission::ViewBilling).is_err() {
+        return Err("Unauthorized: Not your invoice".to_string());
+    }
+    Ok(render_invoice(&invoice, format))
 }
 
 /// Set Paystack configuration (admin only)
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "caller_is_admin")]
 pub fn payment_set_config(config: PaystackConfig) -> Result<String, String> {
-    let caller = ic_cdk::caller();
-    let authorized_principal = candid::Principal::from_text("5mqc2-eelsb-rpsbu-tvroe-paiy3-c4wo3-4xl6q-7nelg-gprk3-rkq46-mqe")
-        .expect("Invalid authorized principal");
-    
-    if caller != authorized_principal {
-        return Err("Unauthorized: Only the authorized principal can update payment configuration".to_string());
-    }
-    
+    require_permission(Permission::ManagePaymentConfig)?;
+
+    audit(AuditAction::PaymentConfigChanged, "paystack", Some(digest(&get_paystack_config())), Some(digest(&config)));
     set_paystack_config(config);
     Ok("Paystack configuration updated successfully".to_string())
 }
@@ -63,20 +93,141 @@ pub fn payment_get_config() -> PaystackConfig {
     config
 }
 
+/// Set Stripe configuration (admin only)
+#[ic_cdk::update(guard = "caller_is_admin")]
+pub fn payment_set_stripe_config(config: StripeConfig) -> Result<String, String> {
+    require_permission(Permission::ManagePaymentConfig)?;
+
+    audit(AuditAction::PaymentConfigChanged, "stripe", Some(digest(&get_stripe_config())), Some(digest(&config)));
+    set_stripe_config(config);
+    Ok("Stripe configuration updated successfully".to_string())
+}
+
+/// Get Stripe configuration (secrets hidden)
+#[ic_cdk::query]
+pub fn payment_get_stripe_config() -> StripeConfig {
+    let mut config = get_stripe_config();
+    config.secret_key = "***HIDDEN***".to_string();
+    config.webhook_secret = "***HIDDEN***".to_string();
+    config
+}
+
+/// Set Safaricom Daraja configuration (admin only)
+#[ic_cdk::update(guard = "caller_is_admin")]
+pub fn payment_set_daraja_config(config: DarajaConfig) -> Result<String, String> {
+    require_permission(Permission::ManagePaymentConfig)?;
+
+    audit(AuditAction::PaymentConfigChanged, "daraja", Some(digest(&get_daraja_config())), Some(digest(&config)));
+    set_daraja_config(config);
+    Ok("Daraja configuration updated successfully".to_string())
+}
+
+/// Get Daraja configuration (secrets hidden)
+#[ic_cdk::query]
+pub fn payment_get_daraja_config() -> DarajaConfig {
+    let mut config = get_daraja_config();
+    config.consumer_secret = "***HIDDEN***".to_string();
+    config.passkey = "***HIDDEN***".to_string();
+    config.callback_token = "***HIDDEN***".to_string();
+    config
+}
+
+/// Route a currency to a specific gateway (admin only)
+#[ic_cdk::update(guard = "caller_is_admin")]
+pub fn payment_set_provider_route(route: ProviderRoute) -> Result<String, String> {
+    require_permission(Permission::ManagePaymentConfig)?;
+
+    let before = get_provider_routes().into_iter().find(|r| r.currency == route.currency);
+    audit(AuditAction::ProviderRouteChanged, format!("{:?}", route.currency), before.as_ref().map(digest), Some(digest(&route)));
+    set_provider_route(route);
+    Ok("Provider route updated successfully".to_string())
+}
+
+/// Get the explicit currency -> gateway routes
+#[ic_cdk::query]
+pub fn payment_get_provider_routes() -> Vec<ProviderRoute> {
+    get_provider_routes()
+}
+
+/// Process a webhook from any gateway (Stripe events, Daraja STK callbacks, Paystack charges)
+#[ic_cdk::update]
+pub async fn payment_provider_webhook(gateway: PaymentGateway, payload: String, signature: String) -> Result<String, String> {
+    let provider = Provider::for_gateway(gateway, IcHttpTransport, ic_cdk::api::time());
+
+    // Verify webhook signature
+    provider.verify_webhook(&payload, &signature)?;
+
+    // Record, deduplicate and process the event
+    receive_webhook(WebhookSource::ProviderWebhook, gateway, &payload, ic_cdk::api::time()).await
+}
+
+/// Refund a payment, fully (`amount` = None) or partially (admin only)
+#[ic_cdk::update(guard = "caller_is_admin")]
+pub async fn payment_refund(reference: String, amount: Option<u64>, reason: String) -> Result<RefundRecord, String> {
+    require_permission(Permission::ManageBilling)?;
+
+    let payment = get_payment(reference.clone())
+        .ok_or_else(|| format!("Payment record not found for reference: {}", reference))?;
+    let provider = Provider::for_gateway(payment.provider, IcHttpTransport, ic_cdk::api::time());
+    let admin = ic_cdk::caller();
+
+    let refund = refund_payment(
+        &provider,
+        &reference,
+        amount,
+        &reason,
+        &admin.to_string(),
+        ic_cdk::api::time(),
+    )
+    .await?;
+    record_audit(admin, AuditAction::RefundIssued, refund.id.clone(), Some(digest(&payment)), Some(digest(&refund)), ic_cdk::api::time());
+    Ok(refund)
+}
+
+/// Get the refunds of a payment (admin only)
+#[ic_cdk::query(guard = "caller_is_admin")]
+pub fn payment_get_refunds(reference: String) -> Result<Vec<RefundRecord>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_payment_refunds(&reference))
+}
+
 /// Process webhook from Paystack (called by Paystack)
 #[ic_cdk::update]
 pub async fn payment_webhook(payload: String, signature: String) -> Result<String, String> {
-    use crate::payments::webhook_handler::{verify_webhook_signature, validate_webhook_payload, process_webhook_event};
+    use crate::payments::webhook_handler::verify_webhook_signature;
     use crate::payments::get_secret_key;
     
     // Verify webhook signature
     verify_webhook_signature(&payload, &signature, &get_secret_key())?;
     
-    // Parse payload
-    let event = validate_webhook_payload(&payload)?;
-    
-    // Process event
-    process_webhook_event(event).await
+    // Record, deduplicate and process the event
+    receive_webhook(WebhookSource::PaystackWebhook, PaymentGateway::Paystack, &payload, ic_cdk::api::time()).await
+}
+
+/// Webhook events whose processing failed, newest first (admin only)
+#[ic_cdk::query(guard = "caller_is_admin")]
+pub fn payment_list_failed_webhook_events() -> Result<Vec<WebhookEvent>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(list_webhook_events(Some(WebhookEventStatus::Failed)))
+}
+
+/// Get a webhook event by id (admin only)
+#[ic_cdk::query(guard = "caller_is_admin")]
+pub fn payment_get_webhook_event(event_id: String) -> Result<Option<WebhookEvent>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_webhook_event(&event_id))
+}
+
+/// Process a failed webhook event again, e.g. after fixing the data it needed (admin only)
+#[ic_cdk::update(guard = "caller_is_admin")]
+pub async fn payment_reprocess_webhook_event(event_id: String) -> Result<String, String> {
+    require_permission(Permission::ManageBilling)?;
+    let admin = ic_cdk::caller();
+    let before = get_webhook_event(&event_id);
+    let outcome = reprocess_webhook_event(&event_id, ic_cdk::api::time()).await;
+    let after = get_webhook_event(&event_id);
+    record_audit(admin, AuditAction::WebhookReprocessed, event_id, before.as_ref().map(digest), after.as_ref().map(digest), ic_cdk::api::time());
+    outcome
 }
 
 /// Transform function for payment HTTP responses (required by IC HTTP outcalls)
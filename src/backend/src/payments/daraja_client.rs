@@ -0,0 +1,300 @@
+//! Safaricom Daraja (M-Pesa Express / STK push) client
+//!
+//! The customer gets a PIN prompt on their phone instead of a checkout page.
+//! Safaricom's `CheckoutRequestID` is kept in the payment record's
+//! `access_code`; the STK callback and the status query both key on it.
+//!
+//! Daraja has no idempotency keys and issues a fresh OAuth token per call, so
+//! on mainnet the outcalls should go through an idempotent relay host.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::Deserialize;
+use serde_json::json;
+use crate::models::payment::{Currency, PaymentChannel, PaymentGateway, PaymentRecord, PaymentStatus};
+use crate::payments::provider::{
+    header, response_text, status_code, HttpCall, HttpTransport, PaymentLookup, PaymentProvider,
+    ProviderEvent, ProviderInitRequest, ProviderInitResponse, ProviderRefund,
+    ProviderVerification,
+};
+use crate::payments::{get_daraja_config, is_daraja_configured, DarajaConfig, DarajaEnvironment};
+
+const SANDBOX_API_BASE: &str = "https://sandbox.safaricom.co.ke";
+const PRODUCTION_API_BASE: &str = "https://api.safaricom.co.ke";
+const EAT_OFFSET_SECS: i64 = 3 * 60 * 60; // Daraja timestamps are in East Africa Time
+const STILL_PROCESSING_ERROR: &str = "500.001.1001"; // Query before the customer has answered
+
+#[derive(Deserialize, Clone, Debug)]
+struct AccessTokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct StkPushResponse {
+    #[serde(rename = "MerchantRequestID")]
+    pub merchant_request_id: String,
+    #[serde(rename = "CheckoutRequestID")]
+    pub checkout_request_id: String,
+    pub response_code: String,
+    pub response_description: String,
+    pub customer_message: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct StkQueryResponse {
+    pub response_code: Option<String>,
+    pub result_code: Option<String>,
+    pub result_desc: Option<String>,
+}
+
+/// Daraja behind the `PaymentProvider` interface
+pub struct DarajaProvider<T: HttpTransport> {
+    http: T,
+    now: u64,
+}
+
+impl<T: HttpTransport> DarajaProvider<T> {
+    pub fn new(http: T, now: u64) -> Self {
+        DarajaProvider { http, now }
+    }
+
+    fn config(&self) -> Result<DarajaConfig, String> {
+        if !is_daraja_configured() {
+            return Err("Daraja not configured. Please set API keys.".to_string());
+        }
+        Ok(get_daraja_config())
+    }
+
+    /// OAuth client-credentials token, fetched per operation
+    async fn access_token(&self, config: &DarajaConfig) -> Result<String, String> {
+        let credentials = BASE64.encode(format!("{}:{}", config.consumer_key, config.consumer_secret));
+        let url = format!("{}/oauth/v1/generate?grant_type=client_credentials", api_base(config));
+        let response = self
+            .http
+            .send(HttpCall::get(url, vec![header("Authorization", format!("Basic {}", credentials))]))
+            .await?;
+
+        let body = response_text(response)?;
+        let token: AccessTokenResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse Daraja token: {}. Response: {}", e, body))?;
+        Ok(token.access_token)
+    }
+
+    async fn post_json(&self, config: &DarajaConfig, path: &str, body: serde_json::Value) -> Result<(u32, String), String> {
+        let token = self.access_token(config).await?;
+        let headers = vec![
+            header("Authorization", format!("Bearer {}", token)),
+            header("Content-Type", "application/json".to_string()),
+        ];
+
+        let url = format!("{}{}", api_base(config), path);
+        let response = self.http.send(HttpCall::post(url, headers, body.to_string())).await?;
+        let status = status_code(&response);
+        let text = String::from_utf8(response.body)
+            .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
+        Ok((status, text))
+    }
+}
+
+impl<T: HttpTransport> PaymentProvider for DarajaProvider<T> {
+    fn gateway(&self) -> PaymentGateway {
+        PaymentGateway::Daraja
+    }
+
+    async fn initialize(&self, request: ProviderInitRequest) -> Result<ProviderInitResponse, String> {
+        if request.currency != Currency::KES {
+            return Err("M-Pesa STK push only supports KES".to_string());
+        }
+        let phone = match request.phone_number.as_deref() {
+            Some(phone) => normalize_phone(phone)?,
+            None => return Err("Phone number is required for M-Pesa payments".to_string()),
+        };
+
+        let config = self.config()?;
+        let timestamp = daraja_timestamp(self.now);
+        let body = json!({
+            "BusinessShortCode": config.short_code,
+            "Password": stk_password(&config, &timestamp),
+            "Timestamp": timestamp,
+            "TransactionType": "CustomerPayBillOnline",
+            "Amount": whole_shillings(request.amount),
+            "PartyA": phone,
+            "PartyB": config.short_code,
+            "PhoneNumber": phone,
+            "CallBackURL": config.callback_url,
+            "AccountReference": "Infoundr",
+            "TransactionDesc": format!("{} {}", request.tier, request.billing_period),
+        });
+
+        let (status, text) = self.post_json(&config, "/mpesa/stkpush/v1/processrequest", body).await?;
+        if !(200..300).contains(&status) {
+            return Err(format!("Daraja API returned status {}: {}", status, text));
+        }
+
+        let response: StkPushResponse = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, text))?;
+        if response.response_code != "0" {
+            return Err(format!("M-Pesa error: {}", response.response_description));
+        }
+
+        Ok(ProviderInitResponse {
+            authorization_url: None,
+            access_code: Some(response.checkout_request_id),
+            message: response
+                .customer_message
+                .unwrap_or_else(|| "Check your phone to complete the M-Pesa payment".to_string()),
+        })
+    }
+
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        let checkout_request_id = payment
+            .access_code
+            .clone()
+            .ok_or_else(|| "Payment has no M-Pesa checkout request".to_string())?;
+
+        let config = self.config()?;
+        let timestamp = daraja_timestamp(self.now);
+        let body = json!({
+            "BusinessShortCode": config.short_code,
+            "Password": stk_password(&config, &timestamp),
+            "Timestamp": timestamp,
+            "CheckoutRequestID": checkout_request_id,
+        });
+
+        let (status, text) = self.post_json(&config, "/mpesa/stkpushquery/v1/query", body).await?;
+
+        // Daraja answers "still being processed" with an error payload
+        let payment_status = if text.contains(STILL_PROCESSING_ERROR) {
+            PaymentStatus::Pending
+        } else if !(200..300).contains(&status) {
+            return Err(format!("Daraja API returned status {}: {}", status, text));
+        } else {
+            let response: StkQueryResponse = serde_json::from_str(&text)
+                .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, text))?;
+            match response.result_code.as_deref() {
+                Some(code) => result_code_status(code),
+                None => PaymentStatus::Pending,
+            }
+        };
+
+        Ok(ProviderVerification {
+            status: payment_status,
+            amount: payment.amount,
+            currency: Some(Currency::KES),
+            channel: Some(PaymentChannel::MobileMoney),
+            customer_email: Some(payment.email.clone()),
+            ..Default::default()
+        })
+    }
+
+    async fn refund(
+        &self,
+        _payment: &PaymentRecord,
+        _amount: Option<u64>,
+        _reason: &str,
+    ) -> Result<ProviderRefund, String> {
+        // Reversals go through the M-Pesa org portal, Daraja has no refund call for STK payments
+        Err("M-Pesa STK payments cannot be refunded through the API".to_string())
+    }
+
+    fn verify_webhook(&self, _payload: &str, signature: &str) -> Result<(), String> {
+        // Safaricom does not sign callbacks; the relay forwards our shared token instead
+        let expected = get_daraja_config().callback_token;
+        if expected.is_empty() {
+            return Err("Daraja callback token not configured".to_string());
+        }
+
+        if signature == expected {
+            Ok(())
+        } else {
+            Err("Invalid webhook signature".to_string())
+        }
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<Option<ProviderEvent>, String> {
+        let event: serde_json::Value = serde_json::from_str(payload)
+            .map_err(|e| format!("Invalid webhook payload: {}", e))?;
+
+        let callback = &event["Body"]["stkCallback"];
+        if callback.is_null() {
+            return Ok(None);
+        }
+
+        let checkout_request_id = callback["CheckoutRequestID"]
+            .as_str()
+            .ok_or("Missing CheckoutRequestID in STK callback")?
+            .to_string();
+
+        // ResultCode is a number in callbacks but a string in query responses
+        let result_code = match &callback["ResultCode"] {
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::String(s) => s.clone(),
+            _ => return Err("Missing ResultCode in STK callback".to_string()),
+        };
+
+        let receipt = callback["CallbackMetadata"]["Item"]
+            .as_array()
+            .and_then(|items| items.iter().find(|item| item["Name"] == "MpesaReceiptNumber"))
+            .and_then(|item| item["Value"].as_str())
+            .map(|value| value.to_string());
+
+        Ok(Some(ProviderEvent {
+            lookup: PaymentLookup::AccessCode(checkout_request_id),
+            status: result_code_status(&result_code),
+            channel: Some(PaymentChannel::MobileMoney),
+            transaction_id: None,
+            provider_transaction_id: receipt,
+            authorization_code: None,
+        }))
+    }
+}
+
+fn api_base(config: &DarajaConfig) -> &'static str {
+    match config.environment {
+        DarajaEnvironment::Sandbox => SANDBOX_API_BASE,
+        DarajaEnvironment::Production => PRODUCTION_API_BASE,
+    }
+}
+
+/// STK result codes: 0 paid, 1032 cancelled by the user, 1037 no answer from the phone
+fn result_code_status(code: &str) -> PaymentStatus {
+    match code {
+        "0" => PaymentStatus::Success,
+        "1032" | "1037" => PaymentStatus::Abandoned,
+        _ => PaymentStatus::Failed,
+    }
+}
+
+/// `base64(shortcode + passkey + timestamp)`
+pub fn stk_password(config: &DarajaConfig, timestamp: &str) -> String {
+    BASE64.encode(format!("{}{}{}", config.short_code, config.passkey, timestamp))
+}
+
+/// `YYYYMMDDHHmmss` in East Africa Time
+pub fn daraja_timestamp(now: u64) -> String {
+    let secs = (now / 1_000_000_000) as i64 + EAT_OFFSET_SECS;
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|dt| dt.format("%Y%m%d%H%M%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Amounts are stored in cents; STK push takes whole shillings (rounded up, at least 1)
+pub fn whole_shillings(amount_cents: u64) -> u64 {
+    amount_cents.div_ceil(100).max(1)
+}
+
+/// Accept 07XXXXXXXX, +2547XXXXXXXX or 2547XXXXXXXX and return 2547XXXXXXXX
+pub fn normalize_phone(phone: &str) -> Result<String, String> {
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    let normalized = match digits.strip_prefix('0') {
+        Some(rest) => format!("254{}", rest),
+        None => digits,
+    };
+
+    if normalized.len() == 12 && normalized.starts_with("254") {
+        Ok(normalized)
+    } else {
+        Err(format!("Invalid M-Pesa phone number: {}", phone))
+    }
+}
@@ -1,15 +1,28 @@
 pub mod api;
+pub mod provider;
 pub mod paystack_client;
 pub mod paystack_models;
+pub mod stripe_client;
+pub mod daraja_client;
 pub mod webhook_handler;
 
 use std::cell::RefCell;
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
+use crate::models::payment::{Currency, PaymentGateway};
 
 thread_local! {
     /// Paystack configuration stored in canister
     static PAYSTACK_CONFIG: RefCell<PaystackConfig> = RefCell::new(PaystackConfig::default());
+
+    /// Stripe configuration stored in canister
+    static STRIPE_CONFIG: RefCell<StripeConfig> = RefCell::new(StripeConfig::default());
+
+    /// Safaricom Daraja configuration stored in canister
+    static DARAJA_CONFIG: RefCell<DarajaConfig> = RefCell::new(DarajaConfig::default());
+
+    /// Explicit currency -> gateway overrides, see `select_gateway`
+    static PROVIDER_ROUTES: RefCell<Vec<ProviderRoute>> = const { RefCell::new(Vec::new()) };
 }
 
 /// Paystack configuration
@@ -66,3 +79,137 @@ pub fn is_configured() -> bool {
     })
 }
 
+/// Stripe configuration
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StripeConfig {
+    pub secret_key: String,
+    pub webhook_secret: String,     // Signing secret of the webhook endpoint (whsec_...)
+}
+
+/// Safaricom Daraja (M-Pesa) configuration
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DarajaConfig {
+    pub consumer_key: String,
+    pub consumer_secret: String,
+    pub short_code: String,         // Paybill / till number
+    pub passkey: String,            // Lipa na M-Pesa online passkey
+    pub callback_url: String,       // Where Safaricom posts the STK result
+    pub callback_token: String,     // Shared secret the callback relay must present
+    pub environment: DarajaEnvironment,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DarajaEnvironment {
+    Sandbox,
+    Production,
+}
+
+impl Default for DarajaConfig {
+    fn default() -> Self {
+        Self {
+            consumer_key: String::new(),
+            consumer_secret: String::new(),
+            short_code: String::new(),
+            passkey: String::new(),
+            callback_url: String::new(),
+            callback_token: String::new(),
+            environment: DarajaEnvironment::Sandbox,
+        }
+    }
+}
+
+/// Send payments in `currency` through `gateway`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProviderRoute {
+    pub currency: Currency,
+    pub gateway: PaymentGateway,
+}
+
+/// Set Stripe configuration (admin only)
+pub fn set_stripe_config(config: StripeConfig) {
+    STRIPE_CONFIG.with(|c| {
+        *c.borrow_mut() = config;
+    });
+}
+
+/// Get Stripe configuration
+pub fn get_stripe_config() -> StripeConfig {
+    STRIPE_CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Check if Stripe is configured
+pub fn is_stripe_configured() -> bool {
+    STRIPE_CONFIG.with(|c| !c.borrow().secret_key.is_empty())
+}
+
+/// Set Daraja configuration (admin only)
+pub fn set_daraja_config(config: DarajaConfig) {
+    DARAJA_CONFIG.with(|c| {
+        *c.borrow_mut() = config;
+    });
+}
+
+/// Get Daraja configuration
+pub fn get_daraja_config() -> DarajaConfig {
+    DARAJA_CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Check if Daraja is configured
+pub fn is_daraja_configured() -> bool {
+    DARAJA_CONFIG.with(|c| {
+        let config = c.borrow();
+        !config.consumer_key.is_empty()
+            && !config.consumer_secret.is_empty()
+            && !config.short_code.is_empty()
+            && !config.passkey.is_empty()
+    })
+}
+
+/// Check if a specific gateway has credentials
+pub fn is_gateway_configured(gateway: PaymentGateway) -> bool {
+    match gateway {
+        PaymentGateway::Paystack => is_configured(),
+        PaymentGateway::Stripe => is_stripe_configured(),
+        PaymentGateway::Daraja => is_daraja_configured(),
+    }
+}
+
+/// Route a currency to a gateway (replaces any existing route for it)
+pub fn set_provider_route(route: ProviderRoute) {
+    PROVIDER_ROUTES.with(|r| {
+        let mut routes = r.borrow_mut();
+        routes.retain(|existing| existing.currency != route.currency);
+        routes.push(route);
+    });
+}
+
+/// Get the explicit routes
+pub fn get_provider_routes() -> Vec<ProviderRoute> {
+    PROVIDER_ROUTES.with(|r| r.borrow().clone())
+}
+
+/// Pick the gateway for a payment.
+///
+/// An explicit route wins. Otherwise KES goes to Daraja when M-Pesa was
+/// requested, USD goes to Stripe, and everything else (and any gateway
+/// without credentials) falls back to Paystack.
+pub fn select_gateway(currency: &Currency, wants_mpesa: bool) -> PaymentGateway {
+    let routed = PROVIDER_ROUTES.with(|r| {
+        r.borrow()
+            .iter()
+            .find(|route| &route.currency == currency)
+            .map(|route| route.gateway)
+    });
+
+    let preferred = routed.unwrap_or(match currency {
+        Currency::KES if wants_mpesa => PaymentGateway::Daraja,
+        Currency::USD => PaymentGateway::Stripe,
+        _ => PaymentGateway::Paystack,
+    });
+
+    if is_gateway_configured(preferred) {
+        preferred
+    } else {
+        PaymentGateway::Paystack
+    }
+}
@@ -1,119 +1,80 @@
-use ic_cdk::api::management_canister::http_request::{
-    http_request, CanisterHttpRequestArgument, HttpMethod, HttpResponse, HttpHeader,
-    TransformContext, TransformFunc,
-};
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpResponse};
+use crate::models::payment::{Currency, PaymentGateway, PaymentRecord, PaymentStatus};
 use crate::payments::paystack_models::{
     InitializeTransactionRequest, InitializeTransactionResponse, VerifyTransactionResponse,
+    ChargeAuthorizationRequest, ChargeAuthorizationResponse, RefundRequest, RefundResponse,
+    parse_payment_channel, parse_payment_status,
+};
+use crate::payments::provider::{
+    header, HttpCall, HttpTransport, PaymentLookup, PaymentProvider, ProviderEvent,
+    ProviderInitRequest, ProviderInitResponse, ProviderRefund, ProviderVerification,
 };
+use crate::payments::webhook_handler::verify_webhook_signature;
 use crate::payments::get_secret_key;
 use serde_json;
 
 const PAYSTACK_API_BASE: &str = "https://api.paystack.co";
-const MAX_RESPONSE_BYTES: u64 = 2_000_000; // 2MB
-const CYCLES_PER_CALL: u128 = 25_000_000_000; // ~25B cycles per HTTP call
 
 /// Initialize a payment transaction with Paystack
-pub async fn initialize_transaction(
+pub async fn initialize_transaction<T: HttpTransport>(
+    http: &T,
     request: InitializeTransactionRequest,
 ) -> Result<InitializeTransactionResponse, String> {
-    let secret_key = get_secret_key();
-    if secret_key.is_empty() {
-        return Err("Paystack not configured. Please set API keys.".to_string());
-    }
-
-    // Serialize request body
     let body = serde_json::to_string(&request)
         .map_err(|e| format!("Failed to serialize request: {}", e))?;
 
-    // Prepare headers
-    let headers = vec![
-        HttpHeader {
-            name: "Authorization".to_string(),
-            value: format!("Bearer {}", secret_key),
-        },
-        HttpHeader {
-            name: "Content-Type".to_string(),
-            value: "application/json".to_string(),
-        },
-    ];
-
-    // Build HTTP request
     let url = format!("{}/transaction/initialize", PAYSTACK_API_BASE);
-    
-    let request = CanisterHttpRequestArgument {
-        url: url.clone(),
-        method: HttpMethod::POST,
-        body: Some(body.into_bytes()),
-        max_response_bytes: Some(MAX_RESPONSE_BYTES),
-        transform: Some(TransformContext {
-            function: TransformFunc(candid::Func {
-                principal: ic_cdk::api::id(),
-                method: "transform_payment_http_response".to_string(),
-            }),
-            context: vec![],
-        }),
-        headers,
-    };
-
-    // Make HTTP outcall
-    match http_request(request, CYCLES_PER_CALL).await {
-        Ok((response,)) => {
-            parse_initialize_response(response)
-        }
-        Err((code, msg)) => {
-            Err(format!("HTTP request failed: {:?} - {}", code, msg))
-        }
-    }
+    let response = http.send(HttpCall::post(url, auth_headers()?, body)).await?;
+    parse_initialize_response(response)
 }
 
 /// Verify a payment transaction with Paystack
-pub async fn verify_transaction(reference: String) -> Result<VerifyTransactionResponse, String> {
+pub async fn verify_transaction<T: HttpTransport>(
+    http: &T,
+    reference: String,
+) -> Result<VerifyTransactionResponse, String> {
+    let url = format!("{}/transaction/verify/{}", PAYSTACK_API_BASE, reference);
+    let response = http.send(HttpCall::get(url, auth_headers()?)).await?;
+    parse_verify_response(response)
+}
+
+/// Charge a reusable authorization code (recurring billing)
+pub async fn charge_authorization<T: HttpTransport>(
+    http: &T,
+    request: ChargeAuthorizationRequest,
+) -> Result<ChargeAuthorizationResponse, String> {
+    let body = serde_json::to_string(&request)
+        .map_err(|e| format!("Failed to serialize request: {}", e))?;
+
+    let url = format!("{}/transaction/charge_authorization", PAYSTACK_API_BASE);
+    let response = http.send(HttpCall::post(url, auth_headers()?, body)).await?;
+    parse_charge_authorization_response(response)
+}
+
+/// Refund a transaction, fully or partially
+pub async fn create_refund<T: HttpTransport>(
+    http: &T,
+    request: RefundRequest,
+) -> Result<RefundResponse, String> {
+    let body = serde_json::to_string(&request)
+        .map_err(|e| format!("Failed to serialize request: {}", e))?;
+
+    let url = format!("{}/refund", PAYSTACK_API_BASE);
+    let response = http.send(HttpCall::post(url, auth_headers()?, body)).await?;
+    parse_refund_response(response)
+}
+
+/// Bearer auth headers, or an error when Paystack has no secret key yet
+fn auth_headers() -> Result<Vec<HttpHeader>, String> {
     let secret_key = get_secret_key();
     if secret_key.is_empty() {
         return Err("Paystack not configured. Please set API keys.".to_string());
     }
 
-    // Prepare headers
-    let headers = vec![
-        HttpHeader {
-            name: "Authorization".to_string(),
-            value: format!("Bearer {}", secret_key),
-        },
-        HttpHeader {
-            name: "Content-Type".to_string(),
-            value: "application/json".to_string(),
-        },
-    ];
-
-    // Build HTTP request
-    let url = format!("{}/transaction/verify/{}", PAYSTACK_API_BASE, reference);
-
-    let request = CanisterHttpRequestArgument {
-        url: url.clone(),
-        method: HttpMethod::GET,
-        body: None,
-        max_response_bytes: Some(MAX_RESPONSE_BYTES),
-        transform: Some(TransformContext {
-            function: TransformFunc(candid::Func {
-                principal: ic_cdk::api::id(),
-                method: "transform_payment_http_response".to_string(),
-            }),
-            context: vec![],
-        }),
-        headers,
-    };
-    ic_cdk::println!("Request: {:?}", request);
-
-    // Make HTTP outcall
-    match http_request(request, CYCLES_PER_CALL).await {
-        Ok((response,)) => {
-            parse_verify_response(response)
-        }
-        Err((code, msg)) => {
-            ic_cdk::println!("HTTP request failed: {:?} - {}", code, msg);
-            Err(format!("HTTP request failed: {:?} - {}", code, msg))
-        }
-    }
+    Ok(vec![
+        header("Authorization", format!("Bearer {}", secret_key)),
+        header("Content-Type", "application/json".to_string()),
+    ])
 }
 
 /// Parse the initialization response
@@ -139,7 +100,6 @@ fn parse_initialize_response(response: HttpResponse) -> Result<InitializeTransac
 fn parse_verify_response(response: HttpResponse) -> Result<VerifyTransactionResponse, String> {
     // Check status code
     if response.status != 200u32 {
-        ic_cdk::println!("Paystack API returned status {}: {}", response.status, String::from_utf8_lossy(&response.body));
         return Err(format!(
             "Paystack API returned status {}: {}",
             response.status,
@@ -150,12 +110,199 @@ fn parse_verify_response(response: HttpResponse) -> Result<VerifyTransactionResp
     // Parse JSON response
     let response_text = String::from_utf8(response.body)
         .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
-    ic_cdk::println!("Response converted to string on parse_verify_response: {:?}", response_text);
 
     serde_json::from_str::<VerifyTransactionResponse>(&response_text)
         .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, response_text))
 }
 
+/// Parse the charge_authorization response
+fn parse_charge_authorization_response(response: HttpResponse) -> Result<ChargeAuthorizationResponse, String> {
+    // Paystack answers declined charges with 400 and a JSON body, keep those parseable
+    if response.status != 200u32 && response.status != 400u32 {
+        return Err(format!(
+            "Paystack API returned status {}: {}",
+            response.status,
+            String::from_utf8_lossy(&response.body)
+        ));
+    }
+
+    // Parse JSON response
+    let response_text = String::from_utf8(response.body)
+        .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
+
+    serde_json::from_str::<ChargeAuthorizationResponse>(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, response_text))
+}
+
+/// Parse the refund response
+fn parse_refund_response(response: HttpResponse) -> Result<RefundResponse, String> {
+    // Rejected refunds (e.g. amount above the refundable balance) come back as 400 with a message
+    if response.status != 200u32 && response.status != 400u32 {
+        return Err(format!(
+            "Paystack API returned status {}: {}",
+            response.status,
+            String::from_utf8_lossy(&response.body)
+        ));
+    }
+
+    // Parse JSON response
+    let response_text = String::from_utf8(response.body)
+        .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
+
+    serde_json::from_str::<RefundResponse>(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, response_text))
+}
+
+/// Paystack behind the `PaymentProvider` interface
+pub struct PaystackProvider<T: HttpTransport> {
+    http: T,
+}
+
+impl<T: HttpTransport> PaystackProvider<T> {
+    pub fn new(http: T) -> Self {
+        PaystackProvider { http }
+    }
+}
+
+impl<T: HttpTransport> PaymentProvider for PaystackProvider<T> {
+    fn gateway(&self) -> PaymentGateway {
+        PaymentGateway::Paystack
+    }
+
+    async fn initialize(&self, request: ProviderInitRequest) -> Result<ProviderInitResponse, String> {
+        let metadata = build_metadata(
+            &request.user_id,
+            &request.tier,
+            &request.billing_period,
+            request.phone_number.clone(),
+        );
+
+        let paystack_request = InitializeTransactionRequest {
+            email: request.email,
+            amount: request.amount.to_string(),
+            currency: request.currency.as_str().to_string(),
+            reference: request.reference,
+            callback_url: request.callback_url,
+            channels: get_payment_channels(request.enable_mpesa, request.enable_card),
+            metadata: Some(metadata),
+            phone: request.phone_number,
+        };
+
+        let response = initialize_transaction(&self.http, paystack_request).await?;
+        if !response.status {
+            return Err(format!("Paystack error: {}", response.message));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "Paystack returned success but no data".to_string())?;
+
+        Ok(ProviderInitResponse {
+            authorization_url: Some(data.authorization_url),
+            access_code: Some(data.access_code),
+            message: response.message,
+        })
+    }
+
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        let response = verify_transaction(&self.http, payment.paystack_reference.clone()).await?;
+        if !response.status {
+            return Err(format!("Verification failed: {}", response.message));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "Verification successful but no data returned".to_string())?;
+
+        // Reusable card authorizations are kept for automatic renewals
+        let authorization_code = if data.authorization.reusable
+            && !data.authorization.authorization_code.is_empty()
+        {
+            Some(data.authorization.authorization_code.clone())
+        } else {
+            None
+        };
+
+        Ok(ProviderVerification {
+            status: parse_payment_status(&data.status),
+            amount: data.amount,
+            currency: Currency::from_code(&data.currency),
+            channel: parse_payment_channel(&data.channel),
+            paid_at: data.paid_at,
+            transaction_id: Some(data.id),
+            provider_transaction_id: None,
+            authorization_code,
+            customer_email: Some(data.customer.email),
+            customer_name: data.customer.first_name,
+            fees: data.fees,
+        })
+    }
+
+    async fn refund(
+        &self,
+        payment: &PaymentRecord,
+        amount: Option<u64>,
+        reason: &str,
+    ) -> Result<ProviderRefund, String> {
+        let request = RefundRequest {
+            transaction: payment.paystack_reference.clone(),
+            amount,
+            merchant_note: if reason.is_empty() { None } else { Some(reason.to_string()) },
+        };
+
+        let response = create_refund(&self.http, request).await?;
+        if !response.status {
+            return Err(format!("Paystack refund failed: {}", response.message));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "Paystack refund returned no data".to_string())?;
+
+        Ok(ProviderRefund {
+            refund_id: data.id.to_string(),
+            amount: data.amount,
+            status: data.status,
+        })
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<(), String> {
+        verify_webhook_signature(payload, signature, &get_secret_key())
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<Option<ProviderEvent>, String> {
+        let event: serde_json::Value = serde_json::from_str(payload)
+            .map_err(|e| format!("Invalid webhook payload: {}", e))?;
+
+        let status = match event["event"].as_str() {
+            Some("charge.success") => PaymentStatus::Success,
+            Some("charge.failed") => PaymentStatus::Failed,
+            _ => return Ok(None),
+        };
+
+        let data = &event["data"];
+        let reference = data["reference"]
+            .as_str()
+            .ok_or("Missing reference in webhook data")?
+            .to_string();
+
+        let authorization_code = if data["authorization"]["reusable"].as_bool() == Some(true) {
+            data["authorization"]["authorization_code"].as_str().map(|code| code.to_string())
+        } else {
+            None
+        };
+
+        Ok(Some(ProviderEvent {
+            lookup: PaymentLookup::Reference(reference),
+            status,
+            channel: data["channel"].as_str().and_then(parse_payment_channel),
+            transaction_id: data["id"].as_u64(),
+            provider_transaction_id: None,
+            authorization_code,
+        }))
+    }
+}
+
 /// Helper to build metadata JSON string
 pub fn build_metadata(
     user_id: &str,
@@ -170,31 +317,30 @@ pub fn build_metadata(
         "phone": phone,
         "source": "infoundr_platform"
     });
-    
+
     serde_json::to_string(&metadata).unwrap_or_default()
 }
 
 /// Helper to determine which payment channels to enable
 pub fn get_payment_channels(include_mpesa: bool, include_card: bool) -> Vec<String> {
     let mut channels = Vec::new();
-    
+
     if include_card {
         channels.push("card".to_string());
     }
-    
+
     if include_mpesa {
         channels.push("mobile_money".to_string());
     }
-    
+
     // You can add more channels as needed
     // channels.push("bank_transfer".to_string());
     // channels.push("ussd".to_string());
-    
+
     if channels.is_empty() {
         // Default to card if nothing specified
         channels.push("card".to_string());
     }
-    
+
     channels
 }
-
@@ -252,6 +252,65 @@ impl VerificationData {
     }
 }
 
+/// Request to charge a saved authorization (used for subscription renewals)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ChargeAuthorizationRequest {
+    pub email: String,
+    pub amount: String,  // Amount in kobo/smallest unit
+    pub authorization_code: String,
+    pub reference: String,
+    pub currency: String,
+    pub metadata: Option<String>,  // JSON string
+}
+
+/// Response from Paystack charge_authorization
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ChargeAuthorizationResponse {
+    pub status: bool,
+    pub message: String,
+    pub data: Option<ChargeAuthorizationData>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChargeAuthorizationData {
+    pub id: u64,
+    pub status: String,  // "success", "failed", "send_otp", ...
+    pub reference: String,
+    pub amount: u64,
+    pub currency: String,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub gateway_response: Option<String>,
+}
+
+/// Request to refund a transaction (full refund when `amount` is omitted)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RefundRequest {
+    pub transaction: String,  // Transaction reference or id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,  // Amount in kobo/smallest unit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merchant_note: Option<String>,
+}
+
+/// Response from Paystack refund
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RefundResponse {
+    pub status: bool,
+    pub message: String,
+    pub data: Option<RefundData>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RefundData {
+    pub id: u64,
+    pub status: String,  // "pending", "processing", "processed", "failed"
+    pub amount: u64,
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
 /// Webhook event from Paystack
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct PaystackWebhookEvent {
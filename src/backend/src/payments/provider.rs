@@ -0,0 +1,265 @@
+//! Payment provider abstraction
+//!
+//! Every gateway (Paystack, Stripe, Daraja) implements `PaymentProvider`.
+//! HTTP goes through `HttpTransport` so providers can be tested against
+//! canned responses; `IcHttpTransport` is the real HTTP outcall.
+
+use std::future::Future;
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse,
+    TransformContext, TransformFunc,
+};
+use crate::models::payment::{Currency, PaymentChannel, PaymentGateway, PaymentRecord, PaymentStatus};
+use crate::payments::daraja_client::DarajaProvider;
+use crate::payments::paystack_client::PaystackProvider;
+use crate::payments::stripe_client::StripeProvider;
+
+const MAX_RESPONSE_BYTES: u64 = 2_000_000; // 2MB
+const CYCLES_PER_CALL: u128 = 25_000_000_000; // ~25B cycles per HTTP call
+
+/// Outgoing HTTP request, without the IC-specific transform/cycles details
+#[derive(Clone, Debug)]
+pub struct HttpCall {
+    pub url: String,
+    pub method: HttpMethod,
+    pub headers: Vec<HttpHeader>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl HttpCall {
+    pub fn get(url: String, headers: Vec<HttpHeader>) -> Self {
+        HttpCall { url, method: HttpMethod::GET, headers, body: None }
+    }
+
+    pub fn post(url: String, headers: Vec<HttpHeader>, body: String) -> Self {
+        HttpCall { url, method: HttpMethod::POST, headers, body: Some(body.into_bytes()) }
+    }
+}
+
+/// Sends `HttpCall`s. The canister uses HTTP outcalls, tests use canned responses.
+pub trait HttpTransport {
+    fn send(&self, call: HttpCall) -> impl Future<Output = Result<HttpResponse, String>>;
+}
+
+/// HTTP outcall through the management canister
+pub struct IcHttpTransport;
+
+impl HttpTransport for IcHttpTransport {
+    async fn send(&self, call: HttpCall) -> Result<HttpResponse, String> {
+        let request = CanisterHttpRequestArgument {
+            url: call.url,
+            method: call.method,
+            body: call.body,
+            max_response_bytes: Some(MAX_RESPONSE_BYTES),
+            transform: Some(TransformContext {
+                function: TransformFunc(candid::Func {
+                    principal: ic_cdk::api::id(),
+                    method: "transform_payment_http_response".to_string(),
+                }),
+                context: vec![],
+            }),
+            headers: call.headers,
+        };
+
+        match http_request(request, CYCLES_PER_CALL).await {
+            Ok((response,)) => Ok(response),
+            Err((code, msg)) => Err(format!("HTTP request failed: {:?} - {}", code, msg)),
+        }
+    }
+}
+
+/// Provider-neutral payment initialization
+#[derive(Clone, Debug)]
+pub struct ProviderInitRequest {
+    pub reference: String,
+    pub user_id: String,
+    pub email: String,
+    pub amount: u64,            // Smallest currency unit
+    pub currency: Currency,
+    pub tier: String,
+    pub billing_period: String,
+    pub callback_url: String,
+    pub phone_number: Option<String>,
+    pub enable_mpesa: bool,
+    pub enable_card: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProviderInitResponse {
+    pub authorization_url: Option<String>,  // Hosted checkout page (None for STK push)
+    pub access_code: Option<String>,        // Provider handle needed to verify later
+    pub message: String,
+}
+
+/// Provider-neutral verification result
+#[derive(Clone, Debug, Default)]
+pub struct ProviderVerification {
+    pub status: PaymentStatus,
+    pub amount: u64,
+    pub currency: Option<Currency>,
+    pub channel: Option<PaymentChannel>,
+    pub paid_at: Option<String>,
+    pub transaction_id: Option<u64>,                // Numeric id (Paystack)
+    pub provider_transaction_id: Option<String>,    // String id (Stripe payment intent, M-Pesa receipt)
+    pub authorization_code: Option<String>,         // Reusable authorization for renewals
+    pub customer_email: Option<String>,
+    pub customer_name: Option<String>,
+    pub fees: Option<u64>,
+}
+
+/// Result of a refund request
+#[derive(Clone, Debug)]
+pub struct ProviderRefund {
+    pub refund_id: String,
+    pub amount: u64,
+    pub status: String,     // Provider status, e.g. "pending", "processed", "succeeded"
+}
+
+/// How a webhook identifies the payment it is about
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaymentLookup {
+    Reference(String),      // Our reference (Paystack, Stripe client_reference_id)
+    AccessCode(String),     // Provider handle stored in `access_code` (Daraja CheckoutRequestID)
+}
+
+/// A webhook event reduced to what the payment service needs
+#[derive(Clone, Debug)]
+pub struct ProviderEvent {
+    pub lookup: PaymentLookup,
+    pub status: PaymentStatus,
+    pub channel: Option<PaymentChannel>,
+    pub transaction_id: Option<u64>,
+    pub provider_transaction_id: Option<String>,
+    pub authorization_code: Option<String>,
+}
+
+/// Common interface of all payment gateways
+pub trait PaymentProvider {
+    fn gateway(&self) -> PaymentGateway;
+
+    /// Start a payment and return where to send the customer
+    fn initialize(
+        &self,
+        request: ProviderInitRequest,
+    ) -> impl Future<Output = Result<ProviderInitResponse, String>>;
+
+    /// Ask the provider for the current state of a payment
+    fn verify(
+        &self,
+        payment: &PaymentRecord,
+    ) -> impl Future<Output = Result<ProviderVerification, String>>;
+
+    /// Refund a payment, fully (`None`) or partially
+    fn refund(
+        &self,
+        payment: &PaymentRecord,
+        amount: Option<u64>,
+        reason: &str,
+    ) -> impl Future<Output = Result<ProviderRefund, String>>;
+
+    /// Check that a webhook really comes from the provider
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<(), String>;
+
+    /// Extract the payment update from a verified webhook. `Ok(None)` for events we ignore.
+    fn parse_webhook(&self, payload: &str) -> Result<Option<ProviderEvent>, String>;
+}
+
+/// Dispatches to the provider selected for a payment
+pub enum Provider<T: HttpTransport> {
+    Paystack(PaystackProvider<T>),
+    Stripe(StripeProvider<T>),
+    Daraja(DarajaProvider<T>),
+}
+
+impl<T: HttpTransport> Provider<T> {
+    /// `now` feeds Stripe's signature tolerance and Daraja's request timestamps
+    pub fn for_gateway(gateway: PaymentGateway, http: T, now: u64) -> Self {
+        match gateway {
+            PaymentGateway::Paystack => Provider::Paystack(PaystackProvider::new(http)),
+            PaymentGateway::Stripe => Provider::Stripe(StripeProvider::new(http, now)),
+            PaymentGateway::Daraja => Provider::Daraja(DarajaProvider::new(http, now)),
+        }
+    }
+}
+
+impl<T: HttpTransport> PaymentProvider for Provider<T> {
+    fn gateway(&self) -> PaymentGateway {
+        match self {
+            Provider::Paystack(p) => p.gateway(),
+            Provider::Stripe(p) => p.gateway(),
+            Provider::Daraja(p) => p.gateway(),
+        }
+    }
+
+    async fn initialize(&self, request: ProviderInitRequest) -> Result<ProviderInitResponse, String> {
+        match self {
+            Provider::Paystack(p) => p.initialize(request).await,
+            Provider::Stripe(p) => p.initialize(request).await,
+            Provider::Daraja(p) => p.initialize(request).await,
+        }
+    }
+
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        match self {
+            Provider::Paystack(p) => p.verify(payment).await,
+            Provider::Stripe(p) => p.verify(payment).await,
+            Provider::Daraja(p) => p.verify(payment).await,
+        }
+    }
+
+    async fn refund(
+        &self,
+        payment: &PaymentRecord,
+        amount: Option<u64>,
+        reason: &str,
+    ) -> Result<ProviderRefund, String> {
+        match self {
+            Provider::Paystack(p) => p.refund(payment, amount, reason).await,
+            Provider::Stripe(p) => p.refund(payment, amount, reason).await,
+            Provider::Daraja(p) => p.refund(payment, amount, reason).await,
+        }
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<(), String> {
+        match self {
+            Provider::Paystack(p) => p.verify_webhook(payload, signature),
+            Provider::Stripe(p) => p.verify_webhook(payload, signature),
+            Provider::Daraja(p) => p.verify_webhook(payload, signature),
+        }
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<Option<ProviderEvent>, String> {
+        match self {
+            Provider::Paystack(p) => p.parse_webhook(payload),
+            Provider::Stripe(p) => p.parse_webhook(payload),
+            Provider::Daraja(p) => p.parse_webhook(payload),
+        }
+    }
+}
+
+/// HTTP status as a plain number (0 if it does not fit)
+pub fn status_code(response: &HttpResponse) -> u32 {
+    u32::try_from(response.status.0.clone()).unwrap_or(0)
+}
+
+/// Read a non-2xx response as an error, otherwise return the body as text
+pub fn response_text(response: HttpResponse) -> Result<String, String> {
+    let status = status_code(&response);
+    let ok = (200..300).contains(&status);
+    let text = String::from_utf8(response.body)
+        .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
+
+    if ok {
+        Ok(text)
+    } else {
+        Err(format!("Provider API returned status {}: {}", status, text))
+    }
+}
+
+/// Shorthand for building request headers
+pub fn header(name: &str, value: String) -> HttpHeader {
+    HttpHeader {
+        name: name.to_string(),
+        value,
+    }
+}
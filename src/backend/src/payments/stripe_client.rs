@@ -0,0 +1,304 @@
+//! Stripe Checkout client
+//!
+//! Payments go through hosted Checkout Sessions. Our payment reference is
+//! sent as `client_reference_id` and the session id is kept in the payment
+//! record's `access_code` so the session can be looked up again on verify.
+
+use ic_cdk::api::management_canister::http_request::HttpHeader;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::models::payment::{Currency, PaymentChannel, PaymentGateway, PaymentRecord, PaymentStatus};
+use crate::payments::provider::{
+    header, response_text, HttpCall, HttpTransport, PaymentLookup, PaymentProvider,
+    ProviderEvent, ProviderInitRequest, ProviderInitResponse, ProviderRefund,
+    ProviderVerification,
+};
+use crate::payments::get_stripe_config;
+
+const STRIPE_API_BASE: &str = "https://api.stripe.com/v1";
+const WEBHOOK_TOLERANCE_SECS: u64 = 5 * 60; // Stripe's recommended replay window
+
+/// Checkout Session object (only the fields we use)
+#[derive(Deserialize, Clone, Debug)]
+pub struct CheckoutSession {
+    pub id: String,
+    pub url: Option<String>,
+    pub status: Option<String>,             // "open", "complete", "expired"
+    pub payment_status: String,             // "paid", "unpaid", "no_payment_required"
+    pub client_reference_id: Option<String>,
+    pub payment_intent: Option<String>,
+    pub amount_total: Option<u64>,
+    pub currency: Option<String>,
+    pub customer_details: Option<CustomerDetails>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CustomerDetails {
+    pub email: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Refund object (only the fields we use)
+#[derive(Deserialize, Clone, Debug)]
+pub struct StripeRefund {
+    pub id: String,
+    pub amount: u64,
+    pub status: String,     // "pending", "succeeded", "failed", "canceled"
+}
+
+/// Stripe behind the `PaymentProvider` interface
+pub struct StripeProvider<T: HttpTransport> {
+    http: T,
+    now: u64,
+}
+
+impl<T: HttpTransport> StripeProvider<T> {
+    pub fn new(http: T, now: u64) -> Self {
+        StripeProvider { http, now }
+    }
+
+    /// POST a form-encoded body. The idempotency key makes replicated outcalls safe.
+    async fn post_form(&self, path: &str, params: &[(String, String)], idempotency_key: &str) -> Result<String, String> {
+        let mut headers = auth_headers()?;
+        headers.push(header("Content-Type", "application/x-www-form-urlencoded".to_string()));
+        headers.push(header("Idempotency-Key", idempotency_key.to_string()));
+
+        let url = format!("{}{}", STRIPE_API_BASE, path);
+        let response = self.http.send(HttpCall::post(url, headers, form_encode(params))).await?;
+        response_text(response)
+    }
+}
+
+impl<T: HttpTransport> PaymentProvider for StripeProvider<T> {
+    fn gateway(&self) -> PaymentGateway {
+        PaymentGateway::Stripe
+    }
+
+    async fn initialize(&self, request: ProviderInitRequest) -> Result<ProviderInitResponse, String> {
+        let separator = if request.callback_url.contains('?') { '&' } else { '?' };
+        let params = vec![
+            ("mode".to_string(), "payment".to_string()),
+            ("client_reference_id".to_string(), request.reference.clone()),
+            ("customer_email".to_string(), request.email.clone()),
+            ("success_url".to_string(), format!("{}{}reference={}", request.callback_url, separator, request.reference)),
+            ("cancel_url".to_string(), request.callback_url.clone()),
+            ("line_items[0][quantity]".to_string(), "1".to_string()),
+            ("line_items[0][price_data][currency]".to_string(), request.currency.as_str().to_lowercase()),
+            ("line_items[0][price_data][unit_amount]".to_string(), request.amount.to_string()),
+            (
+                "line_items[0][price_data][product_data][name]".to_string(),
+                format!("Infoundr {} ({})", request.tier, request.billing_period),
+            ),
+            ("metadata[user_id]".to_string(), request.user_id.clone()),
+            ("metadata[tier]".to_string(), request.tier.clone()),
+            ("metadata[billing_period]".to_string(), request.billing_period.clone()),
+        ];
+
+        let body = self.post_form("/checkout/sessions", &params, &request.reference).await?;
+        let session: CheckoutSession = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, body))?;
+
+        Ok(ProviderInitResponse {
+            authorization_url: session.url,
+            access_code: Some(session.id),
+            message: "Checkout session created".to_string(),
+        })
+    }
+
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        let session_id = payment
+            .access_code
+            .clone()
+            .ok_or_else(|| "Payment has no Stripe checkout session".to_string())?;
+
+        let url = format!("{}/checkout/sessions/{}", STRIPE_API_BASE, session_id);
+        let response = self.http.send(HttpCall::get(url, auth_headers()?)).await?;
+        let body = response_text(response)?;
+        let session: CheckoutSession = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, body))?;
+
+        let customer = session.customer_details.clone();
+        Ok(ProviderVerification {
+            status: session_status(&session),
+            amount: session.amount_total.unwrap_or(payment.amount),
+            currency: session.currency.as_deref().and_then(Currency::from_code),
+            channel: Some(PaymentChannel::Card),
+            paid_at: None,
+            transaction_id: None,
+            provider_transaction_id: session.payment_intent,
+            authorization_code: None,
+            customer_email: customer.as_ref().and_then(|c| c.email.clone()),
+            customer_name: customer.and_then(|c| c.name),
+            fees: None,
+        })
+    }
+
+    async fn refund(
+        &self,
+        payment: &PaymentRecord,
+        amount: Option<u64>,
+        reason: &str,
+    ) -> Result<ProviderRefund, String> {
+        let payment_intent = payment
+            .provider_transaction_id
+            .clone()
+            .ok_or_else(|| "Payment has no Stripe payment intent to refund".to_string())?;
+
+        let mut params = vec![
+            ("payment_intent".to_string(), payment_intent),
+            ("reason".to_string(), "requested_by_customer".to_string()),
+            ("metadata[reference]".to_string(), payment.paystack_reference.clone()),
+        ];
+        if let Some(amount) = amount {
+            params.push(("amount".to_string(), amount.to_string()));
+        }
+        if !reason.is_empty() {
+            params.push(("metadata[note]".to_string(), reason.to_string()));
+        }
+
+        let idempotency_key = format!("refund-{}-{}", payment.paystack_reference, self.now);
+        let body = self.post_form("/refunds", &params, &idempotency_key).await?;
+        let refund: StripeRefund = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse response: {}. Response: {}", e, body))?;
+
+        Ok(ProviderRefund {
+            refund_id: refund.id,
+            amount: refund.amount,
+            status: refund.status,
+        })
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<(), String> {
+        let secret = get_stripe_config().webhook_secret;
+        if secret.is_empty() {
+            return Err("Stripe webhook secret not configured".to_string());
+        }
+        verify_stripe_signature(payload, signature, &secret, self.now)
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<Option<ProviderEvent>, String> {
+        let event: serde_json::Value = serde_json::from_str(payload)
+            .map_err(|e| format!("Invalid webhook payload: {}", e))?;
+
+        let event_type = event["type"].as_str().unwrap_or_default();
+        if !event_type.starts_with("checkout.session.") {
+            return Ok(None);
+        }
+
+        let session: CheckoutSession = serde_json::from_value(event["data"]["object"].clone())
+            .map_err(|e| format!("Invalid checkout session in webhook: {}", e))?;
+
+        let status = match event_type {
+            "checkout.session.completed" | "checkout.session.async_payment_succeeded" => session_status(&session),
+            "checkout.session.async_payment_failed" => PaymentStatus::Failed,
+            "checkout.session.expired" => PaymentStatus::Abandoned,
+            _ => return Ok(None),
+        };
+
+        let reference = session
+            .client_reference_id
+            .clone()
+            .ok_or("Missing client_reference_id in checkout session")?;
+
+        Ok(Some(ProviderEvent {
+            lookup: PaymentLookup::Reference(reference),
+            status,
+            channel: Some(PaymentChannel::Card),
+            transaction_id: None,
+            provider_transaction_id: session.payment_intent,
+            authorization_code: None,
+        }))
+    }
+}
+
+/// Map a Checkout Session onto our payment status
+fn session_status(session: &CheckoutSession) -> PaymentStatus {
+    match (session.payment_status.as_str(), session.status.as_deref()) {
+        ("paid", _) | ("no_payment_required", _) => PaymentStatus::Success,
+        (_, Some("expired")) => PaymentStatus::Abandoned,
+        _ => PaymentStatus::Pending,
+    }
+}
+
+fn auth_headers() -> Result<Vec<HttpHeader>, String> {
+    let secret_key = get_stripe_config().secret_key;
+    if secret_key.is_empty() {
+        return Err("Stripe not configured. Please set API keys.".to_string());
+    }
+
+    Ok(vec![header("Authorization", format!("Bearer {}", secret_key))])
+}
+
+/// Verify a `Stripe-Signature` header (`t=<unix>,v1=<hex hmac>[,v1=...]`).
+///
+/// The signed payload is `"{t}.{body}"`, signed with HMAC-SHA256 using the
+/// endpoint secret. Signatures older than five minutes are rejected.
+pub fn verify_stripe_signature(payload: &str, signature: &str, secret: &str, now: u64) -> Result<(), String> {
+    let mut timestamp: Option<u64> = None;
+    let mut candidates: Vec<&str> = Vec::new();
+    for part in signature.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse().ok(),
+            Some(("v1", value)) => candidates.push(value),
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or("Missing timestamp in Stripe signature")?;
+    let now_secs = now / 1_000_000_000;
+    if now_secs.abs_diff(timestamp) > WEBHOOK_TOLERANCE_SECS {
+        return Err("Stripe signature timestamp outside tolerance".to_string());
+    }
+
+    let signed_payload = format!("{}.{}", timestamp, payload);
+    let expected = hex::encode(hmac_sha256(secret.as_bytes(), signed_payload.as_bytes()));
+
+    if candidates.iter().any(|candidate| *candidate == expected) {
+        Ok(())
+    } else {
+        Err("Invalid webhook signature".to_string())
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104)
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.update(message);
+    let inner_hash = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(block.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(inner_hash);
+    outer.finalize().into()
+}
+
+/// `application/x-www-form-urlencoded` body
+fn form_encode(params: &[(String, String)]) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
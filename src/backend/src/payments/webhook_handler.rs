@@ -1,7 +1,11 @@
 use crate::payments::paystack_models::{PaystackWebhookEvent, parse_payment_channel};
-use crate::models::payment::PaymentStatus;
-use crate::storage::memory::PAYMENT_RECORDS;
-use crate::models::stable_string::StableString;
+use crate::payments::provider::{IcHttpTransport, PaymentProvider, Provider};
+use crate::models::payment::{PaymentGateway, PaymentStatus};
+use crate::models::webhook_event::WebhookSource;
+use crate::services::payment_service::{apply_provider_event, fulfil_payment_at, update_payment_status, PaymentConfirmation};
+use crate::services::refund_service::{complete_refund, fail_refund, find_refund};
+use crate::services::webhook_service::{begin_reprocess, begin_webhook_event, finish_webhook_event};
+use crate::models::payment::RefundRecord;
 use sha2::{Sha512, Digest};
 use hex;
 
@@ -25,11 +29,60 @@ pub fn verify_webhook_signature(
     }
 }
 
+/// Record a verified webhook, reject it if it was already handled, and process it
+pub async fn receive_webhook(
+    source: WebhookSource,
+    gateway: PaymentGateway,
+    payload: &str,
+    now: u64,
+) -> Result<String, String> {
+    let event_id = begin_webhook_event(source, gateway, payload, now)?;
+    let outcome = handle_webhook(source, gateway, payload, now).await;
+    finish_webhook_event(&event_id, &outcome, now);
+    outcome
+}
+
+/// Process a failed webhook event again (admin retry)
+pub async fn reprocess_webhook_event(event_id: &str, now: u64) -> Result<String, String> {
+    let (event, payload) = begin_reprocess(event_id)?;
+    let outcome = handle_webhook(event.source, event.gateway, &payload, now).await;
+    finish_webhook_event(event_id, &outcome, now);
+    outcome
+}
+
+/// Run a webhook through the handler of the endpoint that received it
+async fn handle_webhook(
+    source: WebhookSource,
+    gateway: PaymentGateway,
+    payload: &str,
+    now: u64,
+) -> Result<String, String> {
+    match source {
+        WebhookSource::PaystackWebhook => {
+            let event = validate_webhook_payload(payload)?;
+            process_webhook_event(event, now).await
+        }
+        WebhookSource::ProviderWebhook => {
+            let provider = Provider::for_gateway(gateway, IcHttpTransport, now);
+            match provider.parse_webhook(payload)? {
+                Some(event) => {
+                    let status = event.status.clone();
+                    let reference = apply_provider_event(event, now)?;
+                    Ok(format!("Payment {} updated: {:?}", reference, status))
+                }
+                None => Ok("Unhandled event type".to_string()),
+            }
+        }
+    }
+}
+
 /// Process a Paystack webhook event
-pub async fn process_webhook_event(event: PaystackWebhookEvent) -> Result<String, String> {
+pub async fn process_webhook_event(event: PaystackWebhookEvent, now: u64) -> Result<String, String> {
     match event.event.as_str() {
-        "charge.success" => handle_charge_success(event.data).await,
-        "charge.failed" => handle_charge_failed(event.data).await,
+        "charge.success" => handle_charge_success(event.data, now).await,
+        "charge.failed" => handle_charge_failed(event.data, now).await,
+        "refund.processed" => handle_refund_processed(event.data, now).await,
+        "refund.failed" => handle_refund_failed(event.data, now).await,
         "transfer.success" => handle_transfer_success(event.data).await,
         "transfer.failed" => handle_transfer_failed(event.data).await,
         _ => {
@@ -40,7 +93,7 @@ pub async fn process_webhook_event(event: PaystackWebhookEvent) -> Result<String
 }
 
 /// Handle successful charge
-async fn handle_charge_success(data: String) -> Result<String, String> {
+async fn handle_charge_success(data: String, now: u64) -> Result<String, String> {
     // Parse JSON data
     let data_value: serde_json::Value = serde_json::from_str(&data)
         .map_err(|e| format!("Failed to parse webhook data: {}", e))?;
@@ -51,39 +104,33 @@ async fn handle_charge_success(data: String) -> Result<String, String> {
         .ok_or("Missing reference in webhook data")?
         .to_string();
 
-    // Update payment record
-    PAYMENT_RECORDS.with(|records| {
-        let mut map = records.borrow_mut();
-        let key = StableString::from(reference.clone());
-        
-        if let Some(mut payment) = map.get(&key) {
-            payment.status = PaymentStatus::Success;
-            payment.updated_at = ic_cdk::api::time();
-            payment.paid_at = Some(ic_cdk::api::time());
-            
-            // Extract additional data from webhook
-            if let Some(channel) = data_value["channel"].as_str() {
-                payment.payment_channel = parse_payment_channel(channel);
-            }
-            
-            if let Some(transaction_id) = data_value["id"].as_u64() {
-                payment.paystack_transaction_id = Some(transaction_id);
-            }
-            
-            map.insert(key, payment.clone());
-            
-            // Upgrade user subscription here
-            // This will be handled in the payment service
-            
-            Ok(format!("Payment {} marked as successful", reference))
-        } else {
-            Err(format!("Payment record not found for reference: {}", reference))
-        }
-    })
+    let channel = data_value["channel"].as_str().and_then(parse_payment_channel);
+    let transaction_id = data_value["id"].as_u64();
+
+    // Reusable card authorizations are kept for automatic renewals
+    let authorization_code = if data_value["authorization"]["reusable"].as_bool() == Some(true) {
+        data_value["authorization"]["authorization_code"].as_str().map(|code| code.to_string())
+    } else {
+        None
+    };
+
+    let confirmation = PaymentConfirmation {
+        channel,
+        transaction_id,
+        provider_transaction_id: None,
+        authorization_code,
+    };
+
+    // Mark the payment successful, upgrade the subscription and issue the invoice
+    if fulfil_payment_at(&reference, confirmation, now)? {
+        Ok(format!("Payment {} marked as successful", reference))
+    } else {
+        Ok(format!("Payment {} already fulfilled", reference))
+    }
 }
 
 /// Handle failed charge
-async fn handle_charge_failed(data: String) -> Result<String, String> {
+async fn handle_charge_failed(data: String, now: u64) -> Result<String, String> {
     // Parse JSON data
     let data_value: serde_json::Value = serde_json::from_str(&data)
         .map_err(|e| format!("Failed to parse webhook data: {}", e))?;
@@ -92,25 +139,50 @@ async fn handle_charge_failed(data: String) -> Result<String, String> {
         .as_str()
         .ok_or("Missing reference in webhook data")?
         .to_string();
+    let channel = data_value["channel"].as_str().and_then(parse_payment_channel);
 
-    PAYMENT_RECORDS.with(|records| {
-        let mut map = records.borrow_mut();
-        let key = StableString::from(reference.clone());
-        
-        if let Some(mut payment) = map.get(&key) {
-            payment.status = PaymentStatus::Failed;
-            payment.updated_at = ic_cdk::api::time();
-            
-            map.insert(key, payment);
-            
-            Ok(format!("Payment {} marked as failed", reference))
-        } else {
-            Err(format!("Payment record not found for reference: {}", reference))
-        }
-    })
+    // Leaves a fulfilled payment alone and gives a held coupon back
+    update_payment_status(&reference, PaymentStatus::Failed, channel, now)?;
+    Ok(format!("Payment {} marked as failed", reference))
+}
+
+/// Handle a refund that Paystack has paid out
+async fn handle_refund_processed(data: String, now: u64) -> Result<String, String> {
+    let refund = find_webhook_refund(&data)?;
+    let refund = complete_refund(&refund.id, now)?;
+    Ok(format!("Refund {} processed", refund.id))
+}
+
+/// Handle a refund that Paystack could not complete
+async fn handle_refund_failed(data: String, now: u64) -> Result<String, String> {
+    let refund = find_webhook_refund(&data)?;
+    let refund = fail_refund(&refund.id, "Refund failed at Paystack".to_string(), now)?;
+    Ok(format!("Refund {} marked as failed", refund.id))
+}
+
+/// Match a refund webhook to our refund record
+fn find_webhook_refund(data: &str) -> Result<RefundRecord, String> {
+    let data_value: serde_json::Value = serde_json::from_str(data)
+        .map_err(|e| format!("Failed to parse webhook data: {}", e))?;
+
+    // Paystack sends the refund id as a number or a string depending on the event version
+    let refund_id = match &data_value["id"] {
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::String(s) => Some(s.clone()),
+        _ => None,
+    };
+    let reference = data_value["transaction_reference"].as_str();
+    let amount = match &data_value["amount"] {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+
+    find_refund(refund_id.as_deref(), reference, amount)
+        .ok_or_else(|| format!("Refund not found for webhook (transaction: {:?})", reference))
 }
 
-/// Handle successful transfer (for refunds)
+/// Handle successful transfer (payouts, not used for refunds)
 async fn handle_transfer_success(_data: String) -> Result<String, String> {
     Ok("Transfer success processed".to_string())
 }
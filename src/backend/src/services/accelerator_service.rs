@@ -11,6 +11,14 @@ use crate::models::startup_invite::{StartupInvite, InviteType, InviteStatus};
 use crate::storage::memory::STARTUP_INVITES;
 use crate::models::user::{User, SubscriptionTier};
 use crate::storage::memory::USERS;
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::admin::require_permission;
+use crate::services::auth::caller_is_authenticated;
+use crate::services::sponsorship_service::{find_accelerator, is_team_member};
+use crate::models::record::check_record_size;
+use crate::storage::blob_store::{delete_blob, get_blob, put_blob};
 // use crate::services::auth::register_startup;
 use ic_cdk::api::time;  
 
@@ -26,7 +34,7 @@ pub struct AcceleratorSignUp {
     pub email: String,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn sign_up_accelerator(input: AcceleratorSignUp) -> Result<String, String> {
     let caller_principal = caller();
     let accelerator_id = StablePrincipal::new(caller_principal);
@@ -81,8 +89,13 @@ pub fn get_accelerator_by_id(id: StablePrincipal) -> Result<Option<Accelerator>,
     Ok(accelerator)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn get_my_accelerator() -> Result<Option<Accelerator>, String> {
+    Ok(my_accelerator()?.map(with_logo))
+}
+
+/// The caller's accelerator as stored, without its logo
+fn my_accelerator() -> Result<Option<Accelerator>, String> {
     let caller_principal = caller();
     
     // Search through all accelerators to find one where the caller is an active team member
@@ -107,7 +120,7 @@ pub fn get_my_accelerator() -> Result<Option<Accelerator>, String> {
     }
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_accelerator(id: StablePrincipal, updates: AcceleratorUpdate) -> Result<(), String> {
     let caller_principal = caller();
     
@@ -118,6 +131,7 @@ pub fn update_accelerator(id: StablePrincipal, updates: AcceleratorUpdate) -> Re
 
     let mut accelerator = get_accelerator_by_id(id.clone())?
         .ok_or("Accelerator not found")?;
+    let before = digest(&accelerator);
 
     if let Some(name) = updates.name {
         accelerator.name = name;
@@ -131,9 +145,6 @@ pub fn update_accelerator(id: StablePrincipal, updates: AcceleratorUpdate) -> Re
     if let Some(email_verified) = updates.email_verified {
         accelerator.email_verified = email_verified;
     }
-    if let Some(logo) = updates.logo {
-        accelerator.logo = logo;
-    }
     if let Some(total_startups) = updates.total_startups {
         accelerator.total_startups = total_startups;
     }
@@ -147,6 +158,12 @@ pub fn update_accelerator(id: StablePrincipal, updates: AcceleratorUpdate) -> Re
         accelerator.graduated_startups = graduated_startups;
     }
 
+    check_record_size(&accelerator)?;
+    if let Some(logo) = updates.logo {
+        set_logo(&mut accelerator, logo)?;
+    }
+
+    audit(AuditAction::AcceleratorUpdated, id.to_string(), Some(before), Some(digest(&accelerator)));
     ACCELERATORS.with(|accs| accs.borrow_mut().insert(id, accelerator));
     Ok(())
 }
@@ -157,7 +174,7 @@ pub struct AcceleratorUpdateWithId {
     pub updates: AcceleratorUpdate,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_my_accelerator(input: AcceleratorUpdateWithId) -> Result<(), String> {
     let caller_principal = caller();
     let accelerator = ACCELERATORS.with(|accs| {
@@ -173,6 +190,7 @@ pub fn update_my_accelerator(input: AcceleratorUpdateWithId) -> Result<(), Strin
     if !is_admin {
         return Err("Only SuperAdmins or Admins can update accelerator".to_string());
     }
+    let before = digest(&accelerator);
     // Apply updates
     if let Some(name) = input.updates.name {
         accelerator.name = name;
@@ -186,9 +204,6 @@ pub fn update_my_accelerator(input: AcceleratorUpdateWithId) -> Result<(), Strin
     if let Some(email_verified) = input.updates.email_verified {
         accelerator.email_verified = email_verified;
     }
-    if let Some(logo) = input.updates.logo {
-        accelerator.logo = logo;
-    }
     if let Some(total_startups) = input.updates.total_startups {
         accelerator.total_startups = total_startups;
     }
@@ -201,6 +216,11 @@ pub fn update_my_accelerator(input: AcceleratorUpdateWithId) -> Result<(), Strin
     if let Some(graduated_startups) = input.updates.graduated_startups {
         accelerator.graduated_startups = graduated_startups;
     }
+    check_record_size(&accelerator)?;
+    if let Some(logo) = input.updates.logo {
+        set_logo(&mut accelerator, logo)?;
+    }
+    audit(AuditAction::AcceleratorUpdated, input.accelerator_id.clone(), Some(before), Some(digest(&accelerator)));
     // Save updated accelerator
     ACCELERATORS.with(|accs| {
         let key = accs.borrow().iter().find(|(k, _)| k.to_string() == input.accelerator_id).map(|(k, _)| k.clone());
@@ -224,6 +244,55 @@ pub struct AcceleratorUpdate {
     pub graduated_startups: Option<u32>,
 }
 
+// ==================================================================================================
+// LOGOS
+// ==================================================================================================
+
+// Logos live in the blob store under the accelerator's principal; stored
+// accelerators keep `logo: None`. Older records may still hold theirs inline.
+
+pub fn logo_blob(id: &StablePrincipal) -> String {
+    format!("accelerator_logo/{}", id)
+}
+
+/// Replaces an accelerator's logo, or removes it with `None`
+pub fn set_logo(accelerator: &mut Accelerator, logo: Option<Vec<Vec<u8>>>) -> Result<(), String> {
+    let name = logo_blob(&accelerator.id);
+    match logo {
+        Some(logo) => put_blob(&name, &candid::encode_one(logo).map_err(|e| e.to_string())?)?,
+        None => {
+            delete_blob(&name);
+        }
+    }
+    accelerator.logo = None;
+    Ok(())
+}
+
+/// The accelerator with its logo read back from the blob store
+pub fn with_logo(mut accelerator: Accelerator) -> Accelerator {
+    if accelerator.logo.is_none() {
+        accelerator.logo = get_blob(&logo_blob(&accelerator.id)).and_then(|bytes| candid::decode_one(&bytes).ok());
+    }
+    accelerator
+}
+
+/// Moves logos still stored inline into the blob store; returns how many
+/// accelerators have one, or would be changed with `dry_run`
+pub fn move_inline_logos(dry_run: bool) -> Result<u32, String> {
+    let inline: Vec<(StablePrincipal, Accelerator)> = ACCELERATORS.with(|accs| {
+        accs.borrow().iter().filter(|(_, acc)| acc.logo.is_some()).collect()
+    });
+    let count = inline.len() as u32;
+    if !dry_run {
+        for (key, mut accelerator) in inline {
+            let logo = accelerator.logo.take();
+            set_logo(&mut accelerator, logo)?;
+            ACCELERATORS.with(|accs| accs.borrow_mut().insert(key, accelerator));
+        }
+    }
+    Ok(count)
+}
+
 // ==================================================================================================
 // TEAM MEMBER INVITES
 // ==================================================================================================
@@ -235,10 +304,10 @@ pub struct TeamMemberInviteWithId {
     pub name: String
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn invite_team_member(input: TeamMemberInviteWithId) -> Result<String, String> {
     let caller_principal = caller();
-    let accelerator = get_my_accelerator()?;
+    let accelerator = my_accelerator()?;
     let mut accelerator = match accelerator {
         Some(acc) => acc,
         None => return Err("Accelerator not found".to_string()),
@@ -263,15 +332,20 @@ pub fn invite_team_member(input: TeamMemberInviteWithId) -> Result<String, Strin
     OsRng.fill_bytes(&mut token_bytes);
     let token = BASE64.encode(&token_bytes);
 
-    accelerator.team_members.push(TeamMember {
+    let member = TeamMember {
         name: input.name,
         email: input.email,
         role: input.role,
         status: MemberStatus::Pending,
         token: Some(token.clone()),
         principal: None,
-    });
+    };
+    let member_digest = digest(&member);
+    let member_target = format!("{}:{}", accelerator.id, member.email);
+    accelerator.team_members.push(member);
     accelerator.invites_sent += 1;
+    check_record_size(&accelerator)?;
+    audit(AuditAction::TeamMemberInvited, member_target, None, Some(member_digest));
 
     ACCELERATORS.with(|accs| {
         let key = accs.borrow().iter().find(|(k, _)| k.to_string() == accelerator.id.to_string()).map(|(k, _)| k.clone());
@@ -282,7 +356,7 @@ pub fn invite_team_member(input: TeamMemberInviteWithId) -> Result<String, Strin
     Ok(token)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn accept_invitation(token: String) -> Result<(), String> {
     let caller_principal = caller();
     let mut found = false;
@@ -312,7 +386,7 @@ pub fn accept_invitation(token: String) -> Result<(), String> {
     }
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn decline_invitation(token: String) -> Result<(), String> {
     let caller_principal = caller();
     let mut found = false;
@@ -342,10 +416,10 @@ pub fn decline_invitation(token: String) -> Result<(), String> {
     }
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn list_team_members() -> Result<Vec<TeamMember>, String> {
     let caller_principal = caller();
-    let accelerator = get_my_accelerator()?;
+    let accelerator = my_accelerator()?;
     let accelerator = match accelerator {
         Some(acc) => acc,
         None => return Err("Accelerator not found".to_string()),
@@ -364,10 +438,10 @@ pub struct UpdateTeamMemberRole {
     pub new_role: Role,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_team_member_role(input: UpdateTeamMemberRole) -> Result<(), String> {
     let caller_principal = caller();
-    let accelerator = get_my_accelerator()?;
+    let accelerator = my_accelerator()?;
     let mut accelerator = match accelerator {
         Some(acc) => acc,
         None => return Err("Accelerator not found".to_string()),
@@ -391,17 +465,16 @@ pub fn update_team_member_role(input: UpdateTeamMemberRole) -> Result<(), String
     }
 
     // Find and update the member
-    let mut found = false;
+    let mut previous_role = None;
     for member in accelerator.team_members.iter_mut() {
         if member.email == input.email && member.status == MemberStatus::Active {
-            member.role = input.new_role.clone();
-            found = true;
+            previous_role = Some(std::mem::replace(&mut member.role, input.new_role.clone()));
             break;
         }
     }
-    if !found {
+    let Some(previous_role) = previous_role else {
         return Err("Team member not found or not active".to_string());
-    }
+    };
 
     // Prevent demoting last SuperAdmin
     if input.new_role != Role::SuperAdmin {
@@ -410,6 +483,7 @@ pub fn update_team_member_role(input: UpdateTeamMemberRole) -> Result<(), String
             return Err("Cannot demote the last SuperAdmin".to_string());
         }
     }
+    audit(AuditAction::TeamMemberRoleChanged, format!("{}:{}", accelerator.id, input.email), Some(digest(&previous_role)), Some(digest(&input.new_role)));
     // Save updated accelerator
     ACCELERATORS.with(|accs| {
         let key = accs.borrow().iter().find(|(k, _)| k.to_string() == accelerator.id.to_string()).map(|(k, _)| k.clone());
@@ -425,10 +499,10 @@ pub struct RemoveTeamMember {
     pub email: String,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn remove_team_member(input: RemoveTeamMember) -> Result<(), String> {
     let caller_principal = caller();
-    let accelerator = get_my_accelerator()?;
+    let accelerator = my_accelerator()?;
     let mut accelerator = match accelerator {   
         Some(acc) => acc,
         None => return Err("Accelerator not found".to_string()),
@@ -446,17 +520,16 @@ pub fn remove_team_member(input: RemoveTeamMember) -> Result<(), String> {
     }
 
     // Find and remove the member
-    let orig_len = accelerator.team_members.len();
-    accelerator.team_members.retain(|m| !(m.email == input.email && m.status == MemberStatus::Active));
-    if accelerator.team_members.len() == orig_len {
-        return Err("Team member not found or not active".to_string());
-    }
+    let position = accelerator.team_members.iter().position(|m| m.email == input.email && m.status == MemberStatus::Active)
+        .ok_or("Team member not found or not active")?;
+    let removed = accelerator.team_members.remove(position);
 
     // Prevent removing last SuperAdmin
     let super_admins = accelerator.team_members.iter().filter(|m| m.role == Role::SuperAdmin && m.status == MemberStatus::Active).count();
     if super_admins == 0 {
         return Err("Cannot remove the last SuperAdmin".to_string());
     }
+    audit(AuditAction::TeamMemberRemoved, format!("{}:{}", accelerator.id, input.email), Some(digest(&removed)), None);
     // Save updated accelerator
     ACCELERATORS.with(|accs| {
         let key = accs.borrow().iter().find(|(k, _)| k.to_string() == accelerator.id.to_string()).map(|(k, _)| k.clone());
@@ -513,7 +586,7 @@ pub struct GenerateStartupInviteInput {
     pub expiry_days: Option<u64>,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn generate_startup_invite(input: GenerateStartupInviteInput) -> Result<StartupInvite, String> {
     
     let caller_principal = caller();
@@ -593,7 +666,7 @@ pub struct StartupRegistrationInput {
     pub email: String,
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn accept_startup_invite(input: StartupRegistrationInput) -> Result<(), String> {
 
     let principal = caller();
@@ -627,6 +700,9 @@ pub fn accept_startup_invite(input: StartupRegistrationInput) -> Result<(), Stri
             inv.used_at = Some(now);
             inv.registered_principal = Some(principal);
             inv.registered_at = Some(now);
+            if inv.email.is_none() {
+                inv.email = Some(input.email.clone());
+            }
             // Store the startup registration data
             inv.startup_name = input.startup_name.clone();
             invites_mut.insert(StableString::new(&input.invite_code), inv);
@@ -636,8 +712,13 @@ pub fn accept_startup_invite(input: StartupRegistrationInput) -> Result<(), Stri
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn list_startup_invites(accelerator_id: String) -> Vec<StartupInvite> {
+    let is_member = find_accelerator(&accelerator_id).is_some_and(|acc| is_team_member(&acc, caller()));
+    if !is_member && require_permission(Permission::ViewUsers).is_err() {
+        return vec![];
+    }
+
     let now = ic_cdk::api::time();
     STARTUP_INVITES.with(|invites| {
         let mut invites_mut = invites.borrow_mut();
@@ -667,7 +748,7 @@ pub fn list_startup_invites(accelerator_id: String) -> Vec<StartupInvite> {
     })
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn revoke_startup_invite(invite_code: String) -> Result<(), String> {
     let principal = ic_cdk::caller();
     let now = ic_cdk::api::time();
@@ -701,7 +782,9 @@ pub fn revoke_startup_invite(invite_code: String) -> Result<(), String> {
                     invites.insert(StableString::new(&invite_code), invite);
                     return Err("Cannot revoke an invite that has already expired".to_string());
                 }
+                let before = digest(&invite);
                 invite.status = InviteStatus::Revoked;
+                audit(AuditAction::StartupInviteRevoked, invite_code.clone(), Some(before), Some(digest(&invite)));
                 invites.insert(StableString::new(&invite_code), invite);
                 Ok(())
             }
@@ -735,7 +818,7 @@ pub fn get_startup_invite_by_code(invite_code: String) -> Result<Option<StartupI
     }
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn link_startup_principal(startup_email: String, founder_name: String) -> Result<(), String> {
     let principal = caller();
     let now = time();
@@ -754,6 +837,17 @@ pub fn link_startup_principal(startup_email: String, founder_name: String) -> Re
 
     match startup_user {
         Some(mut user) => {
+            // Only the founder who redeemed an invite for this email may take over its account
+            let redeemed_invite = STARTUP_INVITES.with(|invites| {
+                invites.borrow().iter().any(|(_, invite)| {
+                    invite.registered_principal == Some(principal)
+                        && invite.email.as_deref() == Some(startup_email.as_str())
+                })
+            });
+            if !redeemed_invite {
+                return Err("No startup invite for this email was accepted by the caller".to_string());
+            }
+
             // Update the user's principal to link it
             user.principal = StablePrincipal::new(principal);
             user.name = format!("{} ({})", user.name.split(" (").next().unwrap_or("Startup"), founder_name);
@@ -3,21 +3,29 @@ use crate::models::connected_accounts::{AsanaAccount, ConnectedAccounts, GitHubA
 use crate::models::github::Issue;
 use crate::models::stable_string::StableString;
 use crate::models::task::Task;
-use crate::services::openchat_service::ensure_openchat_user;
-use crate::services::slack_service::ensure_slack_user;
-use crate::services::discord_service::ensure_discord_user;
-use crate::storage::memory::{OPENCHAT_USERS, CHAT_HISTORY, CONNECTED_ACCOUNTS, GITHUB_ISSUES, TASKS, SLACK_USERS, DISCORD_USERS};
+use crate::models::platform::Platform;
+use crate::models::record::check_record_size;
+use crate::services::identity_merge_service::next_chat_seq;
+use crate::services::platform_identity_service::{ensure_identity, playground_principal, resolve_principal};
+use crate::models::admin::Permission;
+use crate::models::bot_principal::BotPermission;
+use crate::services::admin::authorize;
+use crate::services::bot_service::authorize_bot;
+use crate::services::auth::caller_is_authenticated;
+use crate::storage::memory::{CHAT_HISTORY, CONNECTED_ACCOUNTS, GITHUB_ISSUES, TASKS};
 use candid::Principal;
-use ic_cdk::{query, update};
+use ic_cdk::{caller, query, update};
 
 // Asana connection management
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn store_asana_connection(
     identifier: UserIdentifier,
     token: String,
     workspace_id: String,
     project_ids: Vec<(String, String)>,
-) {
+) -> Result<(), String> {
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+
     let store_principal = get_principal_from_identifier(&identifier);
 
     CONNECTED_ACCOUNTS.with(|accounts| {
@@ -38,15 +46,18 @@ pub fn store_asana_connection(
 
         accounts.insert(store_principal.into(), user_accounts);
     });
+    Ok(())
 }
 
 // GitHub connection management
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn store_github_connection(
     identifier: UserIdentifier,
     token: String,
     selected_repo: Option<String>,
-) {
+) -> Result<(), String> {
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+
     let store_principal = get_principal_from_identifier(&identifier);
 
     CONNECTED_ACCOUNTS.with(|accounts| {
@@ -66,17 +77,24 @@ pub fn store_github_connection(
 
         accounts.insert(store_principal.into(), user_accounts);
     });
+    Ok(())
 }
 
 // Get user's connected accounts
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_user_connections(user: Principal) -> Option<ConnectedAccounts> {
+    if check_principal_access(caller(), user, Permission::ManageUsers).is_err() {
+        return None;
+    }
+
     CONNECTED_ACCOUNTS.with(|accounts| accounts.borrow().get(&user.into()))
 }
 
 // Update GitHub selected repository
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_github_selected_repo(identifier: UserIdentifier, repo: String) -> Result<(), String> {
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+
     let store_principal = get_principal_from_identifier(&identifier);
 
     CONNECTED_ACCOUNTS.with(|accounts| {
@@ -100,147 +118,22 @@ pub fn update_github_selected_repo(identifier: UserIdentifier, repo: String) ->
 }
 
 // Message History Management
-#[update]
-pub fn store_chat_message(identifier: UserIdentifier, message: ChatMessage) {
-    let store_principal = match &identifier {
-        UserIdentifier::Principal(principal) => *principal,
-        UserIdentifier::OpenChatId(openchat_id) => {
-            // Ensure OpenChat user exists
-            ensure_openchat_user(openchat_id.clone());
-
-            // Try to get linked principal, otherwise derive from openchat_id
-            OPENCHAT_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(openchat_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        Principal::from_text(openchat_id).unwrap_or_else(|_| Principal::anonymous())
-                    })
-            })
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            ensure_slack_user(slack_id.clone());
-
-            // Try to get linked principal, otherwise use a special Slack principal
-            SLACK_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(slack_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Slack messages
-                        // This is a deterministic way to create a principal from a Slack ID
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 5; // Special type for Slack
-                        // Use the first 28 bytes of the Slack ID
-                        let slack_bytes = slack_id.as_bytes();
-                        let len = std::cmp::min(slack_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            ensure_discord_user(discord_id.clone());
-
-            // Try to get linked principal, otherwise use a special Discord principal
-            DISCORD_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(discord_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Discord messages
-                        // This is a deterministic way to create a principal from a Discord ID
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 6; // Special type for Discord
-                        // Use the first 28 bytes of the Discord ID
-                        let discord_bytes = discord_id.as_bytes();
-                        let len = std::cmp::min(discord_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            // Create a special principal for Playground messages
-            // This is a deterministic way to create a principal from a Playground ID
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            // Use the first 28 bytes of the Playground ID
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            Principal::from_slice(&bytes)
-        }
-    };
+#[update(guard = "caller_is_authenticated")]
+pub fn store_chat_message(identifier: UserIdentifier, message: ChatMessage) -> Result<(), String> {
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+    check_record_size(&message)?;
 
-    // Store the message under the principal
-    CHAT_HISTORY.with(|history| {
-        let mut history = history.borrow_mut();
-        let message_id = history
-            .iter()
-            .filter(|((user_id, _), _)| user_id.get() == store_principal)
-            .count() as u64;
+    let store_principal = resolve_store_principal(&identifier);
 
-        // Add debug logging
-        ic_cdk::println!("Storing message with ID {} for principal {:?}", message_id, store_principal);
+    let message_id = next_chat_seq(store_principal);
 
-        history.insert((store_principal.into(), message_id), message.clone());
-    });
-
-    // If this is a Slack message, also store under the special Slack principal
-    if let UserIdentifier::SlackId(slack_id) = &identifier {
-        // Create the special Slack principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 5; // Special type for Slack
-        // Use the first 28 bytes of the Slack ID
-        let slack_bytes = slack_id.as_bytes();
-        let len = std::cmp::min(slack_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-        let slack_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Created special Slack principal: {:?}", slack_principal);
-
-        CHAT_HISTORY.with(|history| {
-            let mut history = history.borrow_mut();
-            let message_id = history
-                .iter()
-                .filter(|((user_id, _), _)| user_id.get() == slack_principal)
-                .count() as u64;
-
-            ic_cdk::println!("Storing message with ID {} for special Slack principal {:?}", message_id, slack_principal);
-
-            history.insert((slack_principal.into(), message_id), message.clone());
-        });
-    }
+    // Add debug logging
+    ic_cdk::println!("Storing message with ID {} for principal {:?}", message_id, store_principal);
 
-    // If this is a Discord message, also store under the special Discord principal
-    if let UserIdentifier::DiscordId(discord_id) = &identifier {
-        // Create the special Discord principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 6; // Special type for Discord
-        // Use the first 28 bytes of the Discord ID
-        let discord_bytes = discord_id.as_bytes();
-        let len = std::cmp::min(discord_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-        let discord_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Created special Discord principal: {:?}", discord_principal);
-
-        CHAT_HISTORY.with(|history| {
-            let mut history = history.borrow_mut();
-            let message_id = history
-                .iter()
-                .filter(|((user_id, _), _)| user_id.get() == discord_principal)
-                .count() as u64;
-
-            ic_cdk::println!("Storing message with ID {} for special Discord principal {:?}", message_id, discord_principal);
-
-            history.insert((discord_principal.into(), message_id), message.clone());
-        });
-    }
+    CHAT_HISTORY.with(|history| {
+        history.borrow_mut().insert((store_principal.into(), message_id), message);
+    });
+    Ok(())
 }
 
 // #[query]
@@ -255,14 +148,21 @@ pub fn store_chat_message(identifier: UserIdentifier, message: ChatMessage) {
 //     })
 // }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_chat_history(identifier: UserIdentifier) -> Vec<ChatMessage> {
     get_user_activity(identifier).chat_history
 }
 
 // Connected Accounts Status
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_connection_status(identifier: UserIdentifier) -> ConnectionStatus {
+    if check_identifier_access(caller(), &identifier, Permission::ViewUsers, BotPermission::ReadMessages).is_err() {
+        return ConnectionStatus::default();
+    }
+    connection_status(identifier)
+}
+
+fn connection_status(identifier: UserIdentifier) -> ConnectionStatus {
     let principal = get_principal_from_identifier(&identifier);
     CONNECTED_ACCOUNTS.with(|accounts| {
         let accounts = accounts.borrow();
@@ -288,8 +188,12 @@ pub fn get_connection_status(identifier: UserIdentifier) -> ConnectionStatus {
 }
 
 // GitHub Repository Management
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_current_repo(user: Principal) -> Option<String> {
+    if check_principal_access(caller(), user, Permission::ViewUsers).is_err() {
+        return None;
+    }
+
     CONNECTED_ACCOUNTS.with(|accounts| {
         accounts
             .borrow()
@@ -300,113 +204,18 @@ pub fn get_current_repo(user: Principal) -> Option<String> {
 }
 
 // Asana Tasks Management
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn store_asana_task(identifier: UserIdentifier, task: Task) -> Result<(), String> {
-    let store_principal = match &identifier {
-        UserIdentifier::Principal(principal) => *principal,
-        UserIdentifier::OpenChatId(openchat_id) => {
-            ensure_openchat_user(openchat_id.clone());
-
-            OPENCHAT_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(openchat_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        Principal::from_text(openchat_id).unwrap_or_else(|_| Principal::anonymous())
-                    })
-            })
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            SLACK_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(slack_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Slack messages
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 5; // Special type for Slack
-                        let slack_bytes = slack_id.as_bytes();
-                        let len = std::cmp::min(slack_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            DISCORD_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(discord_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Discord messages
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 6; // Special type for Discord
-                        let discord_bytes = discord_id.as_bytes();
-                        let len = std::cmp::min(discord_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            // Create a special principal for Playground messages
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            Principal::from_slice(&bytes)
-        }
-    };
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+
+    let store_principal = resolve_store_principal(&identifier);
 
-    // Store the task under the principal
     TASKS.with(|tasks| {
         let mut tasks = tasks.borrow_mut();
         let task_key = (store_principal.into(), StableString::from(task.id.clone()));
-        tasks.insert(task_key, task.clone());
+        tasks.insert(task_key, task);
     });
 
-    // If this is a Slack message, also store under the special Slack principal
-    if let UserIdentifier::SlackId(slack_id) = &identifier {
-        // Create the special Slack principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 5; // Special type for Slack
-        let slack_bytes = slack_id.as_bytes();
-        let len = std::cmp::min(slack_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-        let slack_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Storing task for special Slack principal: {:?}", slack_principal);
-
-        TASKS.with(|tasks| {
-            let mut tasks = tasks.borrow_mut();
-            let task_key = (slack_principal.into(), StableString::from(task.id.clone()));
-            tasks.insert(task_key, task.clone());
-        });
-    }
-
-    // If this is a Discord message, also store under the special Discord principal
-    if let UserIdentifier::DiscordId(discord_id) = &identifier {
-        // Create the special Discord principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 6; // Special type for Discord
-        let discord_bytes = discord_id.as_bytes();
-        let len = std::cmp::min(discord_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-        let discord_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Storing task for special Discord principal: {:?}", discord_principal);
-
-        TASKS.with(|tasks| {
-            let mut tasks = tasks.borrow_mut();
-            let task_key = (discord_principal.into(), StableString::from(task.id.clone()));
-            tasks.insert(task_key, task);
-        });
-    }
-
     Ok(())
 }
 
@@ -422,120 +231,25 @@ pub fn store_asana_task(identifier: UserIdentifier, task: Task) -> Result<(), St
 //     })
 // }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_user_tasks(identifier: UserIdentifier) -> Vec<Task> {
     get_user_activity(identifier).tasks
 }
 
 // GitHub Issues Management
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn store_github_issue(identifier: UserIdentifier, issue: Issue) -> Result<(), String> {
+    check_identifier_access(caller(), &identifier, Permission::ManageUsers, BotPermission::StoreMessages)?;
+
     ic_cdk::println!("Storing GitHub issue");
-    let store_principal = match &identifier {
-        UserIdentifier::Principal(principal) => *principal,
-        UserIdentifier::OpenChatId(openchat_id) => {
-            ensure_openchat_user(openchat_id.clone());
-
-            OPENCHAT_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(openchat_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        Principal::from_text(openchat_id).unwrap_or_else(|_| Principal::anonymous())
-                    })
-            })
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            SLACK_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(slack_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Slack messages
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 5; // Special type for Slack
-                        let slack_bytes = slack_id.as_bytes();
-                        let len = std::cmp::min(slack_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            DISCORD_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(discord_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Discord messages
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 6; // Special type for Discord
-                        let discord_bytes = discord_id.as_bytes();
-                        let len = std::cmp::min(discord_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            // Create a special principal for Playground messages
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            Principal::from_slice(&bytes)
-        }
-    };
+    let store_principal = resolve_store_principal(&identifier);
 
-    // Store the issue under the principal
     GITHUB_ISSUES.with(|issues| {
         let mut issues = issues.borrow_mut();
         let issue_key = (store_principal.into(), StableString::from(issue.id.clone()));
-        issues.insert(issue_key, issue.clone());
+        issues.insert(issue_key, issue);
     });
 
-    // If this is a Slack message, also store under the special Slack principal
-    if let UserIdentifier::SlackId(slack_id) = &identifier {
-        // Create the special Slack principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 5; // Special type for Slack
-        let slack_bytes = slack_id.as_bytes();
-        let len = std::cmp::min(slack_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-        let slack_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Storing issue for special Slack principal: {:?}", slack_principal);
-
-        GITHUB_ISSUES.with(|issues| {
-            let mut issues = issues.borrow_mut();
-            let issue_key = (slack_principal.into(), StableString::from(issue.id.clone()));
-            issues.insert(issue_key, issue.clone());
-        });
-    }
-
-    // If this is a Discord message, also store under the special Discord principal
-    if let UserIdentifier::DiscordId(discord_id) = &identifier {
-        // Create the special Discord principal
-        let mut bytes = [0u8; 29];
-        bytes[0] = 6; // Special type for Discord
-        let discord_bytes = discord_id.as_bytes();
-        let len = std::cmp::min(discord_bytes.len(), 28);
-        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-        let discord_principal = Principal::from_slice(&bytes);
-
-        ic_cdk::println!("Storing issue for special Discord principal: {:?}", discord_principal);
-
-        GITHUB_ISSUES.with(|issues| {
-            let mut issues = issues.borrow_mut();
-            let issue_key = (discord_principal.into(), StableString::from(issue.id.clone()));
-            issues.insert(issue_key, issue);
-        });
-    }
-
     Ok(())
 }
 
@@ -551,13 +265,13 @@ pub fn store_github_issue(identifier: UserIdentifier, issue: Issue) -> Result<()
 //     })
 // }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_user_issues(identifier: UserIdentifier) -> Vec<Issue> {
     get_user_activity(identifier).issues
 }
 
 // Helper struct for connection status
-#[derive(candid::CandidType, serde::Deserialize, Clone, Debug)]
+#[derive(candid::CandidType, serde::Deserialize, Clone, Debug, Default)]
 pub struct ConnectionStatus {
     pub asana_connected: bool,
     pub github_connected: bool,
@@ -566,8 +280,10 @@ pub struct ConnectionStatus {
 }
 
 // Utility function to check if accounts are properly connected
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn verify_connections(user: Principal) -> Result<(), Vec<String>> {
+    check_principal_access(caller(), user, Permission::ViewUsers).map_err(|e| vec![e])?;
+
     let mut errors = Vec::new();
 
     CONNECTED_ACCOUNTS.with(|accounts| {
@@ -609,51 +325,44 @@ pub fn verify_connections(user: Principal) -> Result<(), Vec<String>> {
 fn get_principal_from_identifier(identifier: &UserIdentifier) -> Principal {
     match identifier {
         UserIdentifier::Principal(principal) => *principal,
-        UserIdentifier::OpenChatId(openchat_id) => OPENCHAT_USERS.with(|users| {
-            users
-                .borrow()
-                .get(&StableString::from(openchat_id.clone()))
-                .and_then(|user| user.site_principal.map(|p| p.get()))
-                .unwrap_or_else(|| {
-                    Principal::from_text(openchat_id).unwrap_or_else(|_| Principal::anonymous())
-                })
-        }),
-        UserIdentifier::SlackId(slack_id) => SLACK_USERS.with(|users| {
-            users
-                .borrow()
-                .get(&StableString::from(slack_id.clone()))
-                .and_then(|user| user.site_principal.map(|p| p.get()))
-                .unwrap_or_else(|| {
-                    Principal::from_text(slack_id).unwrap_or_else(|_| Principal::anonymous())
-                })
-        }),
-        UserIdentifier::DiscordId(discord_id) => DISCORD_USERS.with(|users| {
-            users
-                .borrow()
-                .get(&StableString::from(discord_id.clone()))
-                .and_then(|user| user.site_principal.map(|p| p.get()))
-                .unwrap_or_else(|| {
-                    // Create a special principal for Discord messages
-                    // This is a deterministic way to create a principal from a Discord ID
-                    let mut bytes = [0u8; 29];
-                    bytes[0] = 6; // Special type for Discord
-                    // Use the first 28 bytes of the Discord ID
-                    let discord_bytes = discord_id.as_bytes();
-                    let len = std::cmp::min(discord_bytes.len(), 28);
-                    bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                    Principal::from_slice(&bytes)
-                })
-        }),
-        UserIdentifier::PlaygroundId(playground_id) => {
-            // Create a special principal for Playground messages
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            Principal::from_slice(&bytes)
+        UserIdentifier::PlaygroundId(playground_id) => playground_principal(playground_id),
+        platform_user => platform_user
+            .platform_id()
+            .map_or_else(Principal::anonymous, |(platform, external_id)| resolve_principal(platform, external_id)),
+    }
+}
+
+/// Registers a bot user on first contact and returns the principal to store under
+fn resolve_store_principal(identifier: &UserIdentifier) -> Principal {
+    if let Some((platform, external_id)) = identifier.platform_id() {
+        ensure_identity(platform, external_id, ic_cdk::api::time());
+    }
+    get_principal_from_identifier(identifier)
+}
+
+/// A principal's data is theirs; anyone else needs an admin `permission`
+pub fn check_principal_access(caller: Principal, principal: Principal, permission: Permission) -> Result<(), String> {
+    if caller == principal {
+        return Ok(());
+    }
+    authorize(caller, permission).map_err(|_| "Unauthorized: Not your account".to_string())
+}
+
+/// Platform ids are how the bots address their users, so a registered bot of
+/// that platform holding `bot_permission` may use them. Anyone else is checked
+/// like `check_principal_access` against the principal the identifier resolves to.
+pub fn check_identifier_access(
+    caller: Principal,
+    identifier: &UserIdentifier,
+    permission: Permission,
+    bot_permission: BotPermission,
+) -> Result<(), String> {
+    if let Some(platform) = identifier.bot_platform() {
+        if authorize_bot(caller, bot_permission, platform).is_ok() {
+            return Ok(());
         }
     }
+    check_principal_access(caller, get_principal_from_identifier(identifier), permission)
 }
 
 // Helper enum for user identification
@@ -664,10 +373,34 @@ pub enum UserIdentifier {
     SlackId(String),
     DiscordId(String),
     PlaygroundId(String),
+    TelegramId(String),
+    WhatsAppId(String),
+}
+
+impl UserIdentifier {
+    /// The platform account a bot identifier refers to
+    pub fn platform_id(&self) -> Option<(Platform, &str)> {
+        match self {
+            UserIdentifier::OpenChatId(id) => Some((Platform::OpenChat, id)),
+            UserIdentifier::SlackId(id) => Some((Platform::Slack, id)),
+            UserIdentifier::DiscordId(id) => Some((Platform::Discord, id)),
+            UserIdentifier::TelegramId(id) => Some((Platform::Telegram, id)),
+            UserIdentifier::WhatsAppId(id) => Some((Platform::WhatsApp, id)),
+            UserIdentifier::Principal(_) | UserIdentifier::PlaygroundId(_) => None,
+        }
+    }
+
+    /// The platform whose bot may act for this user; principals belong to none
+    pub fn bot_platform(&self) -> Option<Platform> {
+        match self {
+            UserIdentifier::PlaygroundId(_) => Some(Platform::Playground),
+            _ => self.platform_id().map(|(platform, _)| platform),
+        }
+    }
 }
 
 // Struct to hold all user activity
-#[derive(candid::CandidType, serde::Deserialize, Clone, Debug)]
+#[derive(candid::CandidType, serde::Deserialize, Clone, Debug, Default)]
 pub struct UserActivity {
     pub chat_history: Vec<ChatMessage>,
     pub tasks: Vec<Task>,
@@ -676,224 +409,56 @@ pub struct UserActivity {
 }
 
 // Query function to get all user activity
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_user_activity(identifier: UserIdentifier) -> UserActivity {
-    let principals_to_check = match &identifier {
-        UserIdentifier::Principal(principal) => {
-            // Check if this principal is linked to any platform account
-            let mut principals = vec![*principal];
-
-            // Check OpenChat
-            OPENCHAT_USERS.with(|users| {
-                let users = users.borrow();
-                // Find any OpenChat user linked to this principal
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also derive principal from OpenChat ID
-                            if let Ok(derived_principal) = Principal::from_text(&user.openchat_id) {
-                                principals.push(derived_principal);
-                            }
-                            break;
-                        }
-                    }
-                }
-            });
-
-            // Check Slack
-            SLACK_USERS.with(|users| {
-                let users = users.borrow();
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also create special Slack principal
-                            let mut bytes = [0u8; 29];
-                            bytes[0] = 5; // Special type for Slack
-                            let slack_bytes = user.slack_id.as_bytes();
-                            let len = std::cmp::min(slack_bytes.len(), 28);
-                            bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                            principals.push(Principal::from_slice(&bytes));
-                            break;
-                        }
-                    }
-                }
-            });
-
-            // Check Discord
-            DISCORD_USERS.with(|users| {
-                let users = users.borrow();
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also create special Discord principal
-                            let mut bytes = [0u8; 29];
-                            bytes[0] = 6; // Special type for Discord
-                            let discord_bytes = user.discord_id.as_bytes();
-                            let len = std::cmp::min(discord_bytes.len(), 28);
-                            bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                            principals.push(Principal::from_slice(&bytes));
-                            break;
-                        }
-                    }
-                }
-            });
-
-            principals
-        }
-        UserIdentifier::OpenChatId(openchat_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            OPENCHAT_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(openchat_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include derived principal from OpenChat ID
-            if let Ok(derived_principal) = Principal::from_text(openchat_id) {
-                principals.push(derived_principal);
-            }
-            principals
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            SLACK_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(slack_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include special Slack principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 5; // Special type for Slack
-            let slack_bytes = slack_id.as_bytes();
-            let len = std::cmp::min(slack_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            // Add debug logging
-            ic_cdk::println!("Slack ID: {}", slack_id);
-            ic_cdk::println!("Principals to check: {:?}", principals);
-
-            principals
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            DISCORD_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(discord_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include special Discord principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 6; // Special type for Discord
-            let discord_bytes = discord_id.as_bytes();
-            let len = std::cmp::min(discord_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            // Add debug logging
-            ic_cdk::println!("Discord ID: {}", discord_id);
-            ic_cdk::println!("Principals to check: {:?}", principals);
+    if check_identifier_access(caller(), &identifier, Permission::ViewUsers, BotPermission::ReadMessages).is_err() {
+        return UserActivity::default();
+    }
+    user_activity(identifier)
+}
 
-            principals
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            let mut principals = vec![];
-
-            // Create special Playground principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            principals
-        }
-    };
-
-    // Add debug logging for all principals
-    ic_cdk::println!("All principals to check: {:?}", principals_to_check);
-
-    // Collect all activity across all relevant principals
-    let mut all_chat_history = vec![];
-    let mut all_tasks = vec![];
-    let mut all_issues = vec![];
-    let mut connection_status = None;
-
-    for principal in principals_to_check {
-        // Get chat history
-        CHAT_HISTORY.with(|history| {
-            let history = history.borrow();
-            let messages: Vec<ChatMessage> = history
-                .iter()
-                .filter(|((user_id, _), _)| user_id.get() == principal)
-                .map(|(_, message)| message.clone())
-                .collect();
-            
-            // Add debug logging for found messages
-            ic_cdk::println!("Found {} messages for principal {:?}", messages.len(), principal);
-            
-            all_chat_history.extend(messages);
-        });
+pub(crate) fn user_activity(identifier: UserIdentifier) -> UserActivity {
+    // A linked platform account's records were merged into its principal on linking
+    let principal = get_principal_from_identifier(&identifier);
 
-        // Get tasks
-        TASKS.with(|tasks| {
-            all_tasks.extend(
-                tasks
-                    .borrow()
-                    .iter()
-                    .filter(|((user_id, _), _)| user_id.get() == principal)
-                    .map(|(_, task)| task.clone()),
-            );
-        });
+    // Get chat history
+    let all_chat_history: Vec<ChatMessage> = CHAT_HISTORY.with(|history| {
+        history
+            .borrow()
+            .iter()
+            .filter(|((user_id, _), _)| user_id.get() == principal)
+            .map(|(_, message)| message.clone())
+            .collect()
+    });
 
-        // Get issues
-        GITHUB_ISSUES.with(|issues| {
-            all_issues.extend(
-                issues
-                    .borrow()
-                    .iter()
-                    .filter(|((user_id, _), _)| user_id.get() == principal)
-                    .map(|(_, issue)| issue.clone()),
-            );
-        });
+    // Add debug logging for found messages
+    ic_cdk::println!("Found {} messages for principal {:?}", all_chat_history.len(), principal);
 
-        // Get connection status (use the first one found)
-        if connection_status.is_none() {
-            connection_status = Some(get_connection_status(identifier.clone()));
-        }
-    }
+    // Get tasks
+    let all_tasks = TASKS.with(|tasks| {
+        tasks
+            .borrow()
+            .iter()
+            .filter(|((user_id, _), _)| user_id.get() == principal)
+            .map(|(_, task)| task.clone())
+            .collect()
+    });
 
-    // Add debug logging for final results
-    ic_cdk::println!("Total chat history found: {}", all_chat_history.len());
+    // Get issues
+    let all_issues = GITHUB_ISSUES.with(|issues| {
+        issues
+            .borrow()
+            .iter()
+            .filter(|((user_id, _), _)| user_id.get() == principal)
+            .map(|(_, issue)| issue.clone())
+            .collect()
+    });
 
     UserActivity {
         chat_history: all_chat_history,
         tasks: all_tasks,
         issues: all_issues,
-        connection_status: connection_status.unwrap_or(ConnectionStatus {
-            asana_connected: false,
-            github_connected: false,
-            selected_repo: None,
-            asana_workspace: None,
-        }),
+        connection_status: connection_status(identifier),
     }
 }
 
@@ -5,46 +5,154 @@ use crate::models::accelerator::Accelerator;
 use crate::models::api_message::ApiMessage;
 use crate::models::usage_service::{UsageStats, UserTier, UserSubscription};
 use crate::models::payment::{PaymentRecord, Invoice};
-use crate::services::slack_service::get_registered_slack_users;
-use crate::services::discord_service::get_registered_discord_users;
+use crate::models::platform::Platform;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::services::platform_identity_service::list_identities;
 use crate::services::account_service::{UserIdentifier as AccountUserIdentifier};
-use crate::services::api_service::{get_api_message_history, get_api_messages_by_bot, get_recent_api_messages, UserIdentifier as ApiUserIdentifier};
-use crate::services::pricing_services::{get_usage_stats, get_user_tier, get_user_subscription, can_make_request};
-use crate::storage::memory::{USERS, WAITLIST, ACCELERATORS, ADMINS, SLACK_USERS, DISCORD_USERS, OPENCHAT_USERS, USER_SUBSCRIPTIONS, USER_DAILY_USAGE, PAYMENT_RECORDS, INVOICES};
+use crate::services::accelerator_service::{logo_blob, set_logo, with_logo};
+use crate::services::api_service::{get_api_message_history, get_api_messages_by_bot, get_recent_api_messages, with_response, UserIdentifier as ApiUserIdentifier};
+use crate::models::record::{check_record_size, split_record, VersionedRecord};
+use crate::storage::blob_store::delete_blob;
+use crate::services::payment_service::{get_payment_history, get_user_invoices};
+use crate::services::pricing_services::{get_quota_policy, get_usage_stats, get_user_tier, get_user_subscription, can_make_request, usage_today};
+use crate::storage::memory::{USERS, WAITLIST, ACCELERATORS, ADMINS, USER_SUBSCRIPTIONS, PAYMENT_RECORDS, INVOICES};
 use candid::Principal;
 use ic_cdk::{caller, query, update};
-use crate::models::admin::PlaygroundStats;
-use chrono::{Utc, TimeZone};
+use crate::models::admin::{Admin, AdminRole, Permission, PlaygroundStats};
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest, record_audit};
 
-// Admin callers
-#[query]
-pub fn is_allowed_principal() -> bool {
-    let allowed_principals = vec![
-        Principal::from_text("b3sqw-op7sx-26m67-mieei-h5cg4-qagvd-tpwkw-r2up5-dvtna-yp6dt-oqe")
-            .unwrap(),
-        Principal::from_text("hicyl-bvh4m-2x5wf-ozwt3-4kegq-nx5qh-neq7r-t46dn-e4ygv-kgf2r-6qe")
-            .unwrap(),
-        Principal::from_text("vgsl4-yf65u-gceur-wws44-arng2-vzjja-ozb5k-vs2cq-3cpay-3y3er-qqe")
-            .unwrap(),
-        Principal::from_text("kw5dl-qema7-52m2b-gbwaa-7sixr-gcty6-x5237-b4veb-vwabc-bahd3-dqe")
-            .unwrap(),
-    ];
-
-    let caller_principal = caller();
-    ic_cdk::println!("Caller Principal: {}", caller_principal.to_string());
-    ic_cdk::println!("Allowed Principals:");
-    for principal in &allowed_principals {
-        ic_cdk::println!("  {}", principal.to_string());
+// Admin authorization
+//
+// Admins and their roles live in `ADMINS`. Every admin endpoint declares the
+// `Permission` it needs and checks it with `require_permission`.
+
+fn admin_role(principal: Principal) -> Option<AdminRole> {
+    ADMINS.with(|admins| admins.borrow().get(&StablePrincipal::new(principal)).map(|admin| admin.role))
+}
+
+/// Checks that `principal` holds a role granting `permission`
+pub fn authorize(principal: Principal, permission: Permission) -> Result<(), String> {
+    match admin_role(principal) {
+        Some(role) if role.allows(permission) => Ok(()),
+        Some(_) => Err(format!("Unauthorized: {:?} permission required", permission)),
+        None => Err("Unauthorized: Caller is not an admin".to_string()),
+    }
+}
+
+/// `authorize` for the current caller
+pub fn require_permission(permission: Permission) -> Result<(), String> {
+    authorize(caller(), permission)
+}
+
+/// Guard for admin endpoints: rejects non-admins before the arguments are
+/// decoded. The endpoint still checks the permission it needs.
+pub fn caller_is_admin() -> Result<(), String> {
+    match admin_role(caller()) {
+        Some(_) => Ok(()),
+        None => Err("Unauthorized: Caller is not an admin".to_string()),
+    }
+}
+
+/// Makes each principal a SuperAdmin. Called from `init`/`post_upgrade` with
+/// the canister arguments, so access can be restored without a code change.
+pub fn bootstrap_admins(principals: &[Principal], now: u64) {
+    for principal in principals {
+        upsert_admin(*principal, AdminRole::SuperAdmin, now);
+    }
+}
+
+fn upsert_admin(principal: Principal, role: AdminRole, now: u64) {
+    let stable_principal = StablePrincipal::new(principal);
+    ADMINS.with(|admins| {
+        let mut admins = admins.borrow_mut();
+        let created_at = admins.get(&stable_principal).map(|admin| admin.created_at).unwrap_or(now);
+        admins.insert(stable_principal, Admin {
+            principal_id: principal.to_string(),
+            created_at,
+            role,
+        });
+    });
+}
+
+fn super_admin_count() -> usize {
+    ADMINS.with(|admins| {
+        admins.borrow()
+            .iter()
+            .filter(|(_, admin)| admin.role == AdminRole::SuperAdmin)
+            .count()
+    })
+}
+
+/// Add an admin; without a role they get read-only access
+pub fn add_admin_with_role(caller: Principal, admin_principal: Principal, role: AdminRole, now: u64) -> Result<(), String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    if admin_role(admin_principal).is_some() {
+        return Err("Admin already exists".to_string());
+    }
+    upsert_admin(admin_principal, role, now);
+    record_audit(caller, AuditAction::AdminAdded, admin_principal.to_text(), None, Some(digest(&role)), now);
+    Ok(())
+}
+
+/// Change an admin's role. The last SuperAdmin cannot be demoted.
+pub fn change_admin_role(caller: Principal, admin_principal: Principal, role: AdminRole, now: u64) -> Result<(), String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    let current = admin_role(admin_principal).ok_or("Admin not found")?;
+    if current == AdminRole::SuperAdmin && role != AdminRole::SuperAdmin && super_admin_count() <= 1 {
+        return Err("Cannot demote the last SuperAdmin".to_string());
     }
-    let is_allowed = allowed_principals.contains(&caller_principal);
-    ic_cdk::println!("Is Allowed: {}", is_allowed);
-    is_allowed
+    upsert_admin(admin_principal, role, now);
+    record_audit(caller, AuditAction::AdminRoleChanged, admin_principal.to_text(), Some(digest(&current)), Some(digest(&role)), now);
+    Ok(())
+}
+
+/// Remove an admin. The last SuperAdmin cannot be removed.
+pub fn remove_admin_as(caller: Principal, admin_principal: Principal, now: u64) -> Result<(), String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    let current = admin_role(admin_principal).ok_or("Admin not found")?;
+    if current == AdminRole::SuperAdmin && super_admin_count() <= 1 {
+        return Err("Cannot remove the last SuperAdmin".to_string());
+    }
+    ADMINS.with(|admins| {
+        admins.borrow_mut().remove(&StablePrincipal::new(admin_principal));
+    });
+    record_audit(caller, AuditAction::AdminRemoved, admin_principal.to_text(), Some(digest(&current)), None, now);
+    Ok(())
+}
+
+/// Rewrites admins stored in the byte layouts from before the record envelope
+/// as enveloped records. Returns how many changed, or would with `dry_run`.
+/// Records neither layout reads stay put for `admin_scan_records` to find.
+pub fn envelope_admin_records(dry_run: bool) -> u32 {
+    let rewritten: Vec<(StablePrincipal, Admin)> = ADMINS.with(|admins| {
+        admins
+            .borrow()
+            .raw_iter()
+            .filter(|(_, bytes)| split_record(bytes).map(|(version, _)| version) != Some(Admin::VERSION))
+            .filter_map(|(principal, bytes)| Admin::from_legacy_bytes(&bytes).ok().map(|admin| (principal, admin)))
+            .collect()
+    });
+    let count = rewritten.len() as u32;
+    if !dry_run {
+        ADMINS.with(|admins| {
+            let mut admins = admins.borrow_mut();
+            for (principal, admin) in rewritten {
+                admins.insert(principal, admin);
+            }
+        });
+    }
+    count
 }
 
 // Function to get total number of registered users
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_registered_users() -> Result<Vec<User>, String> {
-    // Get all registered users without admin check
+    require_permission(Permission::ViewUsers)?;
+
     let users = USERS.with(|u| {
         u.borrow()
             .iter()
@@ -55,19 +163,21 @@ pub fn get_registered_users() -> Result<Vec<User>, String> {
     Ok(users)
 }
 
+/// Whether the caller holds any admin role
 #[query]
 pub fn is_admin() -> bool {
-    ic_cdk::println!("Checking admin status for: {}", caller().to_string());
-    let result = is_allowed_principal();
-    ic_cdk::println!("Admin check result: {}", result);
-    result
+    admin_role(caller()).is_some()
 }
 
+/// The caller's admin role, if any
 #[query]
+pub fn get_my_admin_role() -> Option<AdminRole> {
+    admin_role(caller())
+}
+
+#[query(guard = "caller_is_admin")]
 pub fn get_waitlist() -> Result<Vec<WaitlistEntry>, String> {
-    // if !is_allowed_principal() {
-    //     return Err("Unauthorized: Caller is not an admin".to_string());
-    // }
+    require_permission(Permission::ViewUsers)?;
 
     let entries = WAITLIST.with(|w| {
         w.borrow()
@@ -79,11 +189,9 @@ pub fn get_waitlist() -> Result<Vec<WaitlistEntry>, String> {
     Ok(entries)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_users() -> Result<Vec<User>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let users = USERS.with(|u| {
         u.borrow()
@@ -95,75 +203,33 @@ pub fn get_users() -> Result<Vec<User>, String> {
     Ok(users)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_admins() -> Vec<StablePrincipal> {
-    // Return hardcoded admins
-    vec![
-        Principal::from_text("b3sqw-op7sx-26m67-mieei-h5cg4-qagvd-tpwkw-r2up5-dvtna-yp6dt-oqe")
-            .unwrap(),
-        Principal::from_text("hicyl-bvh4m-2x5wf-ozwt3-4kegq-nx5qh-neq7r-t46dn-e4ygv-kgf2r-6qe")
-            .unwrap(),
-        Principal::from_text("vgsl4-yf65u-gceur-wws44-arng2-vzjja-ozb5k-vs2cq-3cpay-3y3er-qqe")
-            .unwrap(),
-    ]
-    .into_iter()
-    .map(StablePrincipal::from)
-    .collect()
-}
-
-#[update]
-pub fn add_admin(admin_principal: Principal) -> Result<(), String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
-
-    let stable_principal = StablePrincipal::new(admin_principal);
-    
-    // Check if admin already exists
-    let exists = ADMINS.with(|admins| admins.borrow().contains_key(&stable_principal));
-    if exists {
-        return Err("Admin already exists".to_string());
+    if require_permission(Permission::ViewAdmins).is_err() {
+        return vec![];
     }
 
-    // Create new admin
-    let admin = crate::models::admin::Admin {
-        principal_id: admin_principal.to_string(),
-        created_at: ic_cdk::api::time(),
-    };
+    ADMINS.with(|admins| admins.borrow().iter().map(|(principal, _)| principal).collect())
+}
 
-    // Add new admin
-    ADMINS.with(|admins| {
-        admins.borrow_mut().insert(stable_principal, admin);
-    });
+#[update(guard = "caller_is_admin")]
+pub fn add_admin(admin_principal: Principal, role: Option<AdminRole>) -> Result<(), String> {
+    add_admin_with_role(caller(), admin_principal, role.unwrap_or(AdminRole::ReadOnly), ic_cdk::api::time())
+}
 
-    Ok(())
+#[update(guard = "caller_is_admin")]
+pub fn set_admin_role(admin_principal: Principal, role: AdminRole) -> Result<(), String> {
+    change_admin_role(caller(), admin_principal, role, ic_cdk::api::time())
 }
 
-#[update]
+#[update(guard = "caller_is_admin")]
 pub fn remove_admin(admin_principal: Principal) -> Result<(), String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
-
-    let stable_principal = StablePrincipal::new(admin_principal);
-    
-    // Check if admin exists
-    let exists = ADMINS.with(|admins| admins.borrow().contains_key(&stable_principal));
-    if !exists {
-        return Err("Admin not found".to_string());
-    }
-
-    // Remove admin
-    ADMINS.with(|admins| {
-        admins.borrow_mut().remove(&stable_principal);
-    });
-
-    Ok(())
+    remove_admin_as(caller(), admin_principal, ic_cdk::api::time())
 }
 
-#[query]
-pub fn get_admin_details() -> Vec<(StablePrincipal, crate::models::admin::Admin)> {
-    if !is_allowed_principal() {
+#[query(guard = "caller_is_admin")]
+pub fn get_admin_details() -> Vec<(StablePrincipal, Admin)> {
+    if require_permission(Permission::ViewAdmins).is_err() {
         return vec![];
     }
 
@@ -175,52 +241,47 @@ pub fn get_admin_details() -> Vec<(StablePrincipal, crate::models::admin::Admin)
     })
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_all_accelerators() -> Result<Vec<Accelerator>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let accelerators = ACCELERATORS.with(|accs| {
         accs.borrow()
             .iter()
-            .map(|(_, accelerator)| accelerator.clone())
+            .map(|(_, accelerator)| with_logo(accelerator))
             .collect::<Vec<Accelerator>>()
     });
 
     Ok(accelerators)
 }
 
-#[update]
+#[update(guard = "caller_is_admin")]
 pub fn delete_accelerator(accelerator_id: Principal) -> Result<(), String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ManageUsers)?;
 
     let stable_id = StablePrincipal::new(accelerator_id);
     
     // Check if accelerator exists
-    let exists = ACCELERATORS.with(|accs| accs.borrow().contains_key(&stable_id));
-    if !exists {
-        return Err("Accelerator not found".to_string());
-    }
+    let accelerator = ACCELERATORS.with(|accs| accs.borrow().get(&stable_id))
+        .ok_or("Accelerator not found")?;
 
     // Delete the accelerator
     ACCELERATORS.with(|accs| accs.borrow_mut().remove(&stable_id));
+    delete_blob(&logo_blob(&accelerator.id));
+    audit(AuditAction::AcceleratorDeleted, accelerator_id.to_text(), Some(digest(&accelerator)), None);
     Ok(())
 }
 
-#[update]
+#[update(guard = "caller_is_admin")]
 pub fn admin_update_accelerator(accelerator_id: Principal, updates: crate::services::accelerator_service::AcceleratorUpdate) -> Result<(), String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ManageUsers)?;
 
     let stable_id = StablePrincipal::new(accelerator_id);
     
     // Get the current accelerator
     let mut accelerator = ACCELERATORS.with(|accs| accs.borrow().get(&stable_id))
         .ok_or("Accelerator not found")?;
+    let before = digest(&accelerator);
 
     // Apply updates
     if let Some(name) = updates.name {
@@ -235,9 +296,6 @@ pub fn admin_update_accelerator(accelerator_id: Principal, updates: crate::servi
     if let Some(email_verified) = updates.email_verified {
         accelerator.email_verified = email_verified;
     }
-    if let Some(logo) = updates.logo {
-        accelerator.logo = logo;
-    }
     if let Some(total_startups) = updates.total_startups {
         accelerator.total_startups = total_startups;
     }
@@ -251,104 +309,93 @@ pub fn admin_update_accelerator(accelerator_id: Principal, updates: crate::servi
         accelerator.graduated_startups = graduated_startups;
     }
 
+    check_record_size(&accelerator)?;
+    if let Some(logo) = updates.logo {
+        set_logo(&mut accelerator, logo)?;
+    }
+
     // Save the updated accelerator
+    let after = digest(&accelerator);
     ACCELERATORS.with(|accs| accs.borrow_mut().insert(stable_id, accelerator));
+    audit(AuditAction::AcceleratorUpdated, accelerator_id.to_text(), Some(before), Some(after));
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_accelerator_by_id(accelerator_id: Principal) -> Result<Option<Accelerator>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let stable_id = StablePrincipal::new(accelerator_id);
     let accelerator = ACCELERATORS.with(|accs| accs.borrow().get(&stable_id));
-    Ok(accelerator)
+    Ok(accelerator.map(with_logo))
 }
 
 // Admin functions for platform user management
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_registered_slack_users_admin() -> Result<Vec<crate::models::slack_user::SlackUser>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
-
-    let users = SLACK_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .map(|(_, user)| user.clone())
-            .collect()
-    });
+    require_permission(Permission::ViewUsers)?;
 
-    Ok(users)
+    Ok(list_identities(Some(Platform::Slack)).into_iter().map(Into::into).collect())
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_registered_discord_users_admin() -> Result<Vec<crate::models::discord_user::DiscordUser>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
-    let users = DISCORD_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .map(|(_, user)| user.clone())
-            .collect()
-    });
-
-    Ok(users)
+    Ok(list_identities(Some(Platform::Discord)).into_iter().map(Into::into).collect())
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn get_registered_openchat_users_admin() -> Result<Vec<crate::models::openchat_user::OpenChatUser>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
-    let users = OPENCHAT_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .map(|(_, user)| user.clone())
-            .collect()
-    });
+    Ok(list_identities(Some(Platform::OpenChat)).into_iter().map(Into::into).collect())
+}
 
-    Ok(users)
+#[query(guard = "caller_is_admin")]
+pub fn get_registered_telegram_users_admin() -> Result<Vec<PlatformIdentity>, String> {
+    require_permission(Permission::ViewUsers)?;
+
+    Ok(list_identities(Some(Platform::Telegram)))
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
+pub fn get_registered_whatsapp_users_admin() -> Result<Vec<PlatformIdentity>, String> {
+    require_permission(Permission::ViewUsers)?;
+
+    Ok(list_identities(Some(Platform::WhatsApp)))
+}
+
+/// Users of the Slack, Discord, Telegram and WhatsApp bots, whose API
+/// messages the admin listings cover
+fn chat_bot_identities() -> Vec<PlatformIdentity> {
+    [Platform::Slack, Platform::Discord, Platform::Telegram, Platform::WhatsApp]
+        .into_iter()
+        .flat_map(|platform| list_identities(Some(platform)))
+        .collect()
+}
+
+#[query(guard = "caller_is_admin")]
 pub fn get_user_activity_admin(identifier: crate::services::account_service::UserIdentifier) -> Result<crate::services::account_service::UserActivity, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
-    let activity = crate::services::account_service::get_user_activity(identifier);
+    let activity = crate::services::account_service::user_activity(identifier);
     Ok(activity)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_api_messages() -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     let mut all_api_messages = Vec::new();
     
-    // Get all Slack users and their API messages
-    let slack_users = get_registered_slack_users();
-    for user in slack_users {
-        let messages = get_api_message_history(ApiUserIdentifier::SlackId(user.slack_id));
-        all_api_messages.extend(messages);
-    }
-    
-    // Get all Discord users and their API messages
-    let discord_users = get_registered_discord_users();
-    for user in discord_users {
-        let messages = get_api_message_history(ApiUserIdentifier::DiscordId(user.discord_id));
+    // Get all chat bot users and their API messages
+    for identity in chat_bot_identities() {
+        let Some(identifier) = ApiUserIdentifier::from_platform(identity.platform, identity.external_id) else {
+            continue;
+        };
+        let messages = get_api_message_history(identifier);
         all_api_messages.extend(messages);
     }
     
@@ -358,25 +405,18 @@ pub fn admin_get_all_api_messages() -> Result<Vec<ApiMessage>, String> {
     Ok(all_api_messages)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_api_messages_by_bot(bot_name: String) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     let mut all_api_messages = Vec::new();
     
-    // Get all Slack users and their API messages for the specified bot
-    let slack_users = get_registered_slack_users();
-    for user in slack_users {
-        let messages = get_api_messages_by_bot(ApiUserIdentifier::SlackId(user.slack_id), bot_name.clone());
-        all_api_messages.extend(messages);
-    }
-    
-    // Get all Discord users and their API messages for the specified bot
-    let discord_users = get_registered_discord_users();
-    for user in discord_users {
-        let messages = get_api_messages_by_bot(ApiUserIdentifier::DiscordId(user.discord_id), bot_name.clone());
+    // Get all chat bot users and their API messages for the specified bot
+    for identity in chat_bot_identities() {
+        let Some(identifier) = ApiUserIdentifier::from_platform(identity.platform, identity.external_id) else {
+            continue;
+        };
+        let messages = get_api_messages_by_bot(identifier, bot_name.clone());
         all_api_messages.extend(messages);
     }
     
@@ -386,25 +426,18 @@ pub fn admin_get_api_messages_by_bot(bot_name: String) -> Result<Vec<ApiMessage>
     Ok(all_api_messages)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_recent_api_messages(limit: u32) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     let mut all_api_messages = Vec::new();
     
-    // Get all Slack users and their recent API messages
-    let slack_users = get_registered_slack_users();
-    for user in slack_users {
-        let messages = get_recent_api_messages(ApiUserIdentifier::SlackId(user.slack_id), limit);
-        all_api_messages.extend(messages);
-    }
-    
-    // Get all Discord users and their recent API messages
-    let discord_users = get_registered_discord_users();
-    for user in discord_users {
-        let messages = get_recent_api_messages(ApiUserIdentifier::DiscordId(user.discord_id), limit);
+    // Get all chat bot users and their recent API messages
+    for identity in chat_bot_identities() {
+        let Some(identifier) = ApiUserIdentifier::from_platform(identity.platform, identity.external_id) else {
+            continue;
+        };
+        let messages = get_recent_api_messages(identifier, limit);
         all_api_messages.extend(messages);
     }
     
@@ -417,11 +450,9 @@ pub fn admin_get_recent_api_messages(limit: u32) -> Result<Vec<ApiMessage>, Stri
     Ok(all_api_messages)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_api_messages_for_user(identifier: AccountUserIdentifier) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     // Convert AccountUserIdentifier to ApiUserIdentifier
     let api_identifier = match identifier {
@@ -430,17 +461,17 @@ pub fn admin_get_api_messages_for_user(identifier: AccountUserIdentifier) -> Res
         AccountUserIdentifier::SlackId(slack_id) => ApiUserIdentifier::SlackId(slack_id),
         AccountUserIdentifier::DiscordId(discord_id) => ApiUserIdentifier::DiscordId(discord_id),
         AccountUserIdentifier::PlaygroundId(playground_id) => ApiUserIdentifier::PlaygroundId(playground_id),
+        AccountUserIdentifier::TelegramId(telegram_id) => ApiUserIdentifier::TelegramId(telegram_id),
+        AccountUserIdentifier::WhatsAppId(whatsapp_id) => ApiUserIdentifier::WhatsAppId(whatsapp_id),
     };
     
     let messages = get_api_message_history(api_identifier);
     Ok(messages)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_api_messages_for_user_by_bot(identifier: AccountUserIdentifier, bot_name: String) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     // Convert AccountUserIdentifier to ApiUserIdentifier
     let api_identifier = match identifier {
@@ -449,17 +480,17 @@ pub fn admin_get_api_messages_for_user_by_bot(identifier: AccountUserIdentifier,
         AccountUserIdentifier::SlackId(slack_id) => ApiUserIdentifier::SlackId(slack_id),
         AccountUserIdentifier::DiscordId(discord_id) => ApiUserIdentifier::DiscordId(discord_id),
         AccountUserIdentifier::PlaygroundId(playground_id) => ApiUserIdentifier::PlaygroundId(playground_id),
+        AccountUserIdentifier::TelegramId(telegram_id) => ApiUserIdentifier::TelegramId(telegram_id),
+        AccountUserIdentifier::WhatsAppId(whatsapp_id) => ApiUserIdentifier::WhatsAppId(whatsapp_id),
     };
     
     let messages = get_api_messages_by_bot(api_identifier, bot_name);
     Ok(messages)
 }
 
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_recent_api_messages_for_user(identifier: AccountUserIdentifier, limit: u32) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
     
     // Convert AccountUserIdentifier to ApiUserIdentifier
     let api_identifier = match identifier {
@@ -468,6 +499,8 @@ pub fn admin_get_recent_api_messages_for_user(identifier: AccountUserIdentifier,
         AccountUserIdentifier::SlackId(slack_id) => ApiUserIdentifier::SlackId(slack_id),
         AccountUserIdentifier::DiscordId(discord_id) => ApiUserIdentifier::DiscordId(discord_id),
         AccountUserIdentifier::PlaygroundId(playground_id) => ApiUserIdentifier::PlaygroundId(playground_id),
+        AccountUserIdentifier::TelegramId(telegram_id) => ApiUserIdentifier::TelegramId(telegram_id),
+        AccountUserIdentifier::WhatsAppId(whatsapp_id) => ApiUserIdentifier::WhatsAppId(whatsapp_id),
     };
     
     let messages = get_recent_api_messages(api_identifier, limit);
@@ -479,51 +512,15 @@ pub fn admin_get_recent_api_messages_for_user(identifier: AccountUserIdentifier,
 // ================================
 
 /// Get usage statistics for all users who have made requests
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_user_usage_stats() -> Result<Vec<UsageStats>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let mut all_usage_stats = Vec::new();
     
     // Get all users with daily usage data (regardless of subscription status)
-    let daily_usage_data: Vec<(String, u32)> = USER_DAILY_USAGE.with(|usage| {
-        usage.borrow()
-            .iter()
-            .map(|((user_id_key, _), requests_count)| (user_id_key.to_string(), requests_count))
-            .collect()
-    });
-    
-    // Create usage stats for each user
-    for (user_id, requests_count) in daily_usage_data {
-        let tier = get_user_tier(&user_id);
-        let daily_limit = match tier {
-            UserTier::Free => Some(20), // FREE_DAILY_LIMIT
-            UserTier::Pro => None,
-        };
-        
-        let now = ic_cdk::api::time();
-        let day_bucket = now / 86_400_000_000_000; // NANOS_PER_DAY
-        
-        // Calculate next reset time
-        let next_bucket_start_ns = (day_bucket + 1) * 86_400_000_000_000;
-        let secs = (next_bucket_start_ns / 1_000_000_000) as i64;
-        let reset_time_rfc3339 = Utc.timestamp_opt(secs, 0)
-            .single()
-            .unwrap_or_else(|| Utc::now())
-            .to_rfc3339();
-        
-        let usage_stats = UsageStats {
-            user_id: user_id.clone(),
-            tier,
-            requests_used: requests_count,
-            requests_limit: daily_limit,
-            day_bucket,
-            reset_time_rfc3339,
-        };
-        
-        all_usage_stats.push(usage_stats);
+    for (user_id, _) in usage_today() {
+        all_usage_stats.push(get_usage_stats(&user_id));
     }
 
     // Sort by requests used (highest first)
@@ -535,11 +532,9 @@ pub fn admin_get_all_user_usage_stats() -> Result<Vec<UsageStats>, String> {
 // Playground-specific admin functions
 
 // Get all playground users and their activity
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_playground_users() -> Result<Vec<String>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     // Get all playground user IDs from API messages
     let playground_users = crate::storage::memory::API_MESSAGES.with(|messages| {
@@ -560,22 +555,18 @@ pub fn admin_get_playground_users() -> Result<Vec<String>, String> {
 }
 
 /// Get usage statistics for a specific user
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_user_usage_stats(user_id: String) -> Result<UsageStats, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let usage_stats = get_usage_stats(&user_id);
     Ok(usage_stats)
 }
 
 // Get all playground API messages
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_playground_messages() -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     // Get all API messages from playground users
     let playground_messages = crate::storage::memory::API_MESSAGES.with(|messages| {
@@ -591,11 +582,9 @@ pub fn admin_get_playground_messages() -> Result<Vec<ApiMessage>, String> {
 }
 
 /// Get subscription details for all users
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_user_subscriptions() -> Result<Vec<(String, UserSubscription)>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let mut subscriptions = Vec::new();
     
@@ -619,22 +608,18 @@ pub fn admin_get_all_user_subscriptions() -> Result<Vec<(String, UserSubscriptio
 }
 
 /// Get subscription details for a specific user
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_user_subscription(user_id: String) -> Result<Option<UserSubscription>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let subscription = get_user_subscription(&user_id);
     Ok(subscription)
 }
 
 // Get playground messages by bot
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_playground_messages_by_bot(bot_name: String) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     // Get all API messages from playground users for a specific bot
     let playground_messages = crate::storage::memory::API_MESSAGES.with(|messages| {
@@ -653,22 +638,17 @@ pub fn admin_get_playground_messages_by_bot(bot_name: String) -> Result<Vec<ApiM
 }
 
 /// Get daily usage summary - users who have made requests today
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_daily_usage_summary() -> Result<Vec<(String, u32, UserTier)>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let mut daily_usage = Vec::new();
     
     // Get all users with daily usage data
-    USER_DAILY_USAGE.with(|usage| {
-        for ((user_id_key, _), requests_count) in usage.borrow().iter() {
-            let user_id = user_id_key.to_string();
-            let tier = get_user_tier(&user_id);
-            daily_usage.push((user_id, requests_count, tier));
-        }
-    });
+    for (user_id, requests_count) in usage_today() {
+        let tier = get_user_tier(&user_id);
+        daily_usage.push((user_id, requests_count, tier));
+    }
 
     // Sort by requests made (highest first)
     daily_usage.sort_by(|a, b| b.1.cmp(&a.1));
@@ -677,42 +657,27 @@ pub fn admin_get_daily_usage_summary() -> Result<Vec<(String, u32, UserTier)>, S
 }
 
 /// Get users who have reached their daily limit
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_users_at_limit() -> Result<Vec<(String, u32, UserTier)>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let mut users_at_limit = Vec::new();
     
     // First, collect all user data without calling functions that might cause borrow conflicts
-    let daily_usage_data: Vec<(String, u32)> = USER_DAILY_USAGE.with(|usage| {
-        usage.borrow()
-            .iter()
-            .map(|((user_id_key, _), requests_count)| (user_id_key.to_string(), requests_count))
-            .collect()
-    });
+    let daily_usage_data = usage_today();
     
     // Then process each user
     for (user_id, requests_count) in daily_usage_data {
         let tier = get_user_tier(&user_id);
         
-        // Only check Free tier users (Pro users have unlimited)
-        if matches!(tier, UserTier::Free) {
-            // Check if user can make more requests without causing borrow conflicts
-            let daily_limit = match tier {
-                UserTier::Free => Some(20), // FREE_DAILY_LIMIT
-                UserTier::Pro => None,
-            };
-            
-            let can_make_more = match daily_limit {
-                None => true, // Pro users have unlimited
-                Some(limit) => requests_count < limit,
-            };
-            
-            if !can_make_more {
-                users_at_limit.push((user_id, requests_count, tier));
-            }
+        // Tiers without a daily limit never reach it
+        let can_make_more = match get_quota_policy(&tier).daily_limit {
+            None => true,
+            Some(limit) => requests_count < limit,
+        };
+
+        if !can_make_more {
+            users_at_limit.push((user_id, requests_count, tier));
         }
     }
 
@@ -723,11 +688,9 @@ pub fn admin_get_users_at_limit() -> Result<Vec<(String, u32, UserTier)>, String
 }
 
 // Get recent playground messages
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_recent_playground_messages(limit: u32) -> Result<Vec<ApiMessage>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     // Get recent API messages from playground users
     let mut playground_messages = crate::storage::memory::API_MESSAGES.with(|messages| {
@@ -743,15 +706,13 @@ pub fn admin_get_recent_playground_messages(limit: u32) -> Result<Vec<ApiMessage
     playground_messages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
     playground_messages.truncate(limit as usize);
 
-    Ok(playground_messages)
+    Ok(playground_messages.into_iter().map(with_response).collect())
 }
 
 /// Get usage statistics grouped by tier
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_usage_by_tier() -> Result<Vec<(UserTier, u32, u32)>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let mut free_users = 0;
     let mut pro_users = 0;
@@ -759,12 +720,7 @@ pub fn admin_get_usage_by_tier() -> Result<Vec<(UserTier, u32, u32)>, String> {
     let mut pro_requests = 0;
     
     // Get all users with daily usage data (regardless of subscription status)
-    let daily_usage_data: Vec<(String, u32)> = USER_DAILY_USAGE.with(|usage| {
-        usage.borrow()
-            .iter()
-            .map(|((user_id_key, _), requests_count)| (user_id_key.to_string(), requests_count))
-            .collect()
-    });
+    let daily_usage_data = usage_today();
     
     // Count users and requests by tier
     for (user_id, requests_made) in daily_usage_data {
@@ -789,20 +745,15 @@ pub fn admin_get_usage_by_tier() -> Result<Vec<(UserTier, u32, u32)>, String> {
     Ok(result)
 }
 
-/// Get total users across all platforms (Discord, Slack, Playground)
-#[query]
+/// Get total users across all platforms (Discord, Slack, Telegram, WhatsApp, Playground)
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_total_users_count() -> Result<u32, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let mut total_users = 0;
     
-    // Count Discord users
-    total_users += DISCORD_USERS.with(|users| users.borrow().len()) as u32;
-    
-    // Count Slack users
-    total_users += SLACK_USERS.with(|users| users.borrow().len()) as u32;
+    // Count chat bot users
+    total_users += chat_bot_identities().len() as u32;
     
     // Count Playground users (from API messages)
     let playground_users = crate::storage::memory::API_MESSAGES.with(|messages| {
@@ -823,19 +774,12 @@ pub fn admin_get_total_users_count() -> Result<u32, String> {
 }
 
 /// Get top users by request count (today) - includes all users who made requests
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_top_users_by_requests(limit: u32) -> Result<Vec<(String, u32, UserTier)>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     // First, collect all user data without calling functions that might cause borrow conflicts
-    let daily_usage_data: Vec<(String, u32)> = USER_DAILY_USAGE.with(|usage| {
-        usage.borrow()
-            .iter()
-            .map(|((user_id_key, _), requests_count)| (user_id_key.to_string(), requests_count))
-            .collect()
-    });
+    let daily_usage_data = usage_today();
     
     let mut all_users = Vec::new();
     
@@ -855,22 +799,21 @@ pub fn admin_get_top_users_by_requests(limit: u32) -> Result<Vec<(String, u32, U
 }
 
 /// Admin function to manually upgrade a user's tier
-#[update]
+#[update(guard = "caller_is_admin")]
 pub fn admin_upgrade_user_tier(user_id: String, tier: UserTier, expires_at_ns: Option<u64>) -> Result<(), String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ManageBilling)?;
 
     // Use the existing upgrade function from pricing_services
-    crate::services::pricing_services::upgrade_user_tier(&user_id, tier, expires_at_ns)
+    let before = get_user_subscription(&user_id);
+    crate::services::pricing_services::upgrade_user_tier(&user_id, tier, expires_at_ns)?;
+    audit(AuditAction::UserTierChanged, user_id.clone(), Some(digest(&before)), Some(digest(&get_user_subscription(&user_id))));
+    Ok(())
 }
 
 /// Get comprehensive user activity report
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_user_activity_report(user_id: String) -> Result<UserActivityReport, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let usage_stats = get_usage_stats(&user_id);
     let subscription = get_user_subscription(&user_id);
@@ -905,23 +848,19 @@ pub struct UserActivityReport {
 }
 
 // Get playground activity for a specific user
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_playground_user_activity(playground_id: String) -> Result<crate::services::account_service::UserActivity, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let identifier = AccountUserIdentifier::PlaygroundId(playground_id);
-    let activity = crate::services::account_service::get_user_activity(identifier);
+    let activity = crate::services::account_service::user_activity(identifier);
     Ok(activity)
 }
 
 // Get playground statistics
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_playground_stats() -> Result<PlaygroundStats, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Caller is not an admin".to_string());
-    }
+    require_permission(Permission::ViewUsers)?;
 
     let stats = crate::storage::memory::API_MESSAGES.with(|messages| {
         let messages = messages.borrow();
@@ -953,11 +892,9 @@ pub fn admin_get_playground_stats() -> Result<PlaygroundStats, String> {
 // ==================== ADMIN PAYMENT MANAGEMENT ====================
 
 /// Get all payment records (admin only)
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_payments() -> Result<Vec<PaymentRecord>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Admin access required".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let payments = PAYMENT_RECORDS.with(|records| {
         records.borrow()
@@ -970,11 +907,9 @@ pub fn admin_get_all_payments() -> Result<Vec<PaymentRecord>, String> {
 }
 
 /// Get all invoices (admin only)
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_invoices() -> Result<Vec<Invoice>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Admin access required".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let invoices = INVOICES.with(|invoices| {
         invoices.borrow()
@@ -986,12 +921,24 @@ pub fn admin_get_all_invoices() -> Result<Vec<Invoice>, String> {
     Ok(invoices)
 }
 
+/// Get a user's payment history (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_user_payments(user_id: String) -> Result<Vec<PaymentRecord>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_payment_history(user_id))
+}
+
+/// Get a user's invoices (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_user_invoices(user_id: String) -> Result<Vec<Invoice>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_user_invoices(user_id))
+}
+
 /// Get all user subscriptions (admin only)
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_all_subscriptions() -> Result<Vec<UserSubscription>, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Admin access required".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let subscriptions = USER_SUBSCRIPTIONS.with(|subs| {
         subs.borrow()
@@ -1004,11 +951,9 @@ pub fn admin_get_all_subscriptions() -> Result<Vec<UserSubscription>, String> {
 }
 
 /// Get payment statistics (admin only)
-#[query]
+#[query(guard = "caller_is_admin")]
 pub fn admin_get_payment_stats() -> Result<PaymentStats, String> {
-    if !is_allowed_principal() {
-        return Err("Unauthorized: Admin access required".to_string());
-    }
+    require_permission(Permission::ViewBilling)?;
 
     let stats = PAYMENT_RECORDS.with(|records| {
         let records = records.borrow();
@@ -7,7 +7,9 @@ use crate::services::analytics_service::{
     record_analytics_data as analytics_record_data
 };
 use crate::services::api_service::{get_recent_api_messages, get_api_messages_by_bot, UserIdentifier};
-use crate::storage::memory::{OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS, MAIN_SITE_USERS};
+use crate::services::auth::caller_is_authenticated;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::services::platform_identity_service::identities_for_principal;
 
 // ============================
 // Wrapper functions for analytics service
@@ -40,48 +42,17 @@ pub fn analytics_get_combined_user_data(
 /// but retrieved by principal ID (e.g., "rdmx6-jaaaa-aaaaa-aaadq-cai")
 fn get_platform_user_id_for_principal(principal: Principal) -> Option<String> {
     ic_cdk::println!("Getting platform user ID for principal: {:?}", principal);
-    // Check OpenChat users
-    OPENCHAT_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(principal))
-            .map(|(_, user)| user.openchat_id.clone())
-    })
-    .or_else(|| {
-        // Check Slack users
-        SLACK_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(principal))
-                .map(|(_, user)| user.slack_id.clone())
-        })
-    })
-    .or_else(|| {
-        // Check Discord users
-        DISCORD_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(principal))
-                .map(|(_, user)| user.discord_id.clone())
-        })
-    })
-    .or_else(|| {
-        // Check MainSite users
-        MAIN_SITE_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(principal))
-                .map(|(_, user)| user.main_site_id.clone())
-        })
-    })
+    get_platform_identity_for_principal(principal)
+        .map(|identity| identity.platform.user_id(&identity.external_id))
+}
+
+/// The first platform account linked to a principal, in platform order
+fn get_platform_identity_for_principal(principal: Principal) -> Option<PlatformIdentity> {
+    identities_for_principal(principal).into_iter().next()
 }
 
 /// Get analytics summary for a user
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn get_user_analytics_summary(days: u32) -> Result<AnalyticsSummary, String> {
     let caller_principal = caller();
     let principal_id = caller_principal.to_string();
@@ -97,7 +68,7 @@ pub fn get_user_analytics_summary(days: u32) -> Result<AnalyticsSummary, String>
 }
 
 /// Get detailed analytics for a user
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn get_user_analytics(days: u32) -> Result<UserAnalytics, String> {
     let caller_principal = caller();
     let principal_id = caller_principal.to_string();
@@ -113,7 +84,7 @@ pub fn get_user_analytics(days: u32) -> Result<UserAnalytics, String> {
 }
 
 /// Update analytics data for a user (call this when user makes requests)
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "caller_is_authenticated")]
 pub fn update_user_analytics() -> Result<(), String> {
     let caller_principal = caller();
     
@@ -125,7 +96,7 @@ pub fn update_user_analytics() -> Result<(), String> {
 }
 
 /// Record analytics data for a user
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "caller_is_authenticated")]
 pub fn record_analytics_data(
     requests_made: u32,
     lines_of_code_edited: u32,
@@ -148,33 +119,15 @@ pub fn record_analytics_data(
 }
 
 /// Get recent messages for the current user
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn get_user_recent_messages(limit: u32) -> Result<Vec<ApiMessage>, String> {
     let caller_principal = caller();
     // let principal_id = caller_principal.to_string();
     
     // First try to get messages from platform-specific accounts
-    if let Some(platform_user_id) = get_platform_user_id_for_principal(caller_principal) {
-        // We need to determine which platform this user belongs to
-        let user_identifier = if OPENCHAT_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.openchat_id == platform_user_id)
-        }) {
-            UserIdentifier::OpenChatId(platform_user_id)
-        } else if SLACK_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.slack_id == platform_user_id)
-        }) {
-            UserIdentifier::SlackId(platform_user_id)
-        } else if DISCORD_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.discord_id == platform_user_id)
-        }) {
-            UserIdentifier::DiscordId(platform_user_id)
-        } else if MAIN_SITE_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.main_site_id == platform_user_id)
-        }) {
-            UserIdentifier::MainSiteId(platform_user_id)
-        } else {
-            return Err("User not found in any platform".to_string());
-        };
+    let platform_identifier = get_platform_identity_for_principal(caller_principal)
+        .and_then(|identity| UserIdentifier::from_platform(identity.platform, identity.external_id));
+    if let Some(user_identifier) = platform_identifier {
 
         // Get recent messages for this platform user
         let platform_messages = get_recent_api_messages(user_identifier, limit);
@@ -205,32 +158,14 @@ pub fn get_user_recent_messages(limit: u32) -> Result<Vec<ApiMessage>, String> {
 }
 
 /// Get messages for the current user by bot
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn get_user_messages_by_bot(bot_name: String) -> Result<Vec<ApiMessage>, String> {
     let caller_principal = caller();
     
     // First try to get messages from platform-specific accounts
-    if let Some(platform_user_id) = get_platform_user_id_for_principal(caller_principal) {
-        // We need to determine which platform this user belongs to
-        let user_identifier = if OPENCHAT_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.openchat_id == platform_user_id)
-        }) {
-            UserIdentifier::OpenChatId(platform_user_id)
-        } else if SLACK_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.slack_id == platform_user_id)
-        }) {
-            UserIdentifier::SlackId(platform_user_id)
-        } else if DISCORD_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.discord_id == platform_user_id)
-        }) {
-            UserIdentifier::DiscordId(platform_user_id)
-        } else if MAIN_SITE_USERS.with(|users| {
-            users.borrow().iter().any(|(_, user)| user.main_site_id == platform_user_id)
-        }) {
-            UserIdentifier::MainSiteId(platform_user_id)
-        } else {
-            return Err("User not found in any platform".to_string());
-        };
+    let platform_identifier = get_platform_identity_for_principal(caller_principal)
+        .and_then(|identity| UserIdentifier::from_platform(identity.platform, identity.external_id));
+    if let Some(user_identifier) = platform_identifier {
         
         // Get messages for this platform user by bot
         let platform_messages = get_api_messages_by_bot(user_identifier, bot_name.clone());
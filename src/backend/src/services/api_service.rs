@@ -1,22 +1,32 @@
 use crate::models::api_message::{ApiMessage, ApiMetadata};
 use crate::models::stable_string::StableString;
-use crate::services::openchat_service::ensure_openchat_user;
-use crate::services::slack_service::ensure_slack_user;
-use crate::services::discord_service::ensure_discord_user;
-use crate::services::main_site_service::ensure_main_site_user;
-use crate::storage::memory::{API_MESSAGES, OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS, MAIN_SITE_USERS};
+use crate::models::platform::Platform;
+use crate::services::platform_identity_service::{ensure_identity, linked_principal};
+use crate::storage::memory::API_MESSAGES;
+use crate::storage::blob_store::{check_blob_size, get_blob, put_blob};
+use crate::models::record::check_record_size;
 use candid::Principal;
-use ic_cdk::{query, update};
+use ic_cdk::{caller, query, update};
 use crate::services::pricing_services::{
     get_usage_stats, get_user_tier, can_make_request, increment_user_requests, upgrade_user_tier, get_user_subscription,
+    quota_account,
 };
 use crate::services::analytics_service::update_user_analytics;
 use crate::services::token_service::generate_dashboard_token;
 use crate::models::usage_service::{UsageStats,UserTier,UserSubscription};
+use crate::models::quota::{QuotaRejection, QuotaRemaining};
+use crate::models::bot_principal::BotPermission;
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::auth::caller_is_authenticated;
+use crate::services::bot_service::{
+    authorize_bot_for, get_bot, bot_can_issue_tokens, bot_can_read_messages, bot_can_read_usage, bot_can_store_messages,
+    bot_can_track_usage, bot_can_upgrade_tier,
+};
 
 
 // API Message Storage Management
-#[update]
+#[update(guard = "bot_can_store_messages")]
 pub fn store_api_message(
     identifier: UserIdentifier,
     message: String,
@@ -24,150 +34,50 @@ pub fn store_api_message(
     bot_name: String,
     metadata: Option<ApiMetadata>,
 )  -> Result<ApiMessage, String>{
-    let _store_principal = match &identifier {
-        UserIdentifier::Principal(principal) => *principal,
-        UserIdentifier::OpenChatId(openchat_id) => {
-            // Ensure OpenChat user exists
-            ensure_openchat_user(openchat_id.clone());
-
-            // Try to get linked principal, otherwise derive from openchat_id
-            OPENCHAT_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(openchat_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        Principal::from_text(openchat_id).unwrap_or_else(|_| Principal::anonymous())
-                    })
-            })
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            ensure_slack_user(slack_id.clone());
-
-            // Try to get linked principal, otherwise use a special Slack principal
-            SLACK_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(slack_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Slack messages
-                        // This is a deterministic way to create a principal from a Slack ID
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 5; // Special type for Slack
-                        // Use the first 28 bytes of the Slack ID
-                        let slack_bytes = slack_id.as_bytes();
-                        let len = std::cmp::min(slack_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            ensure_discord_user(discord_id.clone());
-
-            // Try to get linked principal, otherwise use a special Discord principal
-            DISCORD_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(discord_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for Discord messages
-                        // This is a deterministic way to create a principal from a Discord ID
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 6; // Special type for Discord
-                        // Use the first 28 bytes of the Discord ID
-                        let discord_bytes = discord_id.as_bytes();
-                        let len = std::cmp::min(discord_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            // Create a special principal for Playground messages
-            // This is a deterministic way to create a principal from a Playground ID
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            // Use the first 28 bytes of the Playground ID
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            Principal::from_slice(&bytes)
-        }
-        UserIdentifier::MainSiteId(main_site_id) => {
-            ensure_main_site_user(main_site_id.clone());
-
-            // Try to get linked principal, otherwise use a special MainSite principal
-            MAIN_SITE_USERS.with(|users| {
-                users
-                    .borrow()
-                    .get(&StableString::from(main_site_id.clone()))
-                    .and_then(|user| user.site_principal.map(|p| p.get()))
-                    .unwrap_or_else(|| {
-                        // Create a special principal for MainSite messages
-                        // This is a deterministic way to create a principal from a MainSite ID
-                        let mut bytes = [0u8; 29];
-                        bytes[0] = 8; // Special type for MainSite
-                        // Use the first 28 bytes of the MainSite ID
-                        let main_site_bytes = main_site_id.as_bytes();
-                        let len = std::cmp::min(main_site_bytes.len(), 28);
-                        bytes[1..1+len].copy_from_slice(&main_site_bytes[..len]);
-                        Principal::from_slice(&bytes)
-                    })
-            })
-        }
-    };
+    authorize_bot_for(caller(), BotPermission::StoreMessages, identifier.bot_platform())?;
 
-    let timestamp = ic_cdk::api::time();
-    let user_id = match &identifier {
-        UserIdentifier::Principal(principal) => principal.to_string(),
-        UserIdentifier::OpenChatId(openchat_id) => openchat_id.clone(),
-        UserIdentifier::SlackId(slack_id) => slack_id.clone(),
-        UserIdentifier::DiscordId(discord_id) => discord_id.clone(),
-        UserIdentifier::PlaygroundId(playground_id) => playground_id.clone(),
-        UserIdentifier::MainSiteId(main_site_id) => main_site_id.clone(),
-    };
-    
-    
-    // ✅ Usage validation before storing the request
-    if !can_make_request(&user_id) {
-        return Err(format!(
-            "Daily limit reached. Upgrade to Pro for unlimited access. \
-             Usage: {:?}",
-            get_usage_stats(&user_id)
-        ));
-    }
-
-    if let Err(err) = increment_user_requests(&user_id) {
-        return Err(err);
-    }
-
-    // Update analytics data for dashboard tracking
-    if let Err(err) = update_user_analytics(&user_id) {
-        // Log error but don't fail the request - analytics is not critical
-        ic_cdk::println!("Failed to update analytics for user {}: {}", user_id, err);
+    // Register bot users on first contact
+    if let Some((platform, external_id)) = identifier.platform_id() {
+        ensure_identity(platform, external_id, ic_cdk::api::time());
     }
 
+    let timestamp = ic_cdk::api::time();
+    let user_id = identifier.record_user_id();
 
     // Create unique message ID
     let message_id = format!("{}_{}", user_id, timestamp);
 
     let api_message = ApiMessage {
         id: message_id.clone(),
-        user_id,
+        user_id: user_id.clone(),
         message,
         response,
-        bot_name,
+        bot_name: bot_name.clone(),
         metadata,
         timestamp,
     };
 
+    // Oversized messages are refused before they count against the quota
+    let (record, long_response) = split_long_response(api_message.clone())?;
+
+    // ✅ Usage validation before storing the request
+    if let Err(rejection) = increment_user_requests(&user_id, Some(&bot_name)) {
+        return Err(format!("{} Usage: {:?}", rejection, get_usage_stats(&user_id)));
+    }
+
+    // Update analytics data for dashboard tracking
+    if let Err(err) = update_user_analytics(&user_id) {
+        // Log error but don't fail the request - analytics is not critical
+        ic_cdk::println!("Failed to update analytics for user {}: {}", user_id, err);
+    }
+
     // Store the message under the principal
+    if let Some(response) = long_response {
+        put_blob(&response_blob(&message_id), response.as_bytes())?;
+    }
     API_MESSAGES.with(|messages| {
         let mut messages = messages.borrow_mut();
-        messages.insert((StableString::from(message_id), timestamp), api_message.clone());
+        messages.insert((StableString::from(message_id), timestamp), record);
     });
 
     Ok(api_message)
@@ -178,233 +88,33 @@ pub fn store_api_message(
 
 // Get API message history for a user
 
-#[query]
+#[query(guard = "bot_can_read_messages")]
 pub fn get_api_message_history(identifier: UserIdentifier) -> Vec<ApiMessage> {
-    let principals_to_check = match &identifier {
-        UserIdentifier::Principal(principal) => {
-            // Check if this principal is linked to any platform account
-            let mut principals = vec![*principal];
-
-            // Check OpenChat
-            OPENCHAT_USERS.with(|users| {
-                let users = users.borrow();
-                // Find any OpenChat user linked to this principal
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also derive principal from OpenChat ID
-                            if let Ok(derived_principal) = Principal::from_text(&user.openchat_id) {
-                                principals.push(derived_principal);
-                            }
-                            break;
-                        }
-                    }
-                }
-            });
-
-            // Check Slack
-            SLACK_USERS.with(|users| {
-                let users = users.borrow();
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also create special Slack principal
-                            let mut bytes = [0u8; 29];
-                            bytes[0] = 5; // Special type for Slack
-                            let slack_bytes = user.slack_id.as_bytes();
-                            let len = std::cmp::min(slack_bytes.len(), 28);
-                            bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-                            principals.push(Principal::from_slice(&bytes));
-                            break;
-                        }
-                    }
-                }
-            });
-
-            // Check Discord
-            DISCORD_USERS.with(|users| {
-                let users = users.borrow();
-                for (_, user) in users.iter() {
-                    if let Some(p) = &user.site_principal {
-                        if p.get() == *principal {
-                            // If found, also create special Discord principal
-                            let mut bytes = [0u8; 29];
-                            bytes[0] = 6; // Special type for Discord
-                            let discord_bytes = user.discord_id.as_bytes();
-                            let len = std::cmp::min(discord_bytes.len(), 28);
-                            bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-                            principals.push(Principal::from_slice(&bytes));
-                            break;
-                        }
-                    }
-                }
-            });
-
-            principals
-        }
-        UserIdentifier::OpenChatId(openchat_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            OPENCHAT_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(openchat_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include derived principal from OpenChat ID
-            if let Ok(derived_principal) = Principal::from_text(openchat_id) {
-                principals.push(derived_principal);
-            }
-            principals
-        }
-        UserIdentifier::SlackId(slack_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            SLACK_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(slack_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include special Slack principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 5; // Special type for Slack
-            let slack_bytes = slack_id.as_bytes();
-            let len = std::cmp::min(slack_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&slack_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            principals
-        }
-        UserIdentifier::DiscordId(discord_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            DISCORD_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(discord_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include special Discord principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 6; // Special type for Discord
-            let discord_bytes = discord_id.as_bytes();
-            let len = std::cmp::min(discord_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&discord_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            principals
-        }
-        UserIdentifier::PlaygroundId(playground_id) => {
-            let mut principals = vec![];
-
-            // Create special Playground principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 7; // Special type for Playground
-            let playground_bytes = playground_id.as_bytes();
-            let len = std::cmp::min(playground_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&playground_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            principals
-        }
-        UserIdentifier::MainSiteId(main_site_id) => {
-            let mut principals = vec![];
-
-            // Try to get linked principal
-            MAIN_SITE_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(main_site_id.clone())) {
-                    if let Some(p) = &user.site_principal {
-                        principals.push(p.get());
-                    }
-                }
-            });
-
-            // Also include special MainSite principal
-            let mut bytes = [0u8; 29];
-            bytes[0] = 8; // Special type for MainSite
-            let main_site_bytes = main_site_id.as_bytes();
-            let len = std::cmp::min(main_site_bytes.len(), 28);
-            bytes[1..1+len].copy_from_slice(&main_site_bytes[..len]);
-            principals.push(Principal::from_slice(&bytes));
-
-            principals
-        }
-    };
-
-    // Collect all API messages across all relevant principals
-    let mut all_api_messages = vec![];
-
-    for principal in principals_to_check {
-        // Get API messages by checking the user_id field in stored messages
-        API_MESSAGES.with(|messages| {
-            let messages = messages.borrow();
-            let user_principal_str = principal.to_string();
-            
-            let api_messages: Vec<ApiMessage> = messages
-                .iter()
-                .filter(|(_, api_message)| api_message.user_id == user_principal_str)
-                .map(|(_, message)| message.clone())
-                .collect();
-            
-            all_api_messages.extend(api_messages);
-        });
+    if authorize_bot_for(caller(), BotPermission::ReadMessages, identifier.bot_platform()).is_err() {
+        return vec![];
     }
 
-    // Also check for messages stored with platform-specific user IDs
-    let platform_user_id = match &identifier {
-        UserIdentifier::Principal(principal) => principal.to_string(),
-        UserIdentifier::OpenChatId(openchat_id) => openchat_id.clone(),
-        UserIdentifier::SlackId(slack_id) => slack_id.clone(),
-        UserIdentifier::DiscordId(discord_id) => discord_id.clone(),
-        UserIdentifier::PlaygroundId(playground_id) => playground_id.clone(),
-        UserIdentifier::MainSiteId(main_site_id) => main_site_id.clone(),
-    };
+    // A linked platform account's messages were merged into its principal on linking
+    let user_id = identifier.record_user_id();
 
-    API_MESSAGES.with(|messages| {
-        let messages = messages.borrow();
-        let platform_messages: Vec<ApiMessage> = messages
+    let mut all_api_messages: Vec<ApiMessage> = API_MESSAGES.with(|messages| {
+        messages
+            .borrow()
             .iter()
-            .filter(|(_, api_message)| api_message.user_id == platform_user_id)
-            .map(|(_, message)| message.clone())
-            .collect();
-        
-        all_api_messages.extend(platform_messages);
+            .filter(|(_, api_message)| api_message.user_id == user_id)
+            .map(|(_, message)| with_response(message))
+            .collect()
     });
 
     // Sort by timestamp (newest first)
     all_api_messages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
 
-    // Remove duplicates based on message ID
-    let mut unique_messages = vec![];
-    let mut seen_ids = std::collections::HashSet::new();
-    
-    for message in all_api_messages {
-        if !seen_ids.contains(&message.id) {
-            seen_ids.insert(message.id.clone());
-            unique_messages.push(message);
-        }
-    }
-
-    unique_messages
+    all_api_messages
 }
 
 // Get API messages by bot name
 
-#[query]
+#[query(guard = "bot_can_read_messages")]
 pub fn get_api_messages_by_bot(identifier: UserIdentifier, bot_name: String) -> Vec<ApiMessage> {
     let all_messages = get_api_message_history(identifier);
     all_messages
@@ -414,13 +124,66 @@ pub fn get_api_messages_by_bot(identifier: UserIdentifier, bot_name: String) ->
 }
 
 // Get recent API messages (last N messages)
-#[query]    
+#[query(guard = "bot_can_read_messages")]
 pub fn get_recent_api_messages(identifier: UserIdentifier, limit: u32) -> Vec<ApiMessage> {
     let mut all_messages = get_api_message_history(identifier);
     all_messages.truncate(limit.min(all_messages.len() as u32)as usize);
     all_messages
 }
 
+/* ============================
+   Long responses
+   ============================ */
+
+// Responses over INLINE_RESPONSE_SIZE bytes are kept in the blob store under
+// the message ID, and the stored message holds an empty response.
+
+const INLINE_RESPONSE_SIZE: usize = 2048;
+
+pub fn response_blob(message_id: &str) -> String {
+    format!("api_response/{}", message_id)
+}
+
+/// The message as stored, and its response when that goes to the blob store.
+/// Fails if either is too large to store.
+fn split_long_response(mut message: ApiMessage) -> Result<(ApiMessage, Option<String>), String> {
+    let long_response = (message.response.len() > INLINE_RESPONSE_SIZE).then(|| std::mem::take(&mut message.response));
+    if let Some(response) = &long_response {
+        check_blob_size(&response_blob(&message.id), response.len())?;
+    }
+    check_record_size(&message)?;
+    Ok((message, long_response))
+}
+
+/// The message with its response read back from the blob store
+pub fn with_response(mut message: ApiMessage) -> ApiMessage {
+    if message.response.is_empty() {
+        if let Some(bytes) = get_blob(&response_blob(&message.id)) {
+            message.response = String::from_utf8_lossy(&bytes).into_owned();
+        }
+    }
+    message
+}
+
+/// Moves responses still stored inline past INLINE_RESPONSE_SIZE into the
+/// blob store; returns how many messages have one, or would be changed with
+/// `dry_run`
+pub fn move_long_responses(dry_run: bool) -> Result<u32, String> {
+    let long: Vec<((StableString, u64), ApiMessage)> = API_MESSAGES.with(|messages| {
+        messages.borrow().iter().filter(|(_, message)| message.response.len() > INLINE_RESPONSE_SIZE).collect()
+    });
+    let count = long.len() as u32;
+    if !dry_run {
+        for (key, message) in long {
+            if let (record, Some(response)) = split_long_response(message)? {
+                put_blob(&response_blob(&record.id), response.as_bytes())?;
+                API_MESSAGES.with(|messages| messages.borrow_mut().insert(key, record));
+            }
+        }
+    }
+    Ok(count)
+}
+
 // Helper enum for user identification (same as in account_service.rs)
 #[derive(candid::CandidType, serde::Deserialize, Clone, Debug)]
 pub enum UserIdentifier {
@@ -430,64 +193,139 @@ pub enum UserIdentifier {
     DiscordId(String),
     PlaygroundId(String),
     MainSiteId(String),
-} 
+    TelegramId(String),
+    WhatsAppId(String),
+}
+
+impl UserIdentifier {
+    /// The platform account a bot identifier refers to
+    pub fn platform_id(&self) -> Option<(Platform, &str)> {
+        match self {
+            UserIdentifier::OpenChatId(id) => Some((Platform::OpenChat, id)),
+            UserIdentifier::SlackId(id) => Some((Platform::Slack, id)),
+            UserIdentifier::DiscordId(id) => Some((Platform::Discord, id)),
+            UserIdentifier::MainSiteId(id) => Some((Platform::MainSite, id)),
+            UserIdentifier::TelegramId(id) => Some((Platform::Telegram, id)),
+            UserIdentifier::WhatsAppId(id) => Some((Platform::WhatsApp, id)),
+            UserIdentifier::Principal(_) | UserIdentifier::PlaygroundId(_) => None,
+        }
+    }
+    /// The bot identifier for a platform account; None for an unknown platform
+    pub fn from_platform(platform: Platform, external_id: String) -> Option<Self> {
+        Some(match platform {
+            Platform::OpenChat => UserIdentifier::OpenChatId(external_id),
+            Platform::Slack => UserIdentifier::SlackId(external_id),
+            Platform::Discord => UserIdentifier::DiscordId(external_id),
+            Platform::MainSite => UserIdentifier::MainSiteId(external_id),
+            Platform::Telegram => UserIdentifier::TelegramId(external_id),
+            Platform::WhatsApp => UserIdentifier::WhatsAppId(external_id),
+            Platform::Playground => UserIdentifier::PlaygroundId(external_id),
+            Platform::Unknown => return None,
+        })
+    }
+
+    /// The platform whose bot may act for this user; principals belong to none
+    pub fn bot_platform(&self) -> Option<Platform> {
+        match self {
+            UserIdentifier::PlaygroundId(_) => Some(Platform::Playground),
+            _ => self.platform_id().map(|(platform, _)| platform),
+        }
+    }
+
+    /// The ID an unlinked user's API messages, usage and analytics are
+    /// recorded under; see `Platform::user_id`
+    pub fn user_id(&self) -> String {
+        match self {
+            UserIdentifier::Principal(principal) => principal.to_string(),
+            UserIdentifier::PlaygroundId(id) => id.clone(),
+            _ => self.platform_id().map(|(platform, id)| platform.user_id(id)).unwrap_or_default(),
+        }
+    }
+
+    /// The ID to record under: the linked site principal once a platform
+    /// account is linked, otherwise `user_id`
+    pub fn record_user_id(&self) -> String {
+        self.platform_id()
+            .and_then(|(platform, external_id)| linked_principal(platform, external_id))
+            .map_or_else(|| self.user_id(), |principal| principal.to_text())
+    }
+}
 
 // -------------------- USAGE & PRICING API --------------------
 
+/// The quota account of a user ID sent by the calling bot, whose registered
+/// platform says which platform account the ID is. Users asking about
+/// themselves send their principal, which is its own account.
+fn bot_user_account(user_id: &str) -> String {
+    get_bot(caller()).map_or_else(|| user_id.to_string(), |bot| quota_account(bot.platform, user_id))
+}
+
 // Get current usage stats for a user
-#[query]
+#[query(guard = "bot_can_read_usage")]
 pub fn api_get_usage_stats(user_id: String) -> UsageStats {
-    get_usage_stats(&user_id)
+    UsageStats { user_id: user_id.clone(), ..get_usage_stats(&bot_user_account(&user_id)) }
 }
 
 // Get current tier for a user
-#[query]
+#[query(guard = "bot_can_read_usage")]
 pub fn api_get_user_tier(user_id: String) -> UserTier {
-    get_user_tier(&user_id)
+    get_user_tier(&bot_user_account(&user_id))
 }
 
 // Check if user can make a request
-#[query]
+#[query(guard = "bot_can_read_usage")]
 pub fn api_can_make_request(user_id: String) -> bool {
-    can_make_request(&user_id)
+    can_make_request(&bot_user_account(&user_id))
 }
 
-// Increment requests counter for a user
-#[update]
-pub fn api_increment_user_requests(user_id: String) -> Result<(), String> {
-    increment_user_requests(&user_id)?;
+// Count a request against a user's quota. `bot_name` sets its cost; a
+// rejection says which limit was hit and when to retry.
+#[update(guard = "bot_can_track_usage")]
+pub fn api_increment_user_requests(user_id: String, bot_name: Option<String>) -> Result<QuotaRemaining, QuotaRejection> {
+    let account = bot_user_account(&user_id);
+    let remaining = increment_user_requests(&account, bot_name.as_deref())?;
     
     // Update analytics data for dashboard tracking
-    if let Err(err) = update_user_analytics(&user_id) {
+    if let Err(err) = update_user_analytics(&account) {
         // Log error but don't fail the request - analytics is not critical
-        ic_cdk::println!("Failed to update analytics for user {}: {}", user_id, err);
+        ic_cdk::println!("Failed to update analytics for user {}: {}", account, err);
     }
     
-    Ok(())
+    Ok(remaining)
 }
 
 // Upgrade user tier (Free -> Pro)
-#[update]
+#[update(guard = "bot_can_upgrade_tier")]
 pub fn api_upgrade_user_tier(user_id: String, tier: UserTier, expires_at_ns: Option<u64>) -> Result<(), String> {
-    upgrade_user_tier(&user_id, tier, expires_at_ns)
+    let account = bot_user_account(&user_id);
+    let before = get_user_subscription(&account);
+    upgrade_user_tier(&account, tier, expires_at_ns)?;
+    audit(AuditAction::UserTierChanged, account.clone(), Some(digest(&before)), Some(digest(&get_user_subscription(&account))));
+    Ok(())
 }
 
 // Get full subscription details (tier, expiry, active status, etc.)
-#[query]
+// Bots may look up anyone; signed-in users only themselves
+#[query(guard = "caller_is_authenticated")]
 pub fn api_get_user_subscription(user_id: String) -> Option<UserSubscription> {
-    get_user_subscription(&user_id)
+    if user_id != caller().to_text() && bot_can_read_usage().is_err() {
+        return None;
+    }
+    get_user_subscription(&bot_user_account(&user_id))
 }
 
 // -------------------- WORKSPACE LINKING LOGIC --------------------
 
 // Check if a specific platform ID is linked to any principal
-#[update]
+#[update(guard = "bot_can_issue_tokens")]
 pub async fn api_is_platform_id_linked(platform: String, platform_id: String) -> Result<bool, String> {
-    if has_platform_id_linked(&platform, &platform_id) {
+    let platform: Platform = platform.parse()?;
+    authorize_bot_for(caller(), BotPermission::IssueTokens, Some(platform))?;
+    if has_platform_id_linked(platform.name(), &platform_id) {
         Ok(true)
     } else {
         // Generate auth token for workspace linking
-        let auth_token = generate_dashboard_token(platform_id).await;
+        let auth_token = generate_dashboard_token(platform, platform_id).await;
         Err(auth_token)
     }
 }
@@ -535,47 +373,7 @@ pub async fn api_is_platform_id_linked(platform: String, platform_id: String) ->
 
 /// Check if a specific platform ID has been linked to any principal
 pub fn has_platform_id_linked(platform: &str, platform_id: &str) -> bool {
-    match platform {
-        "slack" => {
-            SLACK_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(platform_id.to_string())) {
-                    user.site_principal.is_some()
-                } else {
-                    false
-                }
-            })
-        }
-        "discord" => {
-            DISCORD_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(platform_id.to_string())) {
-                    user.site_principal.is_some()
-                } else {
-                    false
-                }
-            })
-        }
-        "openchat" => {
-            OPENCHAT_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(platform_id.to_string())) {
-                    user.site_principal.is_some()
-                } else {
-                    false
-                }
-            })
-        }
-        "mainsite" => {
-            MAIN_SITE_USERS.with(|users| {
-                let users = users.borrow();
-                if let Some(user) = users.get(&StableString::from(platform_id.to_string())) {
-                    user.site_principal.is_some()
-                } else {
-                    false
-                }
-            })
-        }
-        _ => false,
-    }
+    platform
+        .parse::<Platform>()
+        .is_ok_and(|platform| linked_principal(platform, platform_id).is_some())
 }
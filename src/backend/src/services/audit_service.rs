@@ -0,0 +1,174 @@
+// backend/src/services/audit_service.rs
+//
+// Append-only audit log of privileged and financial actions: who changed what,
+// when, and digests of the value before and after. Entries older than the
+// retention window are moved to an archive, never dropped.
+
+use std::time::Duration;
+
+use candid::{CandidType, Principal};
+use ic_cdk::{caller, query};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::models::admin::Permission;
+use crate::models::audit_log::{AuditAction, AuditEntry, MAX_AUDIT_TARGET_LEN};
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::storage::memory::{AUDIT_ARCHIVE, AUDIT_LOG};
+use crate::storage::record_map::RecordMap;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const AUDIT_RETENTION: u64 = 180 * NANOS_PER_DAY; // Older entries move to the archive
+const ARCHIVE_INTERVAL_SECS: u64 = 24 * 60 * 60; // Daily
+const MAX_ARCHIVED_PER_RUN: u32 = 500; // Bounds instructions per timer tick
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Narrows an audit log query; every field is optional
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct AuditLogFilter {
+    pub actor: Option<Principal>,
+    pub action: Option<AuditAction>,
+    pub target: Option<String>,
+    pub from: Option<u64>,      // Inclusive, nanoseconds
+    pub to: Option<u64>,        // Exclusive, nanoseconds
+}
+
+impl AuditLogFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.is_none_or(|actor| entry.actor == actor)
+            && self.action.is_none_or(|action| entry.action == action)
+            && self.target.as_ref().is_none_or(|target| &entry.target == target)
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp < to)
+    }
+}
+
+/// A page of entries, newest first. Pass `next_cursor` back as `before_id`
+/// for the next page; it is None on the last page.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditEntry>,
+    pub next_cursor: Option<u64>,
+}
+
+/// SHA-256 hex of the candid encoding of `value`
+pub fn digest<T: CandidType>(value: &T) -> String {
+    let bytes = candid::encode_one(value).expect("Failed to encode audited value");
+    hex::encode(Sha256::digest(&bytes))
+}
+
+fn next_audit_id() -> u64 {
+    // Archived entries keep their ids, so look at both maps
+    let last_live = AUDIT_LOG.with(|log| log.borrow().last_key_value().map(|(id, _)| id));
+    let last_archived = AUDIT_ARCHIVE.with(|archive| archive.borrow().last_key_value().map(|(id, _)| id));
+    last_live.max(last_archived).map_or(0, |id| id + 1)
+}
+
+/// Append an entry to the audit log and return its id
+pub fn record_audit(
+    actor: Principal,
+    action: AuditAction,
+    target: impl Into<String>,
+    before_digest: Option<String>,
+    after_digest: Option<String>,
+    now: u64,
+) -> u64 {
+    let mut target = target.into();
+    if target.len() > MAX_AUDIT_TARGET_LEN {
+        let mut end = MAX_AUDIT_TARGET_LEN;
+        while !target.is_char_boundary(end) {
+            end -= 1;
+        }
+        target.truncate(end);
+    }
+
+    let id = next_audit_id();
+    let entry = AuditEntry {
+        id,
+        timestamp: now,
+        actor,
+        action,
+        target,
+        before_digest,
+        after_digest,
+    };
+    AUDIT_LOG.with(|log| log.borrow_mut().insert(id, entry));
+    id
+}
+
+/// `record_audit` for the current caller, from an endpoint
+pub fn audit(action: AuditAction, target: impl Into<String>, before_digest: Option<String>, after_digest: Option<String>) {
+    record_audit(caller(), action, target, before_digest, after_digest, ic_cdk::api::time());
+}
+
+fn query_entries(entries: &RecordMap<u64, AuditEntry>, filter: &AuditLogFilter, before_id: Option<u64>, limit: u32) -> AuditLogPage {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+
+    let mut entries: Vec<AuditEntry> = entries
+        .range(..before_id.unwrap_or(u64::MAX))
+        .rev()
+        .map(|(_, entry)| entry)
+        .filter(|entry| filter.matches(entry))
+        .take(limit + 1)
+        .collect();
+
+    let has_more = entries.len() > limit;
+    entries.truncate(limit);
+    let next_cursor = if has_more { entries.last().map(|entry| entry.id) } else { None };
+    AuditLogPage { entries, next_cursor }
+}
+
+/// Page through the live audit log, newest first
+pub fn query_audit_log(filter: &AuditLogFilter, before_id: Option<u64>, limit: u32) -> AuditLogPage {
+    AUDIT_LOG.with(|log| query_entries(&log.borrow(), filter, before_id, limit))
+}
+
+/// Page through archived audit entries, newest first
+pub fn query_audit_archive(filter: &AuditLogFilter, before_id: Option<u64>, limit: u32) -> AuditLogPage {
+    AUDIT_ARCHIVE.with(|archive| query_entries(&archive.borrow(), filter, before_id, limit))
+}
+
+/// Move entries older than the retention window to the archive, oldest first
+/// and at most `MAX_ARCHIVED_PER_RUN` per call. Returns how many were moved.
+pub fn archive_audit_entries(now: u64) -> u32 {
+    let cutoff = now.saturating_sub(AUDIT_RETENTION);
+    let expired: Vec<AuditEntry> = AUDIT_LOG.with(|log| {
+        log.borrow()
+            .iter()
+            .take_while(|(_, entry)| entry.timestamp < cutoff)
+            .take(MAX_ARCHIVED_PER_RUN as usize)
+            .map(|(_, entry)| entry)
+            .collect()
+    });
+
+    for entry in &expired {
+        AUDIT_ARCHIVE.with(|archive| archive.borrow_mut().insert(entry.id, entry.clone()));
+        AUDIT_LOG.with(|log| log.borrow_mut().remove(&entry.id));
+    }
+    expired.len() as u32
+}
+
+/// Start the audit retention timer. Timers do not survive upgrades, so this
+/// is called from both `init` and `post_upgrade`.
+pub fn start_audit_retention_timer() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(ARCHIVE_INTERVAL_SECS), || {
+        let archived = archive_audit_entries(ic_cdk::api::time());
+        if archived > 0 {
+            ic_cdk::println!("Archived {} audit log entries", archived);
+        }
+    });
+}
+
+/// Query the audit log, newest first (SuperAdmin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_audit_log(filter: AuditLogFilter, before_id: Option<u64>, limit: u32) -> Result<AuditLogPage, String> {
+    require_permission(Permission::ViewAuditLog)?;
+    Ok(query_audit_log(&filter, before_id, limit))
+}
+
+/// Query archived audit entries, newest first (SuperAdmin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_audit_archive(filter: AuditLogFilter, before_id: Option<u64>, limit: u32) -> Result<AuditLogPage, String> {
+    require_permission(Permission::ViewAuditLog)?;
+    Ok(query_audit_archive(&filter, before_id, limit))
+}
@@ -2,13 +2,29 @@ use crate::models::stable_principal::StablePrincipal;
 // use crate::models::stable_string::StableString;
 use crate::models::user::SubscriptionTier;
 use crate::models::user::User;
-use crate::storage::memory::{OPENCHAT_USERS, USERS, SLACK_USERS, DISCORD_USERS, ACCELERATORS, STARTUPS};
+use crate::models::platform::Platform;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::services::platform_identity_service::{identities_for_principal, linked_external_id};
+use crate::storage::memory::{USERS, ACCELERATORS, STARTUPS};
 use candid::Principal;
 use ic_cdk::api::time;
 use ic_cdk::caller;
 use ic_cdk::{query, update};
 
-#[update]
+/// Guard for endpoints that act on the caller's own data: rejects the
+/// anonymous principal, which every unauthenticated client shares
+pub fn caller_is_authenticated() -> Result<(), String> {
+    ensure_authenticated(caller())
+}
+
+pub fn ensure_authenticated(principal: Principal) -> Result<(), String> {
+    if principal == Principal::anonymous() {
+        return Err("Unauthorized: Anonymous caller".to_string());
+    }
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
 pub fn register_user(name: String) -> Result<User, String> {
     let caller = caller();
 
@@ -16,27 +32,9 @@ pub fn register_user(name: String) -> Result<User, String> {
         return Err("User already exists".to_string());
     }
 
-    let openchat_id = OPENCHAT_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
-    let slack_id = SLACK_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
-    let discord_id = DISCORD_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
+    let openchat_id = linked_external_id(caller, Platform::OpenChat);
+    let slack_id = linked_external_id(caller, Platform::Slack);
+    let discord_id = linked_external_id(caller, Platform::Discord);
 
     let user = User {
         principal: StablePrincipal::new(caller),
@@ -57,7 +55,7 @@ pub fn register_user(name: String) -> Result<User, String> {
     Ok(user)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn register_startup(startup_name: String, founder_name: String, email: String) -> Result<User, String> {
     let caller = caller();
 
@@ -65,27 +63,9 @@ pub fn register_startup(startup_name: String, founder_name: String, email: Strin
         return Err("User already exists".to_string());
     }
 
-    let openchat_id = OPENCHAT_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
-    let slack_id = SLACK_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
-    let discord_id = DISCORD_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-            .map(|(id, _)| id.as_str().to_string())
-    });
+    let openchat_id = linked_external_id(caller, Platform::OpenChat);
+    let slack_id = linked_external_id(caller, Platform::Slack);
+    let discord_id = linked_external_id(caller, Platform::Discord);
 
     let user = User {
         principal: StablePrincipal::new(caller),
@@ -106,7 +86,15 @@ pub fn register_startup(startup_name: String, founder_name: String, email: Strin
     Ok(user)
 }
 
-#[query]
+/// Chat platform accounts linked to `principal`; main site IDs don't make a user
+fn bot_platform_identities(principal: Principal) -> Vec<PlatformIdentity> {
+    identities_for_principal(principal)
+        .into_iter()
+        .filter(|identity| identity.platform != Platform::MainSite)
+        .collect()
+}
+
+#[query(guard = "caller_is_authenticated")]
 pub fn get_current_user() -> Option<User> {
     let caller = caller();
 
@@ -121,70 +109,20 @@ pub fn get_current_user() -> Option<User> {
         return direct_user;
     }
 
-    // If not found, check if this is an OpenChat user
-    let openchat_user = OPENCHAT_USERS.with(|users| {
-        let users = users.borrow();
-        for (openchat_id, openchat_user) in users.iter() {
-            if openchat_user.site_principal.as_ref().map(|p| p.get()) == Some(caller) {
-                return Some((openchat_id.as_str().to_string(), openchat_user.first_interaction));
-            }
-        }
-        None
-    });
-    if let Some((openchat_id, created_at)) = openchat_user {
+    // If not found, check if this is a linked bot platform user
+    let linked = bot_platform_identities(caller);
+    if let Some(identity) = linked.first() {
+        let linked_id = |platform| linked.iter().find(|i| i.platform == platform).map(|i| i.external_id.clone());
         return Some(User {
             principal: StablePrincipal::new(caller),
-            name: format!("OpenChat User {}", openchat_id),
+            name: format!("{} User {}", identity.platform, identity.external_id),
             email: None,
-            created_at,
+            // Slack and Discord users registered before interactions were tracked have no first interaction
+            created_at: if identity.first_interaction > 0 { identity.first_interaction } else { time() },
             subscription_tier: SubscriptionTier::Free,
-            openchat_id: Some(openchat_id),
-            slack_id: None,
-            discord_id: None,
-        });
-    }
-
-    let slack_user = SLACK_USERS.with(|users| {
-        let users = users.borrow();
-        for (slack_id, slack_user) in users.iter() {
-            if slack_user.site_principal.as_ref().map(|p| p.get()) == Some(caller) {
-                return Some((slack_id.as_str().to_string(), slack_user.display_name.clone(), slack_user.team_id.clone()));
-            }
-        }
-        None
-    });
-    if let Some((slack_id, _display_name, _team_id)) = slack_user {
-        return Some(User {
-            principal: StablePrincipal::new(caller),
-            name: format!("Slack User {}", slack_id),
-            email: None,
-            created_at: time(),
-            subscription_tier: SubscriptionTier::Free,
-            openchat_id: None,
-            slack_id: Some(slack_id),
-            discord_id: None,
-        });
-    }
-
-    let discord_user = DISCORD_USERS.with(|users| {
-        let users = users.borrow();
-        for (discord_id, discord_user) in users.iter() {
-            if discord_user.site_principal.as_ref().map(|p| p.get()) == Some(caller) {
-                return Some((discord_id.as_str().to_string(), discord_user.username.clone(), discord_user.guild_id.clone()));
-            }
-        }
-        None
-    });
-    if let Some((discord_id, _username, _guild_id)) = discord_user {
-        return Some(User {
-            principal: StablePrincipal::new(caller),
-            name: format!("Discord User {}", discord_id),
-            email: None,
-            created_at: time(), // You may want to store first_interaction in DiscordUser for accuracy
-            subscription_tier: SubscriptionTier::Free,
-            openchat_id: None,
-            slack_id: None,
-            discord_id: Some(discord_id),
+            openchat_id: linked_id(Platform::OpenChat),
+            slack_id: linked_id(Platform::Slack),
+            discord_id: linked_id(Platform::Discord),
         });
     }
 
@@ -240,26 +178,9 @@ pub fn get_current_user() -> Option<User> {
 pub fn is_registered() -> bool {
     let caller = caller();
 
-    // Check USERS, OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS
+    // Check USERS, linked bot platform users, accelerators and founders
     USERS.with(|users| users.borrow().contains_key(&StablePrincipal::new(caller)))
-        || OPENCHAT_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .any(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-        })
-        || SLACK_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .any(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-        })
-        || DISCORD_USERS.with(|users| {
-            users
-                .borrow()
-                .iter()
-                .any(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller))
-        })
+        || !bot_platform_identities(caller).is_empty()
         || ACCELERATORS.with(|users| {
             users
                 .borrow()
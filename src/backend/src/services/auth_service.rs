@@ -1,9 +1,14 @@
+use crate::models::admin::Permission;
 use crate::models::dashboard_token::DashboardToken;
-use crate::models::stable_principal::StablePrincipal;
+use crate::models::platform::Platform;
 use crate::models::stable_string::StableString;
-use crate::storage::memory::{DASHBOARD_TOKENS, OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS};
+use crate::services::admin::{authorize, caller_is_admin, require_permission};
+use crate::services::auth::caller_is_authenticated;
+use crate::services::identity_merge_service::{link_identity_with_records, unlink_identity_with_records};
+use crate::services::platform_identity_service::{ensure_identity, linked_principal};
+use crate::storage::memory::DASHBOARD_TOKENS;
 use candid::Principal;
-use ic_cdk::{query, update};
+use ic_cdk::{caller, query, update};
 
 #[update]
 pub fn verify_token(token: String) -> Result<(), String> {
@@ -24,8 +29,13 @@ pub fn verify_token(token: String) -> Result<(), String> {
     })
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn link_token_to_principal(token: String, principal: Principal) -> Result<(), String> {
+    // The token proves the platform account; the principal must be the caller's own
+    if principal != caller() {
+        return Err("Unauthorized: Can only link your own principal".to_string());
+    }
+
     // First verify the token
     verify_token(token.clone())?;
 
@@ -35,34 +45,18 @@ pub fn link_token_to_principal(token: String, principal: Principal) -> Result<()
         if let Some(token_record) = tokens.get(&StableString::from(token.clone())) {
             // Link the account using the centralized function
             ic_cdk::println!("Linking account to principal: {}", principal);
-            ic_cdk::println!("Token record platform_id: {}", token_record.openchat_id);
+            ic_cdk::println!("Token record platform_id: {}", token_record.platform_id);
             
-            // Decode the platform the user is coming from
-            let platform_id = token_record.openchat_id.clone();
-            let platform = if platform_id.starts_with('U') {
-                Platform::Slack
-            } else if platform_id.chars().all(|c| c.is_numeric()) {
-                Platform::Discord
-            } else {
-                Platform::OpenChat
-            };
+            // The platform the user is coming from
+            let platform = token_record.platform;
+            let platform_id = token_record.platform_id.clone();
             
-            ic_cdk::println!("Detected platform: {:?}", platform);
+            ic_cdk::println!("Token platform: {:?}", platform);
             
             // Ensure user exists before linking
-            match platform {
-                Platform::Slack => {
-                    crate::services::slack_service::ensure_slack_user(platform_id.clone());
-                }
-                Platform::Discord => {
-                    crate::services::discord_service::ensure_discord_user(platform_id.clone());
-                }
-                Platform::OpenChat => {
-                    crate::services::openchat_service::ensure_openchat_user(platform_id.clone());
-                }
-            }
-            
-            let result = link_accounts(principal, platform_id);
+            ensure_identity(platform, &platform_id, ic_cdk::api::time());
+
+            let result = link_platform_account(principal, platform, platform_id);
             ic_cdk::println!("Link result: {:?}", result);
             // Remove the token after linking
             tokens.remove(&StableString::from(token));
@@ -83,139 +77,28 @@ pub fn get_token_info(token: String) -> Option<DashboardToken> {
     })
 }
 
-// Debug function to list all tokens (remove this before production)
-#[query]
-pub fn list_all_tokens() -> Vec<(String, DashboardToken)> {
-    DASHBOARD_TOKENS.with(|tokens| {
-        let tokens = tokens.borrow();
-        let mut result = Vec::new();
-        for (key, token_record) in tokens.iter() {
-            result.push((key.to_string(), token_record.clone()));
-        }
-        result
-    })
-}
+/// Unlink a platform account from the caller (admins may unlink any account).
+/// The records it brought with it when linked move back to it.
+#[update(guard = "caller_is_authenticated")]
+pub fn unlink_accounts(platform: Platform, platform_id: String) -> Result<(), String> {
+    let caller = caller();
+    if linked_principal(platform, &platform_id) != Some(caller) {
+        authorize(caller, Permission::ManageUsers)
+            .map_err(|_| "Unauthorized: Account is not linked to you".to_string())?;
+    }
 
-#[derive(Debug)]
-pub enum Platform {
-    OpenChat,
-    Slack,
-    Discord,
+    unlink_identity_with_records(platform, &platform_id)
 }
 
-#[update]
-pub fn unlink_accounts(platform_id: String) -> Result<(), String> {
-    // Determine platform based on ID format
-    let platform = if platform_id.starts_with('U') {
-        Platform::Slack
-    } else if platform_id.chars().all(|c| c.is_numeric()) {
-        Platform::Discord
-    } else {
-        Platform::OpenChat
-    };
-
-    match platform {
-        Platform::OpenChat => {
-            OPENCHAT_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    user.site_principal = None;
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("OpenChat user not found".to_string())
-                }
-            })
-        }
-        Platform::Slack => {
-            SLACK_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    user.site_principal = None;
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("Slack user not found".to_string())
-                }
-            })
-        }
-        Platform::Discord => {
-            DISCORD_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    user.site_principal = None;
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("Discord user not found".to_string())
-                }
-            })
-        }
-    }
+/// Link a platform account to any principal, for admins. Users link their own
+/// accounts with `link_token_to_principal`, whose token proves the account is theirs.
+#[update(guard = "caller_is_admin")]
+pub fn link_accounts(site_principal: Principal, platform: Platform, platform_id: String) -> Result<(), String> {
+    require_permission(Permission::ManageUsers)?;
+    link_platform_account(site_principal, platform, platform_id)
 }
 
-#[update]
-pub fn link_accounts(site_principal: Principal, platform_id: String) -> Result<(), String> {
-    // Determine platform based on ID format
-    let platform = if platform_id.starts_with('U') {
-        Platform::Slack
-    } else if platform_id.chars().all(|c| c.is_numeric()) {
-        Platform::Discord
-    } else {
-        Platform::OpenChat
-    };
-
-    match platform {
-        Platform::OpenChat => {
-            OPENCHAT_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    ic_cdk::println!("[link_accounts] OpenChat: platform_id={}, current site_principal={:?}, new principal={:?}", platform_id, user.site_principal, site_principal);
-                    // If already linked, unlink first
-                    if user.site_principal.is_some() {
-                        user.site_principal = None;
-                    }
-                    user.site_principal = Some(StablePrincipal::new(site_principal));
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("OpenChat user not found".to_string())
-                }
-            })
-        }
-        Platform::Slack => {
-            SLACK_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    ic_cdk::println!("[link_accounts] Slack: platform_id={}, current site_principal={:?}, new principal={:?}", platform_id, user.site_principal, site_principal);
-                    // If already linked, unlink first
-                    if user.site_principal.is_some() {
-                        user.site_principal = None;
-                    }
-                    user.site_principal = Some(StablePrincipal::new(site_principal));
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("Slack user not found".to_string())
-                }
-            })
-        }
-        Platform::Discord => {
-            DISCORD_USERS.with(|users| {
-                let mut users = users.borrow_mut();
-                if let Some(mut user) = users.get(&StableString::from(platform_id.clone())) {
-                    ic_cdk::println!("[link_accounts] Discord: platform_id={}, current site_principal={:?}, new principal={:?}", platform_id, user.site_principal, site_principal);
-                    // If already linked, unlink first
-                    if user.site_principal.is_some() {
-                        user.site_principal = None;
-                    }
-                    user.site_principal = Some(StablePrincipal::new(site_principal));
-                    users.insert(StableString::from(platform_id), user);
-                    Ok(())
-                } else {
-                    Err("Discord user not found".to_string())
-                }
-            })
-        }
-    }
-} 
\ No newline at end of file
+fn link_platform_account(site_principal: Principal, platform: Platform, platform_id: String) -> Result<(), String> {
+    ic_cdk::println!("[link_accounts] {}: platform_id={}, new principal={:?}", platform, platform_id, site_principal);
+    link_identity_with_records(platform, &platform_id, site_principal)
+}
@@ -0,0 +1,187 @@
+// backend/src/services/bot_service.rs
+//
+// Registry of trusted bot backends (Slack, Discord, OpenChat and playground
+// bots). Bot-facing endpoints in api_service and token_service are guarded by
+// the permission they need; admins register, rotate and remove bots.
+
+use candid::{CandidType, Principal};
+use ic_cdk::{caller, query, update};
+use serde::Deserialize;
+
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::models::bot_principal::{BotPermission, BotPrincipal};
+use crate::models::platform::Platform;
+use crate::models::stable_principal::StablePrincipal;
+use crate::services::admin::{authorize, caller_is_admin, require_permission};
+use crate::services::audit_service::{digest, record_audit};
+use crate::storage::memory::BOT_PRINCIPALS;
+
+/// A bot backend to register, or new settings for a registered one
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BotRegistration {
+    pub principal: Principal,
+    pub platform: Platform,
+    pub name: String,
+    pub permissions: Vec<BotPermission>,
+}
+
+pub fn get_bot(principal: Principal) -> Option<BotPrincipal> {
+    BOT_PRINCIPALS.with(|bots| bots.borrow().get(&StablePrincipal::new(principal)))
+}
+
+fn registered_bot(principal: Principal, permission: BotPermission) -> Result<BotPrincipal, String> {
+    match get_bot(principal) {
+        Some(bot) if bot.allows(permission) => Ok(bot),
+        Some(bot) => Err(format!("Unauthorized: Bot '{}' lacks the {:?} permission", bot.name, permission)),
+        None => Err("Unauthorized: Caller is not a registered bot".to_string()),
+    }
+}
+
+/// Checks that `principal` is a registered bot for `platform` holding `permission`
+pub fn authorize_bot(principal: Principal, permission: BotPermission, platform: Platform) -> Result<BotPrincipal, String> {
+    let bot = registered_bot(principal, permission)?;
+    if bot.platform != platform {
+        return Err(format!("Unauthorized: Bot '{}' serves {} users, not {} users", bot.name, bot.platform, platform));
+    }
+    Ok(bot)
+}
+
+/// `authorize_bot` for a target that may belong to no platform, such as a
+/// principal, which any bot holding `permission` may act for
+pub fn authorize_bot_for(principal: Principal, permission: BotPermission, platform: Option<Platform>) -> Result<BotPrincipal, String> {
+    match platform {
+        Some(platform) => authorize_bot(principal, permission, platform),
+        None => registered_bot(principal, permission),
+    }
+}
+
+// The guards run before the arguments are known, so they leave the platform
+// to the endpoint
+fn require_bot_permission(permission: BotPermission) -> Result<(), String> {
+    registered_bot(caller(), permission).map(|_| ())
+}
+
+// Guards for the bot-facing endpoints, one per permission
+pub fn bot_can_store_messages() -> Result<(), String> {
+    require_bot_permission(BotPermission::StoreMessages)
+}
+
+pub fn bot_can_read_messages() -> Result<(), String> {
+    require_bot_permission(BotPermission::ReadMessages)
+}
+
+pub fn bot_can_read_usage() -> Result<(), String> {
+    require_bot_permission(BotPermission::ReadUsage)
+}
+
+pub fn bot_can_track_usage() -> Result<(), String> {
+    require_bot_permission(BotPermission::TrackUsage)
+}
+
+pub fn bot_can_upgrade_tier() -> Result<(), String> {
+    require_bot_permission(BotPermission::UpgradeTier)
+}
+
+pub fn bot_can_issue_tokens() -> Result<(), String> {
+    require_bot_permission(BotPermission::IssueTokens)
+}
+
+/// Register a bot, or replace the platform, name and permissions of a registered one
+pub fn register_bot_as(caller: Principal, registration: BotRegistration, now: u64) -> Result<BotPrincipal, String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    if registration.principal == Principal::anonymous() {
+        return Err("The anonymous principal cannot be a bot".to_string());
+    }
+    if registration.name.trim().is_empty() {
+        return Err("Bot name is required".to_string());
+    }
+    if registration.platform == Platform::Unknown {
+        return Err("A bot must serve a known platform".to_string());
+    }
+
+    let mut permissions = Vec::new();
+    for permission in registration.permissions {
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+    }
+    let existing = get_bot(registration.principal);
+    let created_at = existing.as_ref().map(|bot| bot.created_at).unwrap_or(now);
+    let bot = BotPrincipal {
+        principal: registration.principal,
+        platform: registration.platform,
+        name: registration.name.trim().to_string(),
+        permissions,
+        created_at,
+        updated_at: now,
+    };
+    BOT_PRINCIPALS.with(|bots| {
+        bots.borrow_mut().insert(StablePrincipal::new(bot.principal), bot.clone());
+    });
+    record_audit(caller, AuditAction::BotRegistered, bot.principal.to_text(), existing.as_ref().map(digest), Some(digest(&bot)), now);
+    Ok(bot)
+}
+
+/// Move a bot's registration to a new principal, e.g. after its key was rotated.
+/// The old principal loses access immediately.
+pub fn rotate_bot_as(caller: Principal, old_principal: Principal, new_principal: Principal, now: u64) -> Result<BotPrincipal, String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    if new_principal == Principal::anonymous() {
+        return Err("The anonymous principal cannot be a bot".to_string());
+    }
+    if get_bot(new_principal).is_some() {
+        return Err("New principal is already a registered bot".to_string());
+    }
+    let old = get_bot(old_principal).ok_or("Bot not found")?;
+
+    let before = digest(&old);
+    let bot = BotPrincipal {
+        principal: new_principal,
+        updated_at: now,
+        ..old
+    };
+    BOT_PRINCIPALS.with(|bots| {
+        let mut bots = bots.borrow_mut();
+        bots.remove(&StablePrincipal::new(old_principal));
+        bots.insert(StablePrincipal::new(new_principal), bot.clone());
+    });
+    record_audit(caller, AuditAction::BotRotated, old_principal.to_text(), Some(before), Some(digest(&bot)), now);
+    Ok(bot)
+}
+
+pub fn remove_bot_as(caller: Principal, principal: Principal, now: u64) -> Result<(), String> {
+    authorize(caller, Permission::ManageAdmins)?;
+
+    let removed = BOT_PRINCIPALS.with(|bots| bots.borrow_mut().remove(&StablePrincipal::new(principal)))
+        .ok_or("Bot not found")?;
+    record_audit(caller, AuditAction::BotRemoved, principal.to_text(), Some(digest(&removed)), None, now);
+    Ok(())
+}
+
+/// List registered bots (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_bots() -> Result<Vec<BotPrincipal>, String> {
+    require_permission(Permission::ViewAdmins)?;
+    Ok(BOT_PRINCIPALS.with(|bots| bots.borrow().iter().map(|(_, bot)| bot).collect()))
+}
+
+/// Register or update a bot backend (admin only)
+#[update(guard = "caller_is_admin")]
+pub fn admin_register_bot(registration: BotRegistration) -> Result<BotPrincipal, String> {
+    register_bot_as(caller(), registration, ic_cdk::api::time())
+}
+
+/// Hand a bot's registration over to a new principal (admin only)
+#[update(guard = "caller_is_admin")]
+pub fn admin_rotate_bot_principal(old_principal: Principal, new_principal: Principal) -> Result<BotPrincipal, String> {
+    rotate_bot_as(caller(), old_principal, new_principal, ic_cdk::api::time())
+}
+
+/// Revoke a bot's access (admin only)
+#[update(guard = "caller_is_admin")]
+pub fn admin_remove_bot(principal: Principal) -> Result<(), String> {
+    remove_bot_as(caller(), principal, ic_cdk::api::time())
+}
@@ -1,9 +1,10 @@
 use crate::models::chat::{BotType, ChatMessage, MessageRole};
 use crate::models::stable_principal::StablePrincipal;
 use crate::storage::memory::{CHAT_HISTORY, USERS};
+use crate::services::auth::caller_is_authenticated;
 use ic_cdk::update;
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn add_chat_message(content: String, bot_type: BotType) -> Result<ChatMessage, String> {
     let caller = ic_cdk::caller();
     if !USERS.with(|users| users.borrow().contains_key(&StablePrincipal::new(caller))) {
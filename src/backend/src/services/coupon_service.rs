@@ -0,0 +1,304 @@
+// backend/src/services/coupon_service.rs
+//
+// Promo codes for checkout: percentage or fixed discounts with redemption
+// limits, expiry and plan/period restrictions.
+
+use candid::CandidType;
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use serde::Deserialize;
+
+use crate::models::coupon::{Coupon, CouponDiscount, CouponRedemption, RedemptionStatus};
+use crate::models::payment::{AppliedDiscount, Currency};
+use crate::models::stable_string::StableString;
+use crate::models::admin::Permission;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::plan_service::resolve_price;
+use crate::services::auth::caller_is_authenticated;
+use crate::storage::memory::{COUPONS, COUPON_REDEMPTIONS};
+
+const BILLING_PERIODS: [&str; 2] = ["monthly", "yearly"];
+
+/// Price after a coupon, for the checkout page
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CouponQuote {
+    pub code: String,
+    pub original_amount: u64,
+    pub discount_amount: u64,
+    pub amount: u64,
+    pub currency: Currency,
+}
+
+/// Codes are matched case-insensitively and stored upper case
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+// ============= CATALOG =============
+
+pub fn get_coupon(code: &str) -> Option<Coupon> {
+    COUPONS.with(|coupons| coupons.borrow().get(&StableString::from(normalize_code(code))))
+}
+
+pub fn list_coupons() -> Vec<Coupon> {
+    COUPONS.with(|coupons| coupons.borrow().iter().map(|(_, coupon)| coupon).collect())
+}
+
+pub fn create_coupon(mut coupon: Coupon, now: u64) -> Result<Coupon, String> {
+    coupon.code = normalize_code(&coupon.code);
+    validate_coupon(&coupon)?;
+    if get_coupon(&coupon.code).is_some() {
+        return Err(format!("Coupon {} already exists", coupon.code));
+    }
+
+    coupon.created_at = now;
+    coupon.updated_at = now;
+    store_coupon(coupon.clone());
+    Ok(coupon)
+}
+
+/// Replace a coupon's terms. Redemptions made so far still count towards the new limits.
+pub fn update_coupon(mut coupon: Coupon, now: u64) -> Result<Coupon, String> {
+    coupon.code = normalize_code(&coupon.code);
+    validate_coupon(&coupon)?;
+    let existing = get_coupon(&coupon.code)
+        .ok_or_else(|| format!("Coupon {} not found", coupon.code))?;
+
+    coupon.created_at = existing.created_at;
+    coupon.updated_at = now;
+    store_coupon(coupon.clone());
+    Ok(coupon)
+}
+
+fn validate_coupon(coupon: &Coupon) -> Result<(), String> {
+    if coupon.code.is_empty() || !coupon.code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Coupon code must be letters, digits, '-' or '_'".to_string());
+    }
+    match &coupon.discount {
+        CouponDiscount::Percentage(percent) if *percent == 0 || *percent > 100 => {
+            return Err("Percentage discount must be between 1 and 100".to_string());
+        }
+        CouponDiscount::FixedAmount { amount: 0, .. } => {
+            return Err("Fixed discount must be greater than zero".to_string());
+        }
+        _ => {}
+    }
+    if coupon.max_redemptions == Some(0) || coupon.per_user_limit == Some(0) {
+        return Err("Redemption limits must be at least 1".to_string());
+    }
+    if let Some(period) = coupon
+        .allowed_billing_periods
+        .iter()
+        .find(|period| !BILLING_PERIODS.contains(&period.as_str()))
+    {
+        return Err(format!("Unsupported billing period: {}", period));
+    }
+    Ok(())
+}
+
+fn store_coupon(coupon: Coupon) {
+    COUPONS.with(|coupons| {
+        coupons.borrow_mut().insert(StableString::from(coupon.code.clone()), coupon);
+    });
+}
+
+// ============= REDEMPTION =============
+
+/// Check a coupon against a purchase and work out the discount on `amount`
+pub fn quote_coupon(
+    code: &str,
+    user_id: &str,
+    tier: &str,
+    billing_period: &str,
+    currency: &Currency,
+    amount: u64,
+    now: u64,
+) -> Result<AppliedDiscount, String> {
+    let coupon = get_coupon(code)
+        .filter(|coupon| coupon.is_active)
+        .ok_or_else(|| format!("Invalid coupon code: {}", normalize_code(code)))?;
+
+    if coupon.expires_at.is_some_and(|expires_at| now >= expires_at) {
+        return Err(format!("Coupon {} has expired", coupon.code));
+    }
+    if !coupon.allowed_tiers.is_empty() && !coupon.allowed_tiers.iter().any(|t| t == tier) {
+        return Err(format!("Coupon {} does not apply to the {} plan", coupon.code, tier));
+    }
+    if !coupon.allowed_billing_periods.is_empty()
+        && !coupon.allowed_billing_periods.iter().any(|p| p == billing_period)
+    {
+        return Err(format!("Coupon {} does not apply to {} billing", coupon.code, billing_period));
+    }
+
+    let redemptions = active_redemptions(&coupon.code);
+    if let Some(max) = coupon.max_redemptions {
+        if redemptions.len() as u32 >= max {
+            return Err(format!("Coupon {} has been fully redeemed", coupon.code));
+        }
+    }
+    if let Some(limit) = coupon.per_user_limit {
+        let used = redemptions.iter().filter(|r| r.user_id == user_id).count() as u32;
+        if used >= limit {
+            return Err(format!("You have already used coupon {}", coupon.code));
+        }
+    }
+
+    let discount_amount = match &coupon.discount {
+        CouponDiscount::Percentage(percent) => (amount as u128 * *percent as u128 / 100) as u64,
+        CouponDiscount::FixedAmount { amount: off, currency: coupon_currency } => {
+            if coupon_currency != currency {
+                return Err(format!(
+                    "Coupon {} only applies to {} payments",
+                    coupon.code,
+                    coupon_currency.as_str()
+                ));
+            }
+            (*off).min(amount)
+        }
+    };
+
+    Ok(AppliedDiscount {
+        coupon_code: coupon.code,
+        original_amount: amount,
+        discount_amount,
+        recurring: coupon.recurring,
+    })
+}
+
+/// Quote a coupon and hold one use of it for the payment `reference`.
+///
+/// Reserved uses count towards the limits right away, so concurrent checkouts
+/// cannot overshoot `max_redemptions`. The reservation becomes a redemption
+/// when the payment succeeds and is released when it fails.
+#[allow(clippy::too_many_arguments)]
+pub fn reserve_coupon(
+    code: &str,
+    user_id: &str,
+    reference: &str,
+    tier: &str,
+    billing_period: &str,
+    currency: &Currency,
+    amount: u64,
+    now: u64,
+) -> Result<AppliedDiscount, String> {
+    let discount = quote_coupon(code, user_id, tier, billing_period, currency, amount, now)?;
+
+    let redemption = CouponRedemption {
+        code: discount.coupon_code.clone(),
+        user_id: user_id.to_string(),
+        payment_reference: reference.to_string(),
+        tier: tier.to_string(),
+        billing_period: billing_period.to_string(),
+        currency: currency.clone(),
+        original_amount: discount.original_amount,
+        discount_amount: discount.discount_amount,
+        status: RedemptionStatus::Reserved,
+        created_at: now,
+        updated_at: now,
+    };
+    COUPON_REDEMPTIONS.with(|redemptions| {
+        redemptions.borrow_mut().insert(StableString::from(reference.to_string()), redemption);
+    });
+
+    Ok(discount)
+}
+
+/// The payment succeeded: the reserved use is final
+pub fn redeem_coupon(reference: &str, now: u64) {
+    set_redemption_status(reference, RedemptionStatus::Redeemed, now);
+}
+
+/// The payment failed or never started: give the use back
+pub fn release_coupon(reference: &str, now: u64) {
+    set_redemption_status(reference, RedemptionStatus::Released, now);
+}
+
+fn set_redemption_status(reference: &str, status: RedemptionStatus, now: u64) {
+    COUPON_REDEMPTIONS.with(|redemptions| {
+        let mut map = redemptions.borrow_mut();
+        let key = StableString::from(reference.to_string());
+        if let Some(mut redemption) = map.get(&key) {
+            // Only reservations move; a redeemed coupon stays redeemed
+            if redemption.status == RedemptionStatus::Reserved {
+                redemption.status = status;
+                redemption.updated_at = now;
+                map.insert(key, redemption);
+            }
+        }
+    });
+}
+
+/// Reserved and redeemed uses of a coupon
+fn active_redemptions(code: &str) -> Vec<CouponRedemption> {
+    list_redemptions(Some(code))
+        .into_iter()
+        .filter(|r| r.status != RedemptionStatus::Released)
+        .collect()
+}
+
+/// Coupon uses, newest first, optionally for one code
+pub fn list_redemptions(code: Option<&str>) -> Vec<CouponRedemption> {
+    let code = code.map(normalize_code);
+    let mut redemptions = COUPON_REDEMPTIONS.with(|redemptions| {
+        redemptions
+            .borrow()
+            .iter()
+            .map(|(_, redemption)| redemption)
+            .filter(|redemption| code.as_ref().is_none_or(|code| &redemption.code == code))
+            .collect::<Vec<_>>()
+    });
+    redemptions.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+    redemptions
+}
+
+// ============= API =============
+
+/// Preview a coupon on a catalog price for the caller
+#[query(guard = "caller_is_authenticated")]
+pub fn preview_coupon(code: String, tier: String, billing_period: String, currency: String) -> Result<CouponQuote, String> {
+    let currency = Currency::from_code(&currency)
+        .ok_or_else(|| format!("Unsupported currency: {}", currency))?;
+    let price = resolve_price(&tier, &billing_period, &currency)?;
+
+    let discount = quote_coupon(&code, &caller().to_string(), &tier, &billing_period, &currency, price.amount, time())?;
+    Ok(CouponQuote {
+        code: discount.coupon_code,
+        original_amount: discount.original_amount,
+        discount_amount: discount.discount_amount,
+        amount: discount.original_amount - discount.discount_amount,
+        currency,
+    })
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_coupons() -> Result<Vec<Coupon>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(list_coupons())
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_create_coupon(coupon: Coupon) -> Result<Coupon, String> {
+    require_permission(Permission::ManageBilling)?;
+    let coupon = create_coupon(coupon, time())?;
+    audit(AuditAction::CouponChanged, coupon.code.clone(), None, Some(digest(&coupon)));
+    Ok(coupon)
+}
+
+/// Update a coupon; set `is_active = false` to retire it
+#[update(guard = "caller_is_admin")]
+pub fn admin_update_coupon(coupon: Coupon) -> Result<Coupon, String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_coupon(&coupon.code);
+    let coupon = update_coupon(coupon, time())?;
+    audit(AuditAction::CouponChanged, coupon.code.clone(), before.as_ref().map(digest), Some(digest(&coupon)));
+    Ok(coupon)
+}
+
+/// Coupon uses, all codes when `code` is None
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_coupon_redemptions(code: Option<String>) -> Result<Vec<CouponRedemption>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(list_redemptions(code.as_deref()))
+}
@@ -0,0 +1,547 @@
+// backend/src/services/identity_merge_service.rs
+//
+// Moves a platform user's records to their site principal when the account is
+// linked, and back when it is unlinked. Until then their chat history, tasks
+// and issues sit under the principal derived from their platform ID, and their
+// API messages, usage, analytics and subscription under their platform user
+// ID. Every record
+// moved, or dropped because the principal already held its key, is logged in
+// MERGED_RECORDS, so unlinking returns exactly those; what was stored while
+// linked stays with the site principal.
+
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::thread::LocalKey;
+
+use crate::models::analytics::AnalyticsDataPoint;
+use crate::models::api_message::ApiMessage;
+use crate::models::chat::ChatMessage;
+use crate::models::merged_record::MergedRecord;
+use crate::models::record::VersionedRecord;
+use crate::models::platform::Platform;
+use crate::models::stable_principal::StablePrincipal;
+use crate::models::stable_string::StableString;
+use crate::models::usage_service::{UserSubscription, UserTier};
+use crate::services::platform_identity_service::{derived_principal, link_identity, linked_principal, unlink_identity};
+use crate::services::api_service::response_blob;
+use crate::storage::memory::{
+    IdentityKey, API_MESSAGES, CHAT_HISTORY, GITHUB_ISSUES, MERGED_RECORDS, PLATFORM_IDENTITIES_BY_PRINCIPAL, TASKS,
+    USER_ANALYTICS, USER_DAILY_USAGE, USER_SUBSCRIPTIONS,
+};
+use crate::storage::blob_store::rename_blob;
+use crate::storage::record_map::RecordMap;
+
+type OwnedRecords<V> = RecordMap<(StablePrincipal, StableString), V>;
+
+fn identity_key(platform: Platform, external_id: &str) -> IdentityKey {
+    (platform, StableString::from(external_id))
+}
+
+/// Links a platform account to a site principal and moves its records there.
+/// An account linked elsewhere first gets its records back from the old principal.
+pub fn link_identity_with_records(platform: Platform, external_id: &str, site_principal: Principal) -> Result<(), String> {
+    if let Some(previous) = linked_principal(platform, external_id) {
+        if previous != site_principal {
+            split_identity_records(platform, external_id, previous);
+        }
+    }
+    link_identity(platform, external_id, site_principal)?;
+    merge_identity_records(platform, external_id, site_principal);
+    Ok(())
+}
+
+/// Unlinks a platform account and moves the records merged on linking back to it
+pub fn unlink_identity_with_records(platform: Platform, external_id: &str) -> Result<(), String> {
+    if let Some(previous) = linked_principal(platform, external_id) {
+        split_identity_records(platform, external_id, previous);
+    }
+    unlink_identity(platform, external_id)
+}
+
+/// Merges the records of every linked identity. Identities merged already have
+/// nothing left to move, so this is safe to run on every upgrade.
+pub fn merge_linked_identities() -> u32 {
+    let linked = PLATFORM_IDENTITIES_BY_PRINCIPAL.with(|index| index.borrow().iter().map(|(key, _)| key).collect::<Vec<_>>());
+    linked
+        .into_iter()
+        .map(|(principal, (platform, external_id))| merge_identity_records(platform, external_id.as_str(), principal.get()))
+        .sum()
+}
+
+/// How many records merge_linked_identities would move at most. Records whose
+/// key the principal already holds are dropped instead of moved.
+pub fn pending_merge_records() -> u32 {
+    let linked = PLATFORM_IDENTITIES_BY_PRINCIPAL.with(|index| index.borrow().iter().map(|(key, _)| key).collect::<Vec<_>>());
+    linked
+        .into_iter()
+        .map(|(principal, (platform, external_id))| {
+            let mut count = 0;
+            if let Some(from) = derived_principal(platform, external_id.as_str()).filter(|from| *from != principal.get()) {
+                count += chat_messages(from).len() + owned_count(&TASKS, from) + owned_count(&GITHUB_ISSUES, from);
+            }
+            let from_id = platform.user_id(external_id.as_str());
+            count += api_messages(&from_id).len()
+                + daily_usage(&from_id).len()
+                + analytics(&from_id).len()
+                + usize::from(subscription(&from_id).is_some());
+            count as u32
+        })
+        .sum()
+}
+
+fn owned_count<V: VersionedRecord>(map: &'static LocalKey<RefCell<OwnedRecords<V>>>, owner: Principal) -> usize {
+    map.with(|map| {
+        map.borrow()
+            .range((StablePrincipal::new(owner), StableString::default())..)
+            .take_while(|((p, _), _)| p.get() == owner)
+            .count()
+    })
+}
+
+/// The sequence number for the next chat message stored under `principal`
+pub fn next_chat_seq(principal: Principal) -> u64 {
+    chat_messages(principal).last().map_or(0, |(seq, _)| seq + 1)
+}
+
+/// `seq` if `principal` has no chat message there, otherwise the next free one
+fn free_chat_seq(principal: Principal, seq: u64) -> u64 {
+    let taken = CHAT_HISTORY.with(|history| history.borrow().contains_key(&(StablePrincipal::new(principal), seq)));
+    if taken { next_chat_seq(principal) } else { seq }
+}
+
+/* ============================
+   Merge
+   ============================ */
+
+/// Moves a platform identity's records to `principal`. Returns how many moved;
+/// the dropped ones are only logged.
+pub fn merge_identity_records(platform: Platform, external_id: &str, principal: Principal) -> u32 {
+    let mut merged = Vec::new();
+
+    if let Some(from) = derived_principal(platform, external_id).filter(|from| *from != principal) {
+        merge_chat_history(from, principal, &mut merged);
+        merge_tasks(from, principal, &mut merged);
+        merge_issues(from, principal, &mut merged);
+    }
+
+    let from_id = platform.user_id(external_id);
+    let to_id = principal.to_text();
+    merge_api_messages(&from_id, &to_id, &mut merged);
+    merge_daily_usage(&from_id, &to_id, &mut merged);
+    merge_analytics(&from_id, &to_id, &mut merged);
+    merge_subscription(&from_id, &to_id, &mut merged);
+
+    let count = merged.iter().filter(|record| !record.is_dropped()).count() as u32;
+    log_merged(identity_key(platform, external_id), merged);
+    count
+}
+
+fn merge_chat_history(from: Principal, to: Principal, merged: &mut Vec<MergedRecord>) {
+    // Slack and Discord messages used to be written under both principals once linked
+    let existing: HashSet<(u64, String)> =
+        chat_messages(to).into_iter().map(|(_, message)| (message.timestamp, message.content)).collect();
+    let mut seq = next_chat_seq(to);
+    let moving = chat_messages(from);
+
+    CHAT_HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        for (from_seq, message) in moving {
+            history.remove(&(StablePrincipal::new(from), from_seq));
+            if existing.contains(&(message.timestamp, message.content.clone())) {
+                merged.push(MergedRecord::DroppedChatMessage { from_seq, message });
+                continue;
+            }
+            history.insert((StablePrincipal::new(to), seq), message);
+            merged.push(MergedRecord::ChatMessage { from_seq, seq });
+            seq += 1;
+        }
+    });
+}
+
+fn merge_tasks(from: Principal, to: Principal, merged: &mut Vec<MergedRecord>) {
+    TASKS.with(|tasks| {
+        let mut tasks = tasks.borrow_mut();
+        let moving: Vec<_> = tasks
+            .range((StablePrincipal::new(from), StableString::default())..)
+            .take_while(|((owner, _), _)| owner.get() == from)
+            .collect();
+        for ((_, id), task) in moving {
+            tasks.remove(&(StablePrincipal::new(from), id.clone()));
+            let key = (StablePrincipal::new(to), id.clone());
+            if tasks.contains_key(&key) {
+                merged.push(MergedRecord::DroppedTask { id: id.to_string(), task });
+            } else {
+                tasks.insert(key, task);
+                merged.push(MergedRecord::Task { id: id.to_string() });
+            }
+        }
+    });
+}
+
+fn merge_issues(from: Principal, to: Principal, merged: &mut Vec<MergedRecord>) {
+    GITHUB_ISSUES.with(|issues| {
+        let mut issues = issues.borrow_mut();
+        let moving: Vec<_> = issues
+            .range((StablePrincipal::new(from), StableString::default())..)
+            .take_while(|((owner, _), _)| owner.get() == from)
+            .collect();
+        for ((_, id), issue) in moving {
+            issues.remove(&(StablePrincipal::new(from), id.clone()));
+            let key = (StablePrincipal::new(to), id.clone());
+            if issues.contains_key(&key) {
+                merged.push(MergedRecord::DroppedIssue { id: id.to_string(), issue });
+            } else {
+                issues.insert(key, issue);
+                merged.push(MergedRecord::Issue { id: id.to_string() });
+            }
+        }
+    });
+}
+
+fn merge_api_messages(from_id: &str, to_id: &str, merged: &mut Vec<MergedRecord>) {
+    for (key, message) in api_messages(from_id) {
+        let (from_key, timestamp) = (key.0.to_string(), key.1);
+        let id = api_message_id(to_id, timestamp);
+        if move_api_message(&key, &id, to_id) {
+            merged.push(MergedRecord::ApiMessage { from_id: from_key, id, timestamp });
+        } else {
+            // The response blob stays under the original ID for unlinking
+            API_MESSAGES.with(|messages| messages.borrow_mut().remove(&key));
+            merged.push(MergedRecord::DroppedApiMessage { id: from_key, timestamp, message });
+        }
+    }
+}
+
+fn merge_daily_usage(from_id: &str, to_id: &str, merged: &mut Vec<MergedRecord>) {
+    for (day, count) in daily_usage(from_id) {
+        USER_DAILY_USAGE.with(|usage| usage.borrow_mut().remove(&(StableString::from(from_id), day)));
+        add_daily_usage(to_id, day, count);
+        merged.push(MergedRecord::DailyUsage { day, count });
+    }
+}
+
+fn merge_analytics(from_id: &str, to_id: &str, merged: &mut Vec<MergedRecord>) {
+    for (day, data) in analytics(from_id) {
+        USER_ANALYTICS.with(|analytics| analytics.borrow_mut().remove(&(StableString::from(from_id), day)));
+        add_analytics(to_id, day, &data);
+        merged.push(MergedRecord::Analytics { day, data });
+    }
+}
+
+/// A subscription bought through a bot moves over unless the site account's
+/// outlasts it; the one that stays behind applies again after unlinking.
+fn merge_subscription(from_id: &str, to_id: &str, merged: &mut Vec<MergedRecord>) {
+    let Some(platform_subscription) = subscription(from_id) else { return };
+    let replaced = subscription(to_id);
+    if replaced.as_ref().is_some_and(|current| !outlasts(&platform_subscription, current)) {
+        return;
+    }
+
+    let moved = UserSubscription { user_id: to_id.to_string(), ..platform_subscription };
+    USER_SUBSCRIPTIONS.with(|subs| {
+        let mut subs = subs.borrow_mut();
+        subs.remove(&StableString::from(from_id));
+        subs.insert(StableString::from(to_id), moved.clone());
+    });
+    merged.push(MergedRecord::Subscription { moved, replaced });
+}
+
+fn log_merged(key: IdentityKey, records: Vec<MergedRecord>) {
+    MERGED_RECORDS.with(|log| {
+        let mut log = log.borrow_mut();
+        let next = log
+            .range((key.clone(), 0)..)
+            .take_while(|((identity, _), _)| *identity == key)
+            .last()
+            .map_or(0, |((_, seq), _)| seq + 1);
+        for (seq, record) in (next..).zip(records) {
+            log.insert((key.clone(), seq), record);
+        }
+    });
+}
+
+/* ============================
+   Split
+   ============================ */
+
+/// Moves the records merged into `principal` back to the platform identity.
+/// Returns how many moved.
+pub fn split_identity_records(platform: Platform, external_id: &str, principal: Principal) -> u32 {
+    let records = take_merged(identity_key(platform, external_id));
+    let from = derived_principal(platform, external_id);
+    let from_id = platform.user_id(external_id);
+    let to_id = principal.to_text();
+    let count = records.len() as u32;
+
+    for record in records {
+        match record {
+            MergedRecord::ChatMessage { from_seq, seq } => {
+                let Some(from) = from else { continue };
+                let from_seq = free_chat_seq(from, from_seq);
+                CHAT_HISTORY.with(|history| {
+                    let mut history = history.borrow_mut();
+                    if let Some(message) = history.remove(&(StablePrincipal::new(principal), seq)) {
+                        history.insert((StablePrincipal::new(from), from_seq), message);
+                    }
+                });
+            }
+            MergedRecord::DroppedChatMessage { from_seq, message } => {
+                let Some(from) = from else { continue };
+                let from_seq = free_chat_seq(from, from_seq);
+                CHAT_HISTORY.with(|history| history.borrow_mut().insert((StablePrincipal::new(from), from_seq), message));
+            }
+            MergedRecord::Task { id } => {
+                let Some(from) = from else { continue };
+                TASKS.with(|tasks| {
+                    let mut tasks = tasks.borrow_mut();
+                    let key = (StablePrincipal::new(from), StableString::from(id.clone()));
+                    if tasks.contains_key(&key) {
+                        return;
+                    }
+                    if let Some(task) = tasks.remove(&(StablePrincipal::new(principal), StableString::from(id))) {
+                        tasks.insert(key, task);
+                    }
+                });
+            }
+            MergedRecord::DroppedTask { id, task } => {
+                let Some(from) = from else { continue };
+                TASKS.with(|tasks| {
+                    let mut tasks = tasks.borrow_mut();
+                    let key = (StablePrincipal::new(from), StableString::from(id));
+                    if !tasks.contains_key(&key) {
+                        tasks.insert(key, task);
+                    }
+                });
+            }
+            MergedRecord::Issue { id } => {
+                let Some(from) = from else { continue };
+                GITHUB_ISSUES.with(|issues| {
+                    let mut issues = issues.borrow_mut();
+                    let key = (StablePrincipal::new(from), StableString::from(id.clone()));
+                    if issues.contains_key(&key) {
+                        return;
+                    }
+                    if let Some(issue) = issues.remove(&(StablePrincipal::new(principal), StableString::from(id))) {
+                        issues.insert(key, issue);
+                    }
+                });
+            }
+            MergedRecord::DroppedIssue { id, issue } => {
+                let Some(from) = from else { continue };
+                GITHUB_ISSUES.with(|issues| {
+                    let mut issues = issues.borrow_mut();
+                    let key = (StablePrincipal::new(from), StableString::from(id));
+                    if !issues.contains_key(&key) {
+                        issues.insert(key, issue);
+                    }
+                });
+            }
+            MergedRecord::ApiMessage { from_id: original_id, id, timestamp } => {
+                // Left with the site principal if its original key was taken since
+                move_api_message(&(StableString::from(id), timestamp), &original_id, &from_id);
+            }
+            MergedRecord::DroppedApiMessage { id, timestamp, message } => {
+                API_MESSAGES.with(|messages| {
+                    let mut messages = messages.borrow_mut();
+                    let key = (StableString::from(id), timestamp);
+                    if !messages.contains_key(&key) {
+                        messages.insert(key, message);
+                    }
+                });
+            }
+            MergedRecord::DailyUsage { day, count } => {
+                USER_DAILY_USAGE.with(|usage| {
+                    let mut usage = usage.borrow_mut();
+                    let key = (StableString::from(to_id.as_str()), day);
+                    match usage.get(&key).unwrap_or(0).saturating_sub(count) {
+                        0 => usage.remove(&key),
+                        remaining => usage.insert(key, remaining),
+                    };
+                });
+                add_daily_usage(&from_id, day, count);
+            }
+            MergedRecord::Analytics { day, data } => {
+                USER_ANALYTICS.with(|analytics| {
+                    let mut analytics = analytics.borrow_mut();
+                    let key = (StableString::from(to_id.as_str()), day);
+                    if let Some(mut current) = analytics.get(&key) {
+                        subtract_analytics(&mut current, &data);
+                        if has_activity(&current) {
+                            analytics.insert(key, current);
+                        } else {
+                            analytics.remove(&key);
+                        }
+                    }
+                });
+                add_analytics(&from_id, day, &data);
+            }
+            MergedRecord::Subscription { moved, replaced } => {
+                // Renewed or changed since: it belongs to the site account now
+                if subscription(&to_id).as_ref() != Some(&moved) {
+                    continue;
+                }
+                USER_SUBSCRIPTIONS.with(|subs| {
+                    let mut subs = subs.borrow_mut();
+                    match replaced {
+                        Some(replaced) => subs.insert(StableString::from(to_id.as_str()), replaced),
+                        None => subs.remove(&StableString::from(to_id.as_str())),
+                    };
+                    subs.insert(
+                        StableString::from(from_id.as_str()),
+                        UserSubscription { user_id: from_id.clone(), ..moved },
+                    );
+                });
+            }
+        }
+    }
+    count
+}
+
+fn take_merged(key: IdentityKey) -> Vec<MergedRecord> {
+    MERGED_RECORDS.with(|log| {
+        let mut log = log.borrow_mut();
+        let entries: Vec<_> = log
+            .range((key.clone(), 0)..)
+            .take_while(|((identity, _), _)| *identity == key)
+            .collect();
+        entries
+            .into_iter()
+            .map(|(entry_key, record)| {
+                log.remove(&entry_key);
+                record
+            })
+            .collect()
+    })
+}
+
+/* ============================
+   Helpers
+   ============================ */
+
+fn chat_messages(principal: Principal) -> Vec<(u64, ChatMessage)> {
+    CHAT_HISTORY.with(|history| {
+        history
+            .borrow()
+            .range((StablePrincipal::new(principal), 0)..)
+            .take_while(|((owner, _), _)| owner.get() == principal)
+            .map(|((_, seq), message)| (seq, message))
+            .collect()
+    })
+}
+
+/// Message IDs are "{user_id}_{timestamp}", so a user's messages share a key prefix
+fn api_messages(user_id: &str) -> Vec<((StableString, u64), ApiMessage)> {
+    let prefix = format!("{}_", user_id);
+    API_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((StableString::from(prefix.as_str()), 0)..)
+            .take_while(|((id, _), _)| id.as_str().starts_with(&prefix))
+            .filter(|(_, message)| message.user_id == user_id)
+            .collect()
+    })
+}
+
+fn api_message_id(user_id: &str, timestamp: u64) -> String {
+    format!("{}_{}", user_id, timestamp)
+}
+
+/// Re-keys an API message to `id` under another user ID, keeping its
+/// timestamp. Returns false, changing nothing, when the message is gone or
+/// `id` is taken.
+fn move_api_message(key: &(StableString, u64), id: &str, user_id: &str) -> bool {
+    let timestamp = key.1;
+    let new_key = (StableString::from(id), timestamp);
+    let moved = API_MESSAGES.with(|messages| {
+        let mut messages = messages.borrow_mut();
+        if messages.contains_key(&new_key) {
+            return false;
+        }
+        let Some(message) = messages.remove(key) else { return false };
+        messages.insert(new_key, ApiMessage { id: id.to_string(), user_id: user_id.to_string(), ..message });
+        true
+    });
+    if moved {
+        rename_blob(&response_blob(key.0.as_str()), &response_blob(id));
+    }
+    moved
+}
+
+fn daily_usage(user_id: &str) -> Vec<(u64, u32)> {
+    USER_DAILY_USAGE.with(|usage| {
+        usage
+            .borrow()
+            .range((StableString::from(user_id), 0)..)
+            .take_while(|((id, _), _)| id.as_str() == user_id)
+            .map(|((_, day), count)| (day, count))
+            .collect()
+    })
+}
+
+fn add_daily_usage(user_id: &str, day: u64, count: u32) {
+    USER_DAILY_USAGE.with(|usage| {
+        let mut usage = usage.borrow_mut();
+        let key = (StableString::from(user_id), day);
+        let current = usage.get(&key).unwrap_or(0);
+        usage.insert(key, current + count);
+    });
+}
+
+fn analytics(user_id: &str) -> Vec<(u64, AnalyticsDataPoint)> {
+    USER_ANALYTICS.with(|analytics| {
+        analytics
+            .borrow()
+            .range((StableString::from(user_id), 0)..)
+            .take_while(|((id, _), _)| id.as_str() == user_id)
+            .map(|((_, day), data)| (day, data))
+            .collect()
+    })
+}
+
+fn add_analytics(user_id: &str, day: u64, data: &AnalyticsDataPoint) {
+    USER_ANALYTICS.with(|analytics| {
+        let mut analytics = analytics.borrow_mut();
+        let key = (StableString::from(user_id), day);
+        let combined = match analytics.get(&key) {
+            Some(mut current) => {
+                current.requests_made += data.requests_made;
+                current.lines_of_code_edited += data.lines_of_code_edited;
+                current.ai_interactions += data.ai_interactions;
+                current.tasks_completed += data.tasks_completed;
+                current.timestamp_ns = current.timestamp_ns.max(data.timestamp_ns);
+                current
+            }
+            None => data.clone(),
+        };
+        analytics.insert(key, combined);
+    });
+}
+
+fn subtract_analytics(current: &mut AnalyticsDataPoint, data: &AnalyticsDataPoint) {
+    current.requests_made = current.requests_made.saturating_sub(data.requests_made);
+    current.lines_of_code_edited = current.lines_of_code_edited.saturating_sub(data.lines_of_code_edited);
+    current.ai_interactions = current.ai_interactions.saturating_sub(data.ai_interactions);
+    current.tasks_completed = current.tasks_completed.saturating_sub(data.tasks_completed);
+}
+
+fn has_activity(data: &AnalyticsDataPoint) -> bool {
+    data.requests_made > 0 || data.lines_of_code_edited > 0 || data.ai_interactions > 0 || data.tasks_completed > 0
+}
+
+fn subscription(user_id: &str) -> Option<UserSubscription> {
+    USER_SUBSCRIPTIONS.with(|subs| subs.borrow().get(&StableString::from(user_id)))
+}
+
+/// Whether `a` grants Pro for longer than `b`; no expiry means it never ends
+fn outlasts(a: &UserSubscription, b: &UserSubscription) -> bool {
+    match (&a.tier, &b.tier) {
+        (UserTier::Free, _) => false,
+        (UserTier::Pro, UserTier::Free) => true,
+        (UserTier::Pro, UserTier::Pro) => match (a.expires_at_ns, b.expires_at_ns) {
+            (None, Some(_)) => true,
+            (Some(a), Some(b)) => a > b,
+            _ => false,
+        },
+    }
+}
@@ -0,0 +1,537 @@
+// backend/src/services/invoice_service.rs
+//
+// Invoice documents: gap-free numbering per year, VAT contained in the
+// totals, seller/buyer details, and HTML/PDF rendering for bookkeeping.
+
+use candid::{CandidType, Principal};
+use chrono::{DateTime, Datelike};
+use ic_cdk::{query, update};
+use serde::Deserialize;
+
+use crate::models::payment::{
+    Currency, Invoice, InvoiceKind, InvoiceLineItem, InvoiceParty, InvoiceSettings, InvoiceTaxLine, PaymentRecord,
+};
+use crate::models::stable_string::StableString;
+use crate::models::admin::Permission;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::plan_service::get_plan;
+use crate::services::sponsorship_service::{find_accelerator, get_sponsorship};
+use crate::services::payment_service::can_view_billing;
+use crate::storage::memory::{INVOICES, INVOICE_SEQUENCES, INVOICE_SETTINGS};
+
+const INVOICE_SETTINGS_KEY: u8 = 0;
+
+/// Output format of `payment_render_invoice`
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum InvoiceFormat {
+    Html,
+    Pdf,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RenderedInvoice {
+    pub filename: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+// ============= SETTINGS =============
+
+/// Current seller details (defaults until an admin changes them)
+pub fn get_invoice_settings() -> InvoiceSettings {
+    INVOICE_SETTINGS.with(|settings| {
+        settings.borrow().get(&INVOICE_SETTINGS_KEY).unwrap_or_default()
+    })
+}
+
+pub fn set_invoice_settings(settings: InvoiceSettings) -> Result<(), String> {
+    if settings.seller_name.trim().is_empty() {
+        return Err("seller_name is required".to_string());
+    }
+    for registration in &settings.tax_registrations {
+        let country = &registration.country;
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("Invalid country code: {}", country));
+        }
+        if registration.tax_id.trim().is_empty() {
+            return Err(format!("Missing tax id for {}", country));
+        }
+    }
+
+    INVOICE_SETTINGS.with(|s| {
+        s.borrow_mut().insert(INVOICE_SETTINGS_KEY, settings);
+    });
+    Ok(())
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_invoice_settings() -> Result<InvoiceSettings, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_invoice_settings())
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_set_invoice_settings(settings: InvoiceSettings) -> Result<(), String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = digest(&get_invoice_settings());
+    let after = digest(&settings);
+    set_invoice_settings(settings)?;
+    audit(AuditAction::InvoiceSettingsChanged, "invoice_settings", Some(before), Some(after));
+    Ok(())
+}
+
+// ============= TAX =============
+
+/// Country whose VAT applies to a payment. Each supported currency is only
+/// sold to customers in its own country.
+pub fn tax_country(currency: &Currency) -> Option<&'static str> {
+    match currency {
+        Currency::NGN => Some("NG"),
+        Currency::KES => Some("KE"),
+        Currency::GHS => Some("GH"),
+        Currency::ZAR => Some("ZA"),
+        Currency::USD => None,
+    }
+}
+
+/// Taxes charged on digital services in a country, in basis points
+fn tax_rules(country: &str) -> &'static [(&'static str, u32)] {
+    match country {
+        "NG" => &[("VAT", 750)],
+        "KE" => &[("VAT", 1600)],
+        "GH" => &[("VAT", 1500), ("NHIL", 250), ("GETFund Levy", 250)],
+        "ZA" => &[("VAT", 1500)],
+        _ => &[],
+    }
+}
+
+/// Split a tax-inclusive amount into its net amount and tax lines. The
+/// parts always add up to `gross`.
+pub fn split_tax(gross: u64, country: Option<&str>) -> (u64, Vec<InvoiceTaxLine>) {
+    let rules = country.map(tax_rules).unwrap_or(&[]);
+    let total_bps: u32 = rules.iter().map(|(_, rate)| rate).sum();
+    if total_bps == 0 {
+        return (gross, vec![]);
+    }
+
+    let divisor = 10_000 + total_bps as u128;
+    let net = ((gross as u128 * 10_000 + divisor / 2) / divisor) as u64;
+    let total_tax = gross - net;
+
+    // Share the tax by rate; the last line takes the rounding remainder
+    let mut remaining = total_tax;
+    let lines = rules
+        .iter()
+        .enumerate()
+        .map(|(i, (name, rate))| {
+            let amount = if i + 1 == rules.len() {
+                remaining
+            } else {
+                (total_tax as u128 * *rate as u128 / total_bps as u128) as u64
+            };
+            remaining -= amount;
+            InvoiceTaxLine {
+                name: name.to_string(),
+                rate_bps: *rate,
+                amount,
+            }
+        })
+        .collect();
+    (net, lines)
+}
+
+// ============= ISSUING =============
+
+fn year_of(now: u64) -> i32 {
+    DateTime::from_timestamp((now / 1_000_000_000) as i64, 0)
+        .map(|date| date.year())
+        .unwrap_or(1970)
+}
+
+/// Take the next number in the invoice's series for the year of `now`,
+/// e.g. "INF-2025-000042" or "CN-2025-000003"
+fn next_invoice_number(kind: &InvoiceKind, now: u64) -> String {
+    let series = match kind {
+        InvoiceKind::Charge => "INF",
+        InvoiceKind::CreditNote => "CN",
+    };
+    let year = year_of(now);
+    let key = StableString::from(format!("{}-{}", series, year));
+
+    let number = INVOICE_SEQUENCES.with(|sequences| {
+        let mut sequences = sequences.borrow_mut();
+        let number = sequences.get(&key).unwrap_or(0) + 1;
+        sequences.insert(key, number);
+        number
+    });
+    format!("{}-{}-{:06}", series, year, number)
+}
+
+fn seller_for(country: Option<&str>) -> InvoiceParty {
+    let settings = get_invoice_settings();
+    let tax_id = settings
+        .tax_registrations
+        .iter()
+        .find(|registration| Some(registration.country.as_str()) == country)
+        .map(|registration| registration.tax_id.clone());
+    InvoiceParty {
+        name: settings.seller_name,
+        email: settings.seller_email,
+        address: settings.seller_address,
+        country: None,
+        tax_id,
+    }
+}
+
+fn buyer_for(payment: &PaymentRecord, country: Option<&str>) -> InvoiceParty {
+    // Seat purchases are billed to the accelerator
+    if let Some(accelerator) = find_accelerator(&payment.user_id) {
+        return InvoiceParty {
+            name: accelerator.name,
+            email: Some(accelerator.email),
+            address: None,
+            country: country.map(str::to_string),
+            tax_id: None,
+        };
+    }
+
+    let email = (!payment.email.is_empty()).then(|| payment.email.clone());
+    let name = payment
+        .metadata
+        .customer_name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| email.clone())
+        .unwrap_or_else(|| payment.user_id.clone());
+    InvoiceParty {
+        name,
+        email,
+        address: None,
+        country: country.map(str::to_string),
+        tax_id: None,
+    }
+}
+
+/// Items billed by a successful payment, at catalog price before any discount
+pub fn charge_line_items(payment: &PaymentRecord) -> Vec<InvoiceLineItem> {
+    let plan_name = get_plan(&payment.tier)
+        .map(|plan| plan.name)
+        .unwrap_or_else(|| payment.tier.clone());
+    let gross = payment
+        .discount
+        .as_ref()
+        .map(|discount| discount.original_amount)
+        .unwrap_or(payment.amount);
+
+    let (description, quantity) = match get_sponsorship(&payment.paystack_reference) {
+        Some(sponsorship) => (
+            format!("{} plan, sponsored seat ({})", plan_name, payment.billing_period),
+            sponsorship.seats.max(1),
+        ),
+        None => (format!("{} plan ({})", plan_name, payment.billing_period), 1),
+    };
+
+    vec![InvoiceLineItem {
+        description,
+        quantity,
+        unit_amount: gross / quantity as u64,
+        amount: gross,
+    }]
+}
+
+/// Complete and store a new invoice or credit note for `payment`: assigns the
+/// next number in its series, splits out tax and fills in seller and buyer.
+///
+/// Issuing an id that already exists returns the stored invoice without
+/// taking a number, so retries never leave gaps in the sequence.
+pub fn issue_invoice(mut invoice: Invoice, payment: &PaymentRecord, now: u64) -> Invoice {
+    let key = StableString::from(invoice.id.clone());
+    if let Some(existing) = INVOICES.with(|invoices| invoices.borrow().get(&key)) {
+        return existing;
+    }
+
+    let country = tax_country(&invoice.currency);
+    let (net_amount, tax_lines) = split_tax(invoice.amount, country);
+    invoice.invoice_number = next_invoice_number(&invoice.kind, now);
+    invoice.net_amount = net_amount;
+    invoice.tax_lines = tax_lines;
+    invoice.seller = seller_for(country);
+    invoice.buyer = buyer_for(payment, country);
+
+    INVOICES.with(|invoices| {
+        invoices.borrow_mut().insert(key, invoice.clone());
+    });
+    invoice
+}
+
+pub fn get_invoice(invoice_id: &str) -> Option<Invoice> {
+    INVOICES.with(|invoices| invoices.borrow().get(&StableString::from(invoice_id.to_string())))
+}
+
+/// The invoice's owner, or a team member of the accelerator it was issued to
+pub fn can_view_invoice(caller: Principal, invoice: &Invoice) -> bool {
+    can_view_billing(caller, &invoice.user_id)
+}
+
+// ============= RENDERING =============
+
+pub fn render_invoice(invoice: &Invoice, format: InvoiceFormat) -> RenderedInvoice {
+    match format {
+        InvoiceFormat::Html => RenderedInvoice {
+            filename: format!("{}.html", invoice.invoice_number),
+            content_type: "text/html; charset=utf-8".to_string(),
+            body: render_html(invoice).into_bytes(),
+        },
+        InvoiceFormat::Pdf => RenderedInvoice {
+            filename: format!("{}.pdf", invoice.invoice_number),
+            content_type: "application/pdf".to_string(),
+            body: render_pdf(invoice),
+        },
+    }
+}
+
+fn format_money(amount: u64, currency: &Currency) -> String {
+    let units = (amount / 100).to_string();
+    let mut grouped = String::new();
+    for (i, digit) in units.chars().enumerate() {
+        if i > 0 && (units.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{} {}.{:02}", currency.as_str(), grouped, amount % 100)
+}
+
+fn format_date(nanos: u64) -> String {
+    DateTime::from_timestamp((nanos / 1_000_000_000) as i64, 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn format_rate(rate_bps: u32) -> String {
+    if rate_bps.is_multiple_of(100) {
+        format!("{}%", rate_bps / 100)
+    } else {
+        format!("{}.{}%", rate_bps / 100, (rate_bps % 100) / 10)
+    }
+}
+
+fn title(invoice: &Invoice) -> &'static str {
+    match invoice.kind {
+        InvoiceKind::Charge => "Invoice",
+        InvoiceKind::CreditNote => "Credit Note",
+    }
+}
+
+fn party_lines(party: &InvoiceParty) -> Vec<String> {
+    let mut lines = vec![party.name.clone()];
+    lines.extend(party.address.clone());
+    lines.extend(party.email.clone());
+    lines.extend(party.country.clone());
+    lines.extend(party.tax_id.as_ref().map(|id| format!("Tax ID: {}", id)));
+    lines
+}
+
+/// Label/value rows shared by both formats
+fn header_rows(invoice: &Invoice) -> Vec<(String, String)> {
+    let mut rows = vec![
+        ("Number".to_string(), invoice.invoice_number.clone()),
+        ("Date".to_string(), format_date(invoice.created_at)),
+        (
+            "Period".to_string(),
+            format!("{} to {}", format_date(invoice.billing_period_start), format_date(invoice.billing_period_end)),
+        ),
+        ("Payment reference".to_string(), invoice.payment_id.clone()),
+    ];
+    if let Some(original) = &invoice.original_invoice_id {
+        rows.push(("Corrects invoice".to_string(), original.clone()));
+    }
+    rows
+}
+
+fn total_rows(invoice: &Invoice) -> Vec<(String, String)> {
+    let currency = &invoice.currency;
+    let mut rows = vec![];
+    if let Some(discount) = &invoice.discount {
+        rows.push((
+            format!("Discount ({})", discount.coupon_code),
+            format!("-{}", format_money(discount.discount_amount, currency)),
+        ));
+    }
+    rows.push(("Net".to_string(), format_money(invoice.net_amount, currency)));
+    for tax in &invoice.tax_lines {
+        rows.push((format!("{} {}", tax.name, format_rate(tax.rate_bps)), format_money(tax.amount, currency)));
+    }
+    let total_label = match invoice.kind {
+        InvoiceKind::Charge if invoice.paid => "Total paid",
+        InvoiceKind::Charge => "Total due",
+        InvoiceKind::CreditNote => "Total credited",
+    };
+    rows.push((total_label.to_string(), format_money(invoice.amount, currency)));
+    rows
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render_html(invoice: &Invoice) -> String {
+    let currency = &invoice.currency;
+    let party = |label: &str, party: &InvoiceParty| {
+        let lines: Vec<String> = party_lines(party).iter().map(|line| escape_html(line)).collect();
+        format!("<div class=\"party\"><h3>{}</h3><p>{}</p></div>", label, lines.join("<br>"))
+    };
+    let row = |label: &str, value: &str| {
+        format!("<tr><th>{}</th><td>{}</td></tr>", escape_html(label), escape_html(value))
+    };
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+    html.push_str(&format!("<title>{} {}</title>", title(invoice), escape_html(&invoice.invoice_number)));
+    html.push_str(
+        "<style>body{font-family:sans-serif;margin:40px;color:#222}table{border-collapse:collapse;width:100%}\
+         th,td{padding:6px;border-bottom:1px solid #ddd;text-align:left}.num{text-align:right}\
+         .party{display:inline-block;vertical-align:top;width:45%}</style></head><body>\n",
+    );
+    html.push_str(&format!("<h1>{}</h1>\n<table class=\"meta\">", title(invoice)));
+    for (label, value) in header_rows(invoice) {
+        html.push_str(&row(&label, &value));
+    }
+    html.push_str("</table>\n");
+    html.push_str(&party("From", &invoice.seller));
+    html.push_str(&party("Bill to", &invoice.buyer));
+
+    html.push_str("\n<table class=\"items\"><tr><th>Description</th><th class=\"num\">Qty</th>");
+    html.push_str("<th class=\"num\">Unit price</th><th class=\"num\">Amount</th></tr>");
+    for item in &invoice.line_items {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            escape_html(&item.description),
+            item.quantity,
+            format_money(item.unit_amount, currency),
+            format_money(item.amount, currency),
+        ));
+    }
+    html.push_str("</table>\n<table class=\"totals\">");
+    for (label, value) in total_rows(invoice) {
+        html.push_str(&row(&label, &value));
+    }
+    html.push_str("</table>\n");
+    if !invoice.tax_lines.is_empty() {
+        html.push_str("<p>Prices include the taxes shown.</p>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// Escape text for a PDF string literal. The standard fonts only cover
+/// Latin-1, other characters are replaced.
+fn pdf_text(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+/// Single-page A4 PDF using the built-in Helvetica fonts
+fn render_pdf(invoice: &Invoice) -> Vec<u8> {
+    let currency = &invoice.currency;
+    let mut content = String::new();
+    let mut y = 800;
+    let mut text = |x: u32, y: u32, bold: bool, size: u32, value: &str| {
+        let font = if bold { "F2" } else { "F1" };
+        content.push_str(&format!(
+            "BT /{} {} Tf 1 0 0 1 {} {} Tm ({}) Tj ET\n",
+            font, size, x, y, pdf_text(value)
+        ));
+    };
+
+    text(50, y, true, 20, title(invoice));
+    y -= 30;
+    for (label, value) in header_rows(invoice) {
+        text(50, y, true, 10, &label);
+        text(170, y, false, 10, &value);
+        y -= 14;
+    }
+
+    y -= 16;
+    text(50, y, true, 10, "From");
+    text(310, y, true, 10, "Bill to");
+    let seller = party_lines(&invoice.seller);
+    let buyer = party_lines(&invoice.buyer);
+    for i in 0..seller.len().max(buyer.len()) {
+        y -= 14;
+        if let Some(line) = seller.get(i) {
+            text(50, y, false, 10, line);
+        }
+        if let Some(line) = buyer.get(i) {
+            text(310, y, false, 10, line);
+        }
+    }
+
+    y -= 30;
+    text(50, y, true, 10, "Description");
+    text(330, y, true, 10, "Qty");
+    text(370, y, true, 10, "Unit price");
+    text(465, y, true, 10, "Amount");
+    for item in &invoice.line_items {
+        y -= 16;
+        text(50, y, false, 10, &item.description);
+        text(330, y, false, 10, &item.quantity.to_string());
+        text(370, y, false, 10, &format_money(item.unit_amount, currency));
+        text(465, y, false, 10, &format_money(item.amount, currency));
+    }
+
+    y -= 10;
+    for (label, value) in total_rows(invoice) {
+        y -= 16;
+        text(330, y, true, 10, &label);
+        text(465, y, false, 10, &value);
+    }
+    if !invoice.tax_lines.is_empty() {
+        text(50, y - 30, false, 9, "Prices include the taxes shown.");
+    }
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] \
+         /Resources << /Font << /F1 4 0 R /F2 5 0 R >> >> /Contents 6 0 R >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+        format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+    ];
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+    pdf.into_bytes()
+}
@@ -3,10 +3,7 @@ pub mod admin;
 pub mod api_service;
 pub mod auth;
 pub mod chat;
-pub mod openchat_service;
 pub mod waitlist;
-pub mod slack_service;
-pub mod discord_service;
 pub mod auth_service;
 pub mod token_service;
 pub mod accelerator_service;
@@ -15,6 +12,20 @@ pub mod pricing_services;
 pub mod payment_service;
 pub mod analytics_service;
 pub mod analytics_api;
-pub mod main_site_service;
 pub mod workspace_connection_service;
 pub mod settings_service;
+pub mod subscription_service;
+pub mod refund_service;
+pub mod plan_service;
+pub mod coupon_service;
+pub mod sponsorship_service;
+pub mod webhook_service;
+pub mod reconciliation_service;
+pub mod invoice_service;
+pub mod bot_service;
+pub mod audit_service;
+pub mod platform_identity_service;
+pub mod identity_merge_service;
+pub mod schema_migration_service;
+pub mod record_repair_service;
+pub mod snapshot_service;
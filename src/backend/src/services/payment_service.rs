@@ -1,43 +1,36 @@
+use candid::Principal;
 use ic_cdk::api::time;
-use crate::models::payment::{PaymentRecord, PaymentStatus, Currency, Invoice, TransactionDetails, PaymentMetadata};
+use crate::models::payment::{PaymentRecord, PaymentStatus, PaymentChannel, Currency, Invoice, InvoiceKind, TransactionDetails, PaymentMetadata};
 use crate::models::usage_service::{UserTier, UserSubscription};
 use crate::models::stable_string::StableString;
 use crate::storage::memory::{PAYMENT_RECORDS, INVOICES, USER_SUBSCRIPTIONS};
-use crate::payments::paystack_client::{
-    initialize_transaction as paystack_initialize,
-    verify_transaction as paystack_verify,
-    build_metadata,
-    get_payment_channels,
+use crate::payments::provider::{
+    IcHttpTransport, PaymentLookup, PaymentProvider, Provider, ProviderEvent, ProviderInitRequest,
 };
-use crate::payments::paystack_models::{InitializeTransactionRequest, parse_payment_status, parse_payment_channel};
-use crate::payments::is_configured;
+use crate::payments::{is_gateway_configured, select_gateway};
+use crate::services::subscription_service::record_successful_charge;
+use crate::services::plan_service::{plan_tier, resolve_price};
+use crate::services::coupon_service::{redeem_coupon, release_coupon, reserve_coupon};
+use crate::services::invoice_service::{charge_line_items, issue_invoice};
+use crate::services::sponsorship_service::{activate_sponsorship, cancel_sponsorship, find_accelerator, get_sponsorship, is_team_member, sponsored_tier};
+use crate::services::auth::caller_is_authenticated;
 
 const NANOS_PER_MONTH: u64 = 30 * 24 * 60 * 60 * 1_000_000_000; // ~30 days
 const NANOS_PER_YEAR: u64 = 365 * 24 * 60 * 60 * 1_000_000_000; // ~365 days
 
-/// Pricing in kobo (Nigerian currency, smallest unit)
-/// 1 NGN = 100 kobo
-const PRO_MONTHLY_NGN: u64 = 2_900_000; // ₦29,000 = $19 USD approx
-const PRO_YEARLY_NGN: u64 = 29_000_000; // ₦290,000 = $190 USD approx (save ~17%)
-
-/// Pricing in Kenyan Shillings (for M-Pesa)
-/// 1 KES = 100 cents
-// const PRO_MONTHLY_KES: u64 = 290_000; // KES 2,900 = ~$19 USD
-const PRO_MONTHLY_KES: u64 = 1_000; // KES 1 for testing
-const PRO_YEARLY_KES: u64 = 2_900_000; // KES 29,000 = ~$190 USD
-
 /// Request to initialize a payment
 #[derive(candid::CandidType, serde::Deserialize, Clone, Debug)]
 pub struct InitializePaymentRequest {
     pub user_id: String,
     pub email: String,
-    pub tier: String,              // "Pro"
+    pub tier: String,              // Plan id from the catalog, e.g. "Pro"
     pub billing_period: String,    // "monthly" or "yearly"
-    pub currency: String,          // "NGN" or "KES"
+    pub currency: String,          // "NGN", "KES", "GHS", "ZAR" or "USD"
     pub callback_url: String,      // Where to redirect after payment
     pub phone_number: Option<String>, // For M-Pesa
     pub enable_mpesa: bool,        // Enable M-Pesa payment
     pub enable_card: bool,         // Enable card payment
+    pub coupon_code: Option<String>, // Promo code, see coupon_service
 }
 
 /// Response from payment initialization
@@ -56,171 +49,330 @@ pub struct InitializePaymentResponse {
 pub async fn initialize_payment(
     request: InitializePaymentRequest,
 ) -> Result<InitializePaymentResponse, String> {
-    // Validate that Paystack is configured
-    if !is_configured() {
-        return Err("Payment system not configured".to_string());
-    }
-
-    // Determine amount based on tier and billing period
-    let (amount, currency_enum) = calculate_amount(&request.tier, &request.billing_period, &request.currency)?;
-    ic_cdk::println!("Amount: {}, Currency: {:?}", amount, currency_enum);
+    // Look up the price in the catalog
+    let (price, currency_enum) = calculate_amount(&request.tier, &request.billing_period, &request.currency)?;
 
     // Generate unique reference
     let reference = generate_payment_reference(&request.user_id);
     ic_cdk::println!("Reference: {}", reference);
 
-    // Build metadata
-    let metadata = build_metadata(
-        &request.user_id,
-        &request.tier,
-        &request.billing_period,
-        request.phone_number.clone(),
-    );
-    ic_cdk::println!("Metadata: {}", metadata);
-
-    // Determine payment channels
-    let channels = get_payment_channels(request.enable_mpesa, request.enable_card);
-    ic_cdk::println!("Channels: {:?}", channels);
-
-    // Create Paystack request
-    let paystack_request = InitializeTransactionRequest {
+    // Hold the coupon before any outcall so concurrent checkouts respect its limits
+    let coupon_code = request.coupon_code.as_deref().map(str::trim).filter(|code| !code.is_empty());
+    let discount = match coupon_code {
+        Some(code) => Some(reserve_coupon(
+            code,
+            &request.user_id,
+            &reference,
+            &request.tier,
+            &request.billing_period,
+            &currency_enum,
+            price,
+            time(),
+        )?),
+        None => None,
+    };
+    let amount = price - discount.as_ref().map_or(0, |discount| discount.discount_amount);
+    ic_cdk::println!("Amount: {}, Currency: {:?}", amount, currency_enum);
+
+    // Pick the gateway for this currency/region
+    let gateway = select_gateway(&currency_enum, request.enable_mpesa);
+    ic_cdk::println!("Gateway: {:?}", gateway);
+
+    let payment_record = PaymentRecord {
+        id: reference.clone(),
+        user_id: request.user_id.clone(),
+        paystack_reference: reference.clone(),
+        amount,
+        currency: currency_enum.clone(),
         email: request.email.clone(),
-        amount: amount.to_string(),
-        currency: request.currency.clone(),
+        status: PaymentStatus::Pending,
+        payment_channel: None,
+        authorization_url: None,
+        access_code: None,
+        tier: request.tier.clone(),
+        billing_period: request.billing_period.clone(),
+        created_at: time(),
+        updated_at: time(),
+        paid_at: None,
+        paystack_transaction_id: None,
+        metadata: PaymentMetadata {
+            customer_name: None,
+            phone_number: request.phone_number.clone(),
+            custom_fields: vec![],
+        },
+        provider: gateway,
+        provider_transaction_id: None,
+        discount,
+    };
+
+    // A coupon covering the whole price needs no gateway
+    if amount == 0 {
+        store_payment_record(payment_record)?;
+        fulfil_payment(&reference, PaymentConfirmation::default())?;
+
+        return Ok(InitializePaymentResponse {
+            success: true,
+            message: "Coupon covers the full price, subscription activated".to_string(),
+            authorization_url: None,
+            access_code: None,
+            reference,
+            amount,
+            currency: currency_enum.as_str().to_string(),
+        });
+    }
+
+    start_checkout(payment_record, &request.callback_url, request.enable_mpesa, request.enable_card).await
+}
+
+/// Open a checkout for a new payment with its gateway and store the payment.
+/// A coupon held for the payment is released if the gateway refuses it.
+pub async fn start_checkout(
+    mut payment_record: PaymentRecord,
+    callback_url: &str,
+    enable_mpesa: bool,
+    enable_card: bool,
+) -> Result<InitializePaymentResponse, String> {
+    let reference = payment_record.paystack_reference.clone();
+    let gateway = payment_record.provider;
+
+    if !is_gateway_configured(gateway) {
+        release_coupon(&reference, time());
+        return Err("Payment system not configured".to_string());
+    }
+
+    let provider_request = ProviderInitRequest {
         reference: reference.clone(),
-        callback_url: request.callback_url.clone(),
-        channels,
-        metadata: Some(metadata),  // metadata is now String
-        phone: request.phone_number.clone(),  // Pass phone to Paystack
+        user_id: payment_record.user_id.clone(),
+        email: payment_record.email.clone(),
+        amount: payment_record.amount,
+        currency: payment_record.currency.clone(),
+        tier: payment_record.tier.clone(),
+        billing_period: payment_record.billing_period.clone(),
+        callback_url: callback_url.to_string(),
+        phone_number: payment_record.metadata.phone_number.clone(),
+        enable_mpesa,
+        enable_card,
     };
-    ic_cdk::println!("Paystack request: {:?}", paystack_request);
-    
-    ic_cdk::println!("Calling Paystack API");
-    // Call Paystack API
-    match paystack_initialize(paystack_request).await {
-        Ok(response) => {
-            if response.status {
-                ic_cdk::println!("Paystack API returned success");
-                println!("Response is: {:?}", response);
-                if let Some(data) = response.data {
-                    // Store payment record
-                    let payment_record = PaymentRecord {
-                        id: reference.clone(),
-                        user_id: request.user_id.clone(),
-                        paystack_reference: reference.clone(),
-                        amount,
-                        currency: currency_enum,
-                        email: request.email.clone(),
-                        status: PaymentStatus::Pending,
-                        payment_channel: None,
-                        authorization_url: Some(data.authorization_url.clone()),
-                        access_code: Some(data.access_code.clone()),
-                        tier: request.tier.clone(),
-                        billing_period: request.billing_period.clone(),
-                        created_at: time(),
-                        updated_at: time(),
-                        paid_at: None,
-                        paystack_transaction_id: None,
-                        metadata: PaymentMetadata {
-                            customer_name: None,
-                            phone_number: request.phone_number.clone(),
-                            custom_fields: vec![],
-                        },
-                    };
-                    ic_cdk::println!("Payment record: {:?}", payment_record);
-
-                    store_payment_record(payment_record)?;
-                    ic_cdk::println!("Payment record stored");
-
-                    Ok(InitializePaymentResponse {
-                        success: true,
-                        message: "Payment initialized successfully".to_string(),
-                        authorization_url: Some(data.authorization_url),
-                        access_code: Some(data.access_code),
-                        reference: reference.clone(),
-                        amount,
-                        currency: request.currency,
-                    })
-                } else {
-                    ic_cdk::println!("Paystack returned success but no data");
-                    Err("Paystack returned success but no data".to_string())
-                }
-            } else {
-                Err(format!("Paystack error: {}", response.message))
-            }
+
+    let provider = Provider::for_gateway(gateway, IcHttpTransport, time());
+    let response = match provider.initialize(provider_request).await {
+        Ok(response) => response,
+        Err(e) => {
+            release_coupon(&reference, time());
+            return Err(format!("Failed to initialize payment: {}", e));
         }
-        Err(e) => Err(format!("Failed to initialize payment: {}", e)),
-    }
+    };
+
+    // Store payment record
+    payment_record.authorization_url = response.authorization_url.clone();
+    payment_record.access_code = response.access_code.clone();
+    ic_cdk::println!("Payment record: {:?}", payment_record);
+
+    let amount = payment_record.amount;
+    let currency = payment_record.currency.as_str().to_string();
+    store_payment_record(payment_record)?;
+    ic_cdk::println!("Payment record stored");
+
+    Ok(InitializePaymentResponse {
+        success: true,
+        message: response.message,
+        authorization_url: response.authorization_url,
+        access_code: response.access_code,
+        reference,
+        amount,
+        currency,
+    })
 }
 
 /// Verify a payment and upgrade subscription if successful
 pub async fn verify_payment(reference: String) -> Result<TransactionDetails, String> {
     ic_cdk::println!("Verifying payment for reference: {}", reference);
-    // Call Paystack API to verify
-    match paystack_verify(reference.clone()).await {
-        Ok(response) => {
-            ic_cdk::println!("Paystack API returned success");
-            ic_cdk::println!("Response is: {:?}", response);
-            if response.status {
-                if let Some(data) = response.data {
-                    ic_cdk::println!("Data is: {:?}", data);
-                    // Update payment record
-                    let status = parse_payment_status(&data.status);
-                    let channel = parse_payment_channel(&data.channel);
-
-                    PAYMENT_RECORDS.with(|records| {
-                        let mut map = records.borrow_mut();
-                        let key = StableString::from(reference.clone());
-
-                        if let Some(mut payment) = map.get(&key) {
-                            payment.status = status.clone();
-                            payment.payment_channel = channel.clone();
-                            payment.updated_at = time();
-                            payment.paystack_transaction_id = Some(data.id);
-
-                            if status == PaymentStatus::Success {
-                                payment.paid_at = Some(time());
-
-                                // Upgrade user subscription
-                                let tier = match payment.tier.as_str() {
-                                    "Pro" => UserTier::Pro,
-                                    _ => UserTier::Free,
-                                };
-
-                                let expiry = calculate_expiry(&payment.billing_period);
-                                upgrade_user_subscription(&payment.user_id, tier, expiry)?;
-
-                                // Generate invoice
-                                generate_invoice(&payment)?;
-                            }
-
-                            map.insert(key, payment);
-                            
-                            Ok(TransactionDetails {
-                                reference: data.reference,
-                                amount: data.amount,
-                                currency: parse_currency(&data.currency),
-                                status,
-                                channel,
-                                paid_at: data.paid_at,
-                                transaction_id: Some(data.id),
-                                customer_email: data.customer.email,
-                                customer_name: data.customer.first_name,
-                                fees: data.fees,
-                            })
-                        } else {
-                            Err("Payment record not found".to_string())
-                        }
-                    })
-                } else {
-                    Err("Verification successful but no data returned".to_string())
+    let payment = get_payment(reference.clone())
+        .ok_or_else(|| "Payment record not found".to_string())?;
+
+    // Ask the gateway that took the payment
+    let provider = Provider::for_gateway(payment.provider, IcHttpTransport, time());
+    let verification = provider
+        .verify(&payment)
+        .await
+        .map_err(|e| format!("Failed to verify payment: {}", e))?;
+    ic_cdk::println!("Verification: {:?}", verification);
+
+    if verification.status == PaymentStatus::Success {
+        // Shared with the webhooks, safe to run twice
+        fulfil_payment(&reference, PaymentConfirmation {
+            channel: verification.channel.clone(),
+            transaction_id: verification.transaction_id,
+            provider_transaction_id: verification.provider_transaction_id.clone(),
+            authorization_code: verification.authorization_code.clone(),
+        })?;
+    } else {
+        update_payment_status(&reference, verification.status.clone(), verification.channel.clone(), time())?;
+    }
+
+    Ok(TransactionDetails {
+        reference,
+        amount: verification.amount,
+        currency: verification.currency.unwrap_or(payment.currency),
+        status: verification.status,
+        channel: verification.channel,
+        paid_at: verification.paid_at,
+        transaction_id: verification.transaction_id,
+        customer_email: verification.customer_email.unwrap_or(payment.email),
+        customer_name: verification.customer_name,
+        fees: verification.fees,
+    })
+}
+
+/// What the gateway told us about a successful payment
+#[derive(Clone, Debug, Default)]
+pub struct PaymentConfirmation {
+    pub channel: Option<PaymentChannel>,
+    pub transaction_id: Option<u64>,                // Paystack transaction id
+    pub provider_transaction_id: Option<String>,    // Stripe payment intent / M-Pesa receipt
+    pub authorization_code: Option<String>,         // Reusable authorization for renewals
+}
+
+/// Mark a payment as successful and activate the subscription it paid for.
+///
+/// Called from `verify_payment` and the provider webhooks, so it is
+/// idempotent: a payment that is already fulfilled is left untouched and
+/// `Ok(false)` is returned. Returns `Ok(true)` when this call did the upgrade.
+pub fn fulfil_payment(reference: &str, confirmation: PaymentConfirmation) -> Result<bool, String> {
+    let fulfilled = fulfil_payment_at(reference, confirmation, time())?;
+    if !fulfilled {
+        ic_cdk::println!("Payment {} already fulfilled, skipping", reference);
+    }
+    Ok(fulfilled)
+}
+
+/// `fulfil_payment` with an explicit clock, shared with the renewal engine
+pub fn fulfil_payment_at(
+    reference: &str,
+    confirmation: PaymentConfirmation,
+    now: u64,
+) -> Result<bool, String> {
+    let key = StableString::from(reference.to_string());
+    let mut payment = PAYMENT_RECORDS
+        .with(|records| records.borrow().get(&key))
+        .ok_or_else(|| format!("Payment record not found for reference: {}", reference))?;
+
+    if is_fulfilled(&payment) {
+        return Ok(false);
+    }
+
+    payment.status = PaymentStatus::Success;
+    payment.updated_at = now;
+    payment.paid_at = Some(now);
+    if confirmation.channel.is_some() {
+        payment.payment_channel = confirmation.channel;
+    }
+    if confirmation.transaction_id.is_some() {
+        payment.paystack_transaction_id = confirmation.transaction_id;
+    }
+    if confirmation.provider_transaction_id.is_some() {
+        payment.provider_transaction_id = confirmation.provider_transaction_id;
+    }
+
+    // Saved before anything it pays for, so a retry after a failed step
+    // below finds it fulfilled instead of extending the subscription again
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(key, payment.clone());
+    });
+
+    // Seat purchases start the accelerator's sponsorship instead of a personal plan
+    if get_sponsorship(reference).is_some() {
+        let expiry = calculate_expiry(&payment.billing_period, now).unwrap_or(now);
+        activate_sponsorship(reference, now, expiry, now)?;
+        generate_invoice(&payment, now, expiry, now)?;
+        return Ok(true);
+    }
+
+    // Upgrade user subscription. Paying while still Pro extends the
+    // current period instead of restarting it.
+    let tier = plan_tier(&payment.tier).unwrap_or(match payment.tier.as_str() {
+        "Pro" => UserTier::Pro,
+        _ => UserTier::Free,
+    });
+    let period_start = current_period_end(&payment.user_id, &tier, now);
+    let expiry = calculate_expiry(&payment.billing_period, period_start);
+    upgrade_user_subscription(&payment.user_id, tier, expiry, now)?;
+
+    // Generate invoice
+    generate_invoice(&payment, period_start, expiry.unwrap_or(period_start), now)?;
+
+    // Keep the card on file for automatic renewals
+    record_successful_charge(&payment, confirmation.authorization_code, expiry, now);
+    redeem_coupon(&payment.paystack_reference, now);
+
+    Ok(true)
+}
+
+/// Record a non-success status. A fulfilled payment is never downgraded by a late update.
+pub fn update_payment_status(
+    reference: &str,
+    status: PaymentStatus,
+    channel: Option<PaymentChannel>,
+    now: u64,
+) -> Result<(), String> {
+    PAYMENT_RECORDS.with(|records| {
+        let mut map = records.borrow_mut();
+        let key = StableString::from(reference.to_string());
+
+        match map.get(&key) {
+            Some(payment) if payment.status == PaymentStatus::Success => Ok(()),
+            Some(mut payment) => {
+                // A failed checkout gives its coupon back and drops its seat purchase
+                if matches!(status, PaymentStatus::Failed | PaymentStatus::Abandoned) {
+                    release_coupon(reference, now);
+                    cancel_sponsorship(reference, now);
+                }
+                payment.status = status;
+                if channel.is_some() {
+                    payment.payment_channel = channel;
                 }
-            } else {
-                Err(format!("Verification failed: {}", response.message))
+                payment.updated_at = now;
+                map.insert(key, payment);
+                Ok(())
             }
+            None => Err(format!("Payment record not found for reference: {}", reference)),
         }
-        Err(e) => Err(format!("Failed to verify payment: {}", e)),
+    })
+}
+
+/// Apply a verified provider webhook to its payment and return the payment reference
+pub fn apply_provider_event(event: ProviderEvent, now: u64) -> Result<String, String> {
+    let reference = match event.lookup {
+        PaymentLookup::Reference(reference) => reference,
+        PaymentLookup::AccessCode(access_code) => PAYMENT_RECORDS
+            .with(|records| {
+                records
+                    .borrow()
+                    .iter()
+                    .find(|(_, payment)| payment.access_code.as_deref() == Some(access_code.as_str()))
+                    .map(|(_, payment)| payment.paystack_reference)
+            })
+            .ok_or_else(|| format!("No payment found for provider reference: {}", access_code))?,
+    };
+
+    if event.status == PaymentStatus::Success {
+        fulfil_payment_at(&reference, PaymentConfirmation {
+            channel: event.channel,
+            transaction_id: event.transaction_id,
+            provider_transaction_id: event.provider_transaction_id,
+            authorization_code: event.authorization_code,
+        }, now)?;
+    } else {
+        update_payment_status(&reference, event.status, event.channel, now)?;
     }
+
+    Ok(reference)
+}
+
+/// A payment counts as fulfilled once it is saved as successful. Only
+/// `fulfil_payment_at` does that, before it upgrades anything.
+fn is_fulfilled(payment: &PaymentRecord) -> bool {
+    payment.status == PaymentStatus::Success && payment.paid_at.is_some()
 }
 
 /// Get payment history for a user
@@ -242,6 +394,14 @@ pub fn get_payment(reference: String) -> Option<PaymentRecord> {
     })
 }
 
+/// Billing records belong to their user, or to the team of the accelerator they were issued to
+pub fn can_view_billing(caller: Principal, user_id: &str) -> bool {
+    if user_id == caller.to_text() {
+        return true;
+    }
+    find_accelerator(user_id).is_some_and(|accelerator| is_team_member(&accelerator, caller))
+}
+
 /// Get all invoices for a user
 pub fn get_user_invoices(user_id: String) -> Vec<Invoice> {
     INVOICES.with(|invoices| {
@@ -266,46 +426,41 @@ pub fn get_current_user_subscription_status() -> Option<UserSubscription> {
     })
 }
 
-/// Check if current user has an active Pro subscription
+/// Check if current user has an active Pro subscription or a sponsored seat
 pub fn is_current_user_pro() -> bool {
     if let Some(subscription) = get_current_user_subscription_status() {
-        subscription.tier == UserTier::Pro && subscription.is_active
-    } else {
-        false
+        if subscription.tier == UserTier::Pro && subscription.is_active {
+            return true;
+        }
     }
+    sponsored_tier(&ic_cdk::caller().to_string(), time()) == Some(UserTier::Pro)
 }
 
 /// Check if current user has Pro subscription
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn is_user_pro() -> bool {
     is_current_user_pro()
 }
 
 /// Get current user's subscription details
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_authenticated")]
 pub fn get_current_user_subscription() -> Option<UserSubscription> {
     get_current_user_subscription_status()
 }
 
 // ============= HELPER FUNCTIONS =============
 
-/// Calculate amount based on tier, billing period, and currency
+/// Resolve the amount for a plan, billing period and currency from the price catalog
 fn calculate_amount(tier: &str, billing_period: &str, currency: &str) -> Result<(u64, Currency), String> {
-    let currency_enum = parse_currency(currency);
-    
-    let amount = match (tier, billing_period, currency_enum.clone()) {
-        ("Pro", "monthly", Currency::NGN) => PRO_MONTHLY_NGN,
-        ("Pro", "yearly", Currency::NGN) => PRO_YEARLY_NGN,
-        ("Pro", "monthly", Currency::KES) => PRO_MONTHLY_KES,
-        ("Pro", "yearly", Currency::KES) => PRO_YEARLY_KES,
-        _ => return Err(format!("Invalid tier/billing period combination: {} {}", tier, billing_period)),
-    };
+    let currency_enum = Currency::from_code(currency)
+        .ok_or_else(|| format!("Unsupported currency: {}", currency))?;
 
-    Ok((amount, currency_enum))
+    let price = resolve_price(tier, billing_period, &currency_enum)?;
+    Ok((price.amount, currency_enum))
 }
 
 /// Generate unique payment reference
-fn generate_payment_reference(user_id: &str) -> String {
+pub fn generate_payment_reference(user_id: &str) -> String {
     let timestamp = time();
     // Use full timestamp in nanoseconds for uniqueness
     let user_prefix = user_id.chars().take(6).collect::<String>();
@@ -331,7 +486,7 @@ fn store_payment_record(payment: PaymentRecord) -> Result<(), String> {
 }
 
 /// Upgrade user subscription
-fn upgrade_user_subscription(user_id: &str, tier: UserTier, expires_at: Option<u64>) -> Result<(), String> {
+fn upgrade_user_subscription(user_id: &str, tier: UserTier, expires_at: Option<u64>, now: u64) -> Result<(), String> {
     USER_SUBSCRIPTIONS.with(|subs| {
         let mut map = subs.borrow_mut();
         let key = StableString::from(user_id.to_string());
@@ -340,18 +495,18 @@ fn upgrade_user_subscription(user_id: &str, tier: UserTier, expires_at: Option<u
             user_id: user_id.to_string(),
             tier: UserTier::Free,
             is_active: true,
-            started_at_ns: Some(time()),
+            started_at_ns: Some(now),
             renewed_at_ns: None,
             expires_at_ns: None,
         });
 
         subscription.tier = tier;
         subscription.is_active = true;
-        subscription.renewed_at_ns = Some(time());
+        subscription.renewed_at_ns = Some(now);
         subscription.expires_at_ns = expires_at;
 
         if subscription.started_at_ns.is_none() {
-            subscription.started_at_ns = Some(time());
+            subscription.started_at_ns = Some(now);
         }
 
         map.insert(key, subscription);
@@ -359,56 +514,45 @@ fn upgrade_user_subscription(user_id: &str, tier: UserTier, expires_at: Option<u
     })
 }
 
-/// Calculate subscription expiry time
-fn calculate_expiry(billing_period: &str) -> Option<u64> {
-    let now = time();
+/// End of the user's unexpired period on the same tier, or `now` if there is none
+fn current_period_end(user_id: &str, tier: &UserTier, now: u64) -> u64 {
+    USER_SUBSCRIPTIONS.with(|subs| {
+        subs.borrow()
+            .get(&StableString::from(user_id.to_string()))
+            .filter(|s| s.is_active && &s.tier == tier)
+            .and_then(|s| s.expires_at_ns)
+            .filter(|expiry| *expiry > now)
+            .unwrap_or(now)
+    })
+}
+
+/// Calculate subscription expiry time for a period starting at `start`
+fn calculate_expiry(billing_period: &str, start: u64) -> Option<u64> {
     match billing_period {
-        "monthly" => Some(now + NANOS_PER_MONTH),
-        "yearly" => Some(now + NANOS_PER_YEAR),
+        "monthly" => Some(start + NANOS_PER_MONTH),
+        "yearly" => Some(start + NANOS_PER_YEAR),
         _ => None,
     }
 }
 
 /// Generate invoice for successful payment
-fn generate_invoice(payment: &PaymentRecord) -> Result<(), String> {
-    let invoice_id = format!("INV-{}", payment.id);
-    let now = time();
-
-    let (period_start, period_end) = match payment.billing_period.as_str() {
-        "monthly" => (now, now + NANOS_PER_MONTH),
-        "yearly" => (now, now + NANOS_PER_YEAR),
-        _ => (now, now),
-    };
-
+fn generate_invoice(payment: &PaymentRecord, period_start: u64, period_end: u64, now: u64) -> Result<(), String> {
     let invoice = Invoice {
-        id: invoice_id.clone(),
+        id: format!("INV-{}", payment.id),
         user_id: payment.user_id.clone(),
         payment_id: payment.id.clone(),
         amount: payment.amount,
         currency: payment.currency.clone(),
         billing_period_start: period_start,
         billing_period_end: period_end,
-        invoice_number: format!("INF-{}", now / 1_000_000_000), // Unix timestamp
         paid: true,
         created_at: now,
+        kind: InvoiceKind::Charge,
+        discount: payment.discount.clone(),
+        line_items: charge_line_items(payment),
+        ..Default::default()
     };
 
-    INVOICES.with(|invoices| {
-        let mut map = invoices.borrow_mut();
-        map.insert(StableString::from(invoice_id), invoice);
-        Ok(())
-    })
-}
-
-/// Parse currency string to enum
-fn parse_currency(currency: &str) -> Currency {
-    match currency.to_uppercase().as_str() {
-        "NGN" => Currency::NGN,
-        "KES" => Currency::KES,
-        "GHS" => Currency::GHS,
-        "ZAR" => Currency::ZAR,
-        "USD" => Currency::USD,
-        _ => Currency::NGN, // Default
-    }
+    issue_invoice(invoice, payment, now);
+    Ok(())
 }
-
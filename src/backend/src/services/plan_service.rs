@@ -0,0 +1,238 @@
+// backend/src/services/plan_service.rs
+//
+// Price catalog: subscription plans and their prices per billing period
+// and currency, editable by admins without a canister upgrade.
+
+use ic_cdk::api::time;
+use ic_cdk::{query, update};
+
+use crate::models::payment::Currency;
+use crate::models::plan::{Plan, Price};
+use crate::models::stable_string::StableString;
+use crate::models::usage_service::UserTier;
+use crate::models::admin::Permission;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::storage::memory::PLANS;
+
+const BILLING_PERIODS: [&str; 2] = ["monthly", "yearly"];
+
+/// Catalog installed on a fresh canister (and when upgrading from a version without one)
+pub fn default_plans(now: u64) -> Vec<Plan> {
+    let price = |billing_period: &str, currency: Currency, amount: u64| Price {
+        billing_period: billing_period.to_string(),
+        currency,
+        amount,
+    };
+
+    vec![Plan {
+        id: "Pro".to_string(),
+        name: "Pro".to_string(),
+        description: "Unlimited assistant requests".to_string(),
+        tier: UserTier::Pro,
+        prices: vec![
+            price("monthly", Currency::NGN, 2_900_000), // ₦29,000
+            price("yearly", Currency::NGN, 29_000_000), // ₦290,000 (save ~17%)
+            price("monthly", Currency::KES, 290_000),   // KES 2,900
+            price("yearly", Currency::KES, 2_900_000),  // KES 29,000
+            price("monthly", Currency::GHS, 29_000),    // GH₵290
+            price("yearly", Currency::GHS, 290_000),    // GH₵2,900
+            price("monthly", Currency::ZAR, 34_900),    // R349
+            price("yearly", Currency::ZAR, 349_000),    // R3,490
+            price("monthly", Currency::USD, 1_900),     // $19
+            price("yearly", Currency::USD, 19_000),     // $190
+        ],
+        is_active: true,
+        created_at: now,
+        updated_at: now,
+    }]
+}
+
+/// Install the default catalog if no plan exists yet
+pub fn seed_default_plans(now: u64) {
+    if PLANS.with(|plans| plans.borrow().is_empty()) {
+        for plan in default_plans(now) {
+            store_plan(plan);
+        }
+    }
+}
+
+// ============= CATALOG =============
+
+pub fn get_plan(plan_id: &str) -> Option<Plan> {
+    PLANS.with(|plans| plans.borrow().get(&StableString::from(plan_id.to_string())))
+}
+
+pub fn list_plans() -> Vec<Plan> {
+    PLANS.with(|plans| plans.borrow().iter().map(|(_, plan)| plan).collect())
+}
+
+/// Amount to charge for a plan, billing period and currency
+pub fn resolve_price(plan_id: &str, billing_period: &str, currency: &Currency) -> Result<Price, String> {
+    let plan = get_plan(plan_id)
+        .filter(|plan| plan.is_active)
+        .ok_or_else(|| format!("Unknown plan: {}", plan_id))?;
+
+    plan.price(billing_period, currency)
+        .cloned()
+        .ok_or_else(|| format!(
+            "Plan {} has no {} price in {}",
+            plan_id, billing_period, currency.as_str()
+        ))
+}
+
+/// Tier a plan grants, `None` if the plan is not in the catalog
+pub fn plan_tier(plan_id: &str) -> Option<UserTier> {
+    get_plan(plan_id).map(|plan| plan.tier)
+}
+
+/// Add a new plan
+pub fn create_plan(mut plan: Plan, now: u64) -> Result<Plan, String> {
+    validate_plan(&plan)?;
+    if get_plan(&plan.id).is_some() {
+        return Err(format!("Plan {} already exists", plan.id));
+    }
+
+    plan.created_at = now;
+    plan.updated_at = now;
+    store_plan(plan.clone());
+    Ok(plan)
+}
+
+/// Replace an existing plan (name, tier, prices, active flag)
+pub fn update_plan(mut plan: Plan, now: u64) -> Result<Plan, String> {
+    validate_plan(&plan)?;
+    let existing = get_plan(&plan.id)
+        .ok_or_else(|| format!("Plan {} not found", plan.id))?;
+
+    plan.created_at = existing.created_at;
+    plan.updated_at = now;
+    store_plan(plan.clone());
+    Ok(plan)
+}
+
+/// Add or replace one price of a plan
+pub fn set_plan_price(plan_id: &str, price: Price, now: u64) -> Result<Plan, String> {
+    let mut plan = get_plan(plan_id)
+        .ok_or_else(|| format!("Plan {} not found", plan_id))?;
+
+    plan.prices.retain(|p| !(p.billing_period == price.billing_period && p.currency == price.currency));
+    plan.prices.push(price);
+    update_plan(plan, now)
+}
+
+/// Remove the price for a billing period and currency
+pub fn remove_plan_price(plan_id: &str, billing_period: &str, currency: &Currency, now: u64) -> Result<Plan, String> {
+    let mut plan = get_plan(plan_id)
+        .ok_or_else(|| format!("Plan {} not found", plan_id))?;
+
+    let before = plan.prices.len();
+    plan.prices.retain(|p| !(p.billing_period == billing_period && &p.currency == currency));
+    if plan.prices.len() == before {
+        return Err(format!("Plan {} has no {} price in {}", plan_id, billing_period, currency.as_str()));
+    }
+    update_plan(plan, now)
+}
+
+/// Remove a plan. Payments and subscriptions already made on it are unaffected.
+pub fn delete_plan(plan_id: &str) -> Result<(), String> {
+    PLANS.with(|plans| {
+        plans
+            .borrow_mut()
+            .remove(&StableString::from(plan_id.to_string()))
+            .map(|_| ())
+            .ok_or_else(|| format!("Plan {} not found", plan_id))
+    })
+}
+
+fn validate_plan(plan: &Plan) -> Result<(), String> {
+    if plan.id.trim().is_empty() {
+        return Err("Plan id cannot be empty".to_string());
+    }
+    if plan.tier == UserTier::Free {
+        return Err("Plans must grant a paid tier".to_string());
+    }
+
+    for (i, price) in plan.prices.iter().enumerate() {
+        // Subscription expiry is only defined for these periods
+        if !BILLING_PERIODS.contains(&price.billing_period.as_str()) {
+            return Err(format!("Unsupported billing period: {}", price.billing_period));
+        }
+        if price.amount == 0 {
+            return Err(format!("{} {} price must be greater than zero", price.billing_period, price.currency.as_str()));
+        }
+        let duplicate = plan.prices[..i]
+            .iter()
+            .any(|p| p.billing_period == price.billing_period && p.currency == price.currency);
+        if duplicate {
+            return Err(format!("Duplicate {} price in {}", price.billing_period, price.currency.as_str()));
+        }
+    }
+    Ok(())
+}
+
+fn store_plan(plan: Plan) {
+    PLANS.with(|plans| {
+        plans.borrow_mut().insert(StableString::from(plan.id.clone()), plan);
+    });
+}
+
+// ============= API =============
+
+/// Plans that can be bought, for the pricing page
+#[query]
+pub fn get_plans() -> Vec<Plan> {
+    list_plans().into_iter().filter(|plan| plan.is_active).collect()
+}
+
+/// Every plan, including inactive ones (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_plans() -> Result<Vec<Plan>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(list_plans())
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_create_plan(plan: Plan) -> Result<Plan, String> {
+    require_permission(Permission::ManageBilling)?;
+    let plan = create_plan(plan, time())?;
+    audit(AuditAction::PlanChanged, plan.id.clone(), None, Some(digest(&plan)));
+    Ok(plan)
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_update_plan(plan: Plan) -> Result<Plan, String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_plan(&plan.id);
+    let plan = update_plan(plan, time())?;
+    audit(AuditAction::PlanChanged, plan.id.clone(), before.as_ref().map(digest), Some(digest(&plan)));
+    Ok(plan)
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_delete_plan(plan_id: String) -> Result<(), String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_plan(&plan_id);
+    delete_plan(&plan_id)?;
+    audit(AuditAction::PlanChanged, plan_id, before.as_ref().map(digest), None);
+    Ok(())
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_set_plan_price(plan_id: String, price: Price) -> Result<Plan, String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_plan(&plan_id);
+    let plan = set_plan_price(&plan_id, price, time())?;
+    audit(AuditAction::PlanChanged, plan_id, before.as_ref().map(digest), Some(digest(&plan)));
+    Ok(plan)
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_remove_plan_price(plan_id: String, billing_period: String, currency: Currency) -> Result<Plan, String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_plan(&plan_id);
+    let plan = remove_plan_price(&plan_id, &billing_period, &currency, time())?;
+    audit(AuditAction::PlanChanged, plan_id, before.as_ref().map(digest), Some(digest(&plan)));
+    Ok(plan)
+}
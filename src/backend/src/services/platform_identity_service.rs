@@ -0,0 +1,308 @@
+// backend/src/services/platform_identity_service.rs
+//
+// Registry of bot platform users (OpenChat, Slack, Discord, main site), one
+// PlatformIdentity per (platform, external id). Resolves which principal a
+// platform user's data is stored under: their linked site principal if they
+// have one, otherwise a principal derived from their ID.
+
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+
+use crate::models::admin::Permission;
+use crate::models::bot_principal::BotPermission;
+use crate::models::discord_user::DiscordUser;
+use crate::models::openchat_user::OpenChatUser;
+use crate::models::platform::Platform;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::models::slack_user::SlackUser;
+use crate::models::stable_principal::StablePrincipal;
+use crate::models::stable_string::StableString;
+use crate::services::account_service::check_principal_access;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::auth::caller_is_authenticated;
+use crate::services::bot_service::{authorize_bot, bot_can_store_messages};
+use crate::storage::memory::{LEGACY_MAIN_SITE_USERS, PLATFORM_IDENTITIES, PLATFORM_IDENTITIES_BY_PRINCIPAL};
+
+fn identity_key(platform: Platform, external_id: &str) -> (Platform, StableString) {
+    (platform, StableString::from(external_id))
+}
+
+/// Deterministic stand-in principal: the tag byte followed by the first 28 bytes of the ID
+fn synthetic_principal(tag: u8, external_id: &str) -> Principal {
+    let mut bytes = [0u8; 29];
+    bytes[0] = tag;
+    let id_bytes = external_id.as_bytes();
+    let len = std::cmp::min(id_bytes.len(), 28);
+    bytes[1..1 + len].copy_from_slice(&id_bytes[..len]);
+    Principal::from_slice(&bytes)
+}
+
+/// The principal an unlinked platform user's data is stored under. None for
+/// OpenChat IDs that are not valid principals.
+pub fn derived_principal(platform: Platform, external_id: &str) -> Option<Principal> {
+    match platform.principal_tag() {
+        Some(tag) => Some(synthetic_principal(tag, external_id)),
+        None => Principal::from_text(external_id).ok(),
+    }
+}
+
+/// The principal playground messages are stored under
+pub fn playground_principal(playground_id: &str) -> Principal {
+    derived_principal(Platform::Playground, playground_id).unwrap_or_else(Principal::anonymous)
+}
+
+pub fn get_identity(platform: Platform, external_id: &str) -> Option<PlatformIdentity> {
+    PLATFORM_IDENTITIES.with(|ids| ids.borrow().get(&identity_key(platform, external_id)))
+}
+
+/// Writes an identity and keeps the by-principal index in step with its link
+pub fn save_identity(identity: PlatformIdentity) {
+    let key = identity_key(identity.platform, &identity.external_id);
+    let previous = PLATFORM_IDENTITIES.with(|ids| ids.borrow_mut().insert(key.clone(), identity.clone()));
+    let previous_principal = previous.and_then(|p| p.site_principal);
+    if previous_principal == identity.site_principal {
+        return;
+    }
+
+    PLATFORM_IDENTITIES_BY_PRINCIPAL.with(|index| {
+        let mut index = index.borrow_mut();
+        if let Some(principal) = previous_principal {
+            index.remove(&(StablePrincipal::new(principal), key.clone()));
+        }
+        if let Some(principal) = identity.site_principal {
+            index.insert((StablePrincipal::new(principal), key), ());
+        }
+    });
+}
+
+/// Registers a platform user on first contact, otherwise records the interaction
+pub fn ensure_identity(platform: Platform, external_id: &str, now: u64) -> PlatformIdentity {
+    let identity = match get_identity(platform, external_id) {
+        Some(existing) => PlatformIdentity { last_interaction: now, ..existing },
+        None => PlatformIdentity::new(platform, external_id.to_string(), now),
+    };
+    save_identity(identity.clone());
+    identity
+}
+
+/// Links a registered platform user to a site principal, replacing any previous link
+pub fn link_identity(platform: Platform, external_id: &str, site_principal: Principal) -> Result<(), String> {
+    let identity = get_identity(platform, external_id).ok_or_else(|| format!("{} user not found", platform))?;
+    save_identity(PlatformIdentity { site_principal: Some(site_principal), ..identity });
+    Ok(())
+}
+
+pub fn unlink_identity(platform: Platform, external_id: &str) -> Result<(), String> {
+    let identity = get_identity(platform, external_id).ok_or_else(|| format!("{} user not found", platform))?;
+    save_identity(PlatformIdentity { site_principal: None, ..identity });
+    Ok(())
+}
+
+/// The site principal a platform user is linked to, if any
+pub fn linked_principal(platform: Platform, external_id: &str) -> Option<Principal> {
+    get_identity(platform, external_id).and_then(|identity| identity.site_principal)
+}
+
+/// Where a platform user's data is stored: their linked principal, otherwise
+/// the derived one (anonymous for OpenChat IDs that are not principals)
+pub fn resolve_principal(platform: Platform, external_id: &str) -> Principal {
+    linked_principal(platform, external_id)
+        .or_else(|| derived_principal(platform, external_id))
+        .unwrap_or_else(Principal::anonymous)
+}
+
+/// The identities linked to a site principal, in platform order
+pub fn identities_for_principal(principal: Principal) -> Vec<PlatformIdentity> {
+    let start = (StablePrincipal::new(principal), identity_key(Platform::ALL[0], ""));
+    PLATFORM_IDENTITIES_BY_PRINCIPAL.with(|index| {
+        PLATFORM_IDENTITIES.with(|ids| {
+            let ids = ids.borrow();
+            index
+                .borrow()
+                .range(start..)
+                .take_while(|((owner, _), _)| owner.get() == principal)
+                .filter_map(|((_, key), _)| ids.get(&key))
+                .collect()
+        })
+    })
+}
+
+/// The ID of the `platform` account linked to a site principal, if any
+pub fn linked_external_id(principal: Principal, platform: Platform) -> Option<String> {
+    identities_for_principal(principal)
+        .into_iter()
+        .find(|identity| identity.platform == platform)
+        .map(|identity| identity.external_id)
+}
+
+/// All identities, or those of one platform
+pub fn list_identities(platform: Option<Platform>) -> Vec<PlatformIdentity> {
+    PLATFORM_IDENTITIES.with(|ids| {
+        let ids = ids.borrow();
+        match platform {
+            Some(platform) => ids
+                .range(identity_key(platform, "")..)
+                .take_while(|((p, _), _)| *p == platform)
+                .map(|(_, identity)| identity)
+                .collect(),
+            None => ids.iter().map(|(_, identity)| identity).collect(),
+        }
+    })
+}
+
+/// Moves records left in the retired MAIN_SITE_USERS map into the registry.
+/// Records already present in the registry win. Returns how many moved.
+pub fn migrate_legacy_main_site_users() -> u32 {
+    let legacy = LEGACY_MAIN_SITE_USERS.with(|users| users.borrow().iter().collect::<Vec<_>>());
+    let mut moved = 0;
+    for (key, user) in legacy {
+        if get_identity(Platform::MainSite, &user.main_site_id).is_none() {
+            save_identity(user.into());
+            moved += 1;
+        }
+        LEGACY_MAIN_SITE_USERS.with(|users| users.borrow_mut().remove(&key));
+    }
+    moved
+}
+
+/// How many records migrate_legacy_main_site_users would move
+pub fn pending_legacy_main_site_users() -> u32 {
+    LEGACY_MAIN_SITE_USERS.with(|users| {
+        users
+            .borrow()
+            .iter()
+            .filter(|(_, user)| get_identity(Platform::MainSite, &user.main_site_id).is_none())
+            .count() as u32
+    })
+}
+
+/* ============================
+   Endpoints
+   ============================ */
+
+/// Registers a platform user for the calling bot, which must serve that platform
+fn ensure_identity_for_bot(platform: Platform, external_id: &str) -> Result<PlatformIdentity, String> {
+    authorize_bot(caller(), BotPermission::StoreMessages, platform)?;
+    Ok(ensure_identity(platform, external_id, time()))
+}
+
+/// An identity as `caller` may see it. Which site account a platform user
+/// linked is only shown to that account, to admins holding `ViewUsers`, and to
+/// bots of the platform holding `ReadMessages`.
+pub fn visible_identity(caller: Principal, mut identity: PlatformIdentity) -> PlatformIdentity {
+    let may_see_principal = identity.site_principal.is_some_and(|principal| {
+        check_principal_access(caller, principal, Permission::ViewUsers).is_ok()
+            || authorize_bot(caller, BotPermission::ReadMessages, identity.platform).is_ok()
+    });
+    if !may_see_principal {
+        identity.site_principal = None;
+    }
+    identity
+}
+
+fn lookup_identity(platform: Platform, external_id: &str) -> Option<PlatformIdentity> {
+    get_identity(platform, external_id).map(|identity| visible_identity(caller(), identity))
+}
+
+#[update(guard = "bot_can_store_messages")]
+pub fn ensure_platform_identity(platform: Platform, external_id: String) -> Result<PlatformIdentity, String> {
+    ensure_identity_for_bot(platform, &external_id)
+}
+
+#[query]
+pub fn get_platform_identity(platform: Platform, external_id: String) -> Option<PlatformIdentity> {
+    lookup_identity(platform, &external_id)
+}
+
+#[query(guard = "caller_is_authenticated")]
+pub fn get_my_platform_identities() -> Vec<PlatformIdentity> {
+    identities_for_principal(caller())
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_platform_identities(platform: Option<Platform>) -> Result<Vec<PlatformIdentity>, String> {
+    require_permission(Permission::ViewUsers)?;
+    Ok(list_identities(platform))
+}
+
+/* ============================
+   Per-platform endpoints
+   ============================ */
+
+// The bots still call these; each is a view over the registry
+
+fn identity_for_principal(platform: Platform, principal: Principal) -> Option<PlatformIdentity> {
+    if check_principal_access(caller(), principal, Permission::ViewUsers).is_err() {
+        return None;
+    }
+    identities_for_principal(principal).into_iter().find(|identity| identity.platform == platform)
+}
+
+#[update(guard = "bot_can_store_messages")]
+pub fn ensure_openchat_user(openchat_id: String) -> Result<(), String> {
+    ensure_identity_for_bot(Platform::OpenChat, &openchat_id).map(|_| ())
+}
+
+#[query]
+pub fn get_openchat_user(openchat_id: String) -> Option<OpenChatUser> {
+    lookup_identity(Platform::OpenChat, &openchat_id).map(Into::into)
+}
+
+#[query(guard = "caller_is_authenticated")]
+pub fn get_openchat_user_by_principal(principal: Principal) -> Option<OpenChatUser> {
+    identity_for_principal(Platform::OpenChat, principal).map(Into::into)
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn get_active_openchat_users(since: u64) -> Vec<OpenChatUser> {
+    if require_permission(Permission::ViewUsers).is_err() {
+        return vec![];
+    }
+
+    list_identities(Some(Platform::OpenChat))
+        .into_iter()
+        .filter(|identity| identity.last_interaction >= since)
+        .map(Into::into)
+        .collect()
+}
+
+#[update(guard = "bot_can_store_messages")]
+pub fn ensure_slack_user(slack_id: String) -> Result<(), String> {
+    ensure_identity_for_bot(Platform::Slack, &slack_id).map(|_| ())
+}
+
+#[query]
+pub fn get_slack_user(slack_id: String) -> Option<SlackUser> {
+    lookup_identity(Platform::Slack, &slack_id).map(Into::into)
+}
+
+#[query(guard = "caller_is_authenticated")]
+pub fn get_slack_user_by_principal(principal: Principal) -> Option<SlackUser> {
+    identity_for_principal(Platform::Slack, principal).map(Into::into)
+}
+
+#[query]
+pub fn is_slack_user_registered(slack_id: String) -> bool {
+    get_identity(Platform::Slack, &slack_id).is_some()
+}
+
+#[update(guard = "bot_can_store_messages")]
+pub fn ensure_discord_user(discord_id: String) -> Result<(), String> {
+    ensure_identity_for_bot(Platform::Discord, &discord_id).map(|_| ())
+}
+
+#[query]
+pub fn get_discord_user(discord_id: String) -> Option<DiscordUser> {
+    lookup_identity(Platform::Discord, &discord_id).map(Into::into)
+}
+
+#[query(guard = "caller_is_authenticated")]
+pub fn get_discord_user_by_principal(principal: Principal) -> Option<DiscordUser> {
+    identity_for_principal(Platform::Discord, principal).map(Into::into)
+}
+
+#[query]
+pub fn is_discord_user_registered(discord_id: String) -> bool {
+    get_identity(Platform::Discord, &discord_id).is_some()
+}
@@ -1,48 +1,145 @@
 // backend/src/services/pricing_services.rs
 
 use ic_cdk::api::time;
-use chrono::{DateTime, Utc, TimeZone};
+use ic_cdk::{query, update};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc, TimeZone};
+use std::collections::HashSet;
 
-use crate::storage::memory::{USER_DAILY_USAGE, USER_SUBSCRIPTIONS};
+use crate::storage::memory::{QUOTA_BUCKETS, QUOTA_POLICIES, USER_DAILY_USAGE, USER_SUBSCRIPTIONS};
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::models::quota::{BurstLimit, QuotaLimit, QuotaPolicy, QuotaRejection, QuotaRemaining, TokenBucket};
+use crate::models::platform::Platform;
 use crate::models::stable_string::StableString;
 use crate::models::usage_service::{UsageStats, UserSubscription, UserTier};
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::audit_service::{audit, digest};
+use crate::services::subscription_service::is_in_grace_period;
+use crate::services::sponsorship_service::sponsored_tier;
+use crate::services::platform_identity_service::linked_principal;
 
-const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
-const FREE_DAILY_LIMIT: u32 = 20;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const NANOS_PER_MINUTE: u64 = 60 * NANOS_PER_SECOND;
+const NANOS_PER_DAY: u64 = 86_400 * NANOS_PER_SECOND;
+const MILLI_TOKENS: u64 = 1_000;
 
-/// Helper: get daily limit for a tier
-fn get_daily_limit(tier: &UserTier) -> Option<u32> {
-    match tier {
-        UserTier::Free => Some(FREE_DAILY_LIMIT),
-        UserTier::Pro => None,
+/* ============================
+   Quota policies
+   ============================ */
+
+fn policy_key(tier: &UserTier) -> StableString {
+    StableString::from(format!("{:?}", tier))
+}
+
+/// A tier's quota policy (the defaults until an admin changes it)
+pub fn get_quota_policy(tier: &UserTier) -> QuotaPolicy {
+    QUOTA_POLICIES
+        .with(|policies| policies.borrow().get(&policy_key(tier)))
+        .unwrap_or_else(|| QuotaPolicy::default_for(tier.clone()))
+}
+
+/// Replaces a tier's policy. Takes effect on the next request; what was used
+/// today and this month still counts.
+pub fn set_quota_policy(mut policy: QuotaPolicy, now: u64) -> Result<QuotaPolicy, String> {
+    validate_quota_policy(&policy)?;
+    policy.updated_at = now;
+    QUOTA_POLICIES.with(|policies| policies.borrow_mut().insert(policy_key(&policy.tier), policy.clone()));
+    Ok(policy)
+}
+
+fn validate_quota_policy(policy: &QuotaPolicy) -> Result<(), String> {
+    if let Some(burst) = &policy.burst {
+        if burst.capacity == 0 || burst.refill_per_minute == 0 {
+            return Err("Burst capacity and refill rate must be at least 1".to_string());
+        }
     }
+
+    // A bot costing more than a limit allows could never be used
+    let smallest_limit = [policy.burst.as_ref().map(|burst| burst.capacity), policy.daily_limit, policy.monthly_limit]
+        .into_iter()
+        .flatten()
+        .min();
+    let mut bot_names = HashSet::new();
+    for bot in &policy.bot_costs {
+        if bot.cost == 0 {
+            return Err(format!("Cost for {} must be at least 1", bot.bot_name));
+        }
+        if smallest_limit.is_some_and(|limit| bot.cost > limit) {
+            return Err(format!("Cost for {} is more than the policy's limits allow", bot.bot_name));
+        }
+        if !bot_names.insert(bot.bot_name.as_str()) {
+            return Err(format!("{} has more than one cost", bot.bot_name));
+        }
+    }
+    Ok(())
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_quota_policies() -> Result<Vec<QuotaPolicy>, String> {
+    require_permission(Permission::ViewUsers)?;
+    Ok([UserTier::Free, UserTier::Pro].iter().map(get_quota_policy).collect())
 }
 
+#[update(guard = "caller_is_admin")]
+pub fn admin_set_quota_policy(policy: QuotaPolicy) -> Result<QuotaPolicy, String> {
+    require_permission(Permission::ManageBilling)?;
+    let before = get_quota_policy(&policy.tier);
+    let saved = set_quota_policy(policy, time())?;
+    audit(
+        AuditAction::QuotaPolicyChanged,
+        format!("quota_policy:{:?}", saved.tier),
+        Some(digest(&before)),
+        Some(digest(&saved)),
+    );
+    Ok(saved)
+}
+
+/* ============================
+   Usage counters
+   ============================ */
+
 /// Get current day bucket timestamp
 fn get_current_day_timestamp() -> u64 {
     time() / NANOS_PER_DAY
 }
 
-/// Compare if two timestamps fall in the same day bucket
-fn is_same_day(timestamp1: u64, timestamp2: u64) -> bool {
-    timestamp1 == timestamp2
+fn day_date(day: u64) -> NaiveDate {
+    DateTime::UNIX_EPOCH.date_naive() + Days::new(day)
 }
 
-/// Reset daily usage for user if day bucket has changed
-fn reset_daily_usage_if_needed(user_id: &str) {
-    let today = get_current_day_timestamp();
+fn date_day(date: NaiveDate) -> u64 {
+    (date - DateTime::UNIX_EPOCH.date_naive()).num_days() as u64
+}
+
+/// Day buckets on which the UTC month of `day` starts and the next one starts
+fn month_bounds(day: u64) -> (u64, u64) {
+    let first = day_date(day).with_day(1).expect("every month has a first day");
+    (date_day(first), date_day(first + Months::new(1)))
+}
+
+/// The account whose subscription and daily counter a `platform` user ID (see
+/// `Platform::user_id`) draws on: the site principal once the platform account
+/// is linked, otherwise the ID itself. Every platform linked to one principal
+/// shares its quota, so the functions below take this account, not the ID.
+pub fn quota_account(platform: Platform, user_id: &str) -> String {
+    platform
+        .external_id(user_id)
+        .and_then(|external_id| linked_principal(platform, external_id))
+        .map_or_else(|| user_id.to_string(), |principal| principal.to_text())
+}
+
+/// Drops an account's usage from before this month, which no limit counts
+/// any more, and opens today's bucket
+fn prune_daily_usage(account: &str, today: u64) {
+    let (month_start, _) = month_bounds(today);
     USER_DAILY_USAGE.with(|usage| {
         let mut map = usage.borrow_mut();
-        let uid = StableString::from(user_id.to_string());
+        let uid = StableString::from(account);
 
-        // remove outdated entries
         let keys_to_remove: Vec<_> = map
-            .iter()
-            .filter(|((uid_ref, _), _)| uid_ref == &uid)
+            .range((uid.clone(), 0)..(uid.clone(), month_start))
             .map(|(key, _)| key)
-            .filter(|(_, day)| !is_same_day(*day, today))
             .collect();
-
         for key in keys_to_remove {
             map.remove(&key);
         }
@@ -54,76 +151,204 @@ fn reset_daily_usage_if_needed(user_id: &str) {
     });
 }
 
+/// Request units an account used from `first_day` to `last_day`, inclusive
+fn usage_between(account: &str, first_day: u64, last_day: u64) -> u32 {
+    let uid = StableString::from(account);
+    USER_DAILY_USAGE.with(|usage| {
+        usage
+            .borrow()
+            .range((uid.clone(), first_day)..=(uid, last_day))
+            .map(|(_, count)| count)
+            .sum()
+    })
+}
+
+/// Every account's usage today, for the admin views
+pub fn usage_today() -> Vec<(String, u32)> {
+    let today = get_current_day_timestamp();
+    USER_DAILY_USAGE.with(|usage| {
+        usage
+            .borrow()
+            .iter()
+            .filter(|((_, day), _)| *day == today)
+            .map(|((user_id, _), count)| (user_id.to_string(), count))
+            .collect()
+    })
+}
 
 /// Check user subscription tier
 pub fn check_user_tier(user_id: &str) -> UserTier {
-    USER_SUBSCRIPTIONS.with(|subs| {
+    check_user_tier_at(user_id, time())
+}
+
+/// `check_user_tier` with an explicit clock. A seat sponsored by an
+/// accelerator counts until the sponsorship ends.
+pub fn check_user_tier_at(user_id: &str, now: u64) -> UserTier {
+    account_tier(user_id, now)
+}
+
+fn account_tier(account: &str, now: u64) -> UserTier {
+    let tier = USER_SUBSCRIPTIONS.with(|subs| {
         subs.borrow()
-            .get(&StableString::from(user_id.to_string()))
+            .get(&StableString::from(account.to_string()))
             .map(|s| {
-                // handle expired subs
+                // handle expired subs, unless a failed renewal is still in its grace period
                 if let Some(expiry) = s.expires_at_ns {
-                    if time() > expiry {
+                    if now > expiry && !is_in_grace_period(account, now) {
                         return UserTier::Free;
                     }
                 }
                 s.tier
             })
             .unwrap_or(UserTier::Free)
-    })
+    });
+
+    match tier {
+        UserTier::Free => sponsored_tier(account, now).unwrap_or(UserTier::Free),
+        tier => tier,
+    }
 }
 
 /// Get number of requests made today
 pub fn get_user_daily_requests(user_id: &str) -> u32 {
-    reset_daily_usage_if_needed(user_id);
-    let day = get_current_day_timestamp();
-    USER_DAILY_USAGE.with(|usage| {
-        usage
-            .borrow()
-            .get(&(StableString::from(user_id.to_string()), day))
-            .unwrap_or(0)
-    })
+    get_user_daily_requests_at(user_id, time())
+}
+
+/// `get_user_daily_requests` with an explicit clock
+pub fn get_user_daily_requests_at(user_id: &str, now: u64) -> u32 {
+    account_daily_requests(user_id, now / NANOS_PER_DAY)
+}
+
+fn account_daily_requests(account: &str, today: u64) -> u32 {
+    prune_daily_usage(account, today);
+    usage_between(account, today, today)
 }
 
 /// Check if user can make another request
 pub fn can_make_request(user_id: &str) -> bool {
-    let tier = check_user_tier(user_id);
-    match get_daily_limit(&tier) {
-        None => true, // Pro unlimited
-        Some(limit) => get_user_daily_requests(user_id) < limit,
-    }
+    can_make_request_at(user_id, time())
 }
 
-/// Increment user daily request counter
-pub fn increment_user_requests(user_id: &str) -> Result<(), String> {
-    // ✅ Pro users are unlimited
-    if matches!(check_user_tier(user_id), UserTier::Pro) {
-        return Ok(());
-    }
+/// `can_make_request` with an explicit clock, for a request of one unit
+pub fn can_make_request_at(user_id: &str, now: u64) -> bool {
+    let policy = get_quota_policy(&account_tier(user_id, now));
+    check_quota(user_id, &policy, 1, now).is_ok()
+}
 
-    if !can_make_request(user_id) {
-        return Err("Daily limit reached. Upgrade to Pro for unlimited access.".to_string());
-    }
+/// Counts a request against the user's quota, or says which limit it hit and
+/// when to retry. `bot_name` picks the request's cost from the tier's policy.
+pub fn increment_user_requests(user_id: &str, bot_name: Option<&str>) -> Result<QuotaRemaining, QuotaRejection> {
+    increment_user_requests_at(user_id, bot_name, time())
+}
 
-    reset_daily_usage_if_needed(user_id);
-    let day = get_current_day_timestamp();
-    let tier = check_user_tier(user_id);
-    let limit = get_daily_limit(&tier);
+/// `increment_user_requests` with an explicit clock
+pub fn increment_user_requests_at(user_id: &str, bot_name: Option<&str>, now: u64) -> Result<QuotaRemaining, QuotaRejection> {
+    let policy = get_quota_policy(&account_tier(user_id, now));
+    let cost = policy.cost(bot_name);
+    let (remaining, bucket) = check_quota(user_id, &policy, cost, now)?;
 
     USER_DAILY_USAGE.with(|usage| {
         let mut map = usage.borrow_mut();
-        let key = (StableString::from(user_id.to_string()), day);
+        let key = (StableString::from(user_id), now / NANOS_PER_DAY);
         let current = map.get(&key).unwrap_or(0);
+        map.insert(key, current + cost);
+    });
+    if let Some(bucket) = bucket {
+        QUOTA_BUCKETS.with(|buckets| buckets.borrow_mut().insert(StableString::from(user_id), bucket));
+    }
+    Ok(remaining)
+}
+
+/// Checks a request of `cost` units against every limit in `policy`. On
+/// success returns what would be left and the burst bucket after paying.
+fn check_quota(
+    account: &str,
+    policy: &QuotaPolicy,
+    cost: u32,
+    now: u64,
+) -> Result<(QuotaRemaining, Option<TokenBucket>), QuotaRejection> {
+    let today = now / NANOS_PER_DAY;
+    let (month_start, next_month) = month_bounds(today);
+    let daily_used = account_daily_requests(account, today);
+    let monthly_used = usage_between(account, month_start, today);
+    let bucket = policy.burst.as_ref().map(|burst| refilled_bucket(account, burst, now));
+
+    let remaining = QuotaRemaining {
+        burst: bucket.as_ref().map(|bucket| (bucket.milli_tokens / MILLI_TOKENS) as u32),
+        daily: policy.daily_limit.map(|limit| limit.saturating_sub(daily_used)),
+        monthly: policy.monthly_limit.map(|limit| limit.saturating_sub(monthly_used)),
+    };
 
-        if let Some(limit) = limit {
-            if current >= limit {
-                return Err("Daily limit reached. Upgrade to Pro for unlimited access.".to_string());
+    // Longest wait first: retrying before the month or day is over is pointless
+    if remaining.monthly.is_some_and(|left| left < cost) {
+        let wait = next_month * NANOS_PER_DAY - now;
+        return Err(rejection(QuotaLimit::Monthly, policy, cost, wait, remaining));
+    }
+    if remaining.daily.is_some_and(|left| left < cost) {
+        let wait = (today + 1) * NANOS_PER_DAY - now;
+        return Err(rejection(QuotaLimit::Daily, policy, cost, wait, remaining));
+    }
+    let bucket = match (bucket, &policy.burst) {
+        (Some(bucket), Some(burst)) => {
+            let needed = cost as u64 * MILLI_TOKENS;
+            if bucket.milli_tokens < needed {
+                let wait = refill_wait(burst, needed - bucket.milli_tokens);
+                return Err(rejection(QuotaLimit::Burst, policy, cost, wait, remaining));
             }
+            Some(TokenBucket { milli_tokens: bucket.milli_tokens - needed, updated_at: bucket.updated_at })
         }
+        _ => None,
+    };
 
-        map.insert(key, current + 1);
-        Ok(())
-    })
+    Ok((
+        QuotaRemaining {
+            burst: bucket.as_ref().map(|bucket| (bucket.milli_tokens / MILLI_TOKENS) as u32),
+            daily: remaining.daily.map(|left| left - cost),
+            monthly: remaining.monthly.map(|left| left - cost),
+        },
+        bucket,
+    ))
+}
+
+/// An account's burst bucket topped up to `now`. A new bucket starts full.
+fn refilled_bucket(account: &str, burst: &BurstLimit, now: u64) -> TokenBucket {
+    let capacity = burst.capacity as u64 * MILLI_TOKENS;
+    match QUOTA_BUCKETS.with(|buckets| buckets.borrow().get(&StableString::from(account))) {
+        None => TokenBucket { milli_tokens: capacity, updated_at: now },
+        Some(bucket) => {
+            let elapsed = now.saturating_sub(bucket.updated_at) as u128;
+            let refill = elapsed * burst.refill_per_minute as u128 * MILLI_TOKENS as u128 / NANOS_PER_MINUTE as u128;
+            TokenBucket {
+                milli_tokens: (bucket.milli_tokens as u128 + refill).min(capacity as u128) as u64,
+                updated_at: now.max(bucket.updated_at),
+            }
+        }
+    }
+}
+
+/// Nanoseconds until a bucket refills by `missing` milli-tokens
+fn refill_wait(burst: &BurstLimit, missing: u64) -> u64 {
+    let per_minute = burst.refill_per_minute as u128 * MILLI_TOKENS as u128;
+    (missing as u128 * NANOS_PER_MINUTE as u128).div_ceil(per_minute) as u64
+}
+
+fn rejection(limit: QuotaLimit, policy: &QuotaPolicy, cost: u32, wait_ns: u64, remaining: QuotaRemaining) -> QuotaRejection {
+    let reason = match limit {
+        QuotaLimit::Burst => "Too many requests in a short time.",
+        QuotaLimit::Daily => "Daily limit reached.",
+        QuotaLimit::Monthly => "Monthly limit reached.",
+    };
+    let message = match (limit, &policy.tier) {
+        (QuotaLimit::Burst, _) | (_, UserTier::Pro) => reason.to_string(),
+        (_, UserTier::Free) => format!("{} Upgrade to Pro for more requests.", reason),
+    };
+    QuotaRejection {
+        limit,
+        message,
+        cost,
+        retry_after_secs: wait_ns.div_ceil(NANOS_PER_SECOND),
+        remaining,
+    }
 }
 
 /// Calculate reset time in RFC3339 format
@@ -144,9 +369,9 @@ fn next_reset_rfc3339(now_ns: u64) -> String {
 pub fn get_usage_stats(user_id: &str) -> UsageStats {
     let now = time();
     let day_bucket = get_current_day_timestamp();
-    let tier = check_user_tier(user_id);
-    let used = get_user_daily_requests(user_id);
-    let limit = get_daily_limit(&tier);
+    let tier = account_tier(user_id, now);
+    let used = account_daily_requests(user_id, day_bucket);
+    let limit = get_quota_policy(&tier).daily_limit;
 
     UsageStats {
         user_id: user_id.to_string(),
@@ -163,7 +388,7 @@ pub fn upgrade_user_tier(user_id: &str, tier: UserTier, expires_at: Option<u64>)
     USER_SUBSCRIPTIONS.with(|subs| {
         let mut map = subs.borrow_mut();
         let mut sub = map
-            .get(&StableString::from(user_id.to_string()))
+            .get(&StableString::from(user_id))
             .unwrap_or(UserSubscription {
                 user_id: user_id.to_string(),
                 tier: UserTier::Free,
@@ -178,7 +403,7 @@ pub fn upgrade_user_tier(user_id: &str, tier: UserTier, expires_at: Option<u64>)
         sub.renewed_at_ns = Some(time());
         sub.expires_at_ns = expires_at;
 
-        map.insert(StableString::from(user_id.to_string()), sub);
+        map.insert(StableString::from(user_id), sub);
         Ok(())
     })
 }
@@ -192,7 +417,7 @@ pub fn get_user_tier(user_id: &str) -> UserTier {
 pub fn get_user_subscription(user_id: &str) -> Option<UserSubscription> {
     USER_SUBSCRIPTIONS.with(|subs| {
         subs.borrow()
-            .get(&StableString::from(user_id.to_string()))
+            .get(&StableString::from(user_id))
             .map(|s| s.clone())
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,224 @@
+// backend/src/services/reconciliation_service.rs
+//
+// Reconciles payments stuck in Pending (abandoned checkouts, lost webhooks)
+// by asking their gateway what happened to them.
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::time::Duration;
+
+use candid::CandidType;
+use ic_cdk::api::time;
+use ic_cdk::{query, update};
+use serde::Deserialize;
+
+use crate::models::payment::{PaymentRecord, PaymentStatus};
+use crate::models::stable_string::StableString;
+use crate::payments::is_gateway_configured;
+use crate::payments::provider::{IcHttpTransport, PaymentProvider, Provider, ProviderVerification};
+use crate::models::admin::Permission;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::payment_service::{fulfil_payment_at, update_payment_status, PaymentConfirmation};
+use crate::storage::memory::PAYMENT_RECORDS;
+
+const NANOS_PER_MINUTE: u64 = 60 * 1_000_000_000;
+const RECONCILE_INTERVAL_SECS: u64 = 30 * 60; // Every 30 minutes
+const PENDING_THRESHOLD: u64 = 60 * NANOS_PER_MINUTE; // Leave fresh checkouts alone
+const ABANDON_AFTER: u64 = 24 * 60 * NANOS_PER_MINUTE; // Still pending at the gateway after this = abandoned
+const MAX_VERIFICATIONS_PER_RUN: u32 = 20; // Bounds HTTP outcalls per timer tick
+
+thread_local! {
+    static RECONCILE_RUN_IN_PROGRESS: Cell<bool> = const { Cell::new(false) };
+    // Reference after which the next run continues, so every stale payment gets its turn
+    static RECONCILE_CURSOR: RefCell<Option<String>> = const { RefCell::new(None) };
+    // Heap only: empty after an upgrade until the next run
+    static LAST_RECONCILIATION: RefCell<Option<ReconciliationRunSummary>> = const { RefCell::new(None) };
+}
+
+/// Asks a payment's gateway for its status. The real gateways in production, a mock in tests.
+pub trait PaymentVerifier {
+    fn verify(&self, payment: &PaymentRecord) -> impl Future<Output = Result<ProviderVerification, String>>;
+}
+
+/// `PaymentVerifier` backed by the provider that took the payment
+pub struct GatewayVerifier;
+
+impl PaymentVerifier for GatewayVerifier {
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        Provider::for_gateway(payment.provider, IcHttpTransport, time())
+            .verify(payment)
+            .await
+    }
+}
+
+/// Outcome of one reconciliation pass
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct ReconciliationRunSummary {
+    pub started_at: u64,
+    pub checked: u32,
+    pub fulfilled: u32,
+    pub failed: u32,
+    pub abandoned: u32,
+    pub still_pending: u32,
+    pub errors: u32,
+    pub skipped: u32,       // Gateway not configured
+    pub deferred: u32,      // Stale, but over the per-run outcall budget
+    pub last_error: Option<String>,
+}
+
+/// Pending payments old enough to reconcile, in reference order starting after `cursor`
+fn stale_pending_payments(cursor: Option<&str>, now: u64) -> Vec<PaymentRecord> {
+    PAYMENT_RECORDS.with(|records| {
+        let records = records.borrow();
+        let is_stale = |payment: &PaymentRecord| {
+            payment.status == PaymentStatus::Pending && now.saturating_sub(payment.created_at) >= PENDING_THRESHOLD
+        };
+        match cursor {
+            Some(cursor) => records
+                .range(StableString::from(cursor.to_string())..)
+                .filter(|(key, _)| key.as_str() != cursor)
+                .map(|(_, payment)| payment)
+                .filter(is_stale)
+                .collect(),
+            None => records.iter().map(|(_, payment)| payment).filter(is_stale).collect(),
+        }
+    })
+}
+
+/// One bounded pass over stale pending payments
+pub async fn reconcile_pending_payments<V: PaymentVerifier>(verifier: &V, now: u64) -> ReconciliationRunSummary {
+    let mut summary = ReconciliationRunSummary {
+        started_at: now,
+        ..Default::default()
+    };
+
+    // Continue where the last run stopped; start over once the end is reached
+    let cursor = RECONCILE_CURSOR.with(|cursor| cursor.borrow().clone());
+    let mut payments = stale_pending_payments(cursor.as_deref(), now);
+    if payments.is_empty() && cursor.is_some() {
+        payments = stale_pending_payments(None, now);
+    }
+
+    let mut next_cursor = None;
+    for payment in payments {
+        if summary.checked >= MAX_VERIFICATIONS_PER_RUN {
+            summary.deferred += 1;
+            continue;
+        }
+        if !is_gateway_configured(payment.provider) {
+            summary.skipped += 1;
+            continue;
+        }
+
+        summary.checked += 1;
+        next_cursor = Some(payment.paystack_reference.clone());
+        if let Err(e) = reconcile_payment(verifier, &payment, now, &mut summary).await {
+            summary.errors += 1;
+            summary.last_error = Some(format!("{}: {}", payment.paystack_reference, e));
+        }
+    }
+
+    // Nothing left behind: the next run starts from the beginning
+    let next_cursor = if summary.deferred > 0 { next_cursor } else { None };
+    RECONCILE_CURSOR.with(|cursor| *cursor.borrow_mut() = next_cursor);
+
+    summary
+}
+
+async fn reconcile_payment<V: PaymentVerifier>(
+    verifier: &V,
+    payment: &PaymentRecord,
+    now: u64,
+    summary: &mut ReconciliationRunSummary,
+) -> Result<(), String> {
+    let reference = &payment.paystack_reference;
+    let verification = verifier.verify(payment).await?;
+
+    match verification.status {
+        PaymentStatus::Success => {
+            if verification.amount > 0 && verification.amount < payment.amount {
+                return Err(format!(
+                    "Gateway reports {} paid, expected {}",
+                    verification.amount, payment.amount
+                ));
+            }
+            fulfil_payment_at(reference, PaymentConfirmation {
+                channel: verification.channel,
+                transaction_id: verification.transaction_id,
+                provider_transaction_id: verification.provider_transaction_id,
+                authorization_code: verification.authorization_code,
+            }, now)?;
+            summary.fulfilled += 1;
+        }
+        PaymentStatus::Failed => {
+            update_payment_status(reference, PaymentStatus::Failed, verification.channel, now)?;
+            summary.failed += 1;
+        }
+        PaymentStatus::Abandoned => {
+            update_payment_status(reference, PaymentStatus::Abandoned, verification.channel, now)?;
+            summary.abandoned += 1;
+        }
+        _ if now.saturating_sub(payment.created_at) >= ABANDON_AFTER => {
+            // Never completed at the gateway: release the checkout and its coupon
+            update_payment_status(reference, PaymentStatus::Abandoned, verification.channel, now)?;
+            summary.abandoned += 1;
+        }
+        _ => summary.still_pending += 1,
+    }
+    Ok(())
+}
+
+/// Start the reconciliation timer. Timers do not survive upgrades, so this
+/// is called from both `init` and `post_upgrade`.
+pub fn start_reconciliation_timer() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(RECONCILE_INTERVAL_SECS), || {
+        ic_cdk::spawn(async {
+            if let Err(e) = run_reconciliation().await {
+                ic_cdk::println!("Payment reconciliation skipped: {}", e);
+            }
+        });
+    });
+}
+
+/// Reconcile pending payments now instead of waiting for the next tick (admin only)
+#[update(guard = "caller_is_admin")]
+pub async fn admin_run_payment_reconciliation() -> Result<ReconciliationRunSummary, String> {
+    require_permission(Permission::ManageBilling)?;
+    run_reconciliation().await
+}
+
+/// Summary of the most recent reconciliation run (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_last_reconciliation() -> Result<Option<ReconciliationRunSummary>, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(LAST_RECONCILIATION.with(|last| last.borrow().clone()))
+}
+
+async fn run_reconciliation() -> Result<ReconciliationRunSummary, String> {
+    let _guard = ReconcileRunGuard::acquire()
+        .ok_or("Payment reconciliation already in progress")?;
+
+    let summary = reconcile_pending_payments(&GatewayVerifier, time()).await;
+    ic_cdk::println!("Payment reconciliation finished: {:?}", summary);
+    LAST_RECONCILIATION.with(|last| *last.borrow_mut() = Some(summary.clone()));
+    Ok(summary)
+}
+
+/// Prevents overlapping runs while a previous one is awaiting outcalls
+struct ReconcileRunGuard;
+
+impl ReconcileRunGuard {
+    fn acquire() -> Option<Self> {
+        if RECONCILE_RUN_IN_PROGRESS.with(|running| running.replace(true)) {
+            None
+        } else {
+            Some(ReconcileRunGuard)
+        }
+    }
+}
+
+impl Drop for ReconcileRunGuard {
+    fn drop(&mut self) {
+        RECONCILE_RUN_IN_PROGRESS.with(|running| running.set(false));
+    }
+}
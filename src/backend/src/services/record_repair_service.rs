@@ -0,0 +1,205 @@
+// backend/src/services/record_repair_service.rs
+//
+// Finds stored values that no longer decode, and moves them to
+// QUARANTINED_RECORDS so reads of their map stop skipping them. Maps are
+// scanned as the bytes their values are stored as, so scanning never decodes
+// anything it cannot.
+
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+use candid::CandidType;
+use ic_cdk::api::time;
+use ic_cdk::{query, update};
+use ic_stable_structures::Storable;
+use serde::Deserialize;
+
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::models::record::{decode_record, split_record, QuarantinedRecord, VersionedRecord};
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::audit_service::audit;
+use crate::storage::memory::*;
+use crate::storage::record_map::RecordMap;
+
+/// Undecodable records listed per map in a report; the rest are only counted
+const MAX_EXAMPLES: usize = 10;
+
+const MAX_QUARANTINE_PAGE: u32 = 50;
+
+/// One value that failed to decode
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct UndecodableRecord {
+    pub key: Vec<u8>,
+    pub version: Option<u8>,
+    pub error: String,
+}
+
+/// Result of scanning one map
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordScanReport {
+    pub map: String,
+    pub scanned: u64,
+    pub undecodable: u64,
+    pub quarantined: u64,
+    pub examples: Vec<UndecodableRecord>,
+}
+
+type Repair = fn(&'static str, bool, u64) -> RecordScanReport;
+
+/// Every map of enveloped records
+const MAPS: &[(&str, Repair)] = &[
+    ("USERS", |name, quarantine, now| repair_map(name, &USERS, quarantine, now)),
+    ("WAITLIST", |name, quarantine, now| repair_map(name, &WAITLIST, quarantine, now)),
+    ("CHAT_HISTORY", |name, quarantine, now| repair_map(name, &CHAT_HISTORY, quarantine, now)),
+    ("ADMINS", |name, quarantine, now| repair_map(name, &ADMINS, quarantine, now)),
+    ("CONNECTED_ACCOUNTS", |name, quarantine, now| repair_map(name, &CONNECTED_ACCOUNTS, quarantine, now)),
+    ("TASKS", |name, quarantine, now| repair_map(name, &TASKS, quarantine, now)),
+    ("DASHBOARD_TOKENS", |name, quarantine, now| repair_map(name, &DASHBOARD_TOKENS, quarantine, now)),
+    ("GITHUB_ISSUES", |name, quarantine, now| repair_map(name, &GITHUB_ISSUES, quarantine, now)),
+    ("ACCELERATORS", |name, quarantine, now| repair_map(name, &ACCELERATORS, quarantine, now)),
+    ("STARTUPS", |name, quarantine, now| repair_map(name, &STARTUPS, quarantine, now)),
+    ("STARTUP_STATUSES", |name, quarantine, now| repair_map(name, &STARTUP_STATUSES, quarantine, now)),
+    ("STARTUP_COHORTS", |name, quarantine, now| repair_map(name, &STARTUP_COHORTS, quarantine, now)),
+    ("STARTUP_ACTIVITIES", |name, quarantine, now| repair_map(name, &STARTUP_ACTIVITIES, quarantine, now)),
+    ("STARTUP_INVITES", |name, quarantine, now| repair_map(name, &STARTUP_INVITES, quarantine, now)),
+    ("API_MESSAGES", |name, quarantine, now| repair_map(name, &API_MESSAGES, quarantine, now)),
+    ("USER_SUBSCRIPTIONS", |name, quarantine, now| repair_map(name, &USER_SUBSCRIPTIONS, quarantine, now)),
+    ("PAYMENT_RECORDS", |name, quarantine, now| repair_map(name, &PAYMENT_RECORDS, quarantine, now)),
+    ("INVOICES", |name, quarantine, now| repair_map(name, &INVOICES, quarantine, now)),
+    ("USER_ANALYTICS", |name, quarantine, now| repair_map(name, &USER_ANALYTICS, quarantine, now)),
+    ("LEGACY_MAIN_SITE_USERS", |name, quarantine, now| repair_map(name, &LEGACY_MAIN_SITE_USERS, quarantine, now)),
+    ("SUBSCRIPTION_BILLING", |name, quarantine, now| repair_map(name, &SUBSCRIPTION_BILLING, quarantine, now)),
+    ("BILLING_SETTINGS", |name, quarantine, now| repair_map(name, &BILLING_SETTINGS, quarantine, now)),
+    ("REFUNDS", |name, quarantine, now| repair_map(name, &REFUNDS, quarantine, now)),
+    ("PLANS", |name, quarantine, now| repair_map(name, &PLANS, quarantine, now)),
+    ("COUPONS", |name, quarantine, now| repair_map(name, &COUPONS, quarantine, now)),
+    ("COUPON_REDEMPTIONS", |name, quarantine, now| repair_map(name, &COUPON_REDEMPTIONS, quarantine, now)),
+    ("SEAT_SPONSORSHIPS", |name, quarantine, now| repair_map(name, &SEAT_SPONSORSHIPS, quarantine, now)),
+    ("SEAT_ASSIGNMENTS", |name, quarantine, now| repair_map(name, &SEAT_ASSIGNMENTS, quarantine, now)),
+    ("WEBHOOK_EVENTS", |name, quarantine, now| repair_map(name, &WEBHOOK_EVENTS, quarantine, now)),
+    ("INVOICE_SETTINGS", |name, quarantine, now| repair_map(name, &INVOICE_SETTINGS, quarantine, now)),
+    ("BOT_PRINCIPALS", |name, quarantine, now| repair_map(name, &BOT_PRINCIPALS, quarantine, now)),
+    ("AUDIT_LOG", |name, quarantine, now| repair_map(name, &AUDIT_LOG, quarantine, now)),
+    ("AUDIT_ARCHIVE", |name, quarantine, now| repair_map(name, &AUDIT_ARCHIVE, quarantine, now)),
+    ("PLATFORM_IDENTITIES", |name, quarantine, now| repair_map(name, &PLATFORM_IDENTITIES, quarantine, now)),
+    ("MERGED_RECORDS", |name, quarantine, now| repair_map(name, &MERGED_RECORDS, quarantine, now)),
+    ("QUOTA_POLICIES", |name, quarantine, now| repair_map(name, &QUOTA_POLICIES, quarantine, now)),
+    ("QUOTA_BUCKETS", |name, quarantine, now| repair_map(name, &QUOTA_BUCKETS, quarantine, now)),
+];
+
+/// Names of the maps the repair tool covers
+pub fn repairable_maps() -> Vec<&'static str> {
+    MAPS.iter().map(|(name, _)| *name).collect()
+}
+
+/// Scans one map, or all of them, for values that don't decode. With
+/// `quarantine` those are moved to QUARANTINED_RECORDS.
+pub fn repair_records(map: Option<&str>, quarantine: bool, now: u64) -> Result<Vec<RecordScanReport>, String> {
+    let selected: Vec<_> = MAPS.iter().filter(|(name, _)| map.is_none_or(|map| map == *name)).collect();
+    if selected.is_empty() {
+        return Err(format!("Unknown map: {}", map.unwrap_or_default()));
+    }
+    Ok(selected.into_iter().map(|(name, repair)| repair(name, quarantine, now)).collect())
+}
+
+fn repair_map<K, V>(
+    name: &'static str,
+    map: &'static LocalKey<RefCell<RecordMap<K, V>>>,
+    quarantine: bool,
+    now: u64,
+) -> RecordScanReport
+where
+    K: Storable + Ord + Clone,
+    V: VersionedRecord,
+{
+    let mut report = RecordScanReport { map: name.to_string(), scanned: 0, undecodable: 0, quarantined: 0, examples: Vec::new() };
+    let mut undecodable = Vec::new();
+    map.with(|map| {
+        for (key, value) in map.borrow().raw_iter() {
+            report.scanned += 1;
+            if let Err(error) = decode_record::<V>(&value) {
+                report.undecodable += 1;
+                if report.examples.len() < MAX_EXAMPLES {
+                    report.examples.push(UndecodableRecord {
+                        key: key.to_bytes().into_owned(),
+                        version: split_record(&value).map(|(version, _)| version),
+                        error: error.clone(),
+                    });
+                }
+                undecodable.push((key, value, error));
+            }
+        }
+    });
+    if !quarantine {
+        return report;
+    }
+
+    for (key, value, error) in undecodable {
+        map.with(|map| map.borrow_mut().remove_raw(&key));
+        save_quarantined(QuarantinedRecord {
+            id: 0,
+            map: name.to_string(),
+            key: key.to_bytes().into_owned(),
+            value,
+            error,
+            quarantined_at: now,
+        });
+        report.quarantined += 1;
+    }
+    report
+}
+
+fn save_quarantined(mut record: QuarantinedRecord) {
+    QUARANTINED_RECORDS.with(|records| {
+        let mut records = records.borrow_mut();
+        record.id = records.last_key_value().map_or(0, |(id, _)| id + 1);
+        records.insert(record.id, record);
+    });
+}
+
+/// Quarantined records with an id after `after`, oldest first
+pub fn quarantined_records(after: Option<u64>, limit: u32) -> Vec<QuarantinedRecord> {
+    let start = after.map_or(0, |id| id + 1);
+    QUARANTINED_RECORDS.with(|records| {
+        records
+            .borrow()
+            .range(start..)
+            .take(limit.min(MAX_QUARANTINE_PAGE) as usize)
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+/* ============================
+   Endpoints
+   ============================ */
+
+/// Reports undecodable values in `map`, or in every map, without changing anything
+#[query(guard = "caller_is_admin")]
+pub fn admin_scan_records(map: Option<String>) -> Result<Vec<RecordScanReport>, String> {
+    require_permission(Permission::ViewStorage)?;
+    repair_records(map.as_deref(), false, time())
+}
+
+/// Moves undecodable values in `map`, or in every map, to quarantine
+#[update(guard = "caller_is_admin")]
+pub fn admin_quarantine_undecodable_records(map: Option<String>) -> Result<Vec<RecordScanReport>, String> {
+    require_permission(Permission::ManageStorage)?;
+    let reports = repair_records(map.as_deref(), true, time())?;
+    for report in reports.iter().filter(|report| report.quarantined > 0) {
+        audit(
+            AuditAction::RecordsQuarantined,
+            format!("{}:{}", report.map, report.quarantined),
+            None,
+            None,
+        );
+    }
+    Ok(reports)
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_quarantined_records(after: Option<u64>, limit: u32) -> Result<Vec<QuarantinedRecord>, String> {
+    require_permission(Permission::ViewStorage)?;
+    Ok(quarantined_records(after, limit))
+}
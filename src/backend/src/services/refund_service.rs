@@ -0,0 +1,255 @@
+// backend/src/services/refund_service.rs
+//
+// Full and partial refunds through the gateway that took the payment,
+// subscription proration and credit notes.
+
+use crate::models::payment::{Invoice, InvoiceKind, InvoiceLineItem, PaymentStatus, RefundRecord, RefundStatus};
+use crate::models::stable_string::StableString;
+use crate::payments::provider::PaymentProvider;
+use crate::services::invoice_service::issue_invoice;
+use crate::services::sponsorship_service::cancel_sponsorship;
+use crate::services::subscription_service::shorten_subscription;
+use crate::storage::memory::{INVOICES, PAYMENT_RECORDS, REFUNDS};
+
+/// Refund `amount` (everything still refundable when `None`) of a successful payment.
+///
+/// The refund is recorded as Pending before the gateway call so concurrent
+/// requests cannot refund the same money twice. Gateways that settle
+/// immediately complete it right away; the others complete it from the
+/// `refund.processed` webhook.
+pub async fn refund_payment<P: PaymentProvider>(
+    provider: &P,
+    reference: &str,
+    amount: Option<u64>,
+    reason: &str,
+    requested_by: &str,
+    now: u64,
+) -> Result<RefundRecord, String> {
+    let payment = PAYMENT_RECORDS
+        .with(|records| records.borrow().get(&StableString::from(reference.to_string())))
+        .ok_or_else(|| format!("Payment record not found for reference: {}", reference))?;
+
+    if payment.status != PaymentStatus::Success {
+        return Err(format!("Only successful payments can be refunded (status: {:?})", payment.status));
+    }
+
+    let refundable = payment.amount.saturating_sub(refunded_amount(reference));
+    if refundable == 0 {
+        return Err("Payment has already been fully refunded".to_string());
+    }
+
+    let amount = amount.unwrap_or(refundable);
+    if amount == 0 {
+        return Err("Refund amount must be greater than zero".to_string());
+    }
+    if amount > refundable {
+        return Err(format!("Refund amount {} exceeds refundable balance {}", amount, refundable));
+    }
+
+    let mut refund = RefundRecord {
+        id: format!("RF-{}-{}", reference, get_payment_refunds(reference).len() + 1),
+        payment_reference: reference.to_string(),
+        user_id: payment.user_id.clone(),
+        amount,
+        currency: payment.currency.clone(),
+        reason: reason.to_string(),
+        status: RefundStatus::Pending,
+        provider: payment.provider,
+        provider_refund_id: None,
+        credit_note_id: None,
+        requested_by: requested_by.to_string(),
+        error: None,
+        created_at: now,
+        updated_at: now,
+    };
+    store_refund(refund.clone());
+
+    match provider.refund(&payment, Some(amount), reason).await {
+        Ok(response) => {
+            refund.provider_refund_id = Some(response.refund_id);
+            refund.updated_at = now;
+            store_refund(refund.clone());
+
+            match response.status.as_str() {
+                "processed" | "succeeded" => complete_refund(&refund.id, now),
+                "failed" | "canceled" => fail_refund(&refund.id, format!("Gateway reported {}", response.status), now),
+                _ => Ok(refund),
+            }
+        }
+        Err(error) => {
+            fail_refund(&refund.id, error.clone(), now)?;
+            Err(format!("Refund failed: {}", error))
+        }
+    }
+}
+
+/// Settle a refund: prorate the subscription, issue the credit note and
+/// mark the payment Reversed once it is fully refunded. Safe to call twice.
+pub fn complete_refund(refund_id: &str, now: u64) -> Result<RefundRecord, String> {
+    let mut refund = get_refund(refund_id)
+        .ok_or_else(|| format!("Refund not found: {}", refund_id))?;
+
+    match refund.status {
+        RefundStatus::Processed => return Ok(refund),
+        RefundStatus::Failed => return Err(format!("Refund {} has failed", refund_id)),
+        RefundStatus::Pending => {}
+    }
+
+    let payment_key = StableString::from(refund.payment_reference.clone());
+    let mut payment = PAYMENT_RECORDS
+        .with(|records| records.borrow().get(&payment_key))
+        .ok_or_else(|| format!("Payment record not found for reference: {}", refund.payment_reference))?;
+
+    let original_invoice_id = format!("INV-{}", payment.id);
+    let original_invoice = INVOICES.with(|invoices| {
+        invoices.borrow().get(&StableString::from(original_invoice_id.clone()))
+    });
+
+    let processed_before: u64 = get_payment_refunds(&refund.payment_reference)
+        .iter()
+        .filter(|r| r.status == RefundStatus::Processed)
+        .map(|r| r.amount)
+        .sum();
+    let fully_refunded = processed_before + refund.amount >= payment.amount;
+
+    // Take back the refunded share of the period, but never time already used
+    let (credit_start, credit_end) = match &original_invoice {
+        Some(invoice) => {
+            let period = invoice.billing_period_end.saturating_sub(invoice.billing_period_start);
+            let unused = invoice.billing_period_end.saturating_sub(now.max(invoice.billing_period_start));
+            let share = if fully_refunded {
+                unused
+            } else {
+                let prorated = (period as u128 * refund.amount as u128 / payment.amount.max(1) as u128) as u64;
+                prorated.min(unused)
+            };
+
+            shorten_subscription(&payment.user_id, share, fully_refunded, now);
+            (invoice.billing_period_end - share, invoice.billing_period_end)
+        }
+        None => (now, now),
+    };
+
+    // Credit note linked to the original invoice
+    let credit_note_id = format!("CN-{}", refund.id);
+    let description = match &original_invoice {
+        Some(invoice) => format!("Refund for invoice {}", invoice.invoice_number),
+        None => format!("Refund for payment {}", payment.paystack_reference),
+    };
+    let credit_note = Invoice {
+        id: credit_note_id.clone(),
+        user_id: payment.user_id.clone(),
+        payment_id: payment.id.clone(),
+        amount: refund.amount,
+        currency: refund.currency.clone(),
+        billing_period_start: credit_start,
+        billing_period_end: credit_end,
+        paid: true,
+        created_at: now,
+        kind: InvoiceKind::CreditNote,
+        original_invoice_id: original_invoice.map(|invoice| invoice.id),
+        line_items: vec![InvoiceLineItem {
+            description,
+            quantity: 1,
+            unit_amount: refund.amount,
+            amount: refund.amount,
+        }],
+        ..Default::default()
+    };
+    issue_invoice(credit_note, &payment, now);
+
+    if fully_refunded {
+        // A refunded seat purchase takes the seats back too
+        cancel_sponsorship(&payment.paystack_reference, now);
+        payment.status = PaymentStatus::Reversed;
+        payment.updated_at = now;
+        PAYMENT_RECORDS.with(|records| {
+            records.borrow_mut().insert(payment_key, payment);
+        });
+    }
+
+    refund.status = RefundStatus::Processed;
+    refund.credit_note_id = Some(credit_note_id);
+    refund.error = None;
+    refund.updated_at = now;
+    store_refund(refund.clone());
+
+    Ok(refund)
+}
+
+/// Mark a refund as failed, releasing its amount for another attempt
+pub fn fail_refund(refund_id: &str, error: String, now: u64) -> Result<RefundRecord, String> {
+    let mut refund = get_refund(refund_id)
+        .ok_or_else(|| format!("Refund not found: {}", refund_id))?;
+
+    if refund.status == RefundStatus::Processed {
+        return Err(format!("Refund {} is already processed", refund_id));
+    }
+
+    refund.status = RefundStatus::Failed;
+    refund.error = Some(error);
+    refund.updated_at = now;
+    store_refund(refund.clone());
+
+    Ok(refund)
+}
+
+/// Amount refunded or being refunded (failed refunds do not count)
+pub fn refunded_amount(reference: &str) -> u64 {
+    get_payment_refunds(reference)
+        .iter()
+        .filter(|r| r.status != RefundStatus::Failed)
+        .map(|r| r.amount)
+        .sum()
+}
+
+pub fn get_refund(refund_id: &str) -> Option<RefundRecord> {
+    REFUNDS.with(|refunds| refunds.borrow().get(&StableString::from(refund_id.to_string())))
+}
+
+/// All refunds of a payment, oldest first
+pub fn get_payment_refunds(reference: &str) -> Vec<RefundRecord> {
+    let mut refunds = REFUNDS.with(|refunds| {
+        refunds
+            .borrow()
+            .iter()
+            .filter(|(_, refund)| refund.payment_reference == reference)
+            .map(|(_, refund)| refund)
+            .collect::<Vec<_>>()
+    });
+    refunds.sort_by_key(|refund| refund.created_at);
+    refunds
+}
+
+/// Find the refund a gateway webhook is about: by the gateway's refund id,
+/// otherwise the oldest pending refund of that amount on the payment
+pub fn find_refund(provider_refund_id: Option<&str>, payment_reference: Option<&str>, amount: Option<u64>) -> Option<RefundRecord> {
+    if let Some(provider_refund_id) = provider_refund_id {
+        let found = REFUNDS.with(|refunds| {
+            refunds
+                .borrow()
+                .iter()
+                .find(|(_, refund)| refund.provider_refund_id.as_deref() == Some(provider_refund_id))
+                .map(|(_, refund)| refund)
+        });
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    get_payment_refunds(payment_reference?)
+        .into_iter()
+        .find(|refund| {
+            refund.status == RefundStatus::Pending
+                && match amount {
+                    Some(amount) => refund.amount == amount,
+                    None => true,
+                }
+        })
+}
+
+fn store_refund(refund: RefundRecord) {
+    REFUNDS.with(|refunds| {
+        refunds.borrow_mut().insert(StableString::from(refund.id.clone()), refund);
+    });
+}
@@ -0,0 +1,205 @@
+// backend/src/services/schema_migration_service.rs
+//
+// Schema changes to the stable maps, applied in place on upgrade. The schema
+// version last applied is kept in SCHEMA_VERSION; each upgrade runs the steps
+// in MIGRATIONS above it, in order, recording each one as it completes.
+// Versions up to LAST_SNAPSHOT_VERSION are the snapshot layouts in migrations.rs,
+// so a canister without a stored version is at that version.
+//
+// Adding a schema change: append a step with the next version. A step changes
+// individual maps, e.g. giving every Startup a value for a new field or
+// re-keying USER_DAILY_USAGE with rewrite_entries, and must report what it
+// would change without writing anything when called with `dry_run`. A step
+// that fails stops the upgrade before its version is recorded.
+
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+use candid::CandidType;
+use ic_cdk::query;
+use ic_stable_structures::{StableBTreeMap, Storable};
+use serde::Deserialize;
+
+use crate::migrations::LAST_SNAPSHOT_VERSION;
+use crate::models::admin::Permission;
+use crate::services::accelerator_service::move_inline_logos;
+use crate::services::admin::{caller_is_admin, envelope_admin_records, require_permission};
+use crate::services::api_service::move_long_responses;
+use crate::services::identity_merge_service::{merge_linked_identities, pending_merge_records};
+use crate::services::platform_identity_service::{migrate_legacy_main_site_users, pending_legacy_main_site_users};
+use crate::storage::memory::{Memory, SCHEMA_VERSION};
+
+const SCHEMA_VERSION_KEY: u8 = 0;
+
+/// One schema change. `run` returns how many records it changed, or would
+/// change when `dry_run` is set.
+pub struct MigrationStep {
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(dry_run: bool) -> Result<u32, String>,
+}
+
+/// Every schema change since the last snapshot layout, oldest first
+pub const MIGRATIONS: &[MigrationStep] = &[
+    MigrationStep {
+        version: 20,
+        description: "Move users left in the retired MAIN_SITE_USERS map into the platform identity registry",
+        run: |dry_run| Ok(if dry_run { pending_legacy_main_site_users() } else { migrate_legacy_main_site_users() }),
+    },
+    MigrationStep {
+        version: 21,
+        description: "Merge records of platform accounts linked before linking moved them to the site principal",
+        run: |dry_run| Ok(if dry_run { pending_merge_records() } else { merge_linked_identities() }),
+    },
+    MigrationStep {
+        version: 22,
+        description: "Move accelerator logos and long API responses out of their records into the blob store",
+        run: |dry_run| Ok(move_inline_logos(dry_run)? + move_long_responses(dry_run)?),
+    },
+    MigrationStep {
+        version: 23,
+        description: "Store admins as enveloped records instead of their own byte layout",
+        run: |dry_run| Ok(envelope_admin_records(dry_run)),
+    },
+];
+
+/// What one step did, or would do
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct MigrationStepReport {
+    pub version: u32,
+    pub description: String,
+    pub records: u32,
+}
+
+/// What an upgrade from `from_version` would do to the current maps
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SchemaMigrationPlan {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub steps: Vec<MigrationStepReport>,
+}
+
+/* ============================
+   Version header
+   ============================ */
+
+/// The schema version this release migrates to
+pub fn latest_schema_version() -> u32 {
+    MIGRATIONS.last().map_or(LAST_SNAPSHOT_VERSION, |step| step.version)
+}
+
+/// The schema version the maps are at. Canisters from before the header was
+/// stored are at the last snapshot layout.
+pub fn schema_version() -> u32 {
+    SCHEMA_VERSION.with(|v| v.borrow().get(&SCHEMA_VERSION_KEY)).unwrap_or(LAST_SNAPSHOT_VERSION)
+}
+
+pub fn set_schema_version(version: u32) {
+    SCHEMA_VERSION.with(|v| v.borrow_mut().insert(SCHEMA_VERSION_KEY, version));
+}
+
+/* ============================
+   Running steps
+   ============================ */
+
+/// Runs the steps above `from_version` in order, storing each step's version
+/// once it has run unless `dry_run` is set. Stops at the first step that fails,
+/// leaving the version at the last step that completed.
+pub fn run_migrations(steps: &[MigrationStep], from_version: u32, dry_run: bool) -> Result<Vec<MigrationStepReport>, String> {
+    if steps.windows(2).any(|pair| pair[0].version >= pair[1].version) {
+        return Err("Schema migration steps must have strictly increasing versions".to_string());
+    }
+
+    let mut reports = Vec::new();
+    for step in steps.iter().filter(|step| step.version > from_version) {
+        let records = (step.run)(dry_run).map_err(|e| format!("Step {} ({}) failed: {}", step.version, step.description, e))?;
+        if !dry_run {
+            set_schema_version(step.version);
+        }
+        reports.push(MigrationStepReport {
+            version: step.version,
+            description: step.description.to_string(),
+            records,
+        });
+    }
+    Ok(reports)
+}
+
+/// Brings the maps up to the latest schema version. Refuses maps written by a
+/// newer release, whose changes this one cannot know about.
+pub fn migrate_schema() -> Result<Vec<MigrationStepReport>, String> {
+    let current = schema_version();
+    let latest = latest_schema_version();
+    if current > latest {
+        return Err(format!(
+            "Stable memory is at schema version {}, newer than this release's {}",
+            current, latest
+        ));
+    }
+
+    let reports = run_migrations(MIGRATIONS, current, false)?;
+    set_schema_version(latest);
+    Ok(reports)
+}
+
+/// What an upgrade from `from_version` to this release would do, without
+/// changing anything
+pub fn plan_schema_migrations(from_version: u32) -> Result<SchemaMigrationPlan, String> {
+    Ok(SchemaMigrationPlan {
+        from_version,
+        to_version: latest_schema_version().max(from_version),
+        steps: run_migrations(MIGRATIONS, from_version, true)?,
+    })
+}
+
+/// Rewrites the entries of a map that `rewrite` returns a replacement for,
+/// possibly under a new key. Returns how many entries changed; with `dry_run`
+/// nothing is written.
+pub fn rewrite_entries<K, V>(
+    map: &'static LocalKey<RefCell<StableBTreeMap<K, V, Memory>>>,
+    dry_run: bool,
+    mut rewrite: impl FnMut(&K, &V) -> Option<(K, V)>,
+) -> u32
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+{
+    let changes: Vec<(K, (K, V))> = map.with(|map| {
+        map.borrow()
+            .iter()
+            .filter_map(|(key, value)| rewrite(&key, &value).map(|replacement| (key, replacement)))
+            .collect()
+    });
+    let count = changes.len() as u32;
+    if !dry_run {
+        map.with(|map| {
+            let mut map = map.borrow_mut();
+            // Remove first, so a rewritten key can take the place of another
+            for (key, _) in &changes {
+                map.remove(key);
+            }
+            for (_, (key, value)) in changes {
+                map.insert(key, value);
+            }
+        });
+    }
+    count
+}
+
+/* ============================
+   Endpoints
+   ============================ */
+
+/// Dry run: what upgrading this canister to the running release from
+/// `from_version` (by default the version it is at) does to its current maps
+#[query(guard = "caller_is_admin")]
+pub fn admin_preview_schema_migrations(from_version: Option<u32>) -> Result<SchemaMigrationPlan, String> {
+    require_permission(Permission::ViewAdmins)?;
+    plan_schema_migrations(from_version.unwrap_or_else(schema_version))
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_schema_version() -> Result<u32, String> {
+    require_permission(Permission::ViewAdmins)?;
+    Ok(schema_version())
+}
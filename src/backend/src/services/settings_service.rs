@@ -1,6 +1,11 @@
 use crate::models::stable_principal::StablePrincipal;
 use crate::models::user::{User, SubscriptionTier};
-use crate::storage::memory::{USERS, OPENCHAT_USERS, SLACK_USERS, DISCORD_USERS};
+use crate::models::platform::Platform;
+use crate::services::platform_identity_service::linked_external_id;
+use crate::storage::memory::USERS;
+use crate::services::auth::caller_is_authenticated;
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
 use candid::Principal;
 use ic_cdk::{query, update, api::time};
 
@@ -17,29 +22,11 @@ fn ensure_user_exists(caller_principal: Principal) -> Result<User, String> {
     ic_cdk::println!("User not found, creating new user for principal: {:?}", caller_principal);
     
     // Try to find platform-specific user data
-    let openchat_id = OPENCHAT_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller_principal))
-            .map(|(id, _)| id.as_str().to_string())
-    });
+    let openchat_id = linked_external_id(caller_principal, Platform::OpenChat);
     
-    let slack_id = SLACK_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller_principal))
-            .map(|(id, _)| id.as_str().to_string())
-    });
+    let slack_id = linked_external_id(caller_principal, Platform::Slack);
     
-    let discord_id = DISCORD_USERS.with(|users| {
-        users
-            .borrow()
-            .iter()
-            .find(|(_, user)| user.site_principal.as_ref().map(|p| p.get()) == Some(caller_principal))
-            .map(|(id, _)| id.as_str().to_string())
-    });
+    let discord_id = linked_external_id(caller_principal, Platform::Discord);
     
     // Determine default name based on platform
     let default_name = if slack_id.is_some() {
@@ -48,6 +35,10 @@ fn ensure_user_exists(caller_principal: Principal) -> Result<User, String> {
         format!("Discord User {}", discord_id.as_ref().unwrap())
     } else if openchat_id.is_some() {
         format!("OpenChat User {}", openchat_id.as_ref().unwrap())
+    } else if let Some(telegram_id) = linked_external_id(caller_principal, Platform::Telegram) {
+        format!("Telegram User {}", telegram_id)
+    } else if let Some(whatsapp_id) = linked_external_id(caller_principal, Platform::WhatsApp) {
+        format!("WhatsApp User {}", whatsapp_id)
     } else {
         "User".to_string()
     };
@@ -73,7 +64,7 @@ fn ensure_user_exists(caller_principal: Principal) -> Result<User, String> {
 }
 
 /// Update user display name
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_display_name(new_name: String) -> Result<(), String> {
     let caller_principal = ic_cdk::caller();
     let stable_principal = StablePrincipal::from(caller_principal);
@@ -98,7 +89,9 @@ pub fn update_display_name(new_name: String) -> Result<(), String> {
         
         if let Some(mut user) = users.get(&stable_principal) {
             let old_name = user.name.clone();
+            let before = digest(&user);
             user.name = new_name.trim().to_string();
+            audit(AuditAction::ProfileUpdated, caller_principal.to_text(), Some(before), Some(digest(&user)));
             users.insert(stable_principal, user);
             
             ic_cdk::println!("Successfully updated display name from '{}' to '{}'", old_name, new_name.trim());
@@ -111,7 +104,7 @@ pub fn update_display_name(new_name: String) -> Result<(), String> {
 }
 
 /// Get current user's display name
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_display_name() -> Result<String, String> {
     let caller_principal = ic_cdk::caller();
     let stable_principal = StablePrincipal::from(caller_principal);
@@ -131,7 +124,7 @@ pub fn get_display_name() -> Result<String, String> {
 }
 
 /// Get current user's profile information
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_user_profile() -> Result<crate::models::user::User, String> {
     let caller_principal = ic_cdk::caller();
     let stable_principal = StablePrincipal::from(caller_principal);
@@ -151,7 +144,7 @@ pub fn get_user_profile() -> Result<crate::models::user::User, String> {
 }
 
 /// Update user email
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_email(new_email: String) -> Result<(), String> {
     let caller_principal = ic_cdk::caller();
     let stable_principal = StablePrincipal::from(caller_principal);
@@ -176,7 +169,9 @@ pub fn update_email(new_email: String) -> Result<(), String> {
         
         if let Some(mut user) = users.get(&stable_principal) {
             let old_email = user.email.clone();
+            let before = digest(&user);
             user.email = Some(new_email.trim().to_string());
+            audit(AuditAction::ProfileUpdated, caller_principal.to_text(), Some(before), Some(digest(&user)));
             users.insert(stable_principal, user);
             
             ic_cdk::println!("Successfully updated email from '{:?}' to '{}'", old_email, new_email.trim());
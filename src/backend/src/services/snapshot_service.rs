@@ -0,0 +1,523 @@
+// backend/src/services/snapshot_service.rs
+//
+// Off-canister backups. Every stable map can be exported page by page as the
+// bytes its keys and values are stored as, and a fresh canister rebuilt by
+// importing those pages in order. Values keep their record envelope and
+// pages carry the schema version they were exported at, so a snapshot restores
+// into a release at that same version; upgrade after restoring.
+//
+// Writes are not paused while a snapshot is exported. Each page is read in one
+// call, but a map written to between two of its pages, or between two maps,
+// comes out as of different moments. Maps that refer to each other are checked
+// on import: PLATFORM_IDENTITIES_BY_PRINCIPAL has to index exactly the
+// imported PLATFORM_IDENTITIES, and every SUBSCRIPTION_BILLING payment
+// reference has to be in the imported PAYMENT_RECORDS. Take backups while the
+// canister is quiet, and export again if an import is refused.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::ops::Bound::{self, Excluded, Unbounded};
+use std::thread::LocalKey;
+
+use candid::{CandidType, Principal};
+use ic_cdk::{query, update};
+use ic_stable_structures::{StableBTreeMap, Storable};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::models::admin::{Admin, AdminRole, Permission};
+use crate::models::audit_log::AuditAction;
+use crate::models::record::{decode_record, VersionedRecord};
+use crate::models::stable_principal::StablePrincipal;
+use crate::models::stable_string::StableString;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::models::subscription_billing::SubscriptionBilling;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::services::audit_service::audit;
+use crate::services::schema_migration_service::{latest_schema_version, schema_version};
+use crate::storage::memory::*;
+use crate::storage::record_map::RecordMap;
+
+/// Layout of a SnapshotPage and its checksums; raised when either changes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 2;
+
+/// Limits on one page, so it fits in a query response and an update request
+pub const MAX_SNAPSHOT_PAGE_ENTRIES: usize = 500;
+pub const MAX_SNAPSHOT_PAGE_BYTES: usize = 1024 * 1024;
+
+/// One key and value, as stored
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotEntry {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+/// Where the next page of a map starts: after `key`, continuing the
+/// `map_checksum` of the pages before it
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotCursor {
+    pub key: Vec<u8>,
+    pub map_checksum: String,
+}
+
+/// A run of consecutive entries of one map. `cursor` is None for the first
+/// page and `next_cursor` None for the last. `map_checksum` covers the entries
+/// of this page and every page before it, so on the last page it covers the map.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotPage {
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub map: String,
+    pub cursor: Option<SnapshotCursor>,
+    pub entries: Vec<SnapshotEntry>,
+    pub next_cursor: Option<SnapshotCursor>,
+    pub map_checksum: String,
+    pub checksum: String,
+}
+
+type Export = fn(Option<&[u8]>) -> (Vec<SnapshotEntry>, Option<Vec<u8>>);
+type Restore = fn() -> u64;
+
+macro_rules! snapshot_map {
+    ($name:literal, $map:ident) => {
+        (
+            $name,
+            (|cursor| export_records(&$map, cursor)) as Export,
+            (|| restore_records(&$map)) as Restore,
+        )
+    };
+    (plain, $name:literal, $map:ident) => {
+        (
+            $name,
+            (|cursor| export_map(&$map, cursor)) as Export,
+            (|| restore_map(&$map)) as Restore,
+        )
+    };
+}
+
+/// Every map in `storage::memory` but SNAPSHOT_STAGING, in the order a
+/// restore imports them. AUDIT_LOG comes next to last so it ends up as
+/// exported, apart from the entries for importing it and ADMINS. ADMINS comes
+/// last so the importing admin keeps their role until every other map is in.
+const MAPS: &[(&str, Export, Restore)] = &[
+    snapshot_map!("USERS", USERS),
+    snapshot_map!("WAITLIST", WAITLIST),
+    snapshot_map!("CHAT_HISTORY", CHAT_HISTORY),
+    snapshot_map!("CONNECTED_ACCOUNTS", CONNECTED_ACCOUNTS),
+    snapshot_map!("TASKS", TASKS),
+    snapshot_map!("DASHBOARD_TOKENS", DASHBOARD_TOKENS),
+    snapshot_map!("GITHUB_ISSUES", GITHUB_ISSUES),
+    snapshot_map!("ACCELERATORS", ACCELERATORS),
+    snapshot_map!("STARTUPS", STARTUPS),
+    snapshot_map!("STARTUP_STATUSES", STARTUP_STATUSES),
+    snapshot_map!("STARTUP_COHORTS", STARTUP_COHORTS),
+    snapshot_map!("STARTUP_ACTIVITIES", STARTUP_ACTIVITIES),
+    snapshot_map!("STARTUP_INVITES", STARTUP_INVITES),
+    snapshot_map!("API_MESSAGES", API_MESSAGES),
+    snapshot_map!(plain, "USER_DAILY_USAGE", USER_DAILY_USAGE),
+    snapshot_map!("USER_SUBSCRIPTIONS", USER_SUBSCRIPTIONS),
+    snapshot_map!("PAYMENT_RECORDS", PAYMENT_RECORDS),
+    snapshot_map!("INVOICES", INVOICES),
+    snapshot_map!("USER_ANALYTICS", USER_ANALYTICS),
+    snapshot_map!("LEGACY_MAIN_SITE_USERS", LEGACY_MAIN_SITE_USERS),
+    snapshot_map!("SUBSCRIPTION_BILLING", SUBSCRIPTION_BILLING),
+    snapshot_map!("BILLING_SETTINGS", BILLING_SETTINGS),
+    snapshot_map!("REFUNDS", REFUNDS),
+    snapshot_map!("PLANS", PLANS),
+    snapshot_map!("COUPONS", COUPONS),
+    snapshot_map!("COUPON_REDEMPTIONS", COUPON_REDEMPTIONS),
+    snapshot_map!("SEAT_SPONSORSHIPS", SEAT_SPONSORSHIPS),
+    snapshot_map!("SEAT_ASSIGNMENTS", SEAT_ASSIGNMENTS),
+    snapshot_map!("WEBHOOK_EVENTS", WEBHOOK_EVENTS),
+    snapshot_map!(plain, "INVOICE_SEQUENCES", INVOICE_SEQUENCES),
+    snapshot_map!("INVOICE_SETTINGS", INVOICE_SETTINGS),
+    snapshot_map!("BOT_PRINCIPALS", BOT_PRINCIPALS),
+    snapshot_map!("AUDIT_ARCHIVE", AUDIT_ARCHIVE),
+    snapshot_map!("PLATFORM_IDENTITIES", PLATFORM_IDENTITIES),
+    snapshot_map!(plain, "PLATFORM_IDENTITIES_BY_PRINCIPAL", PLATFORM_IDENTITIES_BY_PRINCIPAL),
+    snapshot_map!("MERGED_RECORDS", MERGED_RECORDS),
+    snapshot_map!("QUOTA_POLICIES", QUOTA_POLICIES),
+    snapshot_map!("QUOTA_BUCKETS", QUOTA_BUCKETS),
+    snapshot_map!(plain, "SCHEMA_VERSION", SCHEMA_VERSION),
+    snapshot_map!("QUARANTINED_RECORDS", QUARANTINED_RECORDS),
+    snapshot_map!(plain, "BLOB_CHUNKS", BLOB_CHUNKS),
+    snapshot_map!("AUDIT_LOG", AUDIT_LOG),
+    snapshot_map!("ADMINS", ADMINS),
+];
+
+/// The map being restored and where its next page has to start
+struct StagedImport {
+    map: &'static str,
+    next_cursor: SnapshotCursor,
+}
+
+thread_local! {
+    // Heap only: after an upgrade, a map being restored starts again from its first page
+    static STAGED_IMPORT: RefCell<Option<StagedImport>> = const { RefCell::new(None) };
+}
+
+/// Names of the maps a snapshot covers, in import order
+pub fn snapshot_maps() -> Vec<&'static str> {
+    MAPS.iter().map(|(name, ..)| *name).collect()
+}
+
+fn find_map(map: &str) -> Result<&'static (&'static str, Export, Restore), String> {
+    MAPS.iter().find(|(name, ..)| *name == map).ok_or_else(|| format!("Unknown map: {}", map))
+}
+
+fn field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+fn entries(hasher: &mut Sha256, entries: &[SnapshotEntry]) {
+    hasher.update((entries.len() as u64).to_le_bytes());
+    for entry in entries {
+        field(hasher, &entry.key);
+        field(hasher, &entry.value);
+    }
+}
+
+/// SHA-256 hex over every field of the page but the checksum, each length-prefixed
+pub fn snapshot_checksum(page: &SnapshotPage) -> String {
+    fn cursor(hasher: &mut Sha256, cursor: Option<&SnapshotCursor>) {
+        match cursor {
+            Some(cursor) => {
+                hasher.update([1]);
+                field(hasher, &cursor.key);
+                field(hasher, cursor.map_checksum.as_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(page.format_version.to_le_bytes());
+    hasher.update(page.schema_version.to_le_bytes());
+    field(&mut hasher, page.map.as_bytes());
+    cursor(&mut hasher, page.cursor.as_ref());
+    entries(&mut hasher, &page.entries);
+    cursor(&mut hasher, page.next_cursor.as_ref());
+    field(&mut hasher, page.map_checksum.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// SHA-256 hex chaining the checksum of the pages before with a page's entries;
+/// the first page continues from an empty checksum
+pub fn map_checksum(previous: &str, page_entries: &[SnapshotEntry]) -> String {
+    let mut hasher = Sha256::new();
+    field(&mut hasher, previous.as_bytes());
+    entries(&mut hasher, page_entries);
+    hex::encode(hasher.finalize())
+}
+
+/// The page of `map` following `cursor`, or its first page
+pub fn export_snapshot_page(map: &str, cursor: Option<SnapshotCursor>) -> Result<SnapshotPage, String> {
+    let (name, export, _) = find_map(map)?;
+    let (entries, next_key) = export(cursor.as_ref().map(|cursor| cursor.key.as_slice()));
+    let previous = cursor.as_ref().map_or("", |cursor| cursor.map_checksum.as_str());
+    let map_checksum = map_checksum(previous, &entries);
+    let mut page = SnapshotPage {
+        format_version: SNAPSHOT_FORMAT_VERSION,
+        schema_version: schema_version(),
+        map: name.to_string(),
+        cursor,
+        entries,
+        next_cursor: next_key.map(|key| SnapshotCursor { key, map_checksum: map_checksum.clone() }),
+        map_checksum,
+        checksum: String::new(),
+    };
+    page.checksum = snapshot_checksum(&page);
+    Ok(page)
+}
+
+/// Rejects a page this release cannot import, before anything is written
+pub fn check_snapshot_page(page: &SnapshotPage) -> Result<(), String> {
+    if page.format_version != SNAPSHOT_FORMAT_VERSION {
+        return Err(format!(
+            "Snapshot format {} is not supported, expected {}",
+            page.format_version, SNAPSHOT_FORMAT_VERSION
+        ));
+    }
+    if page.checksum != snapshot_checksum(page) {
+        return Err(format!("Checksum mismatch in a page of {}", page.map));
+    }
+    // Pages are imported as stored, so they have to be at the schema this release writes
+    if page.schema_version != latest_schema_version() {
+        return Err(format!(
+            "Snapshot is at schema version {}, this release at {}; restore it with the release it was exported from, then upgrade",
+            page.schema_version,
+            latest_schema_version()
+        ));
+    }
+    find_map(&page.map).map(|_| ())
+}
+
+/// Stages an exported page of a map. The first page starts the map over and
+/// every later one has to follow the page staged before it. The map itself is
+/// only replaced by its last page, once the checksum over all of its pages
+/// matches and the staged entries agree with the maps imported before it.
+/// Returns the number of entries of the map imported so far.
+pub fn import_snapshot_page(caller: Principal, page: &SnapshotPage) -> Result<u64, String> {
+    check_snapshot_page(page)?;
+    let (name, _, restore) = find_map(&page.map)?;
+    let previous = match &page.cursor {
+        None => {
+            SNAPSHOT_STAGING.with(|staging| staging.borrow_mut().clear_new());
+            String::new()
+        }
+        Some(cursor) => {
+            let follows = STAGED_IMPORT.with(|staged| {
+                staged.borrow().as_ref().is_some_and(|staged| staged.map == *name && staged.next_cursor == *cursor)
+            });
+            if !follows {
+                return Err(format!(
+                    "Page of {} does not follow the last page imported; import its pages in order, starting from the first",
+                    name
+                ));
+            }
+            cursor.map_checksum.clone()
+        }
+    };
+    let map_checksum = map_checksum(&previous, &page.entries);
+
+    let staged = SNAPSHOT_STAGING.with(|staging| {
+        let mut staging = staging.borrow_mut();
+        for entry in &page.entries {
+            staging.insert(entry.key.clone(), entry.value.clone());
+        }
+        staging.len()
+    });
+    let Some(next_cursor) = &page.next_cursor else {
+        STAGED_IMPORT.with(|staged| *staged.borrow_mut() = None);
+        if map_checksum != page.map_checksum {
+            SNAPSHOT_STAGING.with(|staging| staging.borrow_mut().clear_new());
+            return Err(format!("Checksum mismatch across the pages of {}; nothing was imported", name));
+        }
+        if let Err(error) = check_staged(name, caller) {
+            SNAPSHOT_STAGING.with(|staging| staging.borrow_mut().clear_new());
+            return Err(format!("{}; nothing was imported", error));
+        }
+        let len = restore();
+        SNAPSHOT_STAGING.with(|staging| staging.borrow_mut().clear_new());
+        return Ok(len);
+    };
+    let next_cursor = SnapshotCursor { key: next_cursor.key.clone(), map_checksum };
+    STAGED_IMPORT.with(|staged| *staged.borrow_mut() = Some(StagedImport { map: name, next_cursor }));
+    Ok(staged)
+}
+
+/// Checks a fully staged map against what it has to agree with before it
+/// replaces the live one
+fn check_staged(map: &str, caller: Principal) -> Result<(), String> {
+    match map {
+        "ADMINS" => check_staged_admins(caller),
+        "PLATFORM_IDENTITIES_BY_PRINCIPAL" => check_staged_identity_index(),
+        "SUBSCRIPTION_BILLING" => check_staged_billing(),
+        _ => Ok(()),
+    }
+}
+
+// Unlike other records, every admin has to decode: one that doesn't would
+// lock its holder out. The importer has to keep the role that lets them
+// manage admins, which also leaves the canister a SuperAdmin.
+fn check_staged_admins(caller: Principal) -> Result<(), String> {
+    let mut caller_role = None;
+    let mut super_admins = 0;
+    SNAPSHOT_STAGING.with(|staging| {
+        for (key, value) in staging.borrow().iter() {
+            let principal = candid::decode_one::<Principal>(&key).map_err(|e| format!("Unreadable admin key: {}", e))?;
+            let admin = decode_record::<Admin>(&value).map_err(|e| format!("Unreadable admin {}: {}", principal, e))?;
+            if admin.role == AdminRole::SuperAdmin {
+                super_admins += 1;
+            }
+            if principal == caller {
+                caller_role = Some(admin.role);
+            }
+        }
+        Ok::<(), String>(())
+    })?;
+    if super_admins == 0 {
+        return Err("The imported ADMINS have no SuperAdmin".to_string());
+    }
+    if !caller_role.is_some_and(|role| role.allows(Permission::ManageAdmins)) {
+        return Err("The imported ADMINS would take ManageAdmins from the importing admin; import as an admin the snapshot keeps".to_string());
+    }
+    Ok(())
+}
+
+// Identities that don't decode are left out on both sides, since their
+// principal can't be read
+fn check_staged_identity_index() -> Result<(), String> {
+    let (expected, undecodable) = PLATFORM_IDENTITIES.with(|identities| {
+        let mut expected = BTreeSet::new();
+        let mut undecodable = BTreeSet::new();
+        for (key, bytes) in identities.borrow().raw_iter() {
+            match decode_record::<PlatformIdentity>(&bytes) {
+                Ok(identity) => expected.extend(identity.site_principal.map(|principal| (StablePrincipal::new(principal), key))),
+                Err(_) => {
+                    undecodable.insert(key);
+                }
+            }
+        }
+        (expected, undecodable)
+    });
+    let staged: BTreeSet<(StablePrincipal, IdentityKey)> = SNAPSHOT_STAGING.with(|staging| {
+        staging
+            .borrow()
+            .iter()
+            .map(|(key, _)| <(StablePrincipal, IdentityKey)>::from_bytes(key.into()))
+            .filter(|(_, identity_key)| !undecodable.contains(identity_key))
+            .collect()
+    });
+    if staged != expected {
+        return Err(format!(
+            "PLATFORM_IDENTITIES_BY_PRINCIPAL does not index the imported PLATFORM_IDENTITIES ({} entries against {} linked identities); export both again",
+            staged.len(),
+            expected.len()
+        ));
+    }
+    Ok(())
+}
+
+fn check_staged_billing() -> Result<(), String> {
+    let missing = SNAPSHOT_STAGING.with(|staging| {
+        staging
+            .borrow()
+            .iter()
+            .filter_map(|(_, value)| decode_record::<SubscriptionBilling>(&value).ok())
+            .filter_map(|billing| billing.last_payment_reference)
+            .filter(|reference| !PAYMENT_RECORDS.with(|payments| payments.borrow().contains_key(&StableString::from(reference.as_str()))))
+            .count()
+    });
+    if missing > 0 {
+        return Err(format!(
+            "{} SUBSCRIPTION_BILLING records refer to payments missing from the imported PAYMENT_RECORDS; export both again",
+            missing
+        ));
+    }
+    Ok(())
+}
+
+fn start_after<K: Storable>(cursor: Option<&[u8]>) -> (Bound<K>, Bound<K>) {
+    match cursor {
+        Some(cursor) => (Excluded(K::from_bytes(cursor.into())), Unbounded),
+        None => (Unbounded, Unbounded),
+    }
+}
+
+/// Takes entries until a page is full; the cursor is set when any are left
+fn take_page(entries: impl Iterator<Item = SnapshotEntry>) -> (Vec<SnapshotEntry>, Option<Vec<u8>>) {
+    let mut page = Vec::new();
+    let mut bytes = 0;
+    let mut rest = entries.peekable();
+    while let Some(entry) = rest.peek() {
+        let size = entry.key.len() + entry.value.len();
+        if page.len() == MAX_SNAPSHOT_PAGE_ENTRIES || (!page.is_empty() && bytes + size > MAX_SNAPSHOT_PAGE_BYTES) {
+            break;
+        }
+        bytes += size;
+        page.extend(rest.next());
+    }
+    let next_cursor = match rest.peek() {
+        Some(_) => page.last().map(|entry: &SnapshotEntry| entry.key.clone()),
+        None => None,
+    };
+    (page, next_cursor)
+}
+
+// Records leave and return as stored, without being decoded
+fn export_records<K, V>(
+    map: &'static LocalKey<RefCell<RecordMap<K, V>>>,
+    cursor: Option<&[u8]>,
+) -> (Vec<SnapshotEntry>, Option<Vec<u8>>)
+where
+    K: Storable + Ord + Clone,
+    V: VersionedRecord,
+{
+    map.with(|map| {
+        let map = map.borrow();
+        take_page(map.raw_range(start_after::<K>(cursor)).map(|(key, value)| SnapshotEntry { key: key.to_bytes().into_owned(), value }))
+    })
+}
+
+fn restore_records<K, V>(map: &'static LocalKey<RefCell<RecordMap<K, V>>>) -> u64
+where
+    K: Storable + Ord + Clone,
+    V: VersionedRecord,
+{
+    map.with(|map| {
+        let mut map = map.borrow_mut();
+        map.clear_new();
+        SNAPSHOT_STAGING.with(|staging| {
+            for (key, value) in staging.borrow().iter() {
+                map.insert_raw(K::from_bytes(key.into()), value);
+            }
+        });
+        map.len()
+    })
+}
+
+// Maps of plain values store exactly what `Storable` encodes
+fn export_map<K, V>(
+    map: &'static LocalKey<RefCell<StableBTreeMap<K, V, Memory>>>,
+    cursor: Option<&[u8]>,
+) -> (Vec<SnapshotEntry>, Option<Vec<u8>>)
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+{
+    map.with(|map| {
+        let map = map.borrow();
+        take_page(map.range(start_after::<K>(cursor)).map(|(key, value)| SnapshotEntry {
+            key: key.to_bytes().into_owned(),
+            value: value.to_bytes().into_owned(),
+        }))
+    })
+}
+
+fn restore_map<K, V>(map: &'static LocalKey<RefCell<StableBTreeMap<K, V, Memory>>>) -> u64
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+{
+    map.with(|map| {
+        let mut map = map.borrow_mut();
+        map.clear_new();
+        SNAPSHOT_STAGING.with(|staging| {
+            for (key, value) in staging.borrow().iter() {
+                map.insert(K::from_bytes(key.into()), V::from_bytes(value.into()));
+            }
+        });
+        map.len()
+    })
+}
+
+/* ============================
+   Endpoints
+   ============================ */
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_list_snapshot_maps() -> Result<Vec<String>, String> {
+    require_permission(Permission::ManageAdmins)?;
+    Ok(snapshot_maps().into_iter().map(String::from).collect())
+}
+
+/// One page of `map`; pass the previous page's `next_cursor` to continue
+#[query(guard = "caller_is_admin")]
+pub fn admin_export_snapshot(map: String, cursor: Option<SnapshotCursor>) -> Result<SnapshotPage, String> {
+    require_permission(Permission::ManageAdmins)?;
+    export_snapshot_page(&map, cursor)
+}
+
+/// Restores one exported page. Pages of a map go in the order they were
+/// exported, and maps in the order of `admin_list_snapshot_maps`.
+#[update(guard = "caller_is_admin")]
+pub fn admin_import_snapshot(page: SnapshotPage) -> Result<u64, String> {
+    require_permission(Permission::ManageAdmins)?;
+    let imported = import_snapshot_page(ic_cdk::caller(), &page)?;
+    if page.next_cursor.is_none() {
+        audit(AuditAction::SnapshotImported, page.map.clone(), None, None);
+    }
+    Ok(imported)
+}
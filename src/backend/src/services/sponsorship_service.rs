@@ -0,0 +1,371 @@
+// backend/src/services/sponsorship_service.rs
+//
+// Accelerator-sponsored seats: an accelerator pays once for N seats on a plan
+// and its admins hand them to the founders of their startups.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use serde::Deserialize;
+
+use crate::models::accelerator::{Accelerator, MemberStatus, Role};
+use crate::models::payment::{Currency, CustomField, PaymentMetadata, PaymentRecord, PaymentStatus};
+use crate::models::sponsorship::{SeatAssignment, SeatSponsorship, SponsorshipStatus};
+use crate::models::stable_string::StableString;
+use crate::models::usage_service::UserTier;
+use crate::payments::select_gateway;
+use crate::services::payment_service::{
+    generate_payment_reference, start_checkout, InitializePaymentResponse,
+};
+use crate::services::plan_service::{plan_tier, resolve_price};
+use crate::services::auth::caller_is_authenticated;
+use crate::storage::memory::{ACCELERATORS, SEAT_ASSIGNMENTS, SEAT_SPONSORSHIPS, STARTUPS, STARTUP_COHORTS};
+
+/// Request from an accelerator to buy seats
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SeatPurchaseRequest {
+    pub accelerator_id: String,
+    pub cohort_id: Option<String>,   // Restrict the seats to one cohort
+    pub seats: u32,
+    pub tier: String,                // Plan id from the catalog, e.g. "Pro"
+    pub billing_period: String,      // "monthly" or "yearly"
+    pub currency: String,
+    pub email: String,               // Billing contact
+    pub callback_url: String,
+    pub phone_number: Option<String>,
+    pub enable_mpesa: bool,
+    pub enable_card: bool,
+}
+
+/// Seat usage of one sponsorship, for the accelerator dashboard
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SeatUsage {
+    pub sponsorship: SeatSponsorship,
+    pub seats_used: u32,
+    pub seats_available: u32,
+    pub assignments: Vec<SeatAssignment>,   // Including revoked ones
+}
+
+// ============= ACCESS =============
+
+pub(crate) fn find_accelerator(accelerator_id: &str) -> Option<Accelerator> {
+    ACCELERATORS.with(|accs| {
+        accs.borrow()
+            .iter()
+            .find(|(k, _)| k.to_string() == accelerator_id)
+            .map(|(_, v)| v)
+    })
+}
+
+pub(crate) fn is_team_member(accelerator: &Accelerator, principal: Principal) -> bool {
+    accelerator
+        .team_members
+        .iter()
+        .any(|m| m.principal == Some(principal) && m.status == MemberStatus::Active)
+}
+
+fn is_accelerator_admin(accelerator: &Accelerator, principal: Principal) -> bool {
+    accelerator.team_members.iter().any(|m| {
+        m.principal == Some(principal)
+            && (m.role == Role::SuperAdmin || m.role == Role::Admin)
+            && m.status == MemberStatus::Active
+    })
+}
+
+// ============= PURCHASE =============
+
+/// Validate a seat purchase and build its pending payment and sponsorship
+pub fn prepare_seat_purchase(
+    request: &SeatPurchaseRequest,
+    caller: Principal,
+    reference: &str,
+    now: u64,
+) -> Result<(PaymentRecord, SeatSponsorship), String> {
+    let accelerator = find_accelerator(&request.accelerator_id)
+        .ok_or_else(|| "Accelerator not found".to_string())?;
+    if !is_accelerator_admin(&accelerator, caller) {
+        return Err("Only SuperAdmins or Admins can buy seats".to_string());
+    }
+    if request.seats == 0 {
+        return Err("Buy at least one seat".to_string());
+    }
+    if let Some(cohort_id) = &request.cohort_id {
+        let cohort = STARTUP_COHORTS.with(|cohorts| cohorts.borrow().get(&StableString::from(cohort_id.clone())));
+        if cohort.is_none_or(|cohort| cohort.accelerator_id.to_string() != request.accelerator_id) {
+            return Err(format!("Cohort {} not found", cohort_id));
+        }
+    }
+
+    let currency = Currency::from_code(&request.currency)
+        .ok_or_else(|| format!("Unsupported currency: {}", request.currency))?;
+    let price = resolve_price(&request.tier, &request.billing_period, &currency)?;
+    let amount = price
+        .amount
+        .checked_mul(request.seats as u64)
+        .ok_or_else(|| "Too many seats".to_string())?;
+
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: request.accelerator_id.clone(),
+        paystack_reference: reference.to_string(),
+        amount,
+        currency: currency.clone(),
+        email: request.email.clone(),
+        status: PaymentStatus::Pending,
+        tier: request.tier.clone(),
+        billing_period: request.billing_period.clone(),
+        created_at: now,
+        updated_at: now,
+        metadata: PaymentMetadata {
+            customer_name: Some(accelerator.name.clone()),
+            phone_number: request.phone_number.clone(),
+            custom_fields: vec![CustomField {
+                display_name: "Seats".to_string(),
+                variable_name: "seats".to_string(),
+                value: request.seats.to_string(),
+            }],
+        },
+        provider: select_gateway(&currency, request.enable_mpesa),
+        ..Default::default()
+    };
+
+    let sponsorship = SeatSponsorship {
+        id: reference.to_string(),
+        accelerator_id: request.accelerator_id.clone(),
+        cohort_id: request.cohort_id.clone(),
+        tier: request.tier.clone(),
+        billing_period: request.billing_period.clone(),
+        seats: request.seats,
+        amount,
+        currency,
+        status: SponsorshipStatus::Pending,
+        starts_at: None,
+        ends_at: None,
+        purchased_by: caller.to_string(),
+        created_at: now,
+        updated_at: now,
+    };
+
+    Ok((payment, sponsorship))
+}
+
+pub fn get_sponsorship(id: &str) -> Option<SeatSponsorship> {
+    SEAT_SPONSORSHIPS.with(|s| s.borrow().get(&StableString::from(id.to_string())))
+}
+
+fn store_sponsorship(sponsorship: SeatSponsorship) {
+    SEAT_SPONSORSHIPS.with(|s| {
+        s.borrow_mut().insert(StableString::from(sponsorship.id.clone()), sponsorship);
+    });
+}
+
+/// The seat payment succeeded: seats can be assigned until `ends_at`
+pub fn activate_sponsorship(id: &str, starts_at: u64, ends_at: u64, now: u64) -> Result<(), String> {
+    let mut sponsorship = get_sponsorship(id)
+        .ok_or_else(|| format!("Sponsorship not found: {}", id))?;
+
+    sponsorship.status = SponsorshipStatus::Active;
+    sponsorship.starts_at = Some(starts_at);
+    sponsorship.ends_at = Some(ends_at);
+    sponsorship.updated_at = now;
+    store_sponsorship(sponsorship);
+    Ok(())
+}
+
+/// The seat payment failed or was refunded: every seat stops counting as Pro
+pub fn cancel_sponsorship(id: &str, now: u64) {
+    if let Some(mut sponsorship) = get_sponsorship(id) {
+        if sponsorship.status != SponsorshipStatus::Cancelled {
+            sponsorship.status = SponsorshipStatus::Cancelled;
+            sponsorship.updated_at = now;
+            store_sponsorship(sponsorship);
+        }
+    }
+}
+
+fn is_active(sponsorship: &SeatSponsorship, now: u64) -> bool {
+    sponsorship.status == SponsorshipStatus::Active && sponsorship.ends_at.is_some_and(|end| now < end)
+}
+
+// ============= SEATS =============
+
+fn assignment_key(sponsorship_id: &str, startup_id: &str) -> StableString {
+    StableString::from(format!("{}:{}", sponsorship_id, startup_id))
+}
+
+fn sponsorship_assignments(sponsorship_id: &str) -> Vec<SeatAssignment> {
+    SEAT_ASSIGNMENTS.with(|a| {
+        a.borrow()
+            .iter()
+            .map(|(_, assignment)| assignment)
+            .filter(|assignment| assignment.sponsorship_id == sponsorship_id)
+            .collect()
+    })
+}
+
+/// Give a seat to the founder of a startup in the sponsoring accelerator
+pub fn assign_seat(
+    caller: Principal,
+    sponsorship_id: &str,
+    startup_id: &str,
+    now: u64,
+) -> Result<SeatAssignment, String> {
+    let sponsorship = get_sponsorship(sponsorship_id)
+        .ok_or_else(|| format!("Sponsorship not found: {}", sponsorship_id))?;
+    let accelerator = find_accelerator(&sponsorship.accelerator_id)
+        .ok_or_else(|| "Accelerator not found".to_string())?;
+    if !is_accelerator_admin(&accelerator, caller) {
+        return Err("Only SuperAdmins or Admins can assign seats".to_string());
+    }
+    if !is_active(&sponsorship, now) {
+        return Err("Sponsorship is not active".to_string());
+    }
+
+    let startup = STARTUPS
+        .with(|startups| startups.borrow().get(&StableString::from(startup_id.to_string())))
+        .filter(|startup| startup.accelerator_id.to_string() == sponsorship.accelerator_id)
+        .ok_or_else(|| format!("Startup {} not found", startup_id))?;
+    if let Some(cohort_id) = &sponsorship.cohort_id {
+        if &startup.cohort_id != cohort_id {
+            return Err(format!("Startup {} is not in the sponsored cohort", startup.name));
+        }
+    }
+
+    let assignments = sponsorship_assignments(sponsorship_id);
+    let active = assignments.iter().filter(|a| a.revoked_at.is_none());
+    if active.clone().any(|a| a.startup_id == startup_id) {
+        return Err(format!("Startup {} already has a seat", startup.name));
+    }
+    if active.count() as u32 >= sponsorship.seats {
+        return Err(format!("All {} seats are assigned", sponsorship.seats));
+    }
+
+    let assignment = SeatAssignment {
+        sponsorship_id: sponsorship_id.to_string(),
+        startup_id: startup_id.to_string(),
+        user_id: startup.founder_principal.to_string(),
+        assigned_by: caller.to_string(),
+        assigned_at: now,
+        revoked_at: None,
+    };
+    SEAT_ASSIGNMENTS.with(|a| {
+        a.borrow_mut().insert(assignment_key(sponsorship_id, startup_id), assignment.clone());
+    });
+    Ok(assignment)
+}
+
+/// Take a seat back so it can be given to another startup
+pub fn revoke_seat(
+    caller: Principal,
+    sponsorship_id: &str,
+    startup_id: &str,
+    now: u64,
+) -> Result<SeatAssignment, String> {
+    let sponsorship = get_sponsorship(sponsorship_id)
+        .ok_or_else(|| format!("Sponsorship not found: {}", sponsorship_id))?;
+    let accelerator = find_accelerator(&sponsorship.accelerator_id)
+        .ok_or_else(|| "Accelerator not found".to_string())?;
+    if !is_accelerator_admin(&accelerator, caller) {
+        return Err("Only SuperAdmins or Admins can revoke seats".to_string());
+    }
+
+    let key = assignment_key(sponsorship_id, startup_id);
+    let mut assignment = SEAT_ASSIGNMENTS
+        .with(|a| a.borrow().get(&key))
+        .filter(|assignment| assignment.revoked_at.is_none())
+        .ok_or_else(|| format!("Startup {} has no seat", startup_id))?;
+
+    assignment.revoked_at = Some(now);
+    SEAT_ASSIGNMENTS.with(|a| {
+        a.borrow_mut().insert(key, assignment.clone());
+    });
+    Ok(assignment)
+}
+
+/// Tier granted by a seat assigned to the user, if any sponsorship is still running
+pub fn sponsored_tier(user_id: &str, now: u64) -> Option<UserTier> {
+    let assignments: Vec<SeatAssignment> = SEAT_ASSIGNMENTS.with(|a| {
+        a.borrow()
+            .iter()
+            .map(|(_, assignment)| assignment)
+            .filter(|assignment| assignment.user_id == user_id && assignment.revoked_at.is_none())
+            .collect()
+    });
+
+    assignments
+        .iter()
+        .filter_map(|assignment| get_sponsorship(&assignment.sponsorship_id))
+        .filter(|sponsorship| is_active(sponsorship, now))
+        .map(|sponsorship| plan_tier(&sponsorship.tier).unwrap_or(UserTier::Pro))
+        .find(|tier| *tier != UserTier::Free)
+}
+
+/// Seats bought by an accelerator and who holds them, newest purchase first
+pub fn seat_usage(caller: Principal, accelerator_id: &str, now: u64) -> Result<Vec<SeatUsage>, String> {
+    let accelerator = find_accelerator(accelerator_id)
+        .ok_or_else(|| "Accelerator not found".to_string())?;
+    if !is_team_member(&accelerator, caller) {
+        return Err("Unauthorized: Not a team member".to_string());
+    }
+
+    let mut sponsorships: Vec<SeatSponsorship> = SEAT_SPONSORSHIPS.with(|s| {
+        s.borrow()
+            .iter()
+            .map(|(_, sponsorship)| sponsorship)
+            .filter(|sponsorship| sponsorship.accelerator_id == accelerator_id)
+            .collect()
+    });
+    sponsorships.sort_by_key(|sponsorship| std::cmp::Reverse(sponsorship.created_at));
+
+    Ok(sponsorships
+        .into_iter()
+        .map(|sponsorship| {
+            let assignments = sponsorship_assignments(&sponsorship.id);
+            let seats_used = assignments.iter().filter(|a| a.revoked_at.is_none()).count() as u32;
+            let seats_available = if is_active(&sponsorship, now) {
+                sponsorship.seats.saturating_sub(seats_used)
+            } else {
+                0
+            };
+            SeatUsage {
+                sponsorship,
+                seats_used,
+                seats_available,
+                assignments,
+            }
+        })
+        .collect())
+}
+
+// ============= API =============
+
+/// Buy seats for an accelerator. Returns the checkout to complete like `payment_initialize`.
+#[update(guard = "caller_is_authenticated")]
+pub async fn purchase_sponsored_seats(request: SeatPurchaseRequest) -> Result<InitializePaymentResponse, String> {
+    let reference = generate_payment_reference(&request.accelerator_id);
+    let (payment, sponsorship) = prepare_seat_purchase(&request, caller(), &reference, time())?;
+
+    store_sponsorship(sponsorship);
+    match start_checkout(payment, &request.callback_url, request.enable_mpesa, request.enable_card).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            cancel_sponsorship(&reference, time());
+            Err(e)
+        }
+    }
+}
+
+#[update(guard = "caller_is_authenticated")]
+pub fn assign_sponsored_seat(sponsorship_id: String, startup_id: String) -> Result<SeatAssignment, String> {
+    assign_seat(caller(), &sponsorship_id, &startup_id, time())
+}
+
+#[update(guard = "caller_is_authenticated")]
+pub fn revoke_sponsored_seat(sponsorship_id: String, startup_id: String) -> Result<SeatAssignment, String> {
+    revoke_seat(caller(), &sponsorship_id, &startup_id, time())
+}
+
+#[query(guard = "caller_is_authenticated")]
+pub fn get_sponsored_seat_usage(accelerator_id: String) -> Result<Vec<SeatUsage>, String> {
+    seat_usage(caller(), &accelerator_id, time())
+}
@@ -8,6 +8,13 @@ use crate::storage::memory::{
     STARTUPS, STARTUP_STATUSES, STARTUP_COHORTS, STARTUP_ACTIVITIES, ACCELERATORS
 };
 use crate::models::accelerator::{Role, MemberStatus};
+use crate::models::admin::Permission;
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::admin::authorize;
+use crate::services::auth::caller_is_authenticated;
+use crate::services::sponsorship_service::{find_accelerator, is_team_member};
+use candid::Principal;
 // use candid::{CandidType, Deserialize};
 use ic_cdk::{caller, update, query};
 use std::collections::HashMap;
@@ -16,7 +23,23 @@ use std::collections::HashMap;
 // Startup Management
 // ==================================================================================================
 
-#[update]
+/// The founder, the accelerator's active team members and admins with `ViewUsers` may see a startup
+pub fn check_startup_access(caller: Principal, startup_id: &str) -> Result<Startup, String> {
+    let startup = STARTUPS.with(|startups| {
+        startups.borrow().get(&StableString::new(startup_id))
+    }).ok_or("Startup not found")?;
+
+    let is_member = find_accelerator(&startup.accelerator_id.to_string())
+        .is_some_and(|accelerator| is_team_member(&accelerator, caller));
+    if startup.founder_principal.get() == caller || is_member {
+        return Ok(startup);
+    }
+    authorize(caller, Permission::ViewUsers)
+        .map_err(|_| "Unauthorized: Not a member of this startup's accelerator".to_string())?;
+    Ok(startup)
+}
+
+#[update(guard = "caller_is_authenticated")]
 pub fn create_startup(input: StartupInput) -> Result<Startup, String> {
     let caller_principal = caller();
     let now = ic_cdk::api::time();
@@ -85,6 +108,7 @@ pub fn create_startup(input: StartupInput) -> Result<Startup, String> {
     STARTUPS.with(|startups| {
         startups.borrow_mut().insert(StableString::new(&startup_id), startup.clone());
     });
+    audit(AuditAction::StartupCreated, startup_id.clone(), None, Some(digest(&startup)));
 
     // Record activity
     record_startup_activity_internal(&startup_id, StartupActivityType::Other("Startup created".to_string()), "Startup created".to_string(), None);
@@ -92,15 +116,15 @@ pub fn create_startup(input: StartupInput) -> Result<Startup, String> {
     Ok(startup)
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_startup(startup_id: String) -> Result<Option<Startup>, String> {
-    let startup = STARTUPS.with(|startups| {
-        startups.borrow().get(&StableString::new(&startup_id))
-    });
-    Ok(startup)
+    if !STARTUPS.with(|startups| startups.borrow().contains_key(&StableString::new(&startup_id))) {
+        return Ok(None);
+    }
+    check_startup_access(caller(), &startup_id).map(Some)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_startup(startup_id: String, updates: StartupUpdate) -> Result<(), String> {
     let caller_principal = caller();
     let now = ic_cdk::api::time();
@@ -124,6 +148,7 @@ pub fn update_startup(startup_id: String, updates: StartupUpdate) -> Result<(),
     }
 
     // Track changes for activity logging
+    let before = digest(&startup);
     let mut changes = Vec::new();
 
     if let Some(name) = updates.name {
@@ -157,6 +182,7 @@ pub fn update_startup(startup_id: String, updates: StartupUpdate) -> Result<(),
 
     startup.updated_at = now;
 
+    audit(AuditAction::StartupUpdated, startup_id.clone(), Some(before), Some(digest(&startup)));
     STARTUPS.with(|startups| {
         startups.borrow_mut().insert(StableString::new(&startup_id), startup);
     });
@@ -176,7 +202,7 @@ pub fn update_startup(startup_id: String, updates: StartupUpdate) -> Result<(),
     Ok(())
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn delete_startup(startup_id: String) -> Result<(), String> {
     let caller_principal = caller();
 
@@ -201,11 +227,12 @@ pub fn delete_startup(startup_id: String) -> Result<(), String> {
     STARTUPS.with(|startups| {
         startups.borrow_mut().remove(&StableString::new(&startup_id));
     });
+    audit(AuditAction::StartupDeleted, startup_id, Some(digest(&startup)), None);
 
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn list_startups(filter: Option<StartupFilter>) -> Result<Vec<Startup>, String> {
     let caller_principal = caller();
 
@@ -281,7 +308,7 @@ pub fn list_startups(filter: Option<StartupFilter>) -> Result<Vec<Startup>, Stri
     Ok(filtered_startups)
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_startup_stats() -> Result<StartupStats, String> {
     let caller_principal = caller();
 
@@ -344,7 +371,7 @@ pub fn get_startup_stats() -> Result<StartupStats, String> {
 // Custom Status Management
 // ==================================================================================================
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn create_startup_status(input: StartupStatusInput) -> Result<StartupStatus, String> {
     let caller_principal = caller();
     let now = ic_cdk::api::time();
@@ -390,11 +417,12 @@ pub fn create_startup_status(input: StartupStatusInput) -> Result<StartupStatus,
     STARTUP_STATUSES.with(|statuses| {
         statuses.borrow_mut().insert(StableString::new(&status_id), status.clone());
     });
+    audit(AuditAction::StartupStatusChanged, status_id, None, Some(digest(&status)));
 
     Ok(status)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_startup_status( status_id: String, input: StartupStatusInput) -> Result<(), String> {
     let caller_principal = caller();
 
@@ -420,6 +448,7 @@ pub fn update_startup_status( status_id: String, input: StartupStatusInput) -> R
         return Err("Status does not belong to this accelerator".to_string());
     }
 
+    let before = digest(&status);
     status.name = input.name;
     status.color = input.color;
     status.description = input.description;
@@ -427,6 +456,7 @@ pub fn update_startup_status( status_id: String, input: StartupStatusInput) -> R
         status.sort_order = sort_order;
     }
 
+    audit(AuditAction::StartupStatusChanged, status_id.clone(), Some(before), Some(digest(&status)));
     STARTUP_STATUSES.with(|statuses| {
         statuses.borrow_mut().insert(StableString::new(&status_id), status);
     });
@@ -434,7 +464,7 @@ pub fn update_startup_status( status_id: String, input: StartupStatusInput) -> R
     Ok(())
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn delete_startup_status( status_id: String) -> Result<(), String> {
     let caller_principal = caller();
 
@@ -474,11 +504,12 @@ pub fn delete_startup_status( status_id: String) -> Result<(), String> {
     STARTUP_STATUSES.with(|statuses| {
         statuses.borrow_mut().remove(&StableString::new(&status_id));
     });
+    audit(AuditAction::StartupStatusChanged, status_id, Some(digest(&status)), None);
 
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn list_startup_statuses() -> Result<Vec<StartupStatus>, String> {
     let caller_principal = caller();
 
@@ -501,7 +532,7 @@ pub fn list_startup_statuses() -> Result<Vec<StartupStatus>, String> {
 // Custom Cohort Management
 // ==================================================================================================
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn create_startup_cohort( input: StartupCohortInput) -> Result<StartupCohort, String> {
     let caller_principal = caller();
     let now = ic_cdk::api::time();
@@ -536,11 +567,12 @@ pub fn create_startup_cohort( input: StartupCohortInput) -> Result<StartupCohort
     STARTUP_COHORTS.with(|cohorts| {
         cohorts.borrow_mut().insert(StableString::new(&cohort_id), cohort.clone());
     });
+    audit(AuditAction::StartupCohortChanged, cohort_id, None, Some(digest(&cohort)));
 
     Ok(cohort)
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_startup_cohort( cohort_id: String, input: StartupCohortInput) -> Result<(), String> {
     let caller_principal = caller();
 
@@ -566,11 +598,13 @@ pub fn update_startup_cohort( cohort_id: String, input: StartupCohortInput) -> R
         return Err("Cohort does not belong to this accelerator".to_string());
     }
 
+    let before = digest(&cohort);
     cohort.name = input.name;
     cohort.description = input.description;
     cohort.start_date = input.start_date;
     cohort.end_date = input.end_date;
 
+    audit(AuditAction::StartupCohortChanged, cohort_id.clone(), Some(before), Some(digest(&cohort)));
     STARTUP_COHORTS.with(|cohorts| {
         cohorts.borrow_mut().insert(StableString::new(&cohort_id), cohort);
     });
@@ -578,7 +612,7 @@ pub fn update_startup_cohort( cohort_id: String, input: StartupCohortInput) -> R
     Ok(())
 }
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn delete_startup_cohort( cohort_id: String) -> Result<(), String> {
     let caller_principal = caller();
 
@@ -618,11 +652,12 @@ pub fn delete_startup_cohort( cohort_id: String) -> Result<(), String> {
     STARTUP_COHORTS.with(|cohorts| {
         cohorts.borrow_mut().remove(&StableString::new(&cohort_id));
     });
+    audit(AuditAction::StartupCohortChanged, cohort_id, Some(digest(&cohort)), None);
 
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn list_startup_cohorts() -> Result<Vec<StartupCohort>, String> {
     let caller_principal = caller();
 
@@ -645,8 +680,9 @@ pub fn list_startup_cohorts() -> Result<Vec<StartupCohort>, String> {
 // Activity Tracking
 // ==================================================================================================
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn record_startup_activity(startup_id: String, activity_type: StartupActivityType, description: String, metadata: Option<String>) -> Result<(), String> {
+    check_startup_access(caller(), &startup_id)?;
     record_startup_activity_internal(&startup_id, activity_type, description, metadata);
     Ok(())
 }
@@ -683,8 +719,9 @@ fn record_startup_activity_internal(startup_id: &str, activity_type: StartupActi
     }
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn get_startup_activities(startup_id: String, limit: Option<u64>) -> Result<Vec<StartupActivity>, String> {
+    check_startup_access(caller(), &startup_id)?;
     let limit = limit.unwrap_or(50);
     
     let activities: Vec<StartupActivity> = STARTUP_ACTIVITIES.with(|activities| {
@@ -707,7 +744,7 @@ pub fn get_startup_activities(startup_id: String, limit: Option<u64>) -> Result<
 // Engagement Scoring
 // ==================================================================================================
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn update_engagement_score(startup_id: String, score: u32) -> Result<(), String> {
     let caller_principal = caller();
     let now = ic_cdk::api::time();
@@ -748,11 +785,9 @@ pub fn update_engagement_score(startup_id: String, score: u32) -> Result<(), Str
     Ok(())
 }
 
-#[query]
+#[query(guard = "caller_is_authenticated")]
 pub fn calculate_engagement_score(startup_id: String) -> Result<u32, String> {
-    let startup = STARTUPS.with(|startups| {
-        startups.borrow().get(&StableString::new(&startup_id))
-    }).ok_or("Startup not found")?;
+    let startup = check_startup_access(caller(), &startup_id)?;
 
     let now = ic_cdk::api::time();
     let days_since_joined = (now - startup.date_joined) / (24 * 60 * 60 * 1_000_000_000);
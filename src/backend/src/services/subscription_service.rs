@@ -0,0 +1,557 @@
+// backend/src/services/subscription_service.rs
+//
+// Subscription lifecycle: automatic renewals with saved Paystack
+// authorizations, grace period for failed charges, cancel/resume.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::time::Duration;
+
+use candid::CandidType;
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use serde::Deserialize;
+
+use crate::models::payment::{PaymentGateway, PaymentMetadata, PaymentRecord, PaymentStatus};
+use crate::models::stable_string::StableString;
+use crate::models::subscription_billing::{BillingSettings, SubscriptionBilling, SubscriptionStatus};
+use crate::models::usage_service::UserTier;
+use crate::payments::is_configured;
+use crate::payments::paystack_client::{build_metadata, charge_authorization as paystack_charge_authorization};
+use crate::payments::provider::IcHttpTransport;
+use crate::payments::paystack_models::{
+    parse_payment_channel, ChargeAuthorizationRequest, ChargeAuthorizationResponse,
+};
+use crate::models::admin::Permission;
+use crate::services::admin::{caller_is_admin, require_permission};
+use crate::models::audit_log::AuditAction;
+use crate::services::audit_service::{audit, digest};
+use crate::services::payment_service::{fulfil_payment_at, PaymentConfirmation};
+use crate::services::auth::caller_is_authenticated;
+use crate::storage::memory::{BILLING_SETTINGS, PAYMENT_RECORDS, SUBSCRIPTION_BILLING, USER_SUBSCRIPTIONS};
+
+const NANOS_PER_HOUR: u64 = 60 * 60 * 1_000_000_000;
+const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+const BILLING_CHECK_INTERVAL_SECS: u64 = 60 * 60; // Hourly
+const MAX_RENEWALS_PER_RUN: u32 = 10; // Bounds HTTP outcalls per timer tick
+const BILLING_SETTINGS_KEY: u8 = 0;
+
+thread_local! {
+    static BILLING_RUN_IN_PROGRESS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Charges a saved authorization. Paystack in production, a mock in tests.
+pub trait RenewalClient {
+    fn charge_authorization(
+        &self,
+        request: ChargeAuthorizationRequest,
+    ) -> impl Future<Output = Result<ChargeAuthorizationResponse, String>>;
+}
+
+/// `RenewalClient` backed by the Paystack HTTP outcall
+pub struct PaystackRenewalClient;
+
+impl RenewalClient for PaystackRenewalClient {
+    async fn charge_authorization(
+        &self,
+        request: ChargeAuthorizationRequest,
+    ) -> Result<ChargeAuthorizationResponse, String> {
+        paystack_charge_authorization(&IcHttpTransport, request).await
+    }
+}
+
+/// What the engine should do with a subscription on this run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BillingAction {
+    None,
+    Charge,
+    MarkPastDue,
+    Expire,
+}
+
+/// Outcome of one pass over the subscriptions
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct RenewalRunSummary {
+    pub renewed: u32,
+    pub failed: u32,
+    pub past_due: u32,
+    pub expired: u32,
+    pub deferred: u32, // Due, but over the per-run outcall budget
+}
+
+// ============= SETTINGS =============
+
+/// Current billing settings (defaults until an admin changes them)
+pub fn get_billing_settings() -> BillingSettings {
+    BILLING_SETTINGS.with(|settings| {
+        settings.borrow().get(&BILLING_SETTINGS_KEY).unwrap_or_default()
+    })
+}
+
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_billing_settings() -> Result<BillingSettings, String> {
+    require_permission(Permission::ViewBilling)?;
+    Ok(get_billing_settings())
+}
+
+#[update(guard = "caller_is_admin")]
+pub fn admin_set_billing_settings(settings: BillingSettings) -> Result<(), String> {
+    require_permission(Permission::ManageBilling)?;
+    if settings.max_renewal_attempts == 0 {
+        return Err("max_renewal_attempts must be at least 1".to_string());
+    }
+
+    audit(AuditAction::BillingSettingsChanged, "billing_settings", Some(digest(&get_billing_settings())), Some(digest(&settings)));
+    BILLING_SETTINGS.with(|s| {
+        s.borrow_mut().insert(BILLING_SETTINGS_KEY, settings);
+    });
+    Ok(())
+}
+
+// ============= USER API =============
+
+/// Stop automatic renewal. The user keeps Pro until the current period ends.
+#[update(guard = "caller_is_authenticated")]
+pub fn cancel_subscription() -> Result<SubscriptionBilling, String> {
+    cancel_subscription_for(&caller().to_string(), time())
+}
+
+/// Undo a cancellation while the paid period is still running
+#[update(guard = "caller_is_authenticated")]
+pub fn resume_subscription() -> Result<SubscriptionBilling, String> {
+    resume_subscription_for(&caller().to_string(), time())
+}
+
+/// Billing state of the caller's subscription
+#[query(guard = "caller_is_authenticated")]
+pub fn get_my_subscription_billing() -> Option<SubscriptionBilling> {
+    get_subscription_billing(&caller().to_string())
+}
+
+/// Billing state for every subscription (admin only)
+#[query(guard = "caller_is_admin")]
+pub fn admin_get_all_subscription_billing() -> Result<Vec<SubscriptionBilling>, String> {
+    require_permission(Permission::ViewBilling)?;
+
+    Ok(SUBSCRIPTION_BILLING.with(|billing| {
+        billing.borrow().iter().map(|(_, b)| b).collect()
+    }))
+}
+
+pub fn get_subscription_billing(user_id: &str) -> Option<SubscriptionBilling> {
+    SUBSCRIPTION_BILLING.with(|billing| {
+        billing.borrow().get(&StableString::from(user_id.to_string()))
+    })
+}
+
+pub fn cancel_subscription_for(user_id: &str, now: u64) -> Result<SubscriptionBilling, String> {
+    let mut billing = get_subscription_billing(user_id)
+        .ok_or("No subscription found")?;
+
+    match billing.status {
+        SubscriptionStatus::Active | SubscriptionStatus::PastDue => {
+            billing.status = SubscriptionStatus::Cancelled;
+            billing.cancelled_at_ns = Some(now);
+            billing.updated_at_ns = now;
+            store_billing(billing.clone());
+            Ok(billing)
+        }
+        SubscriptionStatus::Cancelled => Err("Subscription is already cancelled".to_string()),
+        SubscriptionStatus::Expired => Err("Subscription has already expired".to_string()),
+    }
+}
+
+pub fn resume_subscription_for(user_id: &str, now: u64) -> Result<SubscriptionBilling, String> {
+    let mut billing = get_subscription_billing(user_id)
+        .ok_or("No subscription found")?;
+
+    match billing.status {
+        SubscriptionStatus::Cancelled if now < billing.current_period_end_ns => {
+            billing.status = SubscriptionStatus::Active;
+            billing.cancelled_at_ns = None;
+            billing.updated_at_ns = now;
+            store_billing(billing.clone());
+            Ok(billing)
+        }
+        SubscriptionStatus::Cancelled | SubscriptionStatus::Expired => {
+            Err("Subscription period has ended. Please subscribe again.".to_string())
+        }
+        SubscriptionStatus::Active | SubscriptionStatus::PastDue => {
+            Err("Subscription is not cancelled".to_string())
+        }
+    }
+}
+
+/// True while a PastDue user is still inside the grace period after expiry
+pub fn is_in_grace_period(user_id: &str, now: u64) -> bool {
+    match get_subscription_billing(user_id) {
+        Some(billing) if billing.status == SubscriptionStatus::PastDue => {
+            now < grace_period_end(&billing, &get_billing_settings())
+        }
+        _ => false,
+    }
+}
+
+// ============= LIFECYCLE ENGINE =============
+
+/// Called by payment fulfilment: the subscription is paid up to `period_end`
+pub fn record_successful_charge(
+    payment: &PaymentRecord,
+    authorization_code: Option<String>,
+    period_end: Option<u64>,
+    now: u64,
+) {
+    // Only fixed-period plans can be renewed
+    let Some(period_end) = period_end else {
+        return;
+    };
+
+    let key = StableString::from(payment.user_id.clone());
+    let mut billing = SUBSCRIPTION_BILLING
+        .with(|b| b.borrow().get(&key))
+        .unwrap_or_default();
+
+    billing.user_id = payment.user_id.clone();
+    billing.status = SubscriptionStatus::Active;
+    billing.tier = match payment.tier.as_str() {
+        "Pro" => UserTier::Pro,
+        _ => UserTier::Free,
+    };
+    billing.billing_period = payment.billing_period.clone();
+    // A one-off coupon only discounts the first period
+    billing.amount = match &payment.discount {
+        Some(discount) if !discount.recurring => discount.original_amount,
+        _ => payment.amount,
+    };
+    billing.currency = payment.currency.clone();
+    billing.email = payment.email.clone();
+    if authorization_code.is_some() {
+        billing.authorization_code = authorization_code;
+    }
+    billing.current_period_end_ns = period_end;
+    billing.past_due_since_ns = None;
+    billing.renewal_attempts = 0;
+    billing.last_error = None;
+    billing.last_payment_reference = Some(payment.id.clone());
+    billing.cancelled_at_ns = None;
+    billing.updated_at_ns = now;
+
+    store_billing(billing);
+}
+
+/// Take back `removed_ns` of paid time after a refund.
+///
+/// The user drops to Free when nothing paid is left. `stop_renewal` (full
+/// refunds) also cancels automatic renewal so the card is not charged again.
+pub fn shorten_subscription(user_id: &str, removed_ns: u64, stop_renewal: bool, now: u64) {
+    let key = StableString::from(user_id.to_string());
+    let mut still_paid = false;
+
+    USER_SUBSCRIPTIONS.with(|subs| {
+        let mut map = subs.borrow_mut();
+        if let Some(mut subscription) = map.get(&key) {
+            if let Some(expiry) = subscription.expires_at_ns {
+                let new_expiry = expiry.saturating_sub(removed_ns);
+                subscription.expires_at_ns = Some(new_expiry);
+                still_paid = new_expiry > now;
+            }
+            if !still_paid {
+                subscription.tier = UserTier::Free;
+                subscription.is_active = false;
+            }
+            map.insert(key.clone(), subscription);
+        }
+    });
+
+    if let Some(mut billing) = get_subscription_billing(user_id) {
+        billing.current_period_end_ns = billing.current_period_end_ns.saturating_sub(removed_ns);
+        if !still_paid {
+            billing.status = SubscriptionStatus::Expired;
+        } else if stop_renewal && billing.status != SubscriptionStatus::Expired {
+            billing.status = SubscriptionStatus::Cancelled;
+            billing.cancelled_at_ns = Some(now);
+        }
+        billing.updated_at_ns = now;
+        store_billing(billing);
+    }
+}
+
+/// Decide what to do with a subscription at `now`
+pub fn next_action(billing: &SubscriptionBilling, settings: &BillingSettings, now: u64) -> BillingAction {
+    match billing.status {
+        SubscriptionStatus::Expired => BillingAction::None,
+        SubscriptionStatus::Cancelled => {
+            if now >= billing.current_period_end_ns {
+                BillingAction::Expire
+            } else {
+                BillingAction::None
+            }
+        }
+        SubscriptionStatus::Active | SubscriptionStatus::PastDue => {
+            if now >= grace_period_end(billing, settings) {
+                return BillingAction::Expire;
+            }
+
+            let can_charge = billing.authorization_code.is_some()
+                && billing.renewal_attempts < settings.max_renewal_attempts;
+            let renew_from = billing
+                .current_period_end_ns
+                .saturating_sub(settings.renew_before_hours as u64 * NANOS_PER_HOUR);
+            let retry_ready = match billing.last_attempt_at_ns {
+                Some(last) => now.saturating_sub(last) >= settings.retry_interval_hours as u64 * NANOS_PER_HOUR,
+                None => true,
+            };
+
+            if can_charge && now >= renew_from && retry_ready {
+                BillingAction::Charge
+            } else if billing.status == SubscriptionStatus::Active && now >= billing.current_period_end_ns {
+                BillingAction::MarkPastDue
+            } else {
+                BillingAction::None
+            }
+        }
+    }
+}
+
+/// One pass over all subscriptions: charge what is due, expire what is over
+pub async fn process_due_subscriptions<C: RenewalClient>(client: &C, now: u64) -> RenewalRunSummary {
+    let settings = get_billing_settings();
+    let subscriptions = SUBSCRIPTION_BILLING.with(|billing| {
+        billing.borrow().iter().map(|(_, b)| b).collect::<Vec<_>>()
+    });
+
+    let mut summary = RenewalRunSummary::default();
+    let mut charges = 0;
+
+    for billing in subscriptions {
+        match next_action(&billing, &settings, now) {
+            BillingAction::None => {}
+            BillingAction::Charge => {
+                if charges >= MAX_RENEWALS_PER_RUN {
+                    summary.deferred += 1;
+                    continue;
+                }
+                charges += 1;
+                if attempt_renewal(client, billing, now).await {
+                    summary.renewed += 1;
+                } else {
+                    summary.failed += 1;
+                }
+            }
+            BillingAction::MarkPastDue => {
+                let reason = if billing.authorization_code.is_none() {
+                    "No reusable payment authorization on file".to_string()
+                } else if billing.renewal_attempts >= settings.max_renewal_attempts {
+                    "Renewal attempts exhausted".to_string()
+                } else {
+                    // Failed inside the paid period, waiting for the next retry
+                    billing.last_error.clone().unwrap_or_else(|| "Renewal charge failed".to_string())
+                };
+                mark_past_due(billing, &reason, now);
+                summary.past_due += 1;
+            }
+            BillingAction::Expire => {
+                expire_subscription(billing, now);
+                summary.expired += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+/// Start the hourly renewal timer. Timers do not survive upgrades, so this
+/// is called from both `init` and `post_upgrade`.
+pub fn start_billing_timer() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(BILLING_CHECK_INTERVAL_SECS), || {
+        ic_cdk::spawn(async {
+            if let Err(e) = run_billing_cycle().await {
+                ic_cdk::println!("Billing run skipped: {}", e);
+            }
+        });
+    });
+}
+
+/// Run the renewal engine now instead of waiting for the next tick (admin only)
+#[update(guard = "caller_is_admin")]
+pub async fn admin_run_billing_cycle() -> Result<RenewalRunSummary, String> {
+    require_permission(Permission::ManageBilling)?;
+    run_billing_cycle().await
+}
+
+async fn run_billing_cycle() -> Result<RenewalRunSummary, String> {
+    // Paystack keys are not persisted across upgrades; wait until they are set again
+    if !is_configured() {
+        return Err("Payment system not configured".to_string());
+    }
+
+    let _guard = BillingRunGuard::acquire()
+        .ok_or("Billing run already in progress")?;
+
+    let summary = process_due_subscriptions(&PaystackRenewalClient, time()).await;
+    ic_cdk::println!("Billing run finished: {:?}", summary);
+    Ok(summary)
+}
+
+/// Prevents overlapping runs while a previous one is awaiting outcalls
+struct BillingRunGuard;
+
+impl BillingRunGuard {
+    fn acquire() -> Option<Self> {
+        if BILLING_RUN_IN_PROGRESS.with(|running| running.replace(true)) {
+            None
+        } else {
+            Some(BillingRunGuard)
+        }
+    }
+}
+
+impl Drop for BillingRunGuard {
+    fn drop(&mut self) {
+        BILLING_RUN_IN_PROGRESS.with(|running| running.set(false));
+    }
+}
+
+// ============= HELPER FUNCTIONS =============
+
+fn grace_period_end(billing: &SubscriptionBilling, settings: &BillingSettings) -> u64 {
+    billing
+        .current_period_end_ns
+        .saturating_add(settings.grace_period_days as u64 * NANOS_PER_DAY)
+}
+
+fn store_billing(billing: SubscriptionBilling) {
+    SUBSCRIPTION_BILLING.with(|b| {
+        b.borrow_mut().insert(StableString::from(billing.user_id.clone()), billing);
+    });
+}
+
+/// Charge the saved authorization and fulfil the renewal on success
+async fn attempt_renewal<C: RenewalClient>(client: &C, mut billing: SubscriptionBilling, now: u64) -> bool {
+    let attempt = billing.renewal_attempts + 1;
+    let user_prefix = billing.user_id.chars().take(6).collect::<String>();
+    let reference = format!("INF-RNW-{}-{}-{}", user_prefix, now, attempt);
+    let tier = match billing.tier {
+        UserTier::Pro => "Pro",
+        UserTier::Free => "Free",
+    };
+
+    // Record the attempt before the outcall so an overlapping run never charges twice
+    billing.last_attempt_at_ns = Some(now);
+    billing.last_payment_reference = Some(reference.clone());
+    billing.updated_at_ns = now;
+    store_billing(billing.clone());
+
+    let payment = PaymentRecord {
+        id: reference.clone(),
+        user_id: billing.user_id.clone(),
+        paystack_reference: reference.clone(),
+        amount: billing.amount,
+        currency: billing.currency.clone(),
+        email: billing.email.clone(),
+        status: PaymentStatus::Pending,
+        payment_channel: None,
+        authorization_url: None,
+        access_code: None,
+        tier: tier.to_string(),
+        billing_period: billing.billing_period.clone(),
+        created_at: now,
+        updated_at: now,
+        paid_at: None,
+        paystack_transaction_id: None,
+        metadata: PaymentMetadata::default(),
+        provider: PaymentGateway::Paystack,
+        provider_transaction_id: None,
+        discount: None,
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.clone()), payment);
+    });
+
+    let request = ChargeAuthorizationRequest {
+        email: billing.email.clone(),
+        amount: billing.amount.to_string(),
+        authorization_code: billing.authorization_code.clone().unwrap_or_default(),
+        reference: reference.clone(),
+        currency: billing.currency.as_str().to_string(),
+        metadata: Some(build_metadata(&billing.user_id, tier, &billing.billing_period, None)),
+    };
+
+    let outcome = match client.charge_authorization(request).await {
+        Ok(response) => match response.data {
+            Some(data) if response.status && data.status == "success" => Ok(data),
+            Some(data) => Err(data.gateway_response.unwrap_or(response.message)),
+            None => Err(response.message),
+        },
+        Err(e) => Err(e),
+    };
+
+    let result = outcome.and_then(|data| {
+        let channel = data.channel.as_deref().and_then(parse_payment_channel);
+        let confirmation = PaymentConfirmation {
+            channel,
+            transaction_id: Some(data.id),
+            ..Default::default()
+        };
+        fulfil_payment_at(&reference, confirmation, now)
+    });
+
+    match result {
+        Ok(_) => true,
+        Err(error) => {
+            record_renewal_failure(&billing.user_id, &reference, error, now);
+            false
+        }
+    }
+}
+
+fn record_renewal_failure(user_id: &str, reference: &str, error: String, now: u64) {
+    PAYMENT_RECORDS.with(|records| {
+        let mut map = records.borrow_mut();
+        let key = StableString::from(reference.to_string());
+        if let Some(mut payment) = map.get(&key) {
+            payment.status = PaymentStatus::Failed;
+            payment.updated_at = now;
+            map.insert(key, payment);
+        }
+    });
+
+    if let Some(mut billing) = get_subscription_billing(user_id) {
+        // Early attempts inside the paid period are only recorded; the
+        // subscription is past due once that period is over. A cancellation
+        // that landed while the charge was in flight wins.
+        if now >= billing.current_period_end_ns && billing.status != SubscriptionStatus::Cancelled {
+            billing.status = SubscriptionStatus::PastDue;
+            billing.past_due_since_ns.get_or_insert(now);
+        }
+        billing.renewal_attempts += 1;
+        billing.last_error = Some(error);
+        billing.updated_at_ns = now;
+        store_billing(billing);
+    }
+}
+
+fn mark_past_due(mut billing: SubscriptionBilling, reason: &str, now: u64) {
+    billing.status = SubscriptionStatus::PastDue;
+    billing.past_due_since_ns.get_or_insert(now);
+    billing.last_error = Some(reason.to_string());
+    billing.updated_at_ns = now;
+    store_billing(billing);
+}
+
+/// Drop the user back to Free once the period and any grace period are over
+fn expire_subscription(mut billing: SubscriptionBilling, now: u64) {
+    USER_SUBSCRIPTIONS.with(|subs| {
+        let mut map = subs.borrow_mut();
+        let key = StableString::from(billing.user_id.clone());
+        if let Some(mut subscription) = map.get(&key) {
+            subscription.tier = UserTier::Free;
+            subscription.is_active = false;
+            map.insert(key, subscription);
+        }
+    });
+
+    billing.status = SubscriptionStatus::Expired;
+    billing.updated_at_ns = now;
+    store_billing(billing);
+}
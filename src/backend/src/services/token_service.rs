@@ -1,10 +1,13 @@
 use crate::models::dashboard_token::DashboardToken;
+use crate::models::platform::Platform;
 use crate::models::stable_string::StableString;
 use crate::storage::memory::DASHBOARD_TOKENS;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use ic_cdk::api::management_canister::main::raw_rand;
 // use ic_cdk::api::time;
-use ic_cdk::{query, update};
+use crate::models::bot_principal::BotPermission;
+use crate::services::bot_service::{authorize_bot, bot_can_issue_tokens};
+use ic_cdk::{caller, query, update};
 use candid::{CandidType, Deserialize};
 
 // 2 minutes in nanoseconds
@@ -12,12 +15,17 @@ const TOKEN_EXPIRY_NANOS: u64 = 2 * 60 * 1_000_000_000;
 
 #[derive(CandidType, Deserialize)]
 pub struct TokenValidationResult {
-    pub platform: String, // "slack", "openchat", etc.
+    pub platform: Platform,
     pub platform_id: String,
 }
 
-#[update]
-pub async fn generate_dashboard_token(platform_id: String) -> String {
+#[update(guard = "bot_can_issue_tokens")]
+pub async fn generate_dashboard_token(platform: Platform, platform_id: String) -> String {
+    // Bots only issue tokens for users of their own platform
+    if let Err(e) = authorize_bot(caller(), BotPermission::IssueTokens, platform) {
+        ic_cdk::trap(&e);
+    }
+
     // Generate random bytes using IC's random number generator
     let random_bytes = raw_rand().await.unwrap().0;
     let token: Vec<u8> = random_bytes.into_iter().take(32).collect();
@@ -33,7 +41,8 @@ pub async fn generate_dashboard_token(platform_id: String) -> String {
     // Create token record
     let token_record = DashboardToken {
         token: token.clone(),
-        openchat_id: platform_id.clone(), // We'll use this field for all platform IDs
+        platform,
+        platform_id: platform_id.clone(),
         created_at: now,
         expires_at: now + TOKEN_EXPIRY_NANOS,
     };
@@ -76,15 +85,9 @@ pub fn validate_dashboard_token(token: Vec<u8>) -> Option<TokenValidationResult>
         if let Some(token_record) = tokens.get(&StableString::from(token_key.clone())) {
             ic_cdk::println!("Token found. Expires at: {}, Current time: {}", token_record.expires_at, now);
             if token_record.expires_at > now {
-                let id = token_record.openchat_id.clone();
-                let platform = if id.starts_with('U') {
-                    "slack"
-                } else {
-                    "openchat"
-                };
                 Some(TokenValidationResult {
-                    platform: platform.to_string(),
-                    platform_id: id,
+                    platform: token_record.platform,
+                    platform_id: token_record.platform_id,
                 })
             } else {
                 ic_cdk::println!("Token expired.");
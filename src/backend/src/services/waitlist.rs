@@ -1,9 +1,10 @@
 use crate::models::stable_string::StableString;
 use crate::models::waitlist::{WaitlistEntry, WaitlistStatus};
 use crate::storage::memory::WAITLIST;
+use crate::services::auth::caller_is_authenticated;
 use ic_cdk::update;
 
-#[update]
+#[update(guard = "caller_is_authenticated")]
 pub fn join_waitlist(name: String) -> Result<WaitlistEntry, String> {
     let caller = ic_cdk::caller();
     let principal_id = caller.to_string();
@@ -0,0 +1,159 @@
+// backend/src/services/webhook_service.rs
+//
+// Durable log of verified webhook deliveries: rejects replays of events
+// that were already handled and keeps failed ones for reprocessing.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::models::payment::PaymentGateway;
+use crate::models::stable_string::StableString;
+use crate::models::webhook_event::{WebhookEvent, WebhookEventStatus, WebhookSource};
+use crate::storage::memory::WEBHOOK_EVENTS;
+
+/// Larger payloads are processed but not kept, so they cannot be reprocessed
+const MAX_STORED_PAYLOAD: usize = 14 * 1024;
+
+/// Stable id of a webhook delivery: the gateway's own event id when the
+/// payload has one, otherwise a hash of the payload
+pub fn webhook_event_id(gateway: PaymentGateway, payload: &str) -> String {
+    let prefix = format!("{:?}", gateway).to_lowercase();
+    let event: Value = serde_json::from_str(payload).unwrap_or(Value::Null);
+
+    let event_id = match gateway {
+        // Paystack has no event id; the event name and its object id identify a delivery
+        PaymentGateway::Paystack => {
+            let data = match &event["data"] {
+                Value::String(data) => serde_json::from_str(data).unwrap_or(Value::Null),
+                data => data.clone(),
+            };
+            let object_id = match &data["id"] {
+                Value::Number(n) => Some(n.to_string()),
+                Value::String(s) => Some(s.clone()),
+                _ => None,
+            };
+            event["event"].as_str().zip(object_id).map(|(name, id)| format!("{}:{}", name, id))
+        }
+        PaymentGateway::Stripe => event["id"].as_str().map(|id| id.to_string()),
+        PaymentGateway::Daraja => event["Body"]["stkCallback"]["CheckoutRequestID"]
+            .as_str()
+            .map(|id| id.to_string()),
+    };
+
+    match event_id {
+        Some(id) => format!("{}:{}", prefix, id),
+        None => format!("{}:sha256:{}", prefix, hex::encode(Sha256::digest(payload.as_bytes()))),
+    }
+}
+
+fn event_type(gateway: PaymentGateway, payload: &str) -> String {
+    let event: Value = serde_json::from_str(payload).unwrap_or(Value::Null);
+    let event_type = match gateway {
+        PaymentGateway::Paystack => event["event"].as_str(),
+        PaymentGateway::Stripe => event["type"].as_str(),
+        PaymentGateway::Daraja => (!event["Body"]["stkCallback"].is_null()).then_some("stkCallback"),
+    };
+    event_type.unwrap_or("unknown").to_string()
+}
+
+pub fn get_webhook_event(id: &str) -> Option<WebhookEvent> {
+    WEBHOOK_EVENTS.with(|events| events.borrow().get(&StableString::from(id.to_string())))
+}
+
+fn store_webhook_event(event: WebhookEvent) {
+    WEBHOOK_EVENTS.with(|events| {
+        events.borrow_mut().insert(StableString::from(event.id.clone()), event);
+    });
+}
+
+/// Record a verified delivery before processing it.
+///
+/// Returns the event id, or an error if the same event was already processed
+/// or is being processed. A redelivery of a failed event is processed again.
+pub fn begin_webhook_event(
+    source: WebhookSource,
+    gateway: PaymentGateway,
+    payload: &str,
+    now: u64,
+) -> Result<String, String> {
+    let id = webhook_event_id(gateway, payload);
+
+    let event = match get_webhook_event(&id) {
+        Some(event) if event.status != WebhookEventStatus::Failed => {
+            return Err(format!("Duplicate webhook event: {}", id));
+        }
+        Some(mut event) => {
+            event.status = WebhookEventStatus::Processing;
+            event.attempts += 1;
+            event
+        }
+        None => WebhookEvent {
+            id: id.clone(),
+            source,
+            gateway,
+            event_type: event_type(gateway, payload),
+            payload: (payload.len() <= MAX_STORED_PAYLOAD).then(|| payload.to_string()),
+            status: WebhookEventStatus::Processing,
+            attempts: 1,
+            received_at: now,
+            processed_at: None,
+            result: None,
+            error: None,
+        },
+    };
+
+    store_webhook_event(event);
+    Ok(id)
+}
+
+/// Record the outcome of processing an event
+pub fn finish_webhook_event(id: &str, outcome: &Result<String, String>, now: u64) {
+    if let Some(mut event) = get_webhook_event(id) {
+        match outcome {
+            Ok(result) => {
+                event.status = WebhookEventStatus::Processed;
+                event.result = Some(result.clone());
+                event.error = None;
+            }
+            Err(error) => {
+                event.status = WebhookEventStatus::Failed;
+                event.error = Some(error.clone());
+            }
+        }
+        event.processed_at = Some(now);
+        store_webhook_event(event);
+    }
+}
+
+/// Claim a failed event for another attempt and return it with its payload
+pub fn begin_reprocess(id: &str) -> Result<(WebhookEvent, String), String> {
+    let mut event = get_webhook_event(id)
+        .ok_or_else(|| format!("Webhook event not found: {}", id))?;
+
+    if event.status != WebhookEventStatus::Failed {
+        return Err(format!("Webhook event {} has not failed", id));
+    }
+    let payload = event
+        .payload
+        .clone()
+        .ok_or_else(|| format!("Payload of webhook event {} was too large to keep", id))?;
+
+    event.status = WebhookEventStatus::Processing;
+    event.attempts += 1;
+    store_webhook_event(event.clone());
+    Ok((event, payload))
+}
+
+/// Webhook events, newest first, optionally with one status
+pub fn list_webhook_events(status: Option<WebhookEventStatus>) -> Vec<WebhookEvent> {
+    let mut events = WEBHOOK_EVENTS.with(|events| {
+        events
+            .borrow()
+            .iter()
+            .map(|(_, event)| event)
+            .filter(|event| status.as_ref().is_none_or(|status| &event.status == status))
+            .collect::<Vec<_>>()
+    });
+    events.sort_by_key(|event| std::cmp::Reverse(event.received_at));
+    events
+}
@@ -0,0 +1,83 @@
+// backend/src/storage/blob_store.rs
+//
+// Named byte blobs kept outside the records that own them. A blob is stored
+// as consecutive chunks in BLOB_CHUNKS, so reading or rewriting the record
+// that refers to it never copies the payload. Owners name their blobs after
+// their own key, e.g. "api_response/{message id}", and remove them with it.
+
+use crate::models::stable_string::StableString;
+use crate::storage::memory::BLOB_CHUNKS;
+
+/// Bytes per stored chunk
+pub const BLOB_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest blob the store accepts
+pub const MAX_BLOB_SIZE: usize = 2 * 1024 * 1024;
+
+/// Rejects a payload too large to store, before anything is written
+pub fn check_blob_size(name: &str, size: usize) -> Result<(), String> {
+    if size > MAX_BLOB_SIZE {
+        return Err(format!("{} is {} bytes, over the {} byte limit", name, size, MAX_BLOB_SIZE));
+    }
+    Ok(())
+}
+
+/// Stores `bytes` under `name`, replacing any blob already stored there
+pub fn put_blob(name: &str, bytes: &[u8]) -> Result<(), String> {
+    check_blob_size(name, bytes.len())?;
+
+    delete_blob(name);
+    BLOB_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        // An empty blob still gets a chunk, so it reads back as present
+        let mut parts: Vec<&[u8]> = bytes.chunks(BLOB_CHUNK_SIZE).collect();
+        if parts.is_empty() {
+            parts.push(&[]);
+        }
+        for (index, part) in parts.into_iter().enumerate() {
+            chunks.insert((StableString::from(name), index as u32), part.to_vec());
+        }
+    });
+    Ok(())
+}
+
+pub fn get_blob(name: &str) -> Option<Vec<u8>> {
+    let parts: Vec<Vec<u8>> = BLOB_CHUNKS.with(|chunks| {
+        chunks
+            .borrow()
+            .range((StableString::from(name), 0)..)
+            .take_while(|((blob, _), _)| blob.as_str() == name)
+            .map(|(_, part)| part)
+            .collect()
+    });
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.concat())
+    }
+}
+
+/// Removes the blob stored under `name`; returns whether there was one
+pub fn delete_blob(name: &str) -> bool {
+    BLOB_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        let keys: Vec<(StableString, u32)> = chunks
+            .range((StableString::from(name), 0)..)
+            .take_while(|((blob, _), _)| blob.as_str() == name)
+            .map(|(key, _)| key)
+            .collect();
+        for key in &keys {
+            chunks.remove(key);
+        }
+        !keys.is_empty()
+    })
+}
+
+/// Moves the blob stored under `from` to `to`, e.g. when its owner is re-keyed
+pub fn rename_blob(from: &str, to: &str) {
+    if let Some(bytes) = get_blob(from) {
+        delete_blob(from);
+        // Fits: it was accepted under its old name
+        let _ = put_blob(to, &bytes);
+    }
+}
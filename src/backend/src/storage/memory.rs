@@ -3,177 +3,401 @@ use crate::models::api_message::ApiMessage;
 use crate::models::connected_accounts::ConnectedAccounts;
 use crate::models::dashboard_token::DashboardToken;
 use crate::models::github::Issue;
-use crate::models::openchat_user::OpenChatUser;
-use crate::models::slack_user::SlackUser;
-use crate::models::discord_user::DiscordUser;
 use crate::models::stable_principal::StablePrincipal;
 use crate::models::stable_string::StableString;
 use crate::models::task::Task;
 use crate::models::{chat::ChatMessage, user::User, waitlist::WaitlistEntry};
-use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::memory_manager::{MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 use std::cell::RefCell;
 use crate::models::accelerator::Accelerator;
 use crate::models::startup_invite::StartupInvite;
 use crate::models::startup::{Startup, StartupStatus, StartupCohort, StartupActivity};
 use crate::models::usage_service::UserSubscription;
-use crate::models::payment::{PaymentRecord, Invoice};
+use crate::models::payment::{PaymentRecord, Invoice, RefundRecord, InvoiceSettings};
+use crate::models::audit_log::AuditEntry;
 use crate::models::main_site_user::MainSiteUser;
 use crate::models::analytics::AnalyticsDataPoint;
+use crate::models::subscription_billing::{SubscriptionBilling, BillingSettings};
+use crate::models::plan::Plan;
+use crate::models::coupon::{Coupon, CouponRedemption};
+use crate::models::sponsorship::{SeatSponsorship, SeatAssignment};
+use crate::models::webhook_event::WebhookEvent;
+use crate::models::bot_principal::BotPrincipal;
+use crate::models::platform::Platform;
+use crate::models::platform_identity::PlatformIdentity;
+use crate::models::merged_record::MergedRecord;
+use crate::models::quota::{QuotaPolicy, TokenBucket};
+use crate::models::record::QuarantinedRecord;
+use crate::storage::record_map::RecordMap;
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
+/// Key of a platform identity: the platform and the user's id on it
+pub type IdentityKey = (Platform, StableString);
+
+/// The memory each stable map lives in. Ids are never reused: 6, 9 and 10
+/// held the OpenChat, Slack and Discord user maps now in PLATFORM_IDENTITIES.
+pub mod memory_ids {
+    use ic_stable_structures::memory_manager::MemoryId;
+
+    pub const USERS: MemoryId = MemoryId::new(0);
+    pub const WAITLIST: MemoryId = MemoryId::new(1);
+    pub const CHAT_HISTORY: MemoryId = MemoryId::new(2);
+    pub const ADMINS: MemoryId = MemoryId::new(3);
+    pub const CONNECTED_ACCOUNTS: MemoryId = MemoryId::new(4);
+    pub const TASKS: MemoryId = MemoryId::new(5);
+    pub const DASHBOARD_TOKENS: MemoryId = MemoryId::new(7);
+    pub const GITHUB_ISSUES: MemoryId = MemoryId::new(8);
+    pub const ACCELERATORS: MemoryId = MemoryId::new(11);
+    pub const STARTUPS: MemoryId = MemoryId::new(12);
+    pub const STARTUP_STATUSES: MemoryId = MemoryId::new(13);
+    pub const STARTUP_COHORTS: MemoryId = MemoryId::new(14);
+    pub const STARTUP_ACTIVITIES: MemoryId = MemoryId::new(15);
+    pub const STARTUP_INVITES: MemoryId = MemoryId::new(16);
+    pub const API_MESSAGES: MemoryId = MemoryId::new(17);
+    pub const USER_DAILY_USAGE: MemoryId = MemoryId::new(18);
+    pub const USER_SUBSCRIPTIONS: MemoryId = MemoryId::new(19);
+    pub const PAYMENT_RECORDS: MemoryId = MemoryId::new(20);
+    pub const INVOICES: MemoryId = MemoryId::new(21);
+    pub const USER_ANALYTICS: MemoryId = MemoryId::new(22);
+    pub const LEGACY_MAIN_SITE_USERS: MemoryId = MemoryId::new(23);
+    pub const SUBSCRIPTION_BILLING: MemoryId = MemoryId::new(24);
+    pub const BILLING_SETTINGS: MemoryId = MemoryId::new(25);
+    pub const REFUNDS: MemoryId = MemoryId::new(26);
+    pub const PLANS: MemoryId = MemoryId::new(27);
+    pub const COUPONS: MemoryId = MemoryId::new(28);
+    pub const COUPON_REDEMPTIONS: MemoryId = MemoryId::new(29);
+    pub const SEAT_SPONSORSHIPS: MemoryId = MemoryId::new(30);
+    pub const SEAT_ASSIGNMENTS: MemoryId = MemoryId::new(31);
+    pub const WEBHOOK_EVENTS: MemoryId = MemoryId::new(32);
+    pub const INVOICE_SEQUENCES: MemoryId = MemoryId::new(33);
+    pub const INVOICE_SETTINGS: MemoryId = MemoryId::new(34);
+    pub const BOT_PRINCIPALS: MemoryId = MemoryId::new(35);
+    pub const AUDIT_LOG: MemoryId = MemoryId::new(36);
+    pub const AUDIT_ARCHIVE: MemoryId = MemoryId::new(37);
+    pub const PLATFORM_IDENTITIES: MemoryId = MemoryId::new(38);
+    pub const PLATFORM_IDENTITIES_BY_PRINCIPAL: MemoryId = MemoryId::new(39);
+    pub const MERGED_RECORDS: MemoryId = MemoryId::new(40);
+    pub const QUOTA_POLICIES: MemoryId = MemoryId::new(41);
+    pub const QUOTA_BUCKETS: MemoryId = MemoryId::new(42);
+    pub const SCHEMA_VERSION: MemoryId = MemoryId::new(43);
+    pub const QUARANTINED_RECORDS: MemoryId = MemoryId::new(44);
+    pub const BLOB_CHUNKS: MemoryId = MemoryId::new(45);
+    pub const SNAPSHOT_STAGING: MemoryId = MemoryId::new(46);
+}
+
 thread_local! {
     pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
         MemoryManager::init(DefaultMemoryImpl::default())
     );
 
-    pub static USERS: RefCell<StableBTreeMap<StablePrincipal, User, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0)))
+    pub static USERS: RefCell<RecordMap<StablePrincipal, User>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::USERS))
         )
     );
 
-    pub static WAITLIST: RefCell<StableBTreeMap<StableString, WaitlistEntry, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
+    pub static WAITLIST: RefCell<RecordMap<StableString, WaitlistEntry>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::WAITLIST))
         )
     );
 
-    pub static CHAT_HISTORY: RefCell<StableBTreeMap<(StablePrincipal, u64), ChatMessage, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))
+    pub static CHAT_HISTORY: RefCell<RecordMap<(StablePrincipal, u64), ChatMessage>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::CHAT_HISTORY))
         )
     );
 
-    pub static ADMINS: RefCell<StableBTreeMap<StablePrincipal, Admin, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))
+    pub static ADMINS: RefCell<RecordMap<StablePrincipal, Admin>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::ADMINS))
         )
     );
 
-    pub static CONNECTED_ACCOUNTS: RefCell<StableBTreeMap<StablePrincipal, ConnectedAccounts, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))
+    pub static CONNECTED_ACCOUNTS: RefCell<RecordMap<StablePrincipal, ConnectedAccounts>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::CONNECTED_ACCOUNTS))
         )
     );
 
-    pub static TASKS: RefCell<StableBTreeMap<(StablePrincipal, StableString), Task, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))
+    pub static TASKS: RefCell<RecordMap<(StablePrincipal, StableString), Task>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::TASKS))
         )
     );
 
-    pub static OPENCHAT_USERS: RefCell<StableBTreeMap<StableString, OpenChatUser, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
+
+    pub static DASHBOARD_TOKENS: RefCell<RecordMap<StableString, DashboardToken>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::DASHBOARD_TOKENS))
         )
     );
 
-    pub static DASHBOARD_TOKENS: RefCell<StableBTreeMap<StableString, DashboardToken, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+    pub static GITHUB_ISSUES: RefCell<RecordMap<(StablePrincipal, StableString), Issue>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::GITHUB_ISSUES))
         )
     );
 
-    pub static GITHUB_ISSUES: RefCell<StableBTreeMap<(StablePrincipal, StableString), Issue, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))
+    pub static API_MESSAGES: RefCell<RecordMap<(StableString, u64), ApiMessage>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::API_MESSAGES))
         )
     );
 
-    pub static SLACK_USERS: RefCell<StableBTreeMap<StableString, SlackUser, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9)))
+    pub static ACCELERATORS: RefCell<RecordMap<StablePrincipal, Accelerator>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::ACCELERATORS))
         )
     );
 
-    pub static DISCORD_USERS: RefCell<StableBTreeMap<StableString, DiscordUser, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+    pub static STARTUP_INVITES: RefCell<RecordMap<StableString, StartupInvite>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::STARTUP_INVITES))
         )
     );
 
-    pub static API_MESSAGES: RefCell<StableBTreeMap<(StableString, u64), ApiMessage, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
+    // Startup Management Storage
+    pub static STARTUPS: RefCell<RecordMap<StableString, Startup>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::STARTUPS))
         )
     );
 
-    pub static ACCELERATORS: RefCell<StableBTreeMap<StablePrincipal, Accelerator, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
+    pub static STARTUP_STATUSES: RefCell<RecordMap<StableString, StartupStatus>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::STARTUP_STATUSES))
         )
     );
 
-    pub static STARTUP_INVITES: RefCell<StableBTreeMap<StableString, StartupInvite, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+    pub static STARTUP_COHORTS: RefCell<RecordMap<StableString, StartupCohort>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::STARTUP_COHORTS))
         )
     );
 
-    // Startup Management Storage
-    pub static STARTUPS: RefCell<StableBTreeMap<StableString, Startup, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+    pub static STARTUP_ACTIVITIES: RefCell<RecordMap<(StableString, u64), StartupActivity>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::STARTUP_ACTIVITIES))
         )
     );
 
-    pub static STARTUP_STATUSES: RefCell<StableBTreeMap<StableString, StartupStatus, Memory>> = RefCell::new(
+    // --- NEW USAGE TRACKING STORAGE ---
+    pub static USER_DAILY_USAGE: RefCell<StableBTreeMap<(StableString, u64), u32, Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::USER_DAILY_USAGE))
         )
     );
 
-    pub static STARTUP_COHORTS: RefCell<StableBTreeMap<StableString, StartupCohort, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+    pub static USER_SUBSCRIPTIONS: RefCell<RecordMap<StableString, UserSubscription>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::USER_SUBSCRIPTIONS))
         )
     );
 
-    pub static STARTUP_ACTIVITIES: RefCell<StableBTreeMap<(StableString, u64), StartupActivity, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
+    // --- PAYMENT STORAGE ---
+    pub static PAYMENT_RECORDS: RefCell<RecordMap<StableString, PaymentRecord>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::PAYMENT_RECORDS))
         )
     );
 
-    // --- NEW USAGE TRACKING STORAGE ---
-    pub static USER_DAILY_USAGE: RefCell<StableBTreeMap<(StableString, u64), u32, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+    pub static INVOICES: RefCell<RecordMap<StableString, Invoice>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::INVOICES))
+        )
+    );
+
+    // --- ANALYTICS STORAGE ---
+    pub static USER_ANALYTICS: RefCell<RecordMap<(StableString, u64), AnalyticsDataPoint>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::USER_ANALYTICS))
+        )
+    );
+
+    // --- MAIN SITE USERS STORAGE ---
+    // Retired: read once on upgrade to move its records into PLATFORM_IDENTITIES,
+    // see platform_identity_service::migrate_legacy_main_site_users
+    pub static LEGACY_MAIN_SITE_USERS: RefCell<RecordMap<StableString, MainSiteUser>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::LEGACY_MAIN_SITE_USERS))
+        )
+    );
+
+    // --- SUBSCRIPTION BILLING STORAGE ---
+    pub static SUBSCRIPTION_BILLING: RefCell<RecordMap<StableString, SubscriptionBilling>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::SUBSCRIPTION_BILLING))
+        )
+    );
+
+    // Single entry under key 0
+    pub static BILLING_SETTINGS: RefCell<RecordMap<u8, BillingSettings>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::BILLING_SETTINGS))
+        )
+    );
+
+    // --- REFUNDS STORAGE ---
+    pub static REFUNDS: RefCell<RecordMap<StableString, RefundRecord>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::REFUNDS))
         )
     );
 
-    pub static USER_SUBSCRIPTIONS: RefCell<StableBTreeMap<StableString, UserSubscription, Memory>> = RefCell::new(
+    // --- PRICE CATALOG STORAGE ---
+    pub static PLANS: RefCell<RecordMap<StableString, Plan>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::PLANS))
+        )
+    );
+
+    // --- COUPONS STORAGE ---
+    pub static COUPONS: RefCell<RecordMap<StableString, Coupon>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::COUPONS))
+        )
+    );
+
+    // Keyed by payment reference
+    pub static COUPON_REDEMPTIONS: RefCell<RecordMap<StableString, CouponRedemption>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::COUPON_REDEMPTIONS))
+        )
+    );
+
+    // --- SPONSORED SEATS STORAGE ---
+    // Keyed by payment reference
+    pub static SEAT_SPONSORSHIPS: RefCell<RecordMap<StableString, SeatSponsorship>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::SEAT_SPONSORSHIPS))
+        )
+    );
+
+    // Keyed by "{sponsorship_id}:{startup_id}"
+    pub static SEAT_ASSIGNMENTS: RefCell<RecordMap<StableString, SeatAssignment>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::SEAT_ASSIGNMENTS))
+        )
+    );
+
+    // --- WEBHOOK EVENTS STORAGE ---
+    // Keyed by gateway event id, see webhook_service::webhook_event_id
+    pub static WEBHOOK_EVENTS: RefCell<RecordMap<StableString, WebhookEvent>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::WEBHOOK_EVENTS))
+        )
+    );
+
+    // --- INVOICE NUMBERING STORAGE ---
+    // Last number issued per series and year, e.g. "INF-2025" -> 42
+    pub static INVOICE_SEQUENCES: RefCell<StableBTreeMap<StableString, u64, Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::INVOICE_SEQUENCES))
         )
     );
 
-    // --- PAYMENT STORAGE ---
-    pub static PAYMENT_RECORDS: RefCell<StableBTreeMap<StableString, PaymentRecord, Memory>> = RefCell::new(
+    pub static INVOICE_SETTINGS: RefCell<RecordMap<u8, InvoiceSettings>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::INVOICE_SETTINGS))
+        )
+    );
+
+    // --- BOT PRINCIPALS STORAGE ---
+    // Trusted bot backends allowed to call the bot-facing API
+    pub static BOT_PRINCIPALS: RefCell<RecordMap<StablePrincipal, BotPrincipal>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::BOT_PRINCIPALS))
+        )
+    );
+
+    // --- AUDIT LOG STORAGE ---
+    // Append-only, keyed by entry id. Entries past the retention window move to
+    // AUDIT_ARCHIVE under the same id, see audit_service::archive_audit_entries
+    pub static AUDIT_LOG: RefCell<RecordMap<u64, AuditEntry>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::AUDIT_LOG))
+        )
+    );
+
+    pub static AUDIT_ARCHIVE: RefCell<RecordMap<u64, AuditEntry>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::AUDIT_ARCHIVE))
+        )
+    );
+
+    // --- PLATFORM IDENTITIES STORAGE ---
+    // One record per (platform, external id), plus an index of linked identities
+    // by site principal. Kept in step by platform_identity_service::save_identity
+    pub static PLATFORM_IDENTITIES: RefCell<RecordMap<IdentityKey, PlatformIdentity>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::PLATFORM_IDENTITIES))
+        )
+    );
+
+    pub static PLATFORM_IDENTITIES_BY_PRINCIPAL: RefCell<StableBTreeMap<(StablePrincipal, IdentityKey), (), Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::PLATFORM_IDENTITIES_BY_PRINCIPAL))
+        )
+    );
+
+    // --- MERGED RECORDS STORAGE ---
+    // Records moved to a site principal when a platform identity was linked,
+    // keyed by identity and a per-identity sequence number
+    pub static MERGED_RECORDS: RefCell<RecordMap<(IdentityKey, u64), MergedRecord>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::MERGED_RECORDS))
         )
     );
 
-    pub static INVOICES: RefCell<StableBTreeMap<StableString, Invoice, Memory>> = RefCell::new(
+    // --- QUOTA STORAGE ---
+    // Per-tier request limits, keyed by tier name; tiers without an entry use
+    // QuotaPolicy::default_for
+    pub static QUOTA_POLICIES: RefCell<RecordMap<StableString, QuotaPolicy>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::QUOTA_POLICIES))
+        )
+    );
+
+    // Burst token bucket of each quota account
+    pub static QUOTA_BUCKETS: RefCell<RecordMap<StableString, TokenBucket>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::QUOTA_BUCKETS))
+        )
+    );
+
+    // --- SCHEMA VERSION ---
+    // Single entry under key 0: the last schema migration applied to the maps
+    pub static SCHEMA_VERSION: RefCell<StableBTreeMap<u8, u32, Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::SCHEMA_VERSION))
         )
     );
 
-    // --- ANALYTICS STORAGE ---
-    pub static USER_ANALYTICS: RefCell<StableBTreeMap<(StableString, u64), AnalyticsDataPoint, Memory>> = RefCell::new(
+    // Undecodable values moved out of their maps by the record repair tool
+    pub static QUARANTINED_RECORDS: RefCell<RecordMap<u64, QuarantinedRecord>> = RefCell::new(
+        RecordMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::QUARANTINED_RECORDS))
+        )
+    );
+
+    // --- BLOB STORE ---
+    // Payloads too large to keep inside a record, e.g. logos and long bot
+    // responses, split into chunks keyed by (blob name, chunk index)
+    pub static BLOB_CHUNKS: RefCell<StableBTreeMap<(StableString, u32), Vec<u8>, Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::BLOB_CHUNKS))
         )
     );
 
-    // --- MAIN SITE USERS STORAGE ---
-    pub static MAIN_SITE_USERS: RefCell<StableBTreeMap<StableString, MainSiteUser, Memory>> = RefCell::new(
+    // --- SNAPSHOT STAGING ---
+    // Pages of a map being restored from a snapshot, as stored, keyed by key
+    // bytes; copied into the map once all its pages are in
+    pub static SNAPSHOT_STAGING: RefCell<StableBTreeMap<Vec<u8>, Vec<u8>, Memory>> = RefCell::new(
         StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23)))
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory_ids::SNAPSHOT_STAGING))
         )
     );
 }
@@ -1 +1,3 @@
 pub mod memory;
+pub mod blob_store;
+pub mod record_map;
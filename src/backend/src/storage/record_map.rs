@@ -0,0 +1,130 @@
+// backend/src/storage/record_map.rs
+//
+// A stable map of enveloped records. Values are kept as the bytes
+// `encode_record` wrote and decoded on every read with `decode_record`, so a
+// value this release can't read never traps the call that reaches it: it is
+// skipped and reported instead. `admin_scan_records` lists such values and
+// `admin_quarantine_undecodable_records` moves them out of the map.
+
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use ic_stable_structures::{StableBTreeMap, Storable};
+
+use crate::models::record::{decode_record, encode_record, VersionedRecord};
+use crate::storage::memory::Memory;
+
+/// `StableBTreeMap` of `VersionedRecord` values that skips values it cannot decode
+pub struct RecordMap<K, V, M = Memory>
+where
+    K: Storable + Ord + Clone,
+    M: ic_stable_structures::Memory,
+{
+    map: StableBTreeMap<K, Vec<u8>, M>,
+    record_type: PhantomData<V>,
+}
+
+impl<K, V, M> RecordMap<K, V, M>
+where
+    K: Storable + Ord + Clone,
+    V: VersionedRecord,
+    M: ic_stable_structures::Memory,
+{
+    pub fn init(memory: M) -> Self {
+        RecordMap { map: StableBTreeMap::init(memory), record_type: PhantomData }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.map.get(key).and_then(|bytes| decode_or_report(key, &bytes))
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Number of stored values, including any that don't decode
+    pub fn len(&self) -> u64 {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Stores `value`, returning the value it replaced if that one decodes
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let replaced = self.map.insert(key.clone(), encode_record(&value))?;
+        decode_or_report(&key, &replaced)
+    }
+
+    /// Removes the value under `key`, returning it if it decodes
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let removed = self.map.remove(key)?;
+        decode_or_report(key, &removed)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (K, V)> + '_ {
+        self.map.iter().filter_map(|(key, bytes)| decode_or_report(&key, &bytes).map(|value| (key, value)))
+    }
+
+    pub fn range(&self, key_range: impl RangeBounds<K>) -> impl DoubleEndedIterator<Item = (K, V)> + '_ {
+        self.map.range(key_range).filter_map(|(key, bytes)| decode_or_report(&key, &bytes).map(|value| (key, value)))
+    }
+
+    /// Keys of every stored value, including any that don't decode
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = K> + '_ {
+        self.map.keys()
+    }
+
+    pub fn first_key_value(&self) -> Option<(K, V)> {
+        self.iter().next()
+    }
+
+    pub fn last_key_value(&self) -> Option<(K, V)> {
+        self.iter().next_back()
+    }
+
+    pub fn clear_new(&mut self) {
+        self.map.clear_new();
+    }
+
+    /// Every entry with its value as stored, without decoding it
+    pub fn raw_iter(&self) -> impl DoubleEndedIterator<Item = (K, Vec<u8>)> + '_ {
+        self.map.iter()
+    }
+
+    /// Entries with their values as stored, without decoding them
+    pub fn raw_range(&self, key_range: impl RangeBounds<K>) -> impl DoubleEndedIterator<Item = (K, Vec<u8>)> + '_ {
+        self.map.range(key_range)
+    }
+
+    /// Stores bytes exactly as given, e.g. a value restored from a snapshot
+    pub fn insert_raw(&mut self, key: K, bytes: Vec<u8>) -> Option<Vec<u8>> {
+        self.map.insert(key, bytes)
+    }
+
+    /// Removes the value under `key` without decoding it
+    pub fn remove_raw(&mut self, key: &K) -> Option<Vec<u8>> {
+        self.map.remove(key)
+    }
+}
+
+fn decode_or_report<K: Storable, V: VersionedRecord>(key: &K, bytes: &[u8]) -> Option<V> {
+    match decode_record(bytes) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            report_skipped(&key.to_bytes(), &error);
+            None
+        }
+    }
+}
+
+/// Logs a value a read skipped, with its key as stored
+fn report_skipped(key: &[u8], error: &str) {
+    let message = format!("Skipped record with key {}: {}. Run admin_scan_records to find it.", hex::encode(key), error);
+    // The canister log is only reachable from inside a canister
+    #[cfg(target_arch = "wasm32")]
+    ic_cdk::println!("{}", message);
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{}", message);
+}
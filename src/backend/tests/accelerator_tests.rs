@@ -1,7 +1,7 @@
 // Accelerator & Team Member Feature Tests
 // =======================================
 
-use candid::{Principal, encode_one, decode_one};
+use candid::{Principal, encode_args, encode_one, decode_one};
 use pocket_ic::PocketIc;
 use std::fs;
 
@@ -12,6 +12,9 @@ const TEST_ACCELERATOR_EMAIL: &str = "test@accelerator.com";
 const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
 const INIT_CYCLES: u128 = 2_000_000_000_000;
 
+// Anonymous callers are rejected, so calls are made as this identity
+const TEST_CALLER: Principal = Principal::from_slice(&[1; 29]);
+
 // Test struct definitions
 #[derive(candid::CandidType, candid::Deserialize, Clone, Debug)]
 struct AcceleratorSignUp {
@@ -88,7 +91,7 @@ fn setup() -> (PocketIc, Principal) {
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, INIT_CYCLES);
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
     (pic, backend_canister)
 }
 
@@ -103,7 +106,7 @@ fn test_accelerator_signup() {
     let encoded = encode_one(signup_data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -122,7 +125,7 @@ fn test_invite_team_member() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -138,7 +141,7 @@ fn test_invite_team_member() {
     let encoded = encode_one(invite_data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
@@ -160,7 +163,7 @@ fn test_accept_invitation() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -176,7 +179,7 @@ fn test_accept_invitation() {
     let encoded = encode_one(invite_data).unwrap();
     let invite_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
@@ -184,7 +187,7 @@ fn test_accept_invitation() {
     
     let token = decode_one::<Result<String, String>>(&invite_result.unwrap()).unwrap().unwrap();
     
-    let non_admin_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let non_admin_principal = TEST_CALLER;
     let encoded = encode_one(token).unwrap();
     let accept_result = pic.update_call(
         canister_id,
@@ -198,7 +201,7 @@ fn test_accept_invitation() {
     let encoded = encode_one(accelerator_id.clone()).unwrap();
     let team_result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "list_team_members",
         encoded,
     );
@@ -238,7 +241,7 @@ fn test_list_team_members() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -249,7 +252,7 @@ fn test_list_team_members() {
     let encoded = encode_one(accelerator_id.clone()).unwrap();
     let result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "list_team_members",
         encoded,
     );
@@ -274,7 +277,7 @@ fn test_update_team_member_role() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -290,14 +293,14 @@ fn test_update_team_member_role() {
     let encoded = encode_one(invite_data).unwrap();
     let invite_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
     assert!(invite_result.is_ok(), "Team member invite should succeed");
     
     let token = decode_one::<Result<String, String>>(&invite_result.unwrap()).unwrap().unwrap();
-    let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let new_principal = TEST_CALLER;
     let encoded = encode_one(token).unwrap();
     let accept_result = pic.update_call(
         canister_id,
@@ -315,7 +318,7 @@ fn test_update_team_member_role() {
     let encoded = encode_one(update_role_data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "update_team_member_role",
         encoded,
     );
@@ -334,7 +337,7 @@ fn test_remove_team_member() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -350,7 +353,7 @@ fn test_remove_team_member() {
     let encoded = encode_one(invite_data).unwrap();
     let invite_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
@@ -358,7 +361,7 @@ fn test_remove_team_member() {
     
     let token = decode_one::<Result<String, String>>(&invite_result.unwrap()).unwrap().unwrap();
     
-    let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let new_principal = TEST_CALLER;
     let encoded = encode_one(token).unwrap();
     let accept_result = pic.update_call(
         canister_id,
@@ -375,7 +378,7 @@ fn test_remove_team_member() {
     let encoded = encode_one(remove_data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "remove_team_member",
         encoded,
     );
@@ -385,8 +388,8 @@ fn test_remove_team_member() {
 // #[test]
 // fn test_permission_checks() {
 //     let pic = PocketIc::new();
-//     let admin_principal = Principal::anonymous();
-//     let program_manager_principal = Principal::anonymous();
+//     let admin_principal = TEST_CALLER;
+//     let program_manager_principal = TEST_CALLER;
 //     let canister_id = pic.create_canister();
 //     pic.add_cycles(canister_id, INIT_CYCLES);
 //     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
@@ -506,7 +509,7 @@ fn test_invalid_invitation_token() {
     let encoded = encode_one(invalid_token).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "accept_invitation",
         encoded,
     );
@@ -528,7 +531,7 @@ fn test_duplicate_email_invite() {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -544,7 +547,7 @@ fn test_duplicate_email_invite() {
     let encoded = encode_one(invite_data.clone()).unwrap();
     let result1 = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
@@ -553,7 +556,7 @@ fn test_duplicate_email_invite() {
     let encoded = encode_one(invite_data).unwrap();
     let result2 = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "invite_team_member",
         encoded,
     );
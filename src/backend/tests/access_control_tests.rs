@@ -0,0 +1,109 @@
+use candid::{encode_args, Principal};
+use pocket_ic::PocketIc;
+use std::fs;
+
+const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
+const BACKEND_DID: &str = "backend.did";
+const INIT_CYCLES: u128 = 2_000_000_000_000;
+
+// Endpoints an anonymous caller may reach: public catalogue and config, webhooks,
+// invite lookups, session checks and the platform user lookups
+const PUBLIC_METHODS: &[&str] = &[
+    "get_plans",
+    "payment_get_config",
+    "payment_get_stripe_config",
+    "payment_get_daraja_config",
+    "payment_get_provider_routes",
+    "payment_webhook",
+    "payment_provider_webhook",
+    "transform_payment_http_response",
+    "is_admin",
+    "get_my_admin_role",
+    "check_auth",
+    "is_registered",
+    "verify_token",
+    "get_token_info",
+    "validate_dashboard_token",
+    "get_team_invite_by_token",
+    "get_startup_invite_by_code",
+    // Bot-facing endpoints outside api_service and token_service
+    "get_slack_user",
+    "get_discord_user",
+    "get_openchat_user",
+    "is_slack_user_registered",
+    "is_discord_user_registered",
+    "get_platform_identity",
+];
+
+fn setup() -> (PocketIc, Principal) {
+    std::env::set_var("POCKET_IC_BIN", "/usr/local/bin/pocket-ic");
+    let pic = PocketIc::new();
+    let backend_canister = pic.create_canister();
+    pic.add_cycles(backend_canister, INIT_CYCLES);
+    let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
+    (pic, backend_canister)
+}
+
+/// (method name, is query) for every method in the service block of backend.did
+fn service_methods() -> Vec<(String, bool)> {
+    let did = fs::read_to_string(BACKEND_DID).expect("backend.did not found");
+    let service = did.split_once("\nservice :").expect("no service block").1;
+    let body = service.split_once('{').unwrap().1;
+
+    let mut methods = vec![];
+    let mut current: Option<(String, String)> = None;
+    for line in body.lines() {
+        // Methods start at two spaces of indentation; longer signatures wrap onto indented lines
+        let (name, mut signature) = match current.take() {
+            Some((name, signature)) => (name, signature + line.trim()),
+            None => match line.strip_prefix("  ").and_then(|l| l.split_once(" : ")) {
+                Some((name, signature)) => (name.to_string(), signature.trim().to_string()),
+                None => continue,
+            },
+        };
+        if signature.ends_with(';') {
+            signature.pop();
+            methods.push((name, signature.ends_with("query")));
+        } else {
+            current = Some((name, signature));
+        }
+    }
+    methods
+}
+
+fn call_anonymously(pic: &PocketIc, canister_id: Principal, method: &str, query: bool) -> Result<(), String> {
+    // Guards run before argument decoding, so empty arguments are enough to reach them
+    let args = encode_args(()).unwrap();
+    let result = if query {
+        pic.query_call(canister_id, Principal::anonymous(), method, args)
+    } else {
+        pic.update_call(canister_id, Principal::anonymous(), method, args)
+    };
+    result.map(|_| ()).map_err(|e| format!("{:?}", e))
+}
+
+#[test]
+fn test_anonymous_callers_are_rejected() {
+    let methods = service_methods();
+    assert!(methods.len() > 100, "parsed only {} methods from backend.did", methods.len());
+    for public in PUBLIC_METHODS {
+        assert!(methods.iter().any(|(name, _)| name == public), "{} is not in backend.did", public);
+    }
+
+    let (pic, canister_id) = setup();
+    let mut unguarded = vec![];
+    for (method, query) in &methods {
+        let result = call_anonymously(&pic, canister_id, method, *query);
+        let rejected = matches!(&result, Err(e) if e.contains("Unauthorized"));
+
+        if PUBLIC_METHODS.contains(&method.as_str()) {
+            assert!(!rejected, "{} should be reachable anonymously: {:?}", method, result);
+        } else if !rejected {
+            unguarded.push(method.clone());
+        }
+    }
+
+    assert!(unguarded.is_empty(), "anonymous callers reach: {:?}", unguarded);
+    println!("✅ Anonymous access test passed");
+}
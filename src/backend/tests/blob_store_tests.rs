@@ -0,0 +1,211 @@
+use backend::models::accelerator::{Accelerator, Activity, ActivityType};
+use backend::models::api_message::ApiMessage;
+use backend::models::chat::{ChatMessage, MessageRole};
+use backend::models::platform::Platform;
+use backend::models::record::{check_record_size, MAX_RECORD_SIZE};
+use backend::models::stable_principal::StablePrincipal;
+use backend::models::stable_string::StableString;
+use backend::models::waitlist::{WaitlistEntry, WaitlistStatus};
+use backend::services::accelerator_service::{logo_blob, move_inline_logos, set_logo, with_logo};
+use backend::services::api_service::{move_long_responses, response_blob, with_response};
+use backend::services::identity_merge_service::link_identity_with_records;
+use backend::services::platform_identity_service::ensure_identity;
+use backend::storage::blob_store::{delete_blob, get_blob, put_blob, rename_blob, BLOB_CHUNK_SIZE, MAX_BLOB_SIZE};
+use backend::storage::memory::{ACCELERATORS, API_MESSAGES, BLOB_CHUNKS};
+use backend::storage::record_map::RecordMap;
+use candid::Principal;
+use ic_stable_structures::VectorMemory;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn accelerator(id: u8, logo: Option<Vec<Vec<u8>>>) -> Accelerator {
+    Accelerator {
+        id: StablePrincipal::new(principal(id)),
+        name: "Nairobi Labs".to_string(),
+        website: "https://nairobi.example".to_string(),
+        email: "team@nairobi.example".to_string(),
+        email_verified: true,
+        logo,
+        total_startups: 0,
+        invites_sent: 0,
+        active_startups: 0,
+        graduated_startups: 0,
+        recent_activity: vec![],
+        team_members: vec![],
+    }
+}
+
+fn api_message(user_id: &str, response: String) -> ((StableString, u64), ApiMessage) {
+    let id = format!("{}_{}", user_id, NOW);
+    let message = ApiMessage {
+        id: id.clone(),
+        user_id: user_id.to_string(),
+        message: "summarise the repo".to_string(),
+        response,
+        bot_name: "GitHub Agent".to_string(),
+        metadata: None,
+        timestamp: NOW,
+    };
+    ((StableString::from(id), NOW), message)
+}
+
+fn chunks(name: &str) -> usize {
+    BLOB_CHUNKS.with(|chunks| chunks.borrow().iter().filter(|((blob, _), _)| blob.as_str() == name).count())
+}
+
+#[test]
+fn test_blobs_round_trip_across_chunks() {
+    let bytes: Vec<u8> = (0..2 * BLOB_CHUNK_SIZE + 5).map(|i| i as u8).collect();
+    put_blob("test/large", &bytes).unwrap();
+    assert_eq!(chunks("test/large"), 3);
+    assert_eq!(get_blob("test/large"), Some(bytes));
+
+    // A shorter replacement leaves no stale chunks behind
+    put_blob("test/large", b"small").unwrap();
+    assert_eq!(chunks("test/large"), 1);
+    assert_eq!(get_blob("test/large"), Some(b"small".to_vec()));
+
+    put_blob("test/empty", &[]).unwrap();
+    assert_eq!(get_blob("test/empty"), Some(vec![]));
+
+    rename_blob("test/large", "test/renamed");
+    assert_eq!((get_blob("test/large"), get_blob("test/renamed")), (None, Some(b"small".to_vec())));
+    assert!(delete_blob("test/renamed"));
+    assert!(!delete_blob("test/renamed"));
+    assert_eq!(get_blob("test/renamed"), None);
+}
+
+#[test]
+fn test_oversized_payloads_are_rejected() {
+    let error = put_blob("test/huge", &vec![0; MAX_BLOB_SIZE + 1]).unwrap_err();
+    assert!(error.contains("over the"), "{}", error);
+    assert_eq!(get_blob("test/huge"), None);
+
+    let message = ChatMessage {
+        id: principal(1),
+        role: MessageRole::User,
+        content: "x".repeat(MAX_RECORD_SIZE),
+        question_asked: None,
+        timestamp: NOW,
+        bot_name: None,
+    };
+    let error = check_record_size(&message).unwrap_err();
+    assert!(error.starts_with("ChatMessage record is"), "{}", error);
+    assert!(check_record_size(&ChatMessage { content: "x".repeat(8 * 1024), ..message }).is_ok());
+}
+
+#[test]
+fn test_records_past_the_old_size_caps_are_stored() {
+    // ApiMessage used to be capped at 4096 bytes and Accelerator at 8192
+    let (key, message) = api_message("big_reader", "y".repeat(10_000));
+    API_MESSAGES.with(|messages| messages.borrow_mut().insert(key.clone(), message));
+    assert_eq!(API_MESSAGES.with(|messages| messages.borrow().get(&key)).unwrap().response.len(), 10_000);
+
+    let mut busy = accelerator(2, None);
+    busy.recent_activity = (0..500)
+        .map(|i| Activity {
+            timestamp: NOW + i,
+            description: format!("Activity {}", i),
+            activity_type: ActivityType::Joined,
+        })
+        .collect();
+    ACCELERATORS.with(|accs| accs.borrow_mut().insert(busy.id.clone(), busy.clone()));
+    let stored = ACCELERATORS.with(|accs| accs.borrow().get(&busy.id)).unwrap();
+    assert_eq!(stored.recent_activity.len(), 500);
+}
+
+#[test]
+fn test_maps_written_with_bounded_values_still_load() {
+    // WAITLIST as ic-stable-structures 0.5.6 left it: 11 entries in the
+    // bounded layout, 1024 byte keys and values, trailing zero pages trimmed
+    let mut bytes = std::fs::read(format!("{}/waitlist_btreemap_v1.bin", FIXTURES)).unwrap();
+    bytes.resize(bytes.len().div_ceil(65536) * 65536, 0);
+    let memory: VectorMemory = Rc::new(RefCell::new(bytes));
+    let mut waitlist: RecordMap<StableString, WaitlistEntry, VectorMemory> = RecordMap::init(memory.clone());
+
+    assert_eq!(waitlist.len(), 11);
+    let first = waitlist.get(&StableString::from("founder00@example.com")).unwrap();
+    assert_eq!(first.name, "Founder 0");
+    assert!(matches!(first.status, WaitlistStatus::Pending));
+
+    // Values past the old 1024 byte cap now fit, and the map reloads with them
+    for i in 0..20 {
+        let email = format!("late{:02}@example.com", i);
+        let entry = WaitlistEntry {
+            email: email.clone(),
+            name: "n".repeat(4000),
+            created_at: NOW,
+            status: WaitlistStatus::Pending,
+        };
+        waitlist.insert(StableString::from(email), entry);
+    }
+    drop(waitlist);
+    let waitlist: RecordMap<StableString, WaitlistEntry, VectorMemory> = RecordMap::init(memory);
+    assert_eq!(waitlist.len(), 31);
+    assert_eq!(waitlist.get(&StableString::from("late19@example.com")).unwrap().name.len(), 4000);
+    assert_eq!(waitlist.get(&StableString::from("founder10@example.com")).unwrap().name, "Founder 10");
+}
+
+#[test]
+fn test_logos_live_in_the_blob_store() {
+    let logo = vec![vec![0x89, 0x50, 0x4e, 0x47], vec![7; 20_000]];
+    let mut acc = accelerator(3, None);
+    set_logo(&mut acc, Some(logo.clone())).unwrap();
+    assert_eq!(acc.logo, None);
+    assert!(get_blob(&logo_blob(&acc.id)).is_some());
+    assert_eq!(with_logo(acc.clone()).logo, Some(logo));
+
+    set_logo(&mut acc, None).unwrap();
+    assert_eq!(get_blob(&logo_blob(&acc.id)), None);
+    assert_eq!(with_logo(acc).logo, None);
+}
+
+#[test]
+fn test_upgrade_moves_inline_payloads_to_the_blob_store() {
+    let legacy = accelerator(4, Some(vec![vec![1, 2, 3]]));
+    ACCELERATORS.with(|accs| accs.borrow_mut().insert(legacy.id.clone(), legacy.clone()));
+    let (key, message) = api_message("legacy_reader", "z".repeat(3000));
+    API_MESSAGES.with(|messages| messages.borrow_mut().insert(key.clone(), message));
+    let (short_key, short) = api_message("short_reader", "ok".to_string());
+    API_MESSAGES.with(|messages| messages.borrow_mut().insert(short_key.clone(), short));
+
+    assert_eq!((move_inline_logos(true), move_long_responses(true)), (Ok(1), Ok(1)));
+    assert!(ACCELERATORS.with(|accs| accs.borrow().get(&legacy.id)).unwrap().logo.is_some());
+
+    move_inline_logos(false).unwrap();
+    move_long_responses(false).unwrap();
+    assert_eq!((move_inline_logos(true), move_long_responses(true)), (Ok(0), Ok(0)));
+
+    let stored = ACCELERATORS.with(|accs| accs.borrow().get(&legacy.id)).unwrap();
+    assert_eq!(stored.logo, None);
+    assert_eq!(with_logo(stored).logo, Some(vec![vec![1, 2, 3]]));
+
+    let stored = API_MESSAGES.with(|messages| messages.borrow().get(&key)).unwrap();
+    assert!(stored.response.is_empty());
+    assert_eq!(with_response(stored).response, "z".repeat(3000));
+    assert_eq!(API_MESSAGES.with(|messages| messages.borrow().get(&short_key)).unwrap().response, "ok");
+}
+
+#[test]
+fn test_long_responses_follow_their_message_on_link() {
+    let site = principal(5);
+    ensure_identity(Platform::Slack, "U900", NOW);
+    let (key, mut message) = api_message("U900", "w".repeat(5000));
+    message.response = String::new();
+    put_blob(&response_blob(&message.id), "w".repeat(5000).as_bytes()).unwrap();
+    API_MESSAGES.with(|messages| messages.borrow_mut().insert(key, message));
+
+    link_identity_with_records(Platform::Slack, "U900", site).unwrap();
+
+    let moved_id = format!("{}_{}", site.to_text(), NOW);
+    let moved = API_MESSAGES.with(|messages| messages.borrow().get(&(StableString::from(moved_id.as_str()), NOW))).unwrap();
+    assert_eq!(with_response(moved).response, "w".repeat(5000));
+    assert_eq!(get_blob(&response_blob(&format!("U900_{}", NOW))), None);
+}
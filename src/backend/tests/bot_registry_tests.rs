@@ -0,0 +1,136 @@
+use backend::migrations::{migrate_from_bytes, StableStateV4};
+use backend::models::bot_principal::BotPermission;
+use backend::models::platform::Platform;
+use backend::services::admin::{add_admin_with_role, bootstrap_admins};
+use backend::services::bot_service::{
+    authorize_bot, authorize_bot_for, get_bot, register_bot_as, remove_bot_as, rotate_bot_as, BotRegistration,
+};
+use backend::models::admin::{AdminRole, Permission};
+use backend::services::account_service::{check_identifier_access, UserIdentifier};
+use backend::services::platform_identity_service::{ensure_identity, get_identity, link_identity, visible_identity};
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn slack_bot(principal: Principal, permissions: Vec<BotPermission>) -> BotRegistration {
+    BotRegistration {
+        principal,
+        platform: Platform::Slack,
+        name: "Slack bot".to_string(),
+        permissions,
+    }
+}
+
+#[test]
+fn test_bots_are_scoped_to_their_permissions() {
+    let (root, bot, stranger) = (principal(1), principal(2), principal(3));
+    bootstrap_admins(&[root], NOW);
+
+    assert!(authorize_bot(bot, BotPermission::StoreMessages, Platform::Slack).unwrap_err().contains("not a registered bot"));
+    register_bot_as(root, slack_bot(bot, vec![BotPermission::StoreMessages, BotPermission::ReadUsage, BotPermission::StoreMessages]), NOW).unwrap();
+
+    assert!(authorize_bot(bot, BotPermission::StoreMessages, Platform::Slack).is_ok());
+    assert!(authorize_bot(bot, BotPermission::ReadUsage, Platform::Slack).is_ok());
+    let err = authorize_bot(bot, BotPermission::UpgradeTier, Platform::Slack).unwrap_err();
+    assert!(err.starts_with("Unauthorized") && err.contains("UpgradeTier"));
+    assert!(authorize_bot(stranger, BotPermission::ReadUsage, Platform::Slack).is_err());
+
+    // A Slack bot does not act for users of other platforms
+    let err = authorize_bot(bot, BotPermission::StoreMessages, Platform::Discord).unwrap_err();
+    assert!(err.starts_with("Unauthorized") && err.contains("Discord"), "{}", err);
+    assert!(authorize_bot_for(bot, BotPermission::StoreMessages, None).is_ok());
+    assert!(authorize_bot_for(bot, BotPermission::StoreMessages, Some(Platform::Playground)).is_err());
+    assert_eq!(get_bot(bot).unwrap().permissions.len(), 2, "duplicates are dropped");
+
+    // Re-registering updates the permissions and keeps the creation time
+    register_bot_as(root, slack_bot(bot, vec![BotPermission::UpgradeTier]), NOW + 1).unwrap();
+    let updated = get_bot(bot).unwrap();
+    assert_eq!((updated.created_at, updated.updated_at), (NOW, NOW + 1));
+    assert!(authorize_bot(bot, BotPermission::UpgradeTier, Platform::Slack).is_ok());
+    assert!(authorize_bot(bot, BotPermission::StoreMessages, Platform::Slack).is_err());
+    println!("✅ Bot permission test passed");
+}
+
+#[test]
+fn test_only_admin_managers_change_the_registry() {
+    let (root, support, bot, new_key) = (principal(4), principal(5), principal(6), principal(7));
+    bootstrap_admins(&[root], NOW);
+    add_admin_with_role(root, support, AdminRole::Support, NOW).unwrap();
+
+    assert!(register_bot_as(support, slack_bot(bot, vec![BotPermission::IssueTokens]), NOW).is_err());
+    assert!(register_bot_as(bot, slack_bot(bot, vec![BotPermission::IssueTokens]), NOW).is_err());
+    assert!(register_bot_as(root, slack_bot(Principal::anonymous(), vec![]), NOW).is_err());
+    register_bot_as(root, slack_bot(bot, vec![BotPermission::IssueTokens]), NOW).unwrap();
+
+    // Rotation hands the registration over and revokes the old key
+    assert!(rotate_bot_as(support, bot, new_key, NOW).is_err());
+    assert!(rotate_bot_as(root, bot, bot, NOW).unwrap_err().contains("already a registered bot"));
+    let rotated = rotate_bot_as(root, bot, new_key, NOW + 1).unwrap();
+    assert_eq!((rotated.principal, rotated.created_at), (new_key, NOW));
+    assert!(authorize_bot(bot, BotPermission::IssueTokens, Platform::Slack).is_err());
+    assert!(authorize_bot(new_key, BotPermission::IssueTokens, Platform::Slack).is_ok());
+
+    assert!(remove_bot_as(support, new_key, NOW).is_err());
+    remove_bot_as(root, new_key, NOW).unwrap();
+    assert!(authorize_bot(new_key, BotPermission::IssueTokens, Platform::Slack).is_err());
+    assert_eq!(remove_bot_as(root, new_key, NOW).unwrap_err(), "Bot not found");
+    println!("✅ Bot registry management test passed");
+}
+
+#[test]
+fn test_platform_ids_are_only_trusted_from_their_bots() {
+    let (root, bot, alice, stranger) = (principal(8), principal(9), principal(10), principal(11));
+    bootstrap_admins(&[root], NOW);
+    register_bot_as(root, slack_bot(bot, vec![BotPermission::ReadMessages]), NOW).unwrap();
+    let slack_user = UserIdentifier::SlackId("U300".to_string());
+    let check = |caller, identifier: &UserIdentifier, bot_permission| {
+        check_identifier_access(caller, identifier, Permission::ViewUsers, bot_permission)
+    };
+
+    assert!(check(bot, &slack_user, BotPermission::ReadMessages).is_ok());
+    assert!(check(bot, &slack_user, BotPermission::StoreMessages).is_err());
+    assert!(check(bot, &UserIdentifier::DiscordId("300".to_string()), BotPermission::ReadMessages).is_err());
+    assert!(check(bot, &UserIdentifier::PlaygroundId("playground_1".to_string()), BotPermission::ReadMessages).is_err());
+    assert!(check(stranger, &slack_user, BotPermission::ReadMessages).is_err());
+
+    // Otherwise only the linked owner and admins reach the platform user's data
+    ensure_identity(Platform::Slack, "U300", NOW);
+    link_identity(Platform::Slack, "U300", alice).unwrap();
+    assert!(check(alice, &slack_user, BotPermission::ReadMessages).is_ok());
+    assert!(check(stranger, &slack_user, BotPermission::ReadMessages).is_err());
+    assert!(check(root, &slack_user, BotPermission::ReadMessages).is_ok());
+    println!("✅ Platform identifier access test passed");
+}
+
+#[test]
+fn test_linked_principals_are_hidden_from_other_callers() {
+    let (root, bot, alice, stranger) = (principal(12), principal(13), principal(14), principal(15));
+    bootstrap_admins(&[root], NOW);
+    register_bot_as(root, slack_bot(bot, vec![BotPermission::ReadMessages]), NOW).unwrap();
+    ensure_identity(Platform::Slack, "U400", NOW);
+    ensure_identity(Platform::Discord, "400", NOW);
+    link_identity(Platform::Slack, "U400", alice).unwrap();
+    link_identity(Platform::Discord, "400", alice).unwrap();
+    let seen_by = |caller, platform, external_id| visible_identity(caller, get_identity(platform, external_id).unwrap()).site_principal;
+
+    assert_eq!(seen_by(bot, Platform::Slack, "U400"), Some(alice));
+    assert_eq!(seen_by(alice, Platform::Slack, "U400"), Some(alice));
+    assert_eq!(seen_by(root, Platform::Slack, "U400"), Some(alice));
+    assert_eq!(seen_by(stranger, Platform::Slack, "U400"), None);
+    assert_eq!(seen_by(Principal::anonymous(), Platform::Slack, "U400"), None);
+    // A bot only sees the links of its own platform's users
+    assert_eq!(seen_by(bot, Platform::Discord, "400"), None);
+    println!("✅ Hidden linked principal test passed");
+}
+
+#[test]
+fn test_snapshots_before_the_registry_have_no_bots() {
+    let v4 = StableStateV4::default();
+    let state = migrate_from_bytes(&bincode::serialize(&v4).unwrap()).unwrap();
+    assert!(state.bot_principals.is_empty());
+    println!("✅ Bot registry migration test passed");
+}
@@ -0,0 +1,179 @@
+use backend::models::coupon::{Coupon, CouponDiscount, RedemptionStatus};
+use backend::models::payment::{AppliedDiscount, Currency, PaymentRecord, PaymentStatus};
+use backend::models::stable_string::StableString;
+use backend::services::coupon_service::{
+    create_coupon, get_coupon, list_redemptions, quote_coupon, reserve_coupon, update_coupon,
+};
+use backend::services::payment_service::{fulfil_payment_at, update_payment_status, PaymentConfirmation};
+use backend::services::subscription_service::get_subscription_billing;
+use backend::storage::memory::{INVOICES, PAYMENT_RECORDS};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const PRICE: u64 = 2_900_000;
+
+fn coupon(code: &str, discount: CouponDiscount) -> Coupon {
+    Coupon {
+        code: code.to_string(),
+        description: "Cohort discount".to_string(),
+        discount,
+        max_redemptions: None,
+        per_user_limit: None,
+        expires_at: None,
+        allowed_tiers: vec![],
+        allowed_billing_periods: vec![],
+        recurring: false,
+        is_active: true,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+fn quote(code: &str, user_id: &str) -> Result<AppliedDiscount, String> {
+    quote_coupon(code, user_id, "Pro", "monthly", &Currency::NGN, PRICE, NOW)
+}
+
+fn reserve(code: &str, user_id: &str, reference: &str) -> Result<AppliedDiscount, String> {
+    reserve_coupon(code, user_id, reference, "Pro", "monthly", &Currency::NGN, PRICE, NOW)
+}
+
+/// Store a pending payment like `initialize_payment` does after reserving the coupon
+fn pending_payment(reference: &str, user_id: &str, discount: AppliedDiscount) {
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: user_id.to_string(),
+        paystack_reference: reference.to_string(),
+        amount: discount.original_amount - discount.discount_amount,
+        currency: Currency::NGN,
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        discount: Some(discount),
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+}
+
+#[test]
+fn test_coupon_validation() {
+    assert!(create_coupon(coupon("BAD CODE", CouponDiscount::Percentage(10)), NOW).is_err());
+    assert!(create_coupon(coupon("ZERO", CouponDiscount::Percentage(0)), NOW).is_err());
+    assert!(create_coupon(coupon("TOOMUCH", CouponDiscount::Percentage(101)), NOW).is_err());
+    assert!(create_coupon(
+        coupon("NOTHING", CouponDiscount::FixedAmount { amount: 0, currency: Currency::NGN }),
+        NOW
+    )
+    .is_err());
+
+    let mut weekly = coupon("WEEKLY", CouponDiscount::Percentage(10));
+    weekly.allowed_billing_periods = vec!["weekly".to_string()];
+    assert!(create_coupon(weekly, NOW).is_err());
+
+    // Codes are case-insensitive
+    let created = create_coupon(coupon("cohort-5", CouponDiscount::Percentage(50)), NOW).unwrap();
+    assert_eq!(created.code, "COHORT-5");
+    assert!(get_coupon(" Cohort-5 ").is_some());
+    assert!(create_coupon(coupon("COHORT-5", CouponDiscount::Percentage(20)), NOW).is_err());
+    println!("✅ Coupon validation test passed");
+}
+
+#[test]
+fn test_discount_calculation_and_restrictions() {
+    create_coupon(coupon("HALF", CouponDiscount::Percentage(50)), NOW).unwrap();
+    let discount = quote("half", "user-1").unwrap();
+    assert_eq!(discount.coupon_code, "HALF");
+    assert_eq!(discount.discount_amount, PRICE / 2);
+
+    // Fixed discounts only apply in their currency and never exceed the price
+    create_coupon(coupon("KES500", CouponDiscount::FixedAmount { amount: 50_000, currency: Currency::KES }), NOW).unwrap();
+    assert!(quote("KES500", "user-1").is_err());
+    let capped = quote_coupon("KES500", "user-1", "Pro", "monthly", &Currency::KES, 30_000, NOW).unwrap();
+    assert_eq!(capped.discount_amount, 30_000);
+
+    let mut yearly_only = coupon("YEARLY", CouponDiscount::Percentage(20));
+    yearly_only.allowed_billing_periods = vec!["yearly".to_string()];
+    yearly_only.allowed_tiers = vec!["Pro".to_string()];
+    create_coupon(yearly_only, NOW).unwrap();
+    assert!(quote("YEARLY", "user-1").is_err());
+    assert!(quote_coupon("YEARLY", "user-1", "Pro", "yearly", &Currency::NGN, PRICE, NOW).is_ok());
+    assert!(quote_coupon("YEARLY", "user-1", "Team", "yearly", &Currency::NGN, PRICE, NOW).is_err());
+
+    let mut expiring = coupon("EXPIRES", CouponDiscount::Percentage(10));
+    expiring.expires_at = Some(NOW + DAY);
+    create_coupon(expiring.clone(), NOW).unwrap();
+    assert!(quote("EXPIRES", "user-1").is_ok());
+    assert!(quote_coupon("EXPIRES", "user-1", "Pro", "monthly", &Currency::NGN, PRICE, NOW + DAY).is_err());
+
+    expiring.is_active = false;
+    update_coupon(expiring, NOW).unwrap();
+    assert!(quote("EXPIRES", "user-1").is_err());
+    assert!(quote("UNKNOWN", "user-1").is_err());
+    println!("✅ Discount calculation test passed");
+}
+
+#[test]
+fn test_redemption_limits() {
+    let mut limited = coupon("COHORT", CouponDiscount::Percentage(30));
+    limited.max_redemptions = Some(2);
+    limited.per_user_limit = Some(1);
+    create_coupon(limited, NOW).unwrap();
+
+    reserve("COHORT", "user-1", "INF-1").unwrap();
+    assert!(reserve("COHORT", "user-1", "INF-2").is_err(), "per-user limit");
+    let discount = reserve("COHORT", "user-2", "INF-3").unwrap();
+    assert!(reserve("COHORT", "user-3", "INF-4").is_err(), "max redemptions");
+
+    // A failed checkout frees its reservation
+    pending_payment("INF-3", "user-2", discount);
+    update_payment_status("INF-3", PaymentStatus::Failed, None, NOW).unwrap();
+    reserve("COHORT", "user-3", "INF-5").unwrap();
+
+    let redemptions = list_redemptions(Some("cohort"));
+    assert_eq!(redemptions.len(), 3);
+    let released = redemptions.iter().find(|r| r.payment_reference == "INF-3").unwrap();
+    assert_eq!(released.status, RedemptionStatus::Released);
+    assert!(list_redemptions(Some("OTHER")).is_empty());
+    println!("✅ Redemption limits test passed");
+}
+
+#[test]
+fn test_fulfilment_records_discount() {
+    create_coupon(coupon("FIRSTMONTH", CouponDiscount::Percentage(25)), NOW).unwrap();
+    let discount = reserve("FIRSTMONTH", "user-1", "INF-pay-1").unwrap();
+    pending_payment("INF-pay-1", "user-1", discount.clone());
+
+    assert!(fulfil_payment_at("INF-pay-1", PaymentConfirmation::default(), NOW).unwrap());
+
+    let redemption = list_redemptions(None).into_iter().next().unwrap();
+    assert_eq!(redemption.status, RedemptionStatus::Redeemed);
+
+    let invoice = INVOICES
+        .with(|invoices| invoices.borrow().get(&StableString::from("INV-INF-pay-1".to_string())))
+        .unwrap();
+    assert_eq!(invoice.amount, PRICE - PRICE / 4);
+    assert_eq!(invoice.discount, Some(discount));
+
+    // One-off coupons only discount the first period
+    assert_eq!(get_subscription_billing("user-1").unwrap().amount, PRICE);
+
+    // A redeemed coupon is not given back by a late failure
+    update_payment_status("INF-pay-1", PaymentStatus::Failed, None, NOW + DAY).unwrap();
+    assert_eq!(list_redemptions(None)[0].status, RedemptionStatus::Redeemed);
+    println!("✅ Discount fulfilment test passed");
+}
+
+#[test]
+fn test_recurring_coupon_discounts_renewals() {
+    let mut recurring = coupon("ACCEL", CouponDiscount::FixedAmount { amount: 900_000, currency: Currency::NGN });
+    recurring.recurring = true;
+    create_coupon(recurring, NOW).unwrap();
+
+    let discount = reserve("ACCEL", "user-2", "INF-pay-2").unwrap();
+    pending_payment("INF-pay-2", "user-2", discount);
+    fulfil_payment_at("INF-pay-2", PaymentConfirmation::default(), NOW).unwrap();
+
+    assert_eq!(get_subscription_billing("user-2").unwrap().amount, PRICE - 900_000);
+    println!("✅ Recurring coupon test passed");
+}
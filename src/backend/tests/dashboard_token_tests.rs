@@ -0,0 +1,57 @@
+use backend::migrations::{migrate_from_bytes, DashboardTokenV1, StableStateV4};
+use backend::models::dashboard_token::DashboardToken;
+use backend::models::platform::Platform;
+use backend::models::record::{decode_record, encode_record};
+use backend::models::stable_string::StableString;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn legacy_token(platform_id: &str) -> DashboardTokenV1 {
+    DashboardTokenV1 {
+        token: vec![7; 32],
+        openchat_id: platform_id.to_string(),
+        created_at: NOW,
+        expires_at: NOW + 120_000_000_000,
+    }
+}
+
+#[test]
+fn test_tokens_keep_their_platform() {
+    // A Slack enterprise user ID starts with 'W'; the stored platform wins over its shape
+    let token = DashboardToken {
+        token: vec![1; 32],
+        platform: Platform::Slack,
+        platform_id: "W012A3CDE".to_string(),
+        created_at: NOW,
+        expires_at: NOW + 1,
+    };
+    let decoded: DashboardToken = decode_record(&encode_record(&token)).unwrap();
+    assert_eq!((decoded.platform, decoded.platform_id.as_str()), (Platform::Slack, "W012A3CDE"));
+
+    assert_eq!("discord".parse::<Platform>(), Ok(Platform::Discord));
+    assert_eq!("mainsite".parse::<Platform>(), Ok(Platform::MainSite));
+    assert!("myspace".parse::<Platform>().unwrap_err().contains("Unsupported platform"));
+    println!("✅ Token platform test passed");
+}
+
+#[test]
+fn test_legacy_tokens_are_migrated() {
+    let v4 = StableStateV4 {
+        dashboard_tokens: vec![
+            (StableString::from("slack".to_string()), legacy_token("U012A3CDE")),
+            (StableString::from("discord".to_string()), legacy_token("123456789012345678")),
+            (StableString::from("openchat".to_string()), legacy_token("2chl6-4hpzw-vqaaa-aaaaa-c")),
+        ],
+        ..Default::default()
+    };
+    let state = migrate_from_bytes(&bincode::serialize(&v4).unwrap()).unwrap();
+    let platforms: Vec<_> = state.dashboard_tokens.iter().map(|(_, t)| t.platform).collect();
+    assert_eq!(platforms, vec![Platform::Slack, Platform::Discord, Platform::OpenChat]);
+    assert_eq!(state.dashboard_tokens[1].1.platform_id, "123456789012345678");
+
+    // Records still in stable memory in the old layout decode too
+    let bytes = candid::encode_one(legacy_token("U012A3CDE")).unwrap();
+    let token: DashboardToken = decode_record(&bytes).unwrap();
+    assert_eq!((token.platform, token.expires_at), (Platform::Slack, NOW + 120_000_000_000));
+    println!("✅ Legacy token migration test passed");
+}
@@ -0,0 +1,223 @@
+use backend::models::analytics::AnalyticsDataPoint;
+use backend::models::api_message::ApiMessage;
+use backend::models::chat::{ChatMessage, MessageRole};
+use backend::models::github::{Issue, IssueStatus};
+use backend::models::platform::Platform;
+use backend::models::stable_principal::StablePrincipal;
+use backend::models::stable_string::StableString;
+use backend::models::task::Task;
+use backend::services::identity_merge_service::{
+    link_identity_with_records, merge_linked_identities, unlink_identity_with_records,
+};
+use backend::services::platform_identity_service::{derived_principal, ensure_identity, link_identity};
+use backend::storage::memory::{API_MESSAGES, CHAT_HISTORY, GITHUB_ISSUES, TASKS, USER_ANALYTICS, USER_DAILY_USAGE};
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const DAY: u64 = NOW / (86_400 * 1_000_000_000);
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn chat(owner: Principal, seq: u64, content: &str) {
+    let message = ChatMessage {
+        id: owner,
+        role: MessageRole::User,
+        content: content.to_string(),
+        question_asked: None,
+        timestamp: NOW + seq,
+        bot_name: None,
+    };
+    CHAT_HISTORY.with(|h| h.borrow_mut().insert((StablePrincipal::new(owner), seq), message));
+}
+
+fn chat_contents(owner: Principal) -> Vec<String> {
+    CHAT_HISTORY.with(|h| {
+        h.borrow()
+            .iter()
+            .filter(|((p, _), _)| p.get() == owner)
+            .map(|(_, message)| message.content)
+            .collect()
+    })
+}
+
+fn task(owner: Principal, id: &str) {
+    let task = Task {
+        id: id.to_string(),
+        title: id.to_string(),
+        description: String::new(),
+        status: "open".to_string(),
+        created_at: NOW,
+        platform: "asana".to_string(),
+        platform_id: id.to_string(),
+        creator: owner,
+    };
+    TASKS.with(|t| t.borrow_mut().insert((StablePrincipal::new(owner), StableString::from(id)), task));
+}
+
+fn issue(owner: Principal, id: &str) {
+    let issue = Issue {
+        id: id.to_string(),
+        title: id.to_string(),
+        body: String::new(),
+        repository: "infoundr/site".to_string(),
+        created_at: NOW,
+        status: IssueStatus::Open,
+    };
+    GITHUB_ISSUES.with(|i| i.borrow_mut().insert((StablePrincipal::new(owner), StableString::from(id)), issue));
+}
+
+fn api_message(user_id: &str, timestamp: u64) {
+    let id = format!("{}_{}", user_id, timestamp);
+    let message = ApiMessage {
+        id: id.clone(),
+        user_id: user_id.to_string(),
+        message: "hi".to_string(),
+        response: "hello".to_string(),
+        bot_name: "Benny".to_string(),
+        metadata: None,
+        timestamp,
+    };
+    API_MESSAGES.with(|m| m.borrow_mut().insert((StableString::from(id), timestamp), message));
+}
+
+fn api_user_ids() -> Vec<String> {
+    API_MESSAGES.with(|m| m.borrow().iter().map(|(_, message)| message.user_id).collect())
+}
+
+fn usage(user_id: &str) -> Option<u32> {
+    USER_DAILY_USAGE.with(|u| u.borrow().get(&(StableString::from(user_id), DAY)))
+}
+
+fn analytics(user_id: &str, requests_made: u32) {
+    let point = AnalyticsDataPoint {
+        date: "2023-11-14".to_string(),
+        requests_made,
+        lines_of_code_edited: 0,
+        ai_interactions: requests_made,
+        tasks_completed: 0,
+        timestamp_ns: NOW,
+    };
+    USER_ANALYTICS.with(|a| a.borrow_mut().insert((StableString::from(user_id), DAY), point));
+}
+
+fn requests_made(user_id: &str) -> Option<u32> {
+    USER_ANALYTICS.with(|a| a.borrow().get(&(StableString::from(user_id), DAY)).map(|p| p.requests_made))
+}
+
+#[test]
+fn test_linking_merges_and_unlinking_splits() {
+    let site = principal(20);
+    let slack = derived_principal(Platform::Slack, "U500").unwrap();
+    ensure_identity(Platform::Slack, "U500", NOW);
+
+    // Records from before linking, plus the site account's own
+    chat(site, 0, "from the web");
+    chat(slack, 0, "from slack");
+    chat(slack, 1, "also from slack");
+    task(slack, "T1");
+    issue(slack, "I1");
+    api_message("U500", NOW);
+    USER_DAILY_USAGE.with(|u| {
+        u.borrow_mut().insert((StableString::from("U500"), DAY), 3);
+        u.borrow_mut().insert((StableString::from(site.to_text()), DAY), 2);
+    });
+    analytics("U500", 3);
+
+    link_identity_with_records(Platform::Slack, "U500", site).unwrap();
+    assert_eq!(chat_contents(site), vec!["from the web", "from slack", "also from slack"]);
+    assert!(chat_contents(slack).is_empty());
+    assert!(TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(site), StableString::from("T1")))));
+    assert!(GITHUB_ISSUES.with(|i| i.borrow().contains_key(&(StablePrincipal::new(site), StableString::from("I1")))));
+    assert!(api_user_ids().contains(&site.to_text()) && !api_user_ids().contains(&"U500".to_string()));
+    assert_eq!((usage("U500"), usage(&site.to_text())), (None, Some(5)));
+    assert_eq!((requests_made("U500"), requests_made(&site.to_text())), (None, Some(3)));
+
+    // Stored while linked: stays with the site account
+    chat(site, 3, "while linked");
+
+    unlink_identity_with_records(Platform::Slack, "U500").unwrap();
+    assert_eq!(chat_contents(site), vec!["from the web", "while linked"]);
+    assert_eq!(chat_contents(slack), vec!["from slack", "also from slack"]);
+    assert!(TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(slack), StableString::from("T1")))));
+    assert!(GITHUB_ISSUES.with(|i| i.borrow().contains_key(&(StablePrincipal::new(slack), StableString::from("I1")))));
+    assert!(api_user_ids().contains(&"U500".to_string()) && !api_user_ids().contains(&site.to_text()));
+    assert_eq!((usage("U500"), usage(&site.to_text())), (Some(3), Some(2)));
+    assert_eq!((requests_made("U500"), requests_made(&site.to_text())), (Some(3), None));
+    println!("✅ Merge and split test passed");
+}
+
+#[test]
+fn test_relinking_moves_records_to_the_new_principal() {
+    let (alice, bob) = (principal(21), principal(22));
+    let discord = derived_principal(Platform::Discord, "600").unwrap();
+    ensure_identity(Platform::Discord, "600", NOW);
+    chat(discord, 0, "discord history");
+    task(discord, "T600");
+
+    link_identity_with_records(Platform::Discord, "600", alice).unwrap();
+    link_identity_with_records(Platform::Discord, "600", bob).unwrap();
+    assert!(chat_contents(alice).is_empty());
+    assert_eq!(chat_contents(bob), vec!["discord history"]);
+    assert!(TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(bob), StableString::from("T600")))));
+
+    assert_eq!(unlink_identity_with_records(Platform::Discord, "404"), Err("Discord user not found".to_string()));
+    println!("✅ Relink test passed");
+}
+
+#[test]
+fn test_upgrade_merges_accounts_linked_before() {
+    let site = principal(23);
+    let slack = derived_principal(Platform::Slack, "U700").unwrap();
+    ensure_identity(Platform::Slack, "U700", NOW);
+    link_identity(Platform::Slack, "U700", site).unwrap();
+
+    // Messages stored after linking used to be written under both principals
+    chat(slack, 0, "before linking");
+    chat(slack, 1, "after linking");
+    chat(site, 1, "after linking");
+    task(slack, "T700");
+    task(site, "T700");
+
+    assert_eq!(merge_linked_identities(), 1);
+    assert_eq!(chat_contents(site), vec!["after linking", "before linking"]);
+    assert!(chat_contents(slack).is_empty());
+    assert!(!TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(slack), StableString::from("T700")))));
+
+    // Nothing left to move on the next upgrade
+    assert_eq!(merge_linked_identities(), 0);
+
+    // The dropped copies were logged, so unlinking puts them back
+    unlink_identity_with_records(Platform::Slack, "U700").unwrap();
+    assert_eq!(chat_contents(slack), vec!["before linking", "after linking"]);
+    assert_eq!(chat_contents(site), vec!["after linking"]);
+    assert!(TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(slack), StableString::from("T700")))));
+    assert!(TASKS.with(|t| t.borrow().contains_key(&(StablePrincipal::new(site), StableString::from("T700")))));
+    println!("✅ Upgrade merge test passed");
+}
+
+#[test]
+fn test_colliding_api_messages_are_not_overwritten() {
+    let site = principal(24);
+    ensure_identity(Platform::Discord, "800", NOW);
+    api_message("800", NOW);
+    api_message(&site.to_text(), NOW);
+    API_MESSAGES.with(|m| {
+        let key = (StableString::from(format!("{}_{}", site.to_text(), NOW)), NOW);
+        let message = m.borrow().get(&key).unwrap();
+        m.borrow_mut().insert(key, ApiMessage { message: "from the web".to_string(), ..message });
+    });
+
+    link_identity_with_records(Platform::Discord, "800", site).unwrap();
+    let messages: Vec<_> = API_MESSAGES.with(|m| m.borrow().iter().map(|(_, message)| message.message).collect());
+    assert_eq!(messages, vec!["from the web"]);
+
+    unlink_identity_with_records(Platform::Discord, "800").unwrap();
+    let mut user_ids = api_user_ids();
+    user_ids.sort();
+    let mut expected = vec!["800".to_string(), site.to_text()];
+    expected.sort();
+    assert_eq!(user_ids, expected);
+    println!("✅ API message collision test passed");
+}
@@ -1,4 +1,4 @@
-use candid::{Principal, encode_one, decode_one};
+use candid::{Principal, encode_args, encode_one, decode_one};
 use pocket_ic::PocketIc;
 use std::fs;
 
@@ -13,6 +13,9 @@ const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.
 // 2T cycles for testing
 const INIT_CYCLES: u128 = 2_000_000_000_000;
 
+// Anonymous callers are rejected, so calls are made as this identity
+const TEST_CALLER: Principal = Principal::from_slice(&[1; 29]);
+
 fn setup() -> (PocketIc, Principal) {
     std::env::set_var("POCKET_IC_BIN", "/usr/local/bin/pocket-ic"); // Path of the pocket-ic binary
     let pic = PocketIc::new();
@@ -20,7 +23,7 @@ fn setup() -> (PocketIc, Principal) {
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, INIT_CYCLES); // 2T Cycles
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
     (pic, backend_canister)
 }
 
@@ -159,8 +162,8 @@ fn test_admin_functions() {
     };
     call_accelerator_signup(&pic, canister_id, signup_data).unwrap();
 
-    // Get the caller's principal (anonymous in this case)
-    let caller_principal = Principal::anonymous();
+    // Get the caller's principal (the test identity)
+    let caller_principal = TEST_CALLER;
 
     // Test get_all_accelerators (as admin)
     let _all_accelerators = call_get_all_accelerators(&pic, canister_id);
@@ -229,7 +232,7 @@ fn call_accelerator_signup(pic: &PocketIc, canister_id: Principal, data: Acceler
     let encoded = encode_one(data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     );
@@ -243,7 +246,7 @@ fn call_accelerator_signup(pic: &PocketIc, canister_id: Principal, data: Acceler
 // fn call_get_my_accelerator(pic: &PocketIc, canister_id: Principal) -> Result<Option<Accelerator>, String> {
 //     let result = pic.query_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "get_my_accelerator",
 //         encode_one(()).unwrap(),
 //     );
@@ -261,7 +264,7 @@ fn call_accelerator_signup(pic: &PocketIc, canister_id: Principal, data: Acceler
 //     let encoded = encode_one(updates).unwrap();
 //     let result = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "update_my_accelerator",
 //         encoded,
 //     );
@@ -275,7 +278,7 @@ fn call_accelerator_signup(pic: &PocketIc, canister_id: Principal, data: Acceler
 fn call_get_all_accelerators(pic: &PocketIc, canister_id: Principal) -> Result<Vec<Accelerator>, String> {
     let result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "get_all_accelerators",
         encode_one(()).unwrap(),
     );
@@ -293,7 +296,7 @@ fn call_get_accelerator_by_id(pic: &PocketIc, canister_id: Principal, target_id:
     let encoded = encode_one(target_id).unwrap();
     let result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "get_accelerator_by_id",
         encoded,
     );
@@ -312,7 +315,7 @@ fn call_admin_update_accelerator(pic: &PocketIc, canister_id: Principal, target_
     let encoded = encode_one(args).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "admin_update_accelerator",
         encoded,
     );
@@ -1,7 +1,7 @@
 // Startup Invite Feature Tests
 // ===========================
 
-use candid::{encode_one, decode_one, Principal};
+use candid::{encode_args, encode_one, decode_one, Principal};
 use pocket_ic::PocketIc;
 use std::fs;
 
@@ -15,13 +15,16 @@ const TEST_ACCELERATOR_EMAIL: &str = "test@accelerator.com";
 const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
 const INIT_CYCLES: u128 = 2_000_000_000_000;
 
+// Anonymous callers are rejected, so calls are made as this identity
+const TEST_CALLER: Principal = Principal::from_slice(&[1; 29]);
+
 fn setup() -> (PocketIc, Principal) {
     std::env::set_var("POCKET_IC_BIN", "/usr/local/bin/pocket-ic");
     let pic = PocketIc::new();
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, INIT_CYCLES);
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
     (pic, backend_canister)
 }
 
@@ -37,13 +40,13 @@ fn setup() -> (PocketIc, Principal) {
 //     };
 //     let _ = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "sign_up_accelerator",
 //         encode_one(signup_data).unwrap(),
 //     ).expect("Accelerator sign up failed");
 
 //     // 2. Generate invite as SuperAdmin
-//     let accelerator_id = Principal::anonymous().to_string();
+//     let accelerator_id = TEST_CALLER.to_string();
 //     println!("Accelerator ID: {}", accelerator_id);
 //     let invite_input = GenerateStartupInviteInput {
 //         startup_name: "Test Startup".to_string(),
@@ -55,7 +58,7 @@ fn setup() -> (PocketIc, Principal) {
 //     };
 //     let result = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "generate_startup_invite",
 //         encode_one(invite_input).unwrap(),
 //     ).expect("Invite generation failed");
@@ -66,7 +69,7 @@ fn setup() -> (PocketIc, Principal) {
 //     let invite_code = &invite.invite_code;
 //     println!("Invite code: {}", invite_code);
 
-//     let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+//     let new_principal = TEST_CALLER;
 //     let registration_input = StartupRegistrationInput {
 //         invite_code: invite_code.to_string(),
 //         startup_name: "Test Startup".to_string(),
@@ -84,7 +87,7 @@ fn setup() -> (PocketIc, Principal) {
 
 //     let list_result = pic.query_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "list_startup_invites",
 //         encode_one(accelerator_id).unwrap(),
 //     ).expect("List invites failed");
@@ -113,13 +116,13 @@ fn test_expired_invite() {
     };
     let _ = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encode_one(signup_data).unwrap(),
     ).expect("Accelerator sign up failed");
 
     // 2. Generate invite with very short expiry (1 second)
-    let accelerator_id = Principal::anonymous().to_string();
+    let accelerator_id = TEST_CALLER.to_string();
     let invite_input = GenerateStartupInviteInput {
         startup_name: "Expired Startup".to_string(),
         program_name: "Test Program".to_string(),
@@ -130,7 +133,7 @@ fn test_expired_invite() {
     };
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "generate_startup_invite",
         encode_one(invite_input).unwrap(),
     ).expect("Invite generation failed");
@@ -142,7 +145,7 @@ fn test_expired_invite() {
     println!("Invite expiry: {}", invite.expiry);
 
     // 3. Try to accept immediately - should fail because invite is expired
-    let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let new_principal = TEST_CALLER;
     let registration_input = StartupRegistrationInput {
         invite_code: invite_code.to_string(),
         startup_name: "Expired Startup".to_string(),
@@ -179,7 +182,7 @@ fn test_expired_invite() {
     // 4. Check invite status is marked as expired
     let list_result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "list_startup_invites",
         encode_one(accelerator_id).unwrap(),
     ).expect("List invites failed");
@@ -206,12 +209,12 @@ fn test_revoked_invite() {
     };
     let _ = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encode_one(signup_data).unwrap(),
     ).expect("Accelerator sign up failed");
 
-    let accelerator_id = Principal::anonymous().to_string();
+    let accelerator_id = TEST_CALLER.to_string();
     let invite_input = GenerateStartupInviteInput {
         startup_name: "Revoked Startup".to_string(),
         program_name: "Test Program".to_string(),
@@ -222,7 +225,7 @@ fn test_revoked_invite() {
     };
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "generate_startup_invite",
         encode_one(invite_input).unwrap(),
     ).expect("Invite generation failed");
@@ -234,14 +237,14 @@ fn test_revoked_invite() {
 
     let revoke_result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "revoke_startup_invite",
         encode_one(invite_code.to_string()).unwrap(),
     );
     assert!(revoke_result.is_ok(), "Invite revocation should succeed");
     println!("✅ Invite revoked successfully");
 
-    let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let new_principal = TEST_CALLER;
     let registration_input = StartupRegistrationInput {
         invite_code: invite_code.to_string(),
         startup_name: "Revoked Startup".to_string(),
@@ -275,7 +278,7 @@ fn test_revoked_invite() {
 
     let list_result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "list_startup_invites",
         encode_one(accelerator_id).unwrap(),
     ).expect("List invites failed");
@@ -302,7 +305,7 @@ fn test_permission_checks() {
     };
     let _ = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encode_one(signup_data).unwrap(),
     ).expect("Accelerator sign up failed");
@@ -311,7 +314,7 @@ fn test_permission_checks() {
     // This should fail because the non-admin principal is not a team member
     // Create a truly different principal by using a different byte array
     let non_admin_principal = Principal::from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-    let accelerator_id = Principal::anonymous().to_string();
+    let accelerator_id = TEST_CALLER.to_string();
     let invite_input = GenerateStartupInviteInput {
         startup_name: "Unauthorized Startup".to_string(),
         program_name: "Test Program".to_string(),
@@ -404,12 +407,12 @@ fn test_invalid_invite_code() {
     };
     let _ = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encode_one(signup_data).unwrap(),
     ).expect("Accelerator sign up failed");
 
-    let new_principal = Principal::from_text("2vxsx-fae").unwrap();
+    let new_principal = TEST_CALLER;
     let registration_input = StartupRegistrationInput {
         invite_code: "INVALID_INVITE_CODE_12345".to_string(),
         startup_name: "Invalid Startup".to_string(),
@@ -455,12 +458,12 @@ fn test_invalid_invite_code() {
 //     };
 //     let _ = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "sign_up_accelerator",
 //         encode_one(signup_data).unwrap(),
 //     ).expect("Accelerator sign up failed");
 
-//     let accelerator_id = Principal::anonymous().to_string();
+//     let accelerator_id = TEST_CALLER.to_string();
 //     let invite_input = GenerateStartupInviteInput {
 //         startup_name: "Duplicate Startup".to_string(),
 //         program_name: "Test Program".to_string(),
@@ -471,7 +474,7 @@ fn test_invalid_invite_code() {
 //     };
 //     let result = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "generate_startup_invite",
 //         encode_one(invite_input).unwrap(),
 //     ).expect("Invite generation failed");
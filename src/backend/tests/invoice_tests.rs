@@ -0,0 +1,172 @@
+use backend::models::payment::{
+    Currency, Invoice, InvoiceKind, InvoiceSettings, PaymentMetadata, PaymentRecord, PaymentStatus, TaxRegistration,
+};
+use backend::models::stable_string::StableString;
+use backend::services::invoice_service::{
+    can_view_invoice, get_invoice, issue_invoice, render_invoice, set_invoice_settings, split_tax, InvoiceFormat,
+};
+use backend::services::payment_service::{fulfil_payment_at, PaymentConfirmation};
+use backend::services::plan_service::seed_default_plans;
+use backend::storage::memory::PAYMENT_RECORDS;
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000; // 2023-11-14
+const NEW_YEAR: u64 = 1_704_067_200 * 1_000_000_000; // 2024-01-01
+const PRICE: u64 = 2_900_000;
+
+fn user(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn paid_invoice(reference: &str, user_id: &str, currency: Currency, amount: u64, at: u64) -> Invoice {
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: user_id.to_string(),
+        paystack_reference: reference.to_string(),
+        amount,
+        currency,
+        email: "ada@startup.ng".to_string(),
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        metadata: PaymentMetadata {
+            customer_name: Some("Ada <Lovelace> & Co".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+    fulfil_payment_at(reference, PaymentConfirmation::default(), at).unwrap();
+    get_invoice(&format!("INV-{}", reference)).unwrap()
+}
+
+#[test]
+fn test_numbers_are_sequential_per_year_and_series() {
+    seed_default_plans(NOW);
+    let first = paid_invoice("INF-num-1", "user-a", Currency::NGN, PRICE, NOW);
+    let second = paid_invoice("INF-num-2", "user-b", Currency::NGN, PRICE, NOW + 1);
+    assert_eq!(first.invoice_number, "INF-2023-000001");
+    assert_eq!(second.invoice_number, "INF-2023-000002");
+
+    // Credit notes have their own series
+    let payment = PAYMENT_RECORDS.with(|r| r.borrow().get(&StableString::from("INF-num-1".to_string()))).unwrap();
+    let credit_note = Invoice {
+        id: "CN-RF-1".to_string(),
+        user_id: payment.user_id.clone(),
+        amount: 1_000,
+        currency: Currency::NGN,
+        kind: InvoiceKind::CreditNote,
+        ..Default::default()
+    };
+    assert_eq!(issue_invoice(credit_note.clone(), &payment, NOW + 2).invoice_number, "CN-2023-000001");
+
+    // Issuing the same id again does not take a number
+    assert_eq!(issue_invoice(credit_note, &payment, NOW + 3).invoice_number, "CN-2023-000001");
+    let third = paid_invoice("INF-num-3", "user-c", Currency::NGN, PRICE, NOW + 4);
+    assert_eq!(third.invoice_number, "INF-2023-000003");
+
+    // A new year starts over
+    let next_year = paid_invoice("INF-num-4", "user-d", Currency::NGN, PRICE, NEW_YEAR);
+    assert_eq!(next_year.invoice_number, "INF-2024-000001");
+    println!("✅ Invoice numbering test passed");
+}
+
+#[test]
+fn test_vat_is_split_out_of_the_total() {
+    // Nigeria: 7.5% VAT contained in the price
+    let (net, tax) = split_tax(PRICE, Some("NG"));
+    assert_eq!(net, 2_697_674);
+    assert_eq!(tax.len(), 1);
+    assert_eq!((tax[0].name.as_str(), tax[0].rate_bps, tax[0].amount), ("VAT", 750, 202_326));
+
+    // Ghana: VAT plus the NHIL and GETFund levies
+    let (net, tax) = split_tax(1_200_000, Some("GH"));
+    assert_eq!(net, 1_000_000);
+    let amounts: Vec<_> = tax.iter().map(|t| (t.name.as_str(), t.amount)).collect();
+    assert_eq!(amounts, vec![("VAT", 150_000), ("NHIL", 25_000), ("GETFund Levy", 25_000)]);
+
+    for (country, rate) in [("KE", 1600), ("ZA", 1500)] {
+        let (net, tax) = split_tax(999_999, Some(country));
+        assert_eq!(tax[0].rate_bps, rate);
+        assert_eq!(net + tax.iter().map(|t| t.amount).sum::<u64>(), 999_999);
+    }
+
+    // No VAT rule for USD payments
+    assert_eq!(split_tax(PRICE, None), (PRICE, vec![]));
+    println!("✅ VAT split test passed");
+}
+
+#[test]
+fn test_invoice_has_items_tax_and_parties() {
+    seed_default_plans(NOW);
+    set_invoice_settings(InvoiceSettings {
+        tax_registrations: vec![TaxRegistration {
+            country: "KE".to_string(),
+            tax_id: "P051234567X".to_string(),
+        }],
+        ..Default::default()
+    })
+    .unwrap();
+    assert!(set_invoice_settings(InvoiceSettings {
+        tax_registrations: vec![TaxRegistration { country: "kenya".to_string(), tax_id: "x".to_string() }],
+        ..Default::default()
+    })
+    .is_err());
+
+    let invoice = paid_invoice("INF-ke-1", "user-ke", Currency::KES, 116_000, NOW);
+    assert_eq!(invoice.line_items.len(), 1);
+    assert_eq!(invoice.line_items[0].amount, 116_000);
+    assert!(invoice.line_items[0].description.contains("monthly"));
+    assert_eq!(invoice.net_amount, 100_000);
+    assert_eq!(invoice.tax_lines[0].amount, 16_000);
+
+    assert_eq!(invoice.seller.name, "Infoundr");
+    assert_eq!(invoice.seller.tax_id.as_deref(), Some("P051234567X"));
+    assert_eq!(invoice.buyer.name, "Ada <Lovelace> & Co");
+    assert_eq!(invoice.buyer.country.as_deref(), Some("KE"));
+
+    // USD invoices carry no tax and no local registration
+    let usd = paid_invoice("INF-usd-1", "user-us", Currency::USD, 2_000, NOW);
+    assert!(usd.tax_lines.is_empty());
+    assert_eq!(usd.net_amount, 2_000);
+    assert_eq!(usd.seller.tax_id, None);
+    println!("✅ Invoice contents test passed");
+}
+
+#[test]
+fn test_rendering_and_access() {
+    seed_default_plans(NOW);
+    let owner = user(7);
+    let invoice = paid_invoice("INF-render-1", &owner.to_text(), Currency::NGN, PRICE, NOW);
+
+    let html = render_invoice(&invoice, InvoiceFormat::Html);
+    assert_eq!(html.content_type, "text/html; charset=utf-8");
+    assert_eq!(html.filename, format!("{}.html", invoice.invoice_number));
+    let html = String::from_utf8(html.body).unwrap();
+    assert!(html.contains(&invoice.invoice_number));
+    assert!(html.contains("NGN 29,000.00"));
+    assert!(html.contains("VAT 7.5%"));
+    assert!(html.contains("Ada &lt;Lovelace&gt; &amp; Co"), "buyer details are escaped");
+
+    let pdf = render_invoice(&invoice, InvoiceFormat::Pdf);
+    assert_eq!(pdf.content_type, "application/pdf");
+    let body = String::from_utf8(pdf.body).unwrap();
+    assert!(body.starts_with("%PDF-1.4\n"));
+    assert!(body.ends_with("%%EOF\n"));
+    assert!(body.contains("(NGN 29,000.00) Tj"));
+    assert!(body.contains("(Ada <Lovelace> & Co) Tj"));
+
+    // The xref table points at every object
+    let xref_at: usize = body.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+    assert!(body[xref_at..].starts_with("xref\n0 7\n"));
+    for (i, entry) in body[xref_at..].lines().skip(3).take(6).enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(body[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+    }
+
+    assert!(can_view_invoice(owner, &invoice));
+    assert!(!can_view_invoice(user(8), &invoice));
+    println!("✅ Invoice rendering test passed");
+}
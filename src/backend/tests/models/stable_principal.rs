@@ -1,5 +1,5 @@
 use candid::{CandidType, Decode, Encode, Principal};
-use ic_stable_structures::{BoundedStorable, Storable};
+use ic_stable_structures::storable::{Bound, Storable};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
@@ -31,11 +31,8 @@ impl Storable for StablePrincipal {
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
         Self(Decode!(bytes.as_ref(), Principal).unwrap())
     }
-}
 
-impl BoundedStorable for StablePrincipal {
-    const MAX_SIZE: u32 = 128;
-    const IS_FIXED_SIZE: bool = false;
+    const BOUND: Bound = Bound::Bounded { max_size: 128, is_fixed_size: false };
 }
 
 impl From<Principal> for StablePrincipal {
@@ -0,0 +1,372 @@
+use backend::models::payment::{Currency, PaymentChannel, PaymentGateway, PaymentRecord, PaymentStatus};
+use backend::models::stable_string::StableString;
+use backend::payments::daraja_client::{normalize_phone, stk_password, DarajaProvider};
+use backend::payments::paystack_client::PaystackProvider;
+use backend::payments::provider::{
+    HttpCall, HttpTransport, PaymentLookup, PaymentProvider, ProviderInitRequest,
+};
+use backend::payments::stripe_client::{hmac_sha256, StripeProvider};
+use backend::payments::{
+    select_gateway, set_daraja_config, set_paystack_config, set_provider_route, set_stripe_config,
+    DarajaConfig, DarajaEnvironment, PaystackConfig, PaystackEnvironment, ProviderRoute,
+    StripeConfig,
+};
+use backend::services::payment_service::apply_provider_event;
+use backend::storage::memory::PAYMENT_RECORDS;
+use ic_cdk::api::management_canister::http_request::{HttpMethod, HttpResponse};
+use sha2::{Digest, Sha512};
+use std::cell::RefCell;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const STRIPE_WEBHOOK_SECRET: &str = "whsec_test";
+const DARAJA_TOKEN: &str = "relay-token";
+
+// Canned HTTP transport: replays responses in order and records every call
+struct CannedHttp {
+    responses: RefCell<Vec<HttpResponse>>,
+    calls: RefCell<Vec<HttpCall>>,
+}
+
+impl CannedHttp {
+    fn new(responses: Vec<(u32, &str)>) -> Self {
+        CannedHttp {
+            responses: RefCell::new(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| HttpResponse {
+                        status: candid::Nat::from(status),
+                        headers: vec![],
+                        body: body.as_bytes().to_vec(),
+                    })
+                    .collect(),
+            ),
+            calls: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl HttpTransport for &CannedHttp {
+    async fn send(&self, call: HttpCall) -> Result<HttpResponse, String> {
+        self.calls.borrow_mut().push(call);
+        Ok(self.responses.borrow_mut().remove(0))
+    }
+}
+
+// Minimal executor: the canned transport never returns Pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn configure_all() {
+    set_paystack_config(PaystackConfig {
+        secret_key: "sk_test_paystack".to_string(),
+        public_key: "pk_test_paystack".to_string(),
+        environment: PaystackEnvironment::Test,
+    });
+    set_stripe_config(StripeConfig {
+        secret_key: "sk_test_stripe".to_string(),
+        webhook_secret: STRIPE_WEBHOOK_SECRET.to_string(),
+    });
+    set_daraja_config(DarajaConfig {
+        consumer_key: "key".to_string(),
+        consumer_secret: "secret".to_string(),
+        short_code: "174379".to_string(),
+        passkey: "passkey".to_string(),
+        callback_url: "https://relay.example.com/mpesa".to_string(),
+        callback_token: DARAJA_TOKEN.to_string(),
+        environment: DarajaEnvironment::Sandbox,
+    });
+}
+
+fn init_request(currency: Currency, phone: Option<&str>) -> ProviderInitRequest {
+    ProviderInitRequest {
+        reference: "INF-test-1".to_string(),
+        user_id: "provider-user".to_string(),
+        email: "founder@example.com".to_string(),
+        amount: 1_900,
+        currency,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        callback_url: "https://infoundr.com/payment/callback".to_string(),
+        phone_number: phone.map(|p| p.to_string()),
+        enable_mpesa: phone.is_some(),
+        enable_card: phone.is_none(),
+    }
+}
+
+fn pending_payment(gateway: PaymentGateway, currency: Currency, access_code: Option<&str>) -> PaymentRecord {
+    let payment = PaymentRecord {
+        id: "INF-test-1".to_string(),
+        user_id: "provider-user".to_string(),
+        paystack_reference: "INF-test-1".to_string(),
+        amount: 1_900,
+        currency,
+        email: "founder@example.com".to_string(),
+        status: PaymentStatus::Pending,
+        access_code: access_code.map(|c| c.to_string()),
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        created_at: NOW,
+        updated_at: NOW,
+        provider: gateway,
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(payment.id.clone()), payment.clone());
+    });
+    payment
+}
+
+fn stored_payment() -> PaymentRecord {
+    PAYMENT_RECORDS
+        .with(|records| records.borrow().get(&StableString::from("INF-test-1".to_string())))
+        .unwrap()
+}
+
+fn body_of(call: &HttpCall) -> String {
+    String::from_utf8(call.body.clone().unwrap_or_default()).unwrap()
+}
+
+#[test]
+fn test_paystack_provider() {
+    configure_all();
+    let http = CannedHttp::new(vec![
+        (200, r#"{"status":true,"message":"Authorization URL created","data":{"authorization_url":"https://checkout.paystack.com/abc","access_code":"abc","reference":"INF-test-1"}}"#),
+        (200, r#"{"status":true,"message":"Verification successful","data":{"id":99,"domain":"test","status":"success","reference":"INF-test-1","amount":1900,"gateway_response":"Approved","paid_at":"2024-01-01T00:00:00Z","created_at":"2024-01-01T00:00:00Z","channel":"card","currency":"NGN","fees":29,"customer":{"id":1,"email":"founder@example.com","customer_code":"CUS_1","risk_action":"default"},"authorization":{"authorization_code":"AUTH_abc","bin":"408408","last4":"4081","exp_month":"12","exp_year":"2030","channel":"card","card_type":"visa","bank":"Test Bank","country_code":"NG","brand":"visa","reusable":true}}}"#),
+        (200, r#"{"status":true,"message":"Refund has been queued for processing","data":{"id":7,"status":"pending","amount":900,"currency":"NGN"}}"#),
+    ]);
+    let provider = PaystackProvider::new(&http);
+
+    let init = block_on(provider.initialize(init_request(Currency::NGN, None))).unwrap();
+    assert_eq!(init.authorization_url.as_deref(), Some("https://checkout.paystack.com/abc"));
+    assert_eq!(init.access_code.as_deref(), Some("abc"));
+
+    let payment = pending_payment(PaymentGateway::Paystack, Currency::NGN, Some("abc"));
+    let verification = block_on(provider.verify(&payment)).unwrap();
+    assert_eq!(verification.status, PaymentStatus::Success);
+    assert_eq!(verification.transaction_id, Some(99));
+    assert_eq!(verification.authorization_code.as_deref(), Some("AUTH_abc"));
+    assert_eq!(verification.currency, Some(Currency::NGN));
+
+    let refund = block_on(provider.refund(&payment, Some(900), "Customer request")).unwrap();
+    assert_eq!(refund.refund_id, "7");
+    assert_eq!(refund.amount, 900);
+
+    let calls = http.calls.borrow();
+    assert_eq!(calls[0].url, "https://api.paystack.co/transaction/initialize");
+    assert_eq!(calls[1].url, "https://api.paystack.co/transaction/verify/INF-test-1");
+    assert!(matches!(calls[1].method, HttpMethod::GET));
+    assert!(body_of(&calls[2]).contains(r#""amount":900"#));
+
+    // Webhook: SHA512 over payload + secret
+    let payload = r#"{"event":"charge.success","data":{"id":99,"reference":"INF-test-1","channel":"card","authorization":{"reusable":true,"authorization_code":"AUTH_abc"}}}"#;
+    let mut hasher = Sha512::new();
+    hasher.update(payload.as_bytes());
+    hasher.update(b"sk_test_paystack");
+    let signature = hex::encode(hasher.finalize());
+    assert!(provider.verify_webhook(payload, &signature).is_ok());
+    assert!(provider.verify_webhook(payload, "bogus").is_err());
+
+    let event = provider.parse_webhook(payload).unwrap().unwrap();
+    assert_eq!(event.lookup, PaymentLookup::Reference("INF-test-1".to_string()));
+    assert_eq!(event.status, PaymentStatus::Success);
+    assert_eq!(event.authorization_code.as_deref(), Some("AUTH_abc"));
+    println!("✅ Paystack provider test passed");
+}
+
+#[test]
+fn test_stripe_provider() {
+    configure_all();
+    let http = CannedHttp::new(vec![
+        (200, r#"{"id":"cs_test_123","url":"https://checkout.stripe.com/c/pay/cs_test_123","status":"open","payment_status":"unpaid","client_reference_id":"INF-test-1","payment_intent":null,"amount_total":1900,"currency":"usd"}"#),
+        (200, r#"{"id":"cs_test_123","url":null,"status":"complete","payment_status":"paid","client_reference_id":"INF-test-1","payment_intent":"pi_123","amount_total":1900,"currency":"usd","customer_details":{"email":"founder@example.com","name":"Ada"}}"#),
+        (400, r#"{"error":{"message":"Charge has already been refunded."}}"#),
+    ]);
+    let provider = StripeProvider::new(&http, NOW);
+
+    let init = block_on(provider.initialize(init_request(Currency::USD, None))).unwrap();
+    assert_eq!(init.access_code.as_deref(), Some("cs_test_123"));
+    assert!(init.authorization_url.unwrap().starts_with("https://checkout.stripe.com/"));
+
+    let payment = pending_payment(PaymentGateway::Stripe, Currency::USD, Some("cs_test_123"));
+    let verification = block_on(provider.verify(&payment)).unwrap();
+    assert_eq!(verification.status, PaymentStatus::Success);
+    assert_eq!(verification.provider_transaction_id.as_deref(), Some("pi_123"));
+    assert_eq!(verification.currency, Some(Currency::USD));
+    assert_eq!(verification.customer_name.as_deref(), Some("Ada"));
+
+    // Refund needs the payment intent, and Stripe errors are surfaced
+    assert!(block_on(provider.refund(&payment, None, "")).is_err());
+    let mut paid = payment.clone();
+    paid.provider_transaction_id = Some("pi_123".to_string());
+    let error = block_on(provider.refund(&paid, None, "")).unwrap_err();
+    assert!(error.contains("already been refunded"));
+
+    let calls = http.calls.borrow();
+    assert_eq!(calls[0].url, "https://api.stripe.com/v1/checkout/sessions");
+    let form = body_of(&calls[0]);
+    assert!(form.contains("client_reference_id=INF-test-1"));
+    assert!(form.contains("line_items%5B0%5D%5Bprice_data%5D%5Bunit_amount%5D=1900"));
+    assert!(calls[0].headers.iter().any(|h| h.name == "Idempotency-Key" && h.value == "INF-test-1"));
+    assert_eq!(calls[1].url, "https://api.stripe.com/v1/checkout/sessions/cs_test_123");
+    assert_eq!(calls.len(), 3);
+    println!("✅ Stripe provider test passed");
+}
+
+#[test]
+fn test_stripe_webhook_signature() {
+    configure_all();
+    let http = CannedHttp::new(vec![]);
+    let provider = StripeProvider::new(&http, NOW);
+
+    let payload = r#"{"id":"evt_1","type":"checkout.session.completed","data":{"object":{"id":"cs_test_123","status":"complete","payment_status":"paid","client_reference_id":"INF-test-1","payment_intent":"pi_123"}}}"#;
+    let timestamp = NOW / 1_000_000_000;
+    let mac = hex::encode(hmac_sha256(
+        STRIPE_WEBHOOK_SECRET.as_bytes(),
+        format!("{}.{}", timestamp, payload).as_bytes(),
+    ));
+
+    let header = format!("t={},v1={}", timestamp, mac);
+    assert!(provider.verify_webhook(payload, &header).is_ok());
+    assert!(provider.verify_webhook(&payload.replace("paid", "unpaid"), &header).is_err());
+
+    // Replayed outside the tolerance window
+    let late = StripeProvider::new(&http, NOW + 10 * 60 * 1_000_000_000);
+    assert!(late.verify_webhook(payload, &header).is_err());
+
+    let event = provider.parse_webhook(payload).unwrap().unwrap();
+    assert_eq!(event.lookup, PaymentLookup::Reference("INF-test-1".to_string()));
+    assert_eq!(event.status, PaymentStatus::Success);
+    assert_eq!(event.provider_transaction_id.as_deref(), Some("pi_123"));
+
+    let ignored = r#"{"id":"evt_2","type":"customer.created","data":{"object":{}}}"#;
+    assert!(provider.parse_webhook(ignored).unwrap().is_none());
+    println!("✅ Stripe webhook test passed");
+}
+
+#[test]
+fn test_hmac_sha256_known_vector() {
+    // RFC 4231 test case 2
+    let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+    assert_eq!(
+        hex::encode(mac),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn test_daraja_provider() {
+    configure_all();
+    let http = CannedHttp::new(vec![
+        (200, r#"{"access_token":"tok_1","expires_in":"3599"}"#),
+        (200, r#"{"MerchantRequestID":"29115-34620561-1","CheckoutRequestID":"ws_CO_191220191020363925","ResponseCode":"0","ResponseDescription":"Success. Request accepted for processing","CustomerMessage":"Success. Request accepted for processing"}"#),
+        (200, r#"{"access_token":"tok_2","expires_in":"3599"}"#),
+        (500, r#"{"requestId":"1","errorCode":"500.001.1001","errorMessage":"The transaction is being processed"}"#),
+        (200, r#"{"access_token":"tok_3","expires_in":"3599"}"#),
+        (200, r#"{"ResponseCode":"0","ResponseDescription":"The service request has been accepted successsfully","MerchantRequestID":"29115-34620561-1","CheckoutRequestID":"ws_CO_191220191020363925","ResultCode":"0","ResultDesc":"The service request is processed successfully."}"#),
+    ]);
+    let provider = DarajaProvider::new(&http, NOW);
+
+    // STK push only makes sense for KES with a phone number
+    assert!(block_on(provider.initialize(init_request(Currency::NGN, Some("0712345678")))).is_err());
+    assert!(block_on(provider.initialize(init_request(Currency::KES, None))).is_err());
+
+    let init = block_on(provider.initialize(init_request(Currency::KES, Some("0712345678")))).unwrap();
+    assert_eq!(init.authorization_url, None);
+    assert_eq!(init.access_code.as_deref(), Some("ws_CO_191220191020363925"));
+
+    let payment = pending_payment(PaymentGateway::Daraja, Currency::KES, Some("ws_CO_191220191020363925"));
+    let still_waiting = block_on(provider.verify(&payment)).unwrap();
+    assert_eq!(still_waiting.status, PaymentStatus::Pending);
+    let paid = block_on(provider.verify(&payment)).unwrap();
+    assert_eq!(paid.status, PaymentStatus::Success);
+    assert_eq!(paid.channel, Some(PaymentChannel::MobileMoney));
+
+    assert!(block_on(provider.refund(&payment, None, "")).is_err());
+
+    let calls = http.calls.borrow();
+    assert_eq!(
+        calls[0].url,
+        "https://sandbox.safaricom.co.ke/oauth/v1/generate?grant_type=client_credentials"
+    );
+    assert_eq!(calls[1].url, "https://sandbox.safaricom.co.ke/mpesa/stkpush/v1/processrequest");
+    assert!(calls[1].headers.iter().any(|h| h.value == "Bearer tok_1"));
+
+    let push: serde_json::Value = serde_json::from_str(&body_of(&calls[1])).unwrap();
+    assert_eq!(push["PhoneNumber"], "254712345678");
+    assert_eq!(push["Amount"], 19);
+    // 2023-11-14 22:13:20 UTC is 2023-11-15 01:13:20 in Nairobi
+    assert_eq!(push["Timestamp"], "20231115011320");
+    let config = backend::payments::get_daraja_config();
+    assert_eq!(push["Password"], stk_password(&config, "20231115011320"));
+    println!("✅ Daraja provider test passed");
+}
+
+#[test]
+fn test_daraja_callback_fulfils_payment() {
+    configure_all();
+    pending_payment(PaymentGateway::Daraja, Currency::KES, Some("ws_CO_191220191020363925"));
+    let http = CannedHttp::new(vec![]);
+    let provider = DarajaProvider::new(&http, NOW);
+
+    let payload = r#"{"Body":{"stkCallback":{"MerchantRequestID":"29115-34620561-1","CheckoutRequestID":"ws_CO_191220191020363925","ResultCode":0,"ResultDesc":"The service request is processed successfully.","CallbackMetadata":{"Item":[{"Name":"Amount","Value":19},{"Name":"MpesaReceiptNumber","Value":"NLJ7RT61SV"},{"Name":"PhoneNumber","Value":254712345678}]}}}}"#;
+    assert!(provider.verify_webhook(payload, "wrong").is_err());
+    provider.verify_webhook(payload, DARAJA_TOKEN).unwrap();
+
+    let event = provider.parse_webhook(payload).unwrap().unwrap();
+    assert_eq!(event.lookup, PaymentLookup::AccessCode("ws_CO_191220191020363925".to_string()));
+    let reference = apply_provider_event(event, NOW).unwrap();
+    assert_eq!(reference, "INF-test-1");
+
+    let payment = stored_payment();
+    assert_eq!(payment.status, PaymentStatus::Success);
+    assert_eq!(payment.provider_transaction_id.as_deref(), Some("NLJ7RT61SV"));
+    assert_eq!(payment.paid_at, Some(NOW));
+
+    // A late cancellation callback does not undo the payment
+    let cancelled = payload.replace(r#""ResultCode":0"#, r#""ResultCode":1032"#);
+    let event = provider.parse_webhook(&cancelled).unwrap().unwrap();
+    assert_eq!(event.status, PaymentStatus::Abandoned);
+    apply_provider_event(event, NOW + 1).unwrap();
+    assert_eq!(stored_payment().status, PaymentStatus::Success);
+    println!("✅ Daraja callback test passed");
+}
+
+#[test]
+fn test_gateway_selection() {
+    // Nothing configured: everything falls back to Paystack
+    assert_eq!(select_gateway(&Currency::USD, false), PaymentGateway::Paystack);
+
+    configure_all();
+    assert_eq!(select_gateway(&Currency::NGN, false), PaymentGateway::Paystack);
+    assert_eq!(select_gateway(&Currency::USD, false), PaymentGateway::Stripe);
+    assert_eq!(select_gateway(&Currency::KES, true), PaymentGateway::Daraja);
+    assert_eq!(select_gateway(&Currency::KES, false), PaymentGateway::Paystack);
+
+    // Explicit routes win
+    set_provider_route(ProviderRoute { currency: Currency::ZAR, gateway: PaymentGateway::Stripe });
+    assert_eq!(select_gateway(&Currency::ZAR, false), PaymentGateway::Stripe);
+    set_provider_route(ProviderRoute { currency: Currency::USD, gateway: PaymentGateway::Paystack });
+    assert_eq!(select_gateway(&Currency::USD, false), PaymentGateway::Paystack);
+
+    assert_eq!(normalize_phone("+254 712 345 678").unwrap(), "254712345678");
+    assert!(normalize_phone("12345").is_err());
+}
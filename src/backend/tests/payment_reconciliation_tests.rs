@@ -0,0 +1,197 @@
+use backend::models::payment::{Currency, PaymentChannel, PaymentGateway, PaymentRecord, PaymentStatus};
+use backend::models::stable_string::StableString;
+use backend::models::usage_service::UserTier;
+use backend::payments::provider::ProviderVerification;
+use backend::payments::{set_paystack_config, PaystackConfig};
+use backend::services::payment_service::get_payment;
+use backend::services::pricing_services::check_user_tier_at;
+use backend::services::reconciliation_service::{reconcile_pending_payments, PaymentVerifier};
+use backend::storage::memory::PAYMENT_RECORDS;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const MINUTE: u64 = 60 * 1_000_000_000;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+// Mock gateway: answers from a table of references and records every lookup
+struct MockVerifier {
+    statuses: HashMap<String, Result<ProviderVerification, String>>,
+    verified: RefCell<Vec<String>>,
+}
+
+impl MockVerifier {
+    fn new(statuses: Vec<(&str, Result<ProviderVerification, String>)>) -> Self {
+        MockVerifier {
+            statuses: statuses.into_iter().map(|(r, s)| (r.to_string(), s)).collect(),
+            verified: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl PaymentVerifier for MockVerifier {
+    async fn verify(&self, payment: &PaymentRecord) -> Result<ProviderVerification, String> {
+        self.verified.borrow_mut().push(payment.paystack_reference.clone());
+        self.statuses
+            .get(&payment.paystack_reference)
+            .cloned()
+            .unwrap_or_else(|| Ok(verification(PaymentStatus::Pending, 0)))
+    }
+}
+
+fn verification(status: PaymentStatus, amount: u64) -> ProviderVerification {
+    ProviderVerification {
+        status,
+        amount,
+        channel: Some(PaymentChannel::Card),
+        transaction_id: Some(42),
+        ..Default::default()
+    }
+}
+
+// Minimal executor: the mock verifier never returns Pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn configure_paystack() {
+    set_paystack_config(PaystackConfig {
+        secret_key: "sk_test_123".to_string(),
+        public_key: "pk_test_123".to_string(),
+        ..Default::default()
+    });
+}
+
+fn pending_payment(reference: &str, provider: PaymentGateway, created_at: u64) {
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: format!("user-{}", reference),
+        paystack_reference: reference.to_string(),
+        amount: 2_900_000,
+        currency: Currency::NGN,
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        provider,
+        created_at,
+        updated_at: created_at,
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+}
+
+fn status_of(reference: &str) -> PaymentStatus {
+    get_payment(reference.to_string()).unwrap().status
+}
+
+#[test]
+fn test_stale_payments_are_settled_from_the_gateway() {
+    configure_paystack();
+    pending_payment("INF-paid", PaymentGateway::Paystack, NOW - 2 * HOUR);
+    pending_payment("INF-declined", PaymentGateway::Paystack, NOW - 2 * HOUR);
+    pending_payment("INF-walked-away", PaymentGateway::Paystack, NOW - 2 * HOUR);
+    pending_payment("INF-fresh", PaymentGateway::Paystack, NOW - 10 * MINUTE);
+
+    let client = MockVerifier::new(vec![
+        ("INF-paid", Ok(verification(PaymentStatus::Success, 2_900_000))),
+        ("INF-declined", Ok(verification(PaymentStatus::Failed, 0))),
+        ("INF-walked-away", Ok(verification(PaymentStatus::Abandoned, 0))),
+    ]);
+    let summary = block_on(reconcile_pending_payments(&client, NOW));
+
+    assert_eq!(summary.checked, 3);
+    assert_eq!(summary.fulfilled, 1);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.abandoned, 1);
+    assert_eq!(summary.errors, 0);
+    assert!(!client.verified.borrow().contains(&"INF-fresh".to_string()), "fresh checkouts are left alone");
+
+    // A lost webhook no longer costs the customer their upgrade
+    assert_eq!(status_of("INF-paid"), PaymentStatus::Success);
+    assert_eq!(check_user_tier_at("user-INF-paid", NOW), UserTier::Pro);
+    assert_eq!(status_of("INF-declined"), PaymentStatus::Failed);
+    assert_eq!(status_of("INF-walked-away"), PaymentStatus::Abandoned);
+    assert_eq!(status_of("INF-fresh"), PaymentStatus::Pending);
+    println!("✅ Reconciliation outcome test passed");
+}
+
+#[test]
+fn test_long_pending_checkouts_are_abandoned() {
+    configure_paystack();
+    pending_payment("INF-recent", PaymentGateway::Paystack, NOW - 3 * HOUR);
+    pending_payment("INF-old", PaymentGateway::Paystack, NOW - 2 * DAY);
+    pending_payment("INF-short", PaymentGateway::Paystack, NOW - 2 * HOUR);
+    pending_payment("INF-stripe", PaymentGateway::Stripe, NOW - 2 * DAY);
+
+    let client = MockVerifier::new(vec![
+        ("INF-short", Ok(verification(PaymentStatus::Success, 1_000))),
+    ]);
+    let summary = block_on(reconcile_pending_payments(&client, NOW));
+
+    // Still pending at the gateway: kept for a day, then abandoned
+    assert_eq!(summary.still_pending, 1);
+    assert_eq!(summary.abandoned, 1);
+    assert_eq!(status_of("INF-recent"), PaymentStatus::Pending);
+    assert_eq!(status_of("INF-old"), PaymentStatus::Abandoned);
+
+    // Underpaid checkouts are reported, not fulfilled
+    assert_eq!(summary.errors, 1);
+    assert!(summary.last_error.as_deref().unwrap().starts_with("INF-short"));
+    assert_eq!(status_of("INF-short"), PaymentStatus::Pending);
+
+    // Gateways without credentials are not called
+    assert_eq!(summary.skipped, 1);
+    assert!(!client.verified.borrow().contains(&"INF-stripe".to_string()));
+    assert_eq!(status_of("INF-stripe"), PaymentStatus::Pending);
+    println!("✅ Reconciliation abandon test passed");
+}
+
+#[test]
+fn test_outcalls_are_bounded_per_run() {
+    configure_paystack();
+    for i in 0..25 {
+        pending_payment(&format!("INF-{:02}", i), PaymentGateway::Paystack, NOW - 2 * HOUR);
+    }
+
+    let client = MockVerifier::new(vec![]);
+    let first = block_on(reconcile_pending_payments(&client, NOW));
+    assert_eq!(first.checked, 20);
+    assert_eq!(first.deferred, 5);
+    assert_eq!(first.still_pending, 20);
+
+    // The next run picks up where the last one stopped
+    let client = MockVerifier::new(vec![]);
+    let second = block_on(reconcile_pending_payments(&client, NOW + HOUR));
+    assert_eq!(second.checked, 5);
+    assert_eq!(second.deferred, 0);
+    assert_eq!(client.verified.borrow()[0], "INF-20");
+
+    // ...and then wraps around to the start
+    let client = MockVerifier::new(vec![]);
+    let third = block_on(reconcile_pending_payments(&client, NOW + 2 * HOUR));
+    assert_eq!(third.checked, 20);
+    assert_eq!(client.verified.borrow()[0], "INF-00");
+    println!("✅ Reconciliation budget test passed");
+}
+
@@ -0,0 +1,140 @@
+use backend::migrations::{migrate_from_bytes, StableStateV4};
+use backend::models::payment::{Currency, PaymentRecord, PaymentStatus};
+use backend::models::plan::{Plan, Price};
+use backend::models::stable_string::StableString;
+use backend::models::usage_service::UserTier;
+use backend::services::payment_service::{fulfil_payment_at, PaymentConfirmation};
+use backend::services::plan_service::{
+    create_plan, delete_plan, get_plan, remove_plan_price, resolve_price, seed_default_plans,
+    set_plan_price, update_plan,
+};
+use backend::storage::memory::{PAYMENT_RECORDS, USER_SUBSCRIPTIONS};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn price(billing_period: &str, currency: Currency, amount: u64) -> Price {
+    Price {
+        billing_period: billing_period.to_string(),
+        currency,
+        amount,
+    }
+}
+
+fn team_plan(prices: Vec<Price>) -> Plan {
+    Plan {
+        id: "Team".to_string(),
+        name: "Team".to_string(),
+        description: "Pro for the whole team".to_string(),
+        tier: UserTier::Pro,
+        prices,
+        is_active: true,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+#[test]
+fn test_default_catalog_prices_every_currency() {
+    seed_default_plans(NOW);
+
+    for currency in [Currency::NGN, Currency::KES, Currency::GHS, Currency::ZAR, Currency::USD] {
+        let monthly = resolve_price("Pro", "monthly", &currency).expect("monthly price");
+        let yearly = resolve_price("Pro", "yearly", &currency).expect("yearly price");
+        assert!(yearly.amount > monthly.amount, "{:?} yearly should cost more", currency);
+    }
+
+    // The KES test price is gone
+    assert_eq!(resolve_price("Pro", "monthly", &Currency::KES).unwrap().amount, 290_000);
+
+    // Seeding twice keeps admin changes
+    set_plan_price("Pro", price("monthly", Currency::USD, 2_400), NOW).unwrap();
+    seed_default_plans(NOW);
+    assert_eq!(resolve_price("Pro", "monthly", &Currency::USD).unwrap().amount, 2_400);
+    println!("✅ Default catalog test passed");
+}
+
+#[test]
+fn test_price_changes_without_upgrade() {
+    seed_default_plans(NOW);
+
+    let plan = set_plan_price("Pro", price("monthly", Currency::GHS, 35_000), NOW + 1).unwrap();
+    assert_eq!(plan.prices.iter().filter(|p| p.currency == Currency::GHS && p.billing_period == "monthly").count(), 1);
+    assert_eq!(plan.created_at, NOW);
+    assert_eq!(plan.updated_at, NOW + 1);
+    assert_eq!(resolve_price("Pro", "monthly", &Currency::GHS).unwrap().amount, 35_000);
+
+    remove_plan_price("Pro", "yearly", &Currency::ZAR, NOW + 2).unwrap();
+    assert!(resolve_price("Pro", "yearly", &Currency::ZAR).is_err());
+    assert!(remove_plan_price("Pro", "yearly", &Currency::ZAR, NOW + 3).is_err());
+
+    // Deactivated plans cannot be bought
+    let mut plan = get_plan("Pro").unwrap();
+    plan.is_active = false;
+    update_plan(plan, NOW + 4).unwrap();
+    assert!(resolve_price("Pro", "monthly", &Currency::NGN).is_err());
+    println!("✅ Price update test passed");
+}
+
+#[test]
+fn test_plan_validation() {
+    assert!(create_plan(team_plan(vec![price("weekly", Currency::USD, 500)]), NOW).is_err());
+    assert!(create_plan(team_plan(vec![price("monthly", Currency::USD, 0)]), NOW).is_err());
+    assert!(create_plan(
+        team_plan(vec![price("monthly", Currency::USD, 900), price("monthly", Currency::USD, 1_000)]),
+        NOW
+    )
+    .is_err());
+
+    let mut free = team_plan(vec![]);
+    free.tier = UserTier::Free;
+    assert!(create_plan(free, NOW).is_err());
+
+    create_plan(team_plan(vec![price("monthly", Currency::USD, 4_900)]), NOW).unwrap();
+    assert!(create_plan(team_plan(vec![]), NOW).is_err(), "duplicate id");
+    assert!(update_plan(Plan { id: "Missing".to_string(), ..team_plan(vec![]) }, NOW).is_err());
+
+    delete_plan("Team").unwrap();
+    assert!(resolve_price("Team", "monthly", &Currency::USD).is_err());
+    assert!(delete_plan("Team").is_err());
+    println!("✅ Plan validation test passed");
+}
+
+#[test]
+fn test_fulfilment_grants_plan_tier() {
+    create_plan(team_plan(vec![price("yearly", Currency::ZAR, 900_000)]), NOW).unwrap();
+
+    let payment = PaymentRecord {
+        id: "INF-team-1".to_string(),
+        user_id: "team-user".to_string(),
+        paystack_reference: "INF-team-1".to_string(),
+        amount: 900_000,
+        currency: Currency::ZAR,
+        status: PaymentStatus::Pending,
+        tier: "Team".to_string(),
+        billing_period: "yearly".to_string(),
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from("INF-team-1".to_string()), payment);
+    });
+
+    assert!(fulfil_payment_at("INF-team-1", PaymentConfirmation::default(), NOW).unwrap());
+
+    let tier = USER_SUBSCRIPTIONS.with(|subs| {
+        subs.borrow().get(&StableString::from("team-user".to_string())).unwrap().tier
+    });
+    assert_eq!(tier, UserTier::Pro);
+    println!("✅ Plan tier fulfilment test passed");
+}
+
+#[test]
+fn test_upgrade_from_v4_installs_default_catalog() {
+    let bytes = bincode::serialize(&StableStateV4::default()).unwrap();
+    let state = migrate_from_bytes(&bytes).expect("V4 snapshot should migrate");
+
+    assert_eq!(state.plans.len(), 1);
+    let (key, plan) = &state.plans[0];
+    assert_eq!(key, &StableString::from("Pro".to_string()));
+    assert_eq!(plan.price("monthly", &Currency::NGN).unwrap().amount, 2_900_000);
+    println!("✅ V4 catalog migration test passed");
+}
@@ -0,0 +1,113 @@
+use backend::migrations::{migrate_from_bytes, StableStateV4};
+use backend::models::discord_user::DiscordUser;
+use backend::models::main_site_user::MainSiteUser;
+use backend::models::openchat_user::OpenChatUser;
+use backend::models::platform::Platform;
+use backend::models::slack_user::SlackUser;
+use backend::models::stable_string::StableString;
+use backend::services::platform_identity_service::{
+    derived_principal, ensure_identity, get_identity, identities_for_principal, link_identity, list_identities,
+    migrate_legacy_main_site_users, playground_principal, resolve_principal, unlink_identity,
+};
+use backend::storage::memory::LEGACY_MAIN_SITE_USERS;
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+#[test]
+fn test_identities_are_keyed_by_platform() {
+    // The same external ID on two platforms is two users
+    ensure_identity(Platform::Slack, "U100", NOW);
+    ensure_identity(Platform::Discord, "U100", NOW + 1);
+    let again = ensure_identity(Platform::Slack, "U100", NOW + 2);
+    assert_eq!((again.first_interaction, again.last_interaction), (NOW, NOW + 2));
+
+    let slack: Vec<_> = list_identities(Some(Platform::Slack)).into_iter().filter(|i| i.external_id == "U100").collect();
+    assert_eq!(slack.len(), 1);
+    assert_eq!(get_identity(Platform::Discord, "U100").unwrap().last_interaction, NOW + 1);
+    assert!(get_identity(Platform::OpenChat, "U100").is_none());
+    println!("✅ Identity keying test passed");
+}
+
+#[test]
+fn test_links_keep_the_principal_index_in_step() {
+    let (alice, bob) = (principal(1), principal(2));
+    ensure_identity(Platform::Discord, "200", NOW);
+    ensure_identity(Platform::Slack, "U200", NOW);
+
+    link_identity(Platform::Discord, "200", alice).unwrap();
+    link_identity(Platform::Slack, "U200", alice).unwrap();
+    let platforms: Vec<_> = identities_for_principal(alice).iter().map(|i| i.platform).collect();
+    assert_eq!(platforms, vec![Platform::Slack, Platform::Discord]);
+
+    // Relinking moves the identity; unlinking drops it from the index
+    link_identity(Platform::Slack, "U200", bob).unwrap();
+    unlink_identity(Platform::Discord, "200").unwrap();
+    assert!(identities_for_principal(alice).is_empty());
+    assert_eq!(identities_for_principal(bob)[0].external_id, "U200");
+
+    assert_eq!(link_identity(Platform::Slack, "U404", alice), Err("Slack user not found".to_string()));
+    assert_eq!(unlink_identity(Platform::MainSite, "m-404"), Err("Main site user not found".to_string()));
+    println!("✅ Principal index test passed");
+}
+
+#[test]
+fn test_principal_resolution() {
+    let site = principal(3);
+    let slack_principal = derived_principal(Platform::Slack, "U300").unwrap();
+    assert_eq!(&slack_principal.as_slice()[..5], &[5, b'U', b'3', b'0', b'0']);
+    assert_eq!(derived_principal(Platform::Discord, "300").unwrap().as_slice()[0], 6);
+    assert_eq!(playground_principal("session").as_slice()[0], 7);
+    assert_eq!(derived_principal(Platform::OpenChat, "not a principal"), None);
+    assert_eq!(derived_principal(Platform::OpenChat, &site.to_text()), Some(site));
+
+    ensure_identity(Platform::Slack, "U300", NOW);
+    assert_eq!(resolve_principal(Platform::Slack, "U300"), slack_principal);
+
+    link_identity(Platform::Slack, "U300", site).unwrap();
+    assert_eq!(resolve_principal(Platform::Slack, "U300"), site);
+    println!("✅ Principal resolution test passed");
+}
+
+#[test]
+fn test_platform_user_tables_are_migrated() {
+    let site = principal(4);
+    let v4 = StableStateV4 {
+        openchat_users: vec![(
+            StableString::from("oc-1"),
+            OpenChatUser { openchat_id: "oc-1".to_string(), site_principal: Some(site.into()), first_interaction: NOW, last_interaction: NOW + 5 },
+        )],
+        slack_users: vec![(
+            StableString::from("U400"),
+            SlackUser { slack_id: "U400".to_string(), site_principal: None, display_name: Some("ada".to_string()), team_id: Some("T1".to_string()) },
+        )],
+        discord_users: vec![(
+            StableString::from("400"),
+            DiscordUser { discord_id: "400".to_string(), site_principal: Some(site.into()), username: Some("grace".to_string()), guild_id: Some("G1".to_string()) },
+        )],
+        ..Default::default()
+    };
+
+    let state = migrate_from_bytes(&bincode::serialize(&v4).unwrap()).unwrap();
+    assert!(state.openchat_users.is_empty() && state.slack_users.is_empty() && state.discord_users.is_empty());
+    let identities: Vec<_> = state.platform_identities.iter().map(|((platform, id), identity)| {
+        assert_eq!((*platform, id.as_str()), (identity.platform, identity.external_id.as_str()));
+        identity.clone()
+    }).collect();
+    assert_eq!(identities.len(), 3);
+    assert_eq!((identities[0].platform, identities[0].site_principal, identities[0].last_interaction), (Platform::OpenChat, Some(site), NOW + 5));
+    assert_eq!((identities[1].display_name.as_deref(), identities[1].workspace_id.as_deref()), (Some("ada"), Some("T1")));
+    assert_eq!((identities[2].platform, identities[2].display_name.as_deref()), (Platform::Discord, Some("grace")));
+
+    // Main site users were never in the snapshot; they move over from their old map
+    let user = MainSiteUser { main_site_id: "m-1".to_string(), site_principal: Some(site.into()), first_interaction: NOW, last_interaction: NOW };
+    LEGACY_MAIN_SITE_USERS.with(|users| users.borrow_mut().insert(StableString::from("m-1"), user));
+    assert_eq!(migrate_legacy_main_site_users(), 1);
+    assert_eq!(migrate_legacy_main_site_users(), 0);
+    assert_eq!(identities_for_principal(site).iter().map(|i| i.platform).collect::<Vec<_>>(), vec![Platform::MainSite]);
+    println!("✅ Platform user migration test passed");
+}
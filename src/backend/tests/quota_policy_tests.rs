@@ -0,0 +1,132 @@
+use backend::models::quota::{BotCost, BurstLimit, QuotaLimit, QuotaPolicy, QuotaRemaining};
+use backend::models::usage_service::UserTier;
+use backend::services::pricing_services::{
+    can_make_request_at, get_quota_policy, get_user_daily_requests_at, increment_user_requests_at, set_quota_policy,
+};
+
+// Tuesday 14 November 2023, 22:13:20 UTC
+const NOW_SECS: u64 = 1_700_000_000;
+const NOW: u64 = NOW_SECS * SECOND;
+const SECOND: u64 = 1_000_000_000;
+const DAY: u64 = 86_400 * SECOND;
+// 1 December 2023, 00:00 UTC
+const NEXT_MONTH_SECS: u64 = 1_701_388_800;
+
+fn free_policy() -> QuotaPolicy {
+    QuotaPolicy::default_for(UserTier::Free)
+}
+
+fn use_requests(user_id: &str, count: u32, now: u64) {
+    for _ in 0..count {
+        increment_user_requests_at(user_id, None, now).unwrap();
+    }
+}
+
+#[test]
+fn test_default_policies_keep_the_old_limits() {
+    assert_eq!(get_quota_policy(&UserTier::Free).daily_limit, Some(20));
+    assert_eq!(get_quota_policy(&UserTier::Pro).daily_limit, None);
+
+    use_requests("free_user", 19, NOW);
+    assert_eq!(
+        increment_user_requests_at("free_user", None, NOW),
+        Ok(QuotaRemaining { burst: None, daily: Some(0), monthly: None })
+    );
+
+    let rejection = increment_user_requests_at("free_user", None, NOW).unwrap_err();
+    assert_eq!(rejection.limit, QuotaLimit::Daily);
+    assert_eq!(rejection.retry_after_secs, 86_400 - NOW_SECS % 86_400);
+    assert_eq!(rejection.remaining.daily, Some(0));
+    assert!(rejection.message.contains("Upgrade to Pro"));
+    assert!(rejection.to_string().contains("Retry after 6400 seconds"));
+    println!("✅ Default policy test passed");
+}
+
+#[test]
+fn test_burst_bucket_refills_over_time() {
+    let policy = QuotaPolicy { burst: Some(BurstLimit { capacity: 3, refill_per_minute: 6 }), ..free_policy() };
+    set_quota_policy(policy, NOW).unwrap();
+
+    use_requests("bursty", 3, NOW);
+    let rejection = increment_user_requests_at("bursty", None, NOW).unwrap_err();
+    assert_eq!(rejection.limit, QuotaLimit::Burst);
+    assert_eq!(rejection.retry_after_secs, 10);
+    assert_eq!(rejection.remaining, QuotaRemaining { burst: Some(0), daily: Some(17), monthly: None });
+
+    // Refused requests are not counted
+    assert_eq!(get_user_daily_requests_at("bursty", NOW), 3);
+
+    // One unit back every ten seconds, never more than the capacity
+    assert!(!can_make_request_at("bursty", NOW + 9 * SECOND));
+    assert_eq!(increment_user_requests_at("bursty", None, NOW + 10 * SECOND).unwrap().burst, Some(0));
+    assert_eq!(increment_user_requests_at("bursty", None, NOW + 10 * 60 * SECOND).unwrap().burst, Some(2));
+    println!("✅ Burst test passed");
+}
+
+#[test]
+fn test_bots_cost_what_the_policy_says() {
+    let policy = QuotaPolicy {
+        bot_costs: vec![BotCost { bot_name: "GitHub Agent".to_string(), cost: 5 }],
+        ..free_policy()
+    };
+    set_quota_policy(policy, NOW).unwrap();
+
+    for _ in 0..3 {
+        increment_user_requests_at("builder", Some("GitHub Agent"), NOW).unwrap();
+    }
+    assert_eq!(increment_user_requests_at("builder", Some("Benny"), NOW).unwrap().daily, Some(4));
+
+    // Four units left: too few for the agent, enough for a chat bot
+    let rejection = increment_user_requests_at("builder", Some("GitHub Agent"), NOW).unwrap_err();
+    assert_eq!((rejection.limit, rejection.cost, rejection.remaining.daily), (QuotaLimit::Daily, 5, Some(4)));
+    assert!(increment_user_requests_at("builder", None, NOW).is_ok());
+    assert_eq!(get_user_daily_requests_at("builder", NOW), 17);
+    println!("✅ Bot cost test passed");
+}
+
+#[test]
+fn test_monthly_cap_spans_days() {
+    set_quota_policy(QuotaPolicy { monthly_limit: Some(50), ..free_policy() }, NOW).unwrap();
+
+    use_requests("steady", 20, NOW);
+    use_requests("steady", 20, NOW + DAY);
+    assert_eq!(increment_user_requests_at("steady", None, NOW + 2 * DAY).unwrap().monthly, Some(9));
+    use_requests("steady", 9, NOW + 2 * DAY);
+
+    let later = NOW + 2 * DAY;
+    let rejection = increment_user_requests_at("steady", None, later).unwrap_err();
+    assert_eq!(rejection.limit, QuotaLimit::Monthly);
+    assert_eq!(rejection.retry_after_secs, NEXT_MONTH_SECS - later / SECOND);
+    assert_eq!(rejection.remaining, QuotaRemaining { burst: None, daily: Some(10), monthly: Some(0) });
+
+    // A new month starts from zero
+    assert!(can_make_request_at("steady", NEXT_MONTH_SECS * SECOND));
+    println!("✅ Monthly cap test passed");
+}
+
+#[test]
+fn test_policy_changes_apply_at_runtime() {
+    use_requests("regular", 10, NOW);
+    let saved = set_quota_policy(QuotaPolicy { daily_limit: Some(10), ..free_policy() }, NOW + SECOND).unwrap();
+    assert_eq!(saved.updated_at, NOW + SECOND);
+    assert_eq!(get_quota_policy(&UserTier::Free), saved);
+    assert!(!can_make_request_at("regular", NOW + SECOND));
+
+    let invalid = [
+        QuotaPolicy { burst: Some(BurstLimit { capacity: 0, refill_per_minute: 1 }), ..free_policy() },
+        QuotaPolicy { bot_costs: vec![BotCost { bot_name: "Benny".to_string(), cost: 0 }], ..free_policy() },
+        QuotaPolicy { bot_costs: vec![BotCost { bot_name: "Benny".to_string(), cost: 21 }], ..free_policy() },
+        QuotaPolicy {
+            bot_costs: vec![
+                BotCost { bot_name: "Benny".to_string(), cost: 1 },
+                BotCost { bot_name: "Benny".to_string(), cost: 2 },
+            ],
+            ..free_policy()
+        },
+    ];
+    for policy in invalid {
+        assert!(set_quota_policy(policy, NOW).is_err());
+    }
+    assert_eq!(get_quota_policy(&UserTier::Free), saved);
+    println!("✅ Runtime policy test passed");
+}
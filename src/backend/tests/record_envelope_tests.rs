@@ -0,0 +1,159 @@
+use backend::models::record::{decode_record, encode_record, split_record, VersionedRecord, LEGACY_RECORD_VERSION};
+use backend::models::stable_string::StableString;
+use backend::models::waitlist::{WaitlistEntry, WaitlistStatus};
+use backend::services::record_repair_service::{quarantined_records, repair_records, repairable_maps};
+use backend::storage::memory::WAITLIST;
+use backend::storage::record_map::RecordMap;
+use candid::{CandidType, Deserialize};
+use ic_stable_structures::VectorMemory;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn entry(email: &str) -> WaitlistEntry {
+    WaitlistEntry {
+        email: email.to_string(),
+        name: "Ada".to_string(),
+        created_at: NOW,
+        status: WaitlistStatus::Pending,
+    }
+}
+
+/* ============================
+   Envelope
+   ============================ */
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+struct NoteV1 {
+    text: String,
+}
+
+impl VersionedRecord for NoteV1 {}
+
+// Version 2 added a required field, so version 1 payloads need an upgrade
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+struct Note {
+    text: String,
+    pinned: bool,
+}
+
+impl VersionedRecord for Note {
+    const VERSION: u8 = 2;
+
+    fn upgrade(version: u8, payload: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => {
+                let old: NoteV1 = candid::decode_one(payload).map_err(|e| e.to_string())?;
+                Ok(Note { text: old.text, pinned: false })
+            }
+            _ => Err(format!("no upgrade from version {}", version)),
+        }
+    }
+}
+
+#[test]
+fn test_records_round_trip_with_their_version() {
+    let note = Note { text: "hi".to_string(), pinned: true };
+    let bytes = encode_record(&note);
+    assert_eq!(split_record(&bytes).map(|(version, _)| version), Some(2));
+    assert_eq!(decode_record::<Note>(&bytes), Ok(note));
+}
+
+#[test]
+fn test_legacy_records_without_envelope_still_decode() {
+    let bare = candid::encode_one(entry("legacy@example.com")).unwrap();
+    assert_eq!(split_record(&bare).map(|(version, _)| version), Some(LEGACY_RECORD_VERSION));
+    assert_eq!(decode_record::<WaitlistEntry>(&bare).unwrap().email, "legacy@example.com");
+}
+
+#[test]
+fn test_older_versions_are_upgraded() {
+    let old = encode_record(&NoteV1 { text: "old".to_string() });
+    assert_eq!(decode_record::<Note>(&old), Ok(Note { text: "old".to_string(), pinned: false }));
+}
+
+#[test]
+fn test_newer_and_corrupt_records_are_errors() {
+    let newer = encode_record(&Note { text: "new".to_string(), pinned: false });
+    let error = decode_record::<NoteV1>(&newer).unwrap_err();
+    assert!(error.contains("newer release"), "{}", error);
+
+    assert!(decode_record::<Note>(&[]).is_err());
+    let error = decode_record::<Note>(&[2, 0xde, 0xad]).unwrap_err();
+    assert!(error.starts_with("Undecodable Note record, version 2"), "{}", error);
+}
+
+/* ============================
+   Record maps
+   ============================ */
+
+#[test]
+fn test_record_maps_skip_values_they_cannot_decode() {
+    let mut map: RecordMap<StableString, WaitlistEntry, VectorMemory> = RecordMap::init(VectorMemory::default());
+    map.insert(StableString::from("a@example.com".to_string()), entry("a@example.com"));
+    map.insert_raw(StableString::from("b@example.com".to_string()), vec![1, 0xde, 0xad]);
+    let legacy = candid::encode_one(entry("c@example.com")).unwrap();
+    map.insert_raw(StableString::from("c@example.com".to_string()), legacy);
+
+    // Reads skip the bad value instead of trapping
+    assert!(map.get(&StableString::from("b@example.com".to_string())).is_none());
+    assert!(map.contains_key(&StableString::from("b@example.com".to_string())));
+    let emails: Vec<String> = map.iter().map(|(_, entry)| entry.email).collect();
+    assert_eq!(emails, vec!["a@example.com", "c@example.com"]);
+    assert_eq!(map.last_key_value().map(|(_, entry)| entry.email), Some("c@example.com".to_string()));
+    assert_eq!(map.len(), 3);
+
+    // Writes over it still go through
+    assert!(map.insert(StableString::from("b@example.com".to_string()), entry("b@example.com")).is_none());
+    assert_eq!(map.iter().count(), 3);
+}
+
+/* ============================
+   Repair
+   ============================ */
+
+fn corrupt_waitlist_entry(email: &str) {
+    WAITLIST.with(|waitlist| {
+        waitlist.borrow_mut().insert_raw(StableString::from(email.to_string()), vec![1, 0xde, 0xad, 0xbe, 0xef]);
+    });
+}
+
+#[test]
+fn test_undecodable_records_are_found_and_quarantined() {
+    WAITLIST.with(|waitlist| {
+        let mut waitlist = waitlist.borrow_mut();
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            waitlist.insert(StableString::from(email.to_string()), entry(email));
+        }
+    });
+    corrupt_waitlist_entry("b@example.com");
+
+    let reports = repair_records(Some("WAITLIST"), false, NOW).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!((reports[0].scanned, reports[0].undecodable, reports[0].quarantined), (3, 1, 0));
+    assert_eq!(reports[0].examples[0].version, Some(1));
+    assert!(reports[0].examples[0].error.contains("WaitlistEntry"));
+    assert!(quarantined_records(None, 10).is_empty());
+
+    let reports = repair_records(Some("WAITLIST"), true, NOW).unwrap();
+    assert_eq!(reports[0].quarantined, 1);
+
+    let quarantined = quarantined_records(None, 10);
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].map, "WAITLIST");
+    assert_eq!(quarantined[0].value, vec![1, 0xde, 0xad, 0xbe, 0xef]);
+
+    // The map reads cleanly again and accepts writes
+    let emails: Vec<String> = WAITLIST.with(|waitlist| waitlist.borrow().iter().map(|(_, entry)| entry.email).collect());
+    assert_eq!(emails, vec!["a@example.com", "c@example.com"]);
+    WAITLIST.with(|waitlist| waitlist.borrow_mut().insert(StableString::from("d@example.com".to_string()), entry("d@example.com")));
+    assert_eq!(WAITLIST.with(|waitlist| waitlist.borrow().len()), 3);
+
+    let reports = repair_records(None, false, NOW).unwrap();
+    assert_eq!(reports.len(), repairable_maps().len());
+    assert!(reports.iter().all(|report| report.undecodable == 0));
+}
+
+#[test]
+fn test_unknown_map_is_rejected() {
+    assert_eq!(repair_records(Some("NOPE"), false, NOW), Err("Unknown map: NOPE".to_string()));
+}
@@ -0,0 +1,252 @@
+use backend::models::payment::{
+    Currency, InvoiceKind, PaymentChannel, PaymentRecord, PaymentStatus, RefundStatus,
+};
+use backend::models::stable_string::StableString;
+use backend::models::subscription_billing::SubscriptionStatus;
+use backend::models::usage_service::UserTier;
+use backend::payments::paystack_client::PaystackProvider;
+use backend::payments::provider::{HttpCall, HttpTransport};
+use backend::payments::{set_paystack_config, PaystackConfig, PaystackEnvironment};
+use backend::services::payment_service::{fulfil_payment_at, PaymentConfirmation};
+use backend::services::refund_service::{
+    complete_refund, find_refund, get_payment_refunds, refund_payment, refunded_amount,
+};
+use backend::services::subscription_service::get_subscription_billing;
+use backend::storage::memory::{INVOICES, PAYMENT_RECORDS, USER_SUBSCRIPTIONS};
+use ic_cdk::api::management_canister::http_request::HttpResponse;
+use std::cell::RefCell;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MONTH: u64 = 30 * DAY;
+const USER_ID: &str = "refund-user";
+const REFERENCE: &str = "INF-refund-1";
+const AMOUNT: u64 = 2_900_000;
+
+// Canned HTTP transport: replays responses in order and records every call
+struct CannedHttp {
+    responses: RefCell<Vec<HttpResponse>>,
+    calls: RefCell<Vec<HttpCall>>,
+}
+
+impl CannedHttp {
+    fn new(responses: Vec<(u32, &str)>) -> Self {
+        CannedHttp {
+            responses: RefCell::new(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| HttpResponse {
+                        status: candid::Nat::from(status),
+                        headers: vec![],
+                        body: body.as_bytes().to_vec(),
+                    })
+                    .collect(),
+            ),
+            calls: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl HttpTransport for &CannedHttp {
+    async fn send(&self, call: HttpCall) -> Result<HttpResponse, String> {
+        self.calls.borrow_mut().push(call);
+        Ok(self.responses.borrow_mut().remove(0))
+    }
+}
+
+// Minimal executor: the canned transport never returns Pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn refund_queued(id: u64, amount: u64) -> String {
+    format!(
+        r#"{{"status":true,"message":"Refund has been queued for processing","data":{{"id":{},"status":"pending","amount":{},"currency":"NGN"}}}}"#,
+        id, amount
+    )
+}
+
+/// Configure Paystack and fulfil a monthly Pro payment at NOW
+fn paid_subscription() {
+    set_paystack_config(PaystackConfig {
+        secret_key: "sk_test_paystack".to_string(),
+        public_key: "pk_test_paystack".to_string(),
+        environment: PaystackEnvironment::Test,
+    });
+
+    let payment = PaymentRecord {
+        id: REFERENCE.to_string(),
+        user_id: USER_ID.to_string(),
+        paystack_reference: REFERENCE.to_string(),
+        amount: AMOUNT,
+        currency: Currency::NGN,
+        email: "founder@example.com".to_string(),
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        created_at: NOW,
+        updated_at: NOW,
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(REFERENCE.to_string()), payment);
+    });
+
+    let confirmation = PaymentConfirmation {
+        channel: Some(PaymentChannel::Card),
+        transaction_id: Some(1),
+        provider_transaction_id: None,
+        authorization_code: Some("AUTH_refund".to_string()),
+    };
+    assert!(fulfil_payment_at(REFERENCE, confirmation, NOW).unwrap());
+}
+
+fn payment_status() -> PaymentStatus {
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow().get(&StableString::from(REFERENCE.to_string())).unwrap().status
+    })
+}
+
+fn subscription() -> (UserTier, Option<u64>) {
+    USER_SUBSCRIPTIONS.with(|subs| {
+        let subscription = subs.borrow().get(&StableString::from(USER_ID.to_string())).unwrap();
+        (subscription.tier, subscription.expires_at_ns)
+    })
+}
+
+#[test]
+fn test_partial_refund_prorates_and_issues_credit_note() {
+    paid_subscription();
+    let http = CannedHttp::new(vec![(200, &refund_queued(3018284, AMOUNT / 2))]);
+    let provider = PaystackProvider::new(&http);
+
+    let refund_at = NOW + 10 * DAY;
+    let refund = block_on(refund_payment(&provider, REFERENCE, Some(AMOUNT / 2), "Downgrade", "admin", refund_at))
+        .expect("refund should be accepted");
+
+    // Paystack queues refunds, so nothing changes until the webhook
+    assert_eq!(refund.status, RefundStatus::Pending);
+    assert_eq!(refund.provider_refund_id.as_deref(), Some("3018284"));
+    assert_eq!(refunded_amount(REFERENCE), AMOUNT / 2);
+    assert_eq!(subscription().1, Some(NOW + MONTH));
+
+    let body = String::from_utf8(http.calls.borrow()[0].body.clone().unwrap()).unwrap();
+    assert!(body.contains(r#""transaction":"INF-refund-1""#));
+    assert!(body.contains(r#""amount":1450000"#));
+
+    // refund.processed webhook
+    let found = find_refund(Some("3018284"), None, None).expect("refund should be found");
+    let processed = complete_refund(&found.id, refund_at).unwrap();
+    assert_eq!(processed.status, RefundStatus::Processed);
+
+    // Half the amount takes back half the period
+    let (tier, expiry) = subscription();
+    assert_eq!(tier, UserTier::Pro);
+    assert_eq!(expiry, Some(NOW + MONTH - 15 * DAY));
+    assert_eq!(payment_status(), PaymentStatus::Success);
+
+    let credit_note = INVOICES
+        .with(|invoices| invoices.borrow().get(&StableString::from(processed.credit_note_id.clone().unwrap())))
+        .expect("credit note should be issued");
+    assert_eq!(credit_note.kind, InvoiceKind::CreditNote);
+    assert_eq!(credit_note.amount, AMOUNT / 2);
+    assert_eq!(credit_note.original_invoice_id.as_deref(), Some("INV-INF-refund-1"));
+
+    // Replayed webhook is a no-op
+    complete_refund(&found.id, refund_at + DAY).unwrap();
+    assert_eq!(INVOICES.with(|invoices| invoices.borrow().len()), 2);
+    assert_eq!(subscription().1, Some(NOW + MONTH - 15 * DAY));
+    println!("✅ Partial refund test passed");
+}
+
+#[test]
+fn test_full_refund_reverses_payment_and_downgrades() {
+    paid_subscription();
+    let http = CannedHttp::new(vec![(200, &refund_queued(3018285, AMOUNT))]);
+    let provider = PaystackProvider::new(&http);
+
+    let refund_at = NOW + 10 * DAY;
+    let refund = block_on(refund_payment(&provider, REFERENCE, None, "Requested by customer", "admin", refund_at))
+        .unwrap();
+    assert_eq!(refund.amount, AMOUNT);
+
+    // Webhook matched by transaction reference and amount
+    let found = find_refund(None, Some(REFERENCE), Some(AMOUNT)).unwrap();
+    complete_refund(&found.id, refund_at).unwrap();
+
+    assert_eq!(payment_status(), PaymentStatus::Reversed);
+    assert_eq!(subscription().0, UserTier::Free);
+
+    let billing = get_subscription_billing(USER_ID).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Expired);
+    assert_eq!(billing.current_period_end_ns, refund_at);
+
+    // Nothing left to refund
+    let again = block_on(refund_payment(&provider, REFERENCE, None, "Again", "admin", refund_at));
+    assert!(again.is_err());
+    println!("✅ Full refund test passed");
+}
+
+#[test]
+fn test_refund_limits() {
+    paid_subscription();
+    let http = CannedHttp::new(vec![]);
+    let provider = PaystackProvider::new(&http);
+
+    let over = block_on(refund_payment(&provider, REFERENCE, Some(AMOUNT + 1), "Too much", "admin", NOW));
+    assert!(over.unwrap_err().contains("exceeds refundable balance"));
+
+    let zero = block_on(refund_payment(&provider, REFERENCE, Some(0), "Nothing", "admin", NOW));
+    assert!(zero.is_err());
+
+    let missing = block_on(refund_payment(&provider, "INF-unknown", None, "Missing", "admin", NOW));
+    assert!(missing.is_err());
+
+    // No gateway call was made for rejected refunds
+    assert!(http.calls.borrow().is_empty());
+    assert!(get_payment_refunds(REFERENCE).is_empty());
+    println!("✅ Refund limits test passed");
+}
+
+#[test]
+fn test_gateway_error_releases_refund_amount() {
+    paid_subscription();
+    let http = CannedHttp::new(vec![
+        (400, r#"{"status":false,"message":"Transaction has been fully reversed"}"#),
+        (200, &refund_queued(3018286, AMOUNT)),
+    ]);
+    let provider = PaystackProvider::new(&http);
+
+    let failed = block_on(refund_payment(&provider, REFERENCE, None, "Duplicate", "admin", NOW + DAY));
+    assert!(failed.is_err());
+
+    let refunds = get_payment_refunds(REFERENCE);
+    assert_eq!(refunds.len(), 1);
+    assert_eq!(refunds[0].status, RefundStatus::Failed);
+    assert!(refunds[0].error.is_some());
+    assert_eq!(refunded_amount(REFERENCE), 0);
+
+    // The full amount can be refunded again
+    let retry = block_on(refund_payment(&provider, REFERENCE, None, "Duplicate", "admin", NOW + 2 * DAY)).unwrap();
+    assert_eq!(retry.amount, AMOUNT);
+    assert_ne!(retry.id, refunds[0].id);
+    println!("✅ Refund gateway error test passed");
+}
@@ -0,0 +1,343 @@
+use backend::migrations::*;
+use backend::models::analytics::AnalyticsDataPoint;
+use backend::models::api_message::ApiMessage;
+use backend::models::chat::{ChatMessage, MessageRole};
+use backend::models::main_site_user::MainSiteUser;
+use backend::models::openchat_user::OpenChatUser;
+use backend::models::payment::{Currency, PaymentMetadata, PaymentStatus};
+use backend::models::stable_principal::StablePrincipal;
+use backend::models::stable_string::StableString;
+use backend::models::usage_service::{UserSubscription, UserTier};
+use backend::models::user::{SubscriptionTier, User};
+use backend::models::waitlist::{WaitlistEntry, WaitlistStatus};
+use backend::services::schema_migration_service::{
+    latest_schema_version, migrate_schema, plan_schema_migrations, rewrite_entries, run_migrations, schema_version,
+    set_schema_version, MigrationStep, MIGRATIONS,
+};
+use backend::storage::memory::{LEGACY_MAIN_SITE_USERS, USER_DAILY_USAGE};
+use candid::Principal;
+use serde::Serialize;
+use std::fs;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const DAY: u64 = 86_400 * 1_000_000_000;
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/snapshots");
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+/// The layouts that shipped, then the current one
+const SNAPSHOT_VERSIONS: [u32; 5] = [1, 2, 3, 4, LAST_SNAPSHOT_VERSION];
+
+fn fixture(version: u32, extension: &str) -> String {
+    format!("{}/v{}.{}", FIXTURES, version, extension)
+}
+
+/* ============================
+   Snapshot fixtures
+   ============================ */
+
+fn sample_v1() -> StableStateV1 {
+    let owner = principal(1);
+    StableStateV1 {
+        users: vec![(
+            StablePrincipal::new(owner),
+            User {
+                principal: StablePrincipal::new(owner),
+                name: "Ada".to_string(),
+                email: Some("ada@example.com".to_string()),
+                created_at: NOW,
+                subscription_tier: SubscriptionTier::Free,
+                openchat_id: Some("oc_ada".to_string()),
+                slack_id: None,
+                discord_id: None,
+            },
+        )],
+        waitlist: vec![(
+            StableString::from("grace@example.com"),
+            WaitlistEntry {
+                email: "grace@example.com".to_string(),
+                name: "Grace".to_string(),
+                created_at: NOW,
+                status: WaitlistStatus::Pending,
+            },
+        )],
+        chat_history: vec![(
+            (StablePrincipal::new(owner), 0),
+            ChatMessage {
+                id: owner,
+                role: MessageRole::User,
+                content: "How do I raise a seed round?".to_string(),
+                question_asked: None,
+                timestamp: NOW,
+                bot_name: Some("Benny".to_string()),
+            },
+        )],
+        api_messages: vec![(
+            (StableString::from("U1_1"), NOW),
+            ApiMessage {
+                id: "U1_1".to_string(),
+                user_id: "U1".to_string(),
+                message: "hi".to_string(),
+                response: "hello".to_string(),
+                bot_name: "Benny".to_string(),
+                metadata: None,
+                timestamp: NOW,
+            },
+        )],
+        connected_accounts: vec![],
+        tasks: vec![],
+        github_issues: vec![],
+        openchat_users: vec![(
+            StableString::from("oc_ada"),
+            OpenChatUser {
+                openchat_id: "oc_ada".to_string(),
+                site_principal: Some(StablePrincipal::new(owner)),
+                first_interaction: NOW,
+                last_interaction: NOW,
+            },
+        )],
+        slack_users: vec![],
+        discord_users: vec![],
+        dashboard_tokens: vec![(
+            StableString::from("token"),
+            DashboardTokenV1 { token: vec![1, 2, 3], openchat_id: "oc_ada".to_string(), created_at: NOW, expires_at: NOW + DAY },
+        )],
+        accelerators: vec![],
+        startup_invites: vec![],
+        startups: vec![],
+        startup_statuses: vec![],
+        startup_cohorts: vec![],
+        startup_activities: vec![],
+        admins: vec![(StablePrincipal::new(owner), AdminV1 { principal_id: owner.to_text(), created_at: NOW })],
+    }
+}
+
+fn write_fixture<T: Serialize>(version: u32, state: &T) {
+    fs::write(fixture(version, "bin"), bincode::serialize(state).unwrap()).unwrap();
+}
+
+/// Writes one snapshot per historical layout: the same canister upgraded
+/// through each release, gaining records in the maps each one added
+#[test]
+#[ignore = "rewrites tests/fixtures/snapshots; run only when adding a layout"]
+fn regenerate_snapshot_fixtures() {
+    let v1 = sample_v1();
+    write_fixture(1, &v1);
+
+    let mut v2 = StableStateV2::from(v1);
+    v2.user_subscriptions.push((
+        StableString::from("U1"),
+        UserSubscription {
+            user_id: "U1".to_string(),
+            tier: UserTier::Pro,
+            is_active: true,
+            started_at_ns: Some(NOW),
+            renewed_at_ns: None,
+            expires_at_ns: Some(NOW + 30 * DAY),
+        },
+    ));
+    v2.user_daily_usage.push(((StableString::from("U1"), NOW / DAY), 7));
+    write_fixture(2, &v2);
+
+    let mut v3 = StableStateV3::from(v2);
+    v3.payment_records.push((
+        StableString::from("pay_1"),
+        PaymentRecordV1 {
+            id: "pay_1".to_string(),
+            user_id: "U1".to_string(),
+            paystack_reference: "ref_1".to_string(),
+            amount: 500_000,
+            currency: Currency::KES,
+            email: "ada@example.com".to_string(),
+            status: PaymentStatus::Success,
+            payment_channel: None,
+            authorization_url: None,
+            access_code: None,
+            tier: "Pro".to_string(),
+            billing_period: "monthly".to_string(),
+            created_at: NOW,
+            updated_at: NOW,
+            paid_at: Some(NOW),
+            paystack_transaction_id: Some(42),
+            metadata: PaymentMetadata { customer_name: Some("Ada".to_string()), phone_number: None, custom_fields: vec![] },
+        },
+    ));
+    v3.invoices.push((
+        StableString::from("inv_1"),
+        InvoiceV1 {
+            id: "inv_1".to_string(),
+            user_id: "U1".to_string(),
+            payment_id: "pay_1".to_string(),
+            amount: 500_000,
+            currency: Currency::KES,
+            billing_period_start: NOW,
+            billing_period_end: NOW + 30 * DAY,
+            invoice_number: "INV-0001".to_string(),
+            paid: true,
+            created_at: NOW,
+        },
+    ));
+    write_fixture(3, &v3);
+
+    let mut v4 = StableStateV4::from(v3);
+    v4.user_analytics.push((
+        (StableString::from("U1"), NOW / DAY),
+        AnalyticsDataPoint {
+            date: "2023-11-14".to_string(),
+            requests_made: 7,
+            lines_of_code_edited: 0,
+            ai_interactions: 7,
+            tasks_completed: 0,
+            timestamp_ns: NOW,
+        },
+    ));
+    write_fixture(4, &v4);
+
+    // The current layout adds maps the sample leaves empty
+    let current = CurrentStableState::from(v4);
+    write_fixture(LAST_SNAPSHOT_VERSION, &current);
+}
+
+/// Each fixture must be recognised as the layout it was saved in and migrate
+/// to exactly its golden state. Set UPDATE_GOLDEN=1 to accept a change.
+#[test]
+fn test_snapshot_fixtures_migrate_to_golden_state() {
+    for version in SNAPSHOT_VERSIONS {
+        let bytes = fs::read(fixture(version, "bin")).expect("Fixture missing, run the ignored regenerate_snapshot_fixtures");
+        let (detected, state) = migrate_snapshot(&bytes).unwrap();
+        assert_eq!(detected, version, "V{} snapshot detected as V{}", version, detected);
+
+        let migrated = serde_json::to_string_pretty(&state).unwrap() + "\n";
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            fs::write(fixture(version, "json"), &migrated).unwrap();
+        } else {
+            let golden = fs::read_to_string(fixture(version, "json")).unwrap();
+            assert!(migrated == golden, "V{} snapshot no longer migrates to its golden state", version);
+        }
+    }
+    assert!(migrate_from_bytes(b"not a snapshot").is_err());
+    println!("✅ Golden snapshot test passed");
+}
+
+/* ============================
+   Schema migration registry
+   ============================ */
+
+fn legacy_main_site_user(id: &str) {
+    let user = MainSiteUser {
+        main_site_id: id.to_string(),
+        site_principal: None,
+        first_interaction: NOW,
+        last_interaction: NOW,
+    };
+    LEGACY_MAIN_SITE_USERS.with(|users| users.borrow_mut().insert(StableString::from(id), user));
+}
+
+#[test]
+fn test_registry_continues_from_the_snapshot_layouts() {
+    let versions: Vec<u32> = MIGRATIONS.iter().map(|step| step.version).collect();
+    let expected: Vec<u32> = (LAST_SNAPSHOT_VERSION + 1..=latest_schema_version()).collect();
+    assert_eq!(versions, expected);
+    println!("✅ Registry order test passed");
+}
+
+#[test]
+fn test_upgrade_runs_each_step_once() {
+    // A canister from before the version header was stored
+    assert_eq!(schema_version(), LAST_SNAPSHOT_VERSION);
+    legacy_main_site_user("site_1");
+    legacy_main_site_user("site_2");
+
+    // The dry run reports the work and leaves the maps alone
+    let plan = plan_schema_migrations(schema_version()).unwrap();
+    assert_eq!((plan.from_version, plan.to_version), (LAST_SNAPSHOT_VERSION, latest_schema_version()));
+    assert_eq!(plan.steps.len(), MIGRATIONS.len());
+    assert_eq!(plan.steps[0].records, 2);
+    assert_eq!(LEGACY_MAIN_SITE_USERS.with(|users| users.borrow().len()), 2);
+    assert_eq!(schema_version(), LAST_SNAPSHOT_VERSION);
+
+    let reports = migrate_schema().unwrap();
+    assert_eq!(reports, plan.steps);
+    assert_eq!(LEGACY_MAIN_SITE_USERS.with(|users| users.borrow().len()), 0);
+    assert_eq!(schema_version(), latest_schema_version());
+
+    // The next upgrade has nothing to do
+    legacy_main_site_user("site_3");
+    assert!(migrate_schema().unwrap().is_empty());
+    assert!(plan_schema_migrations(schema_version()).unwrap().steps.is_empty());
+    assert_eq!(LEGACY_MAIN_SITE_USERS.with(|users| users.borrow().len()), 1);
+
+    // Maps written by a newer release are refused
+    set_schema_version(latest_schema_version() + 1);
+    assert!(migrate_schema().is_err());
+    println!("✅ Run once test passed");
+}
+
+/// Usage used to be counted per "<platform>:<id>"; count it per bare ID
+fn rekey_daily_usage(dry_run: bool) -> Result<u32, String> {
+    Ok(rewrite_entries(&USER_DAILY_USAGE, dry_run, |(user_id, day), count| {
+        let (_, bare_id) = user_id.as_str().split_once(':')?;
+        Some(((StableString::from(bare_id), *day), *count))
+    }))
+}
+
+fn double_daily_usage(dry_run: bool) -> Result<u32, String> {
+    Ok(rewrite_entries(&USER_DAILY_USAGE, dry_run, |key, count| Some((key.clone(), count * 2))))
+}
+
+fn fail(_dry_run: bool) -> Result<u32, String> {
+    Err("map is corrupt".to_string())
+}
+
+#[test]
+fn test_steps_transform_individual_maps() {
+    let steps = [
+        MigrationStep { version: 30, description: "Re-key daily usage", run: rekey_daily_usage },
+        MigrationStep { version: 31, description: "Double daily usage", run: double_daily_usage },
+    ];
+    let usage = |user_id: &str| USER_DAILY_USAGE.with(|u| u.borrow().get(&(StableString::from(user_id), 1)));
+    USER_DAILY_USAGE.with(|u| {
+        u.borrow_mut().insert((StableString::from("slack:U1"), 1), 3);
+        u.borrow_mut().insert((StableString::from("U2"), 1), 4);
+    });
+
+    let dry_run = run_migrations(&steps, 29, true).unwrap();
+    assert_eq!(dry_run.iter().map(|step| step.records).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!((usage("slack:U1"), usage("U1")), (Some(3), None));
+
+    // Only the steps above the stored version run
+    run_migrations(&steps, 30, false).unwrap();
+    assert_eq!((usage("slack:U1"), usage("U2")), (Some(6), Some(8)));
+    assert_eq!(schema_version(), 31);
+
+    run_migrations(&steps[..1], 29, false).unwrap();
+    assert_eq!((usage("slack:U1"), usage("U1")), (None, Some(6)));
+
+    let unordered = [
+        MigrationStep { version: 31, description: "Double daily usage", run: double_daily_usage },
+        MigrationStep { version: 30, description: "Re-key daily usage", run: rekey_daily_usage },
+    ];
+    assert!(run_migrations(&unordered, 29, false).is_err());
+    assert_eq!(usage("U1"), Some(6));
+    println!("✅ Map transform test passed");
+}
+
+#[test]
+fn test_failed_step_stops_the_version_bump() {
+    let steps = [
+        MigrationStep { version: 30, description: "Double daily usage", run: double_daily_usage },
+        MigrationStep { version: 31, description: "Broken step", run: fail },
+        MigrationStep { version: 32, description: "Double daily usage", run: double_daily_usage },
+    ];
+    USER_DAILY_USAGE.with(|u| u.borrow_mut().insert((StableString::from("U3"), 1), 5));
+    set_schema_version(29);
+
+    let error = run_migrations(&steps, 29, false).unwrap_err();
+    assert!(error.contains("Step 31") && error.contains("map is corrupt"), "{}", error);
+    assert_eq!(schema_version(), 30, "the failed step is not recorded");
+    assert_eq!(USER_DAILY_USAGE.with(|u| u.borrow().get(&(StableString::from("U3"), 1))), Some(10));
+    assert!(run_migrations(&steps, 29, true).is_err());
+    println!("✅ Failed step test passed");
+}
@@ -0,0 +1,264 @@
+use backend::models::admin::AdminRole;
+use backend::models::chat::{ChatMessage, MessageRole};
+use backend::models::platform::Platform;
+use backend::models::stable_principal::StablePrincipal;
+use backend::models::stable_string::StableString;
+use backend::models::subscription_billing::SubscriptionBilling;
+use backend::models::waitlist::{WaitlistEntry, WaitlistStatus};
+use backend::services::admin::{add_admin_with_role, bootstrap_admins, change_admin_role};
+use backend::services::platform_identity_service::{ensure_identity, link_identity};
+use backend::services::schema_migration_service::{latest_schema_version, set_schema_version};
+use backend::services::snapshot_service::{
+    export_snapshot_page, import_snapshot_page, map_checksum, snapshot_checksum, snapshot_maps, SnapshotPage,
+    MAX_SNAPSHOT_PAGE_BYTES, MAX_SNAPSHOT_PAGE_ENTRIES,
+};
+use backend::storage::blob_store::{get_blob, put_blob};
+use backend::storage::memory::{ADMINS, CHAT_HISTORY, SUBSCRIPTION_BILLING, WAITLIST};
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const IMPORTER: Principal = Principal::from_slice(&[9; 29]);
+
+fn add_waitlist_entry(i: usize) {
+    let email = format!("founder{:04}@example.com", i);
+    let entry = WaitlistEntry {
+        email: email.clone(),
+        name: format!("Founder {}", i),
+        created_at: NOW,
+        status: WaitlistStatus::Pending,
+    };
+    WAITLIST.with(|waitlist| waitlist.borrow_mut().insert(StableString::from(email), entry));
+}
+
+fn add_chat_message(owner: Principal, timestamp: u64) {
+    let message = ChatMessage {
+        id: owner,
+        role: MessageRole::User,
+        content: format!("message {}", timestamp),
+        question_asked: None,
+        timestamp,
+        bot_name: None,
+    };
+    CHAT_HISTORY.with(|history| history.borrow_mut().insert((StablePrincipal::new(owner), timestamp), message));
+}
+
+fn export_all(map: &str) -> Vec<SnapshotPage> {
+    let mut pages = vec![export_snapshot_page(map, None).unwrap()];
+    while let Some(cursor) = pages.last().unwrap().next_cursor.clone() {
+        pages.push(export_snapshot_page(map, Some(cursor)).unwrap());
+    }
+    pages
+}
+
+// As `init` leaves a new canister
+fn start_at_current_schema() {
+    set_schema_version(latest_schema_version());
+}
+
+fn waitlist_len() -> u64 {
+    WAITLIST.with(|waitlist| waitlist.borrow().len())
+}
+
+#[test]
+fn test_snapshot_round_trip_restores_every_map() {
+    start_at_current_schema();
+    for i in 0..3 {
+        add_waitlist_entry(i);
+    }
+    add_chat_message(Principal::from_slice(&[1; 29]), NOW);
+    ensure_identity(Platform::Slack, "U100", NOW);
+    put_blob("test/blob", &[7; 100]).unwrap();
+    bootstrap_admins(&[IMPORTER], NOW);
+
+    let snapshot: Vec<Vec<SnapshotPage>> = snapshot_maps().into_iter().map(export_all).collect();
+
+    // Changes after the export are undone by restoring it
+    add_waitlist_entry(3);
+    WAITLIST.with(|waitlist| waitlist.borrow_mut().remove(&StableString::from("founder0000@example.com")));
+    put_blob("test/blob", b"changed").unwrap();
+
+    for page in snapshot.iter().flatten() {
+        import_snapshot_page(IMPORTER, page).unwrap();
+    }
+    let restored: Vec<Vec<SnapshotPage>> = snapshot_maps().into_iter().map(export_all).collect();
+    assert_eq!(restored, snapshot);
+
+    // The typed maps see the restored entries
+    assert_eq!(waitlist_len(), 3);
+    let first = WAITLIST.with(|waitlist| waitlist.borrow().get(&StableString::from("founder0000@example.com")));
+    assert_eq!(first.unwrap().name, "Founder 0");
+    assert_eq!(get_blob("test/blob"), Some(vec![7; 100]));
+}
+
+#[test]
+fn test_snapshot_pages_stay_within_limits() {
+    for i in 0..MAX_SNAPSHOT_PAGE_ENTRIES + 10 {
+        add_waitlist_entry(i);
+    }
+    let pages = export_all("WAITLIST");
+    assert_eq!(pages.iter().map(|page| page.entries.len()).collect::<Vec<_>>(), vec![MAX_SNAPSHOT_PAGE_ENTRIES, 10]);
+    assert_eq!(pages[1].cursor, pages[0].next_cursor);
+
+    // 20 blobs of 128 KiB are 40 chunks, more than fit in one page by size
+    for i in 0..20 {
+        put_blob(&format!("test/blob{:02}", i), &vec![i as u8; 128 * 1024]).unwrap();
+    }
+    let pages = export_all("BLOB_CHUNKS");
+    assert!(pages.len() > 1);
+    for page in &pages {
+        let bytes: usize = page.entries.iter().map(|entry| entry.key.len() + entry.value.len()).sum();
+        assert!(bytes <= MAX_SNAPSHOT_PAGE_BYTES, "{} byte page", bytes);
+    }
+    assert_eq!(pages.iter().map(|page| page.entries.len()).sum::<usize>(), 40);
+}
+
+#[test]
+fn test_invalid_pages_are_rejected() {
+    start_at_current_schema();
+    for i in 0..MAX_SNAPSHOT_PAGE_ENTRIES + 1 {
+        add_waitlist_entry(i);
+    }
+    let pages = export_all("WAITLIST");
+    assert_eq!(pages.len(), 2);
+
+    let mut tampered = pages[0].clone();
+    tampered.entries[0].value[1] ^= 0xff;
+    let error = import_snapshot_page(IMPORTER, &tampered).unwrap_err();
+    assert!(error.starts_with("Checksum mismatch"), "{}", error);
+
+    // Pages are written as stored, so only pages at this release's schema import
+    for schema_version in [latest_schema_version() - 1, latest_schema_version() + 1] {
+        let mut other = pages[0].clone();
+        other.schema_version = schema_version;
+        other.checksum = snapshot_checksum(&other);
+        let error = import_snapshot_page(IMPORTER, &other).unwrap_err();
+        assert!(error.contains("restore it with the release it was exported from"), "{}", error);
+    }
+
+    let mut unknown = pages[0].clone();
+    unknown.map = "NOPE".to_string();
+    unknown.checksum = snapshot_checksum(&unknown);
+    assert_eq!(import_snapshot_page(IMPORTER, &unknown), Err("Unknown map: NOPE".to_string()));
+
+    // A later page only follows the page before it
+    let error = import_snapshot_page(IMPORTER, &pages[1]).unwrap_err();
+    assert!(error.contains("does not follow"), "{}", error);
+
+    assert_eq!(import_snapshot_page(IMPORTER, &pages[0]), Ok(MAX_SNAPSHOT_PAGE_ENTRIES as u64));
+    assert_eq!(import_snapshot_page(IMPORTER, &pages[1]), Ok(MAX_SNAPSHOT_PAGE_ENTRIES as u64 + 1));
+    assert_eq!(waitlist_len(), MAX_SNAPSHOT_PAGE_ENTRIES as u64 + 1);
+}
+
+#[test]
+fn test_maps_are_replaced_only_once_every_page_matches() {
+    start_at_current_schema();
+    for i in 0..MAX_SNAPSHOT_PAGE_ENTRIES + 1 {
+        add_waitlist_entry(i);
+    }
+    let pages = export_all("WAITLIST");
+    add_waitlist_entry(9999);
+
+    // Earlier pages are staged; the map keeps what it held until the last one
+    import_snapshot_page(IMPORTER, &pages[0]).unwrap();
+    assert_eq!(waitlist_len(), MAX_SNAPSHOT_PAGE_ENTRIES as u64 + 2);
+
+    // A last page from another export does not continue the staged pages
+    WAITLIST.with(|waitlist| waitlist.borrow_mut().remove(&StableString::from("founder0000@example.com")));
+    let other = export_all("WAITLIST");
+    let error = import_snapshot_page(IMPORTER, &other[1]).unwrap_err();
+    assert!(error.contains("does not follow"), "{}", error);
+
+    // A whole-map checksum that doesn't match leaves the map as it was
+    let mut last = pages[1].clone();
+    last.map_checksum = map_checksum("", &last.entries);
+    last.checksum = snapshot_checksum(&last);
+    let error = import_snapshot_page(IMPORTER, &last).unwrap_err();
+    assert!(error.starts_with("Checksum mismatch across the pages of WAITLIST"), "{}", error);
+    assert_eq!(waitlist_len(), MAX_SNAPSHOT_PAGE_ENTRIES as u64 + 1);
+    assert!(WAITLIST.with(|waitlist| waitlist.borrow().get(&StableString::from("founder9999@example.com"))).is_some());
+
+    // Restarting from the first page restores the export
+    for page in &pages {
+        import_snapshot_page(IMPORTER, page).unwrap();
+    }
+    assert_eq!(waitlist_len(), MAX_SNAPSHOT_PAGE_ENTRIES as u64 + 1);
+    assert!(WAITLIST.with(|waitlist| waitlist.borrow().get(&StableString::from("founder9999@example.com"))).is_none());
+    assert_eq!(snapshot_maps().last(), Some(&"ADMINS"));
+}
+
+#[test]
+fn test_admin_imports_keep_the_importer_a_super_admin() {
+    start_at_current_schema();
+    let other = Principal::from_slice(&[10; 29]);
+    bootstrap_admins(&[IMPORTER], NOW);
+    add_admin_with_role(IMPORTER, other, AdminRole::SuperAdmin, NOW).unwrap();
+    let current = export_all("ADMINS");
+    let import_admins = |pages: &[SnapshotPage]| pages.iter().try_for_each(|page| import_snapshot_page(IMPORTER, page).map(|_| ()));
+
+    // A SuperAdmin is left, but not the importer
+    change_admin_role(IMPORTER, IMPORTER, AdminRole::Support, NOW).unwrap();
+    let importer_demoted = export_all("ADMINS");
+    import_admins(&current).unwrap();
+    let error = import_admins(&importer_demoted).unwrap_err();
+    assert!(error.contains("would take ManageAdmins from the importing admin"), "{}", error);
+
+    // No SuperAdmin left
+    change_admin_role(IMPORTER, other, AdminRole::Billing, NOW).unwrap();
+    ADMINS.with(|admins| {
+        let mut admins = admins.borrow_mut();
+        let mut importer = admins.get(&StablePrincipal::new(IMPORTER)).unwrap();
+        importer.role = AdminRole::ReadOnly;
+        admins.insert(StablePrincipal::new(IMPORTER), importer);
+    });
+    let without_super_admin = export_all("ADMINS");
+    import_admins(&current).unwrap();
+    let error = import_admins(&without_super_admin).unwrap_err();
+    assert!(error.starts_with("The imported ADMINS have no SuperAdmin"), "{}", error);
+
+    // An admin that doesn't decode
+    ADMINS.with(|admins| admins.borrow_mut().insert_raw(StablePrincipal::new(Principal::from_slice(&[11; 29])), vec![9, 9, 9]));
+    let garbled = export_all("ADMINS");
+    import_admins(&current).unwrap();
+    let error = import_admins(&garbled).unwrap_err();
+    assert!(error.starts_with("Unreadable admin"), "{}", error);
+    assert!(error.ends_with("nothing was imported"), "{}", error);
+
+    // Each refused import left the admins as they were
+    assert_eq!(export_all("ADMINS"), current);
+}
+
+#[test]
+fn test_maps_that_refer_to_each_other_must_agree() {
+    start_at_current_schema();
+    let owner = Principal::from_slice(&[12; 29]);
+    ensure_identity(Platform::Slack, "U200", NOW);
+    ensure_identity(Platform::Discord, "D200", NOW);
+    link_identity(Platform::Slack, "U200", owner).unwrap();
+
+    // The index exported after another link no longer matches the identities
+    let identities = export_all("PLATFORM_IDENTITIES");
+    let index_of_identities = export_all("PLATFORM_IDENTITIES_BY_PRINCIPAL");
+    link_identity(Platform::Discord, "D200", owner).unwrap();
+    let index = export_all("PLATFORM_IDENTITIES_BY_PRINCIPAL");
+    for page in &identities {
+        import_snapshot_page(IMPORTER, page).unwrap();
+    }
+    let error = import_snapshot_page(IMPORTER, &index[0]).unwrap_err();
+    assert!(error.starts_with("PLATFORM_IDENTITIES_BY_PRINCIPAL does not index the imported PLATFORM_IDENTITIES"), "{}", error);
+    assert!(error.ends_with("nothing was imported"), "{}", error);
+
+    // The index exported with the identities imports
+    for page in &index_of_identities {
+        import_snapshot_page(IMPORTER, page).unwrap();
+    }
+
+    // Billing that refers to a payment the imported PAYMENT_RECORDS lacks
+    let billing = SubscriptionBilling {
+        user_id: owner.to_text(),
+        last_payment_reference: Some("INF-RNW-missing".to_string()),
+        ..Default::default()
+    };
+    SUBSCRIPTION_BILLING.with(|map| map.borrow_mut().insert(StableString::from(owner.to_text()), billing));
+    let error = import_snapshot_page(IMPORTER, &export_all("SUBSCRIPTION_BILLING")[0]).unwrap_err();
+    assert!(error.starts_with("1 SUBSCRIPTION_BILLING records refer to payments missing"), "{}", error);
+}
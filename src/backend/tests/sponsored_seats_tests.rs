@@ -0,0 +1,225 @@
+use backend::models::accelerator::{Accelerator, MemberStatus, Role, TeamMember};
+use backend::models::payment::{Currency, PaymentStatus};
+use backend::models::sponsorship::{SeatSponsorship, SponsorshipStatus};
+use backend::models::stable_principal::StablePrincipal;
+use backend::models::stable_string::StableString;
+use backend::models::startup::{Startup, StartupCohort};
+use backend::models::usage_service::UserTier;
+use backend::services::payment_service::{fulfil_payment_at, update_payment_status, PaymentConfirmation};
+use backend::services::plan_service::seed_default_plans;
+use backend::services::pricing_services::check_user_tier_at;
+use backend::services::sponsorship_service::{
+    assign_seat, get_sponsorship, prepare_seat_purchase, revoke_seat, seat_usage, SeatPurchaseRequest,
+};
+use backend::storage::memory::{
+    ACCELERATORS, INVOICES, PAYMENT_RECORDS, SEAT_SPONSORSHIPS, STARTUPS, STARTUP_COHORTS, USER_SUBSCRIPTIONS,
+};
+use candid::Principal;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const PRICE: u64 = 2_900_000;
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn member(id: u8, role: Role) -> TeamMember {
+    TeamMember {
+        email: format!("member{}@accelerator.com", id),
+        role,
+        status: MemberStatus::Active,
+        token: None,
+        principal: Some(principal(id)),
+        name: format!("Member {}", id),
+    }
+}
+
+/// Accelerator owned by principal 1, with an admin (2) and a viewer (3),
+/// a "spring" cohort and three founders (11, 12, 13)
+fn setup() -> String {
+    seed_default_plans(NOW);
+
+    let accelerator_id = StablePrincipal::new(principal(1));
+    let accelerator = Accelerator {
+        id: accelerator_id.clone(),
+        name: "Lagos Ventures".to_string(),
+        website: "https://lagos.vc".to_string(),
+        email: "team@lagos.vc".to_string(),
+        email_verified: true,
+        logo: None,
+        total_startups: 3,
+        invites_sent: 0,
+        active_startups: 3,
+        graduated_startups: 0,
+        recent_activity: vec![],
+        team_members: vec![
+            member(1, Role::SuperAdmin),
+            member(2, Role::Admin),
+            member(3, Role::Viewer),
+        ],
+    };
+    ACCELERATORS.with(|accs| accs.borrow_mut().insert(accelerator_id.clone(), accelerator));
+
+    STARTUP_COHORTS.with(|cohorts| {
+        cohorts.borrow_mut().insert(
+            StableString::from("spring".to_string()),
+            StartupCohort {
+                id: "spring".to_string(),
+                accelerator_id: accelerator_id.clone(),
+                name: "Spring 2024".to_string(),
+                description: None,
+                start_date: None,
+                end_date: None,
+                is_active: true,
+                created_at: NOW,
+            },
+        )
+    });
+
+    for (founder, cohort) in [(11u8, "spring"), (12, "spring"), (13, "autumn")] {
+        let id = format!("startup-{}", founder);
+        let startup = Startup {
+            id: id.clone(),
+            accelerator_id: accelerator_id.clone(),
+            name: id.clone(),
+            description: None,
+            industry: None,
+            contact_email: format!("{}@startup.com", id),
+            founder_principal: StablePrincipal::new(principal(founder)),
+            date_joined: NOW,
+            status_id: String::new(),
+            cohort_id: cohort.to_string(),
+            engagement_score: 0,
+            total_logins: 0,
+            documents_submitted: 0,
+            tasks_completed: 0,
+            last_activity: NOW,
+            created_at: NOW,
+            updated_at: NOW,
+        };
+        STARTUPS.with(|startups| startups.borrow_mut().insert(StableString::from(id), startup));
+    }
+
+    accelerator_id.to_string()
+}
+
+fn purchase_request(accelerator_id: &str, seats: u32, cohort_id: Option<&str>) -> SeatPurchaseRequest {
+    SeatPurchaseRequest {
+        accelerator_id: accelerator_id.to_string(),
+        cohort_id: cohort_id.map(str::to_string),
+        seats,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        currency: "NGN".to_string(),
+        email: "billing@lagos.vc".to_string(),
+        callback_url: "https://infoundr.com/accelerator/billing".to_string(),
+        phone_number: None,
+        enable_mpesa: false,
+        enable_card: true,
+    }
+}
+
+/// Store a seat purchase like `purchase_sponsored_seats` does before the checkout
+fn buy_seats(request: &SeatPurchaseRequest, reference: &str) -> SeatSponsorship {
+    let (payment, sponsorship) = prepare_seat_purchase(request, principal(2), reference, NOW).unwrap();
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+    SEAT_SPONSORSHIPS.with(|s| {
+        s.borrow_mut().insert(StableString::from(reference.to_string()), sponsorship.clone());
+    });
+    sponsorship
+}
+
+fn founder(id: u8) -> String {
+    principal(id).to_string()
+}
+
+#[test]
+fn test_seat_purchase_is_priced_per_seat_for_admins() {
+    let accelerator_id = setup();
+
+    let (payment, sponsorship) =
+        prepare_seat_purchase(&purchase_request(&accelerator_id, 5, None), principal(1), "INF-seats-1", NOW).unwrap();
+    assert_eq!(payment.amount, 5 * PRICE);
+    assert_eq!(payment.currency, Currency::NGN);
+    assert_eq!(payment.user_id, accelerator_id);
+    assert_eq!(sponsorship.status, SponsorshipStatus::Pending);
+    assert_eq!(sponsorship.seats, 5);
+
+    // Viewers and outsiders cannot spend the accelerator's money
+    assert!(prepare_seat_purchase(&purchase_request(&accelerator_id, 5, None), principal(3), "INF-seats-2", NOW).is_err());
+    assert!(prepare_seat_purchase(&purchase_request(&accelerator_id, 5, None), principal(9), "INF-seats-3", NOW).is_err());
+    assert!(prepare_seat_purchase(&purchase_request(&accelerator_id, 0, None), principal(1), "INF-seats-4", NOW).is_err());
+    assert!(prepare_seat_purchase(&purchase_request(&accelerator_id, 5, Some("missing")), principal(1), "INF-seats-5", NOW).is_err());
+    println!("✅ Seat purchase test passed");
+}
+
+#[test]
+fn test_assigned_seat_is_pro_until_sponsorship_ends() {
+    let accelerator_id = setup();
+    buy_seats(&purchase_request(&accelerator_id, 2, None), "INF-seats-1");
+
+    // Seats cannot be handed out before the payment clears
+    assert!(assign_seat(principal(2), "INF-seats-1", "startup-11", NOW).is_err());
+
+    assert!(fulfil_payment_at("INF-seats-1", PaymentConfirmation::default(), NOW).unwrap());
+    let sponsorship = get_sponsorship("INF-seats-1").unwrap();
+    assert_eq!(sponsorship.status, SponsorshipStatus::Active);
+    let ends_at = sponsorship.ends_at.unwrap();
+
+    // The accelerator gets an invoice but no personal subscription
+    assert!(INVOICES.with(|invoices| invoices.borrow().contains_key(&StableString::from("INV-INF-seats-1".to_string()))));
+    assert!(USER_SUBSCRIPTIONS.with(|subs| subs.borrow().get(&StableString::from(accelerator_id.clone())).is_none()));
+
+    assert_eq!(check_user_tier_at(&founder(11), NOW), UserTier::Free);
+    let assignment = assign_seat(principal(2), "INF-seats-1", "startup-11", NOW).unwrap();
+    assert_eq!(assignment.user_id, founder(11));
+    assert_eq!(check_user_tier_at(&founder(11), NOW + DAY), UserTier::Pro);
+    assert_eq!(check_user_tier_at(&founder(11), ends_at), UserTier::Free);
+    println!("✅ Sponsored seat tier test passed");
+}
+
+#[test]
+fn test_seat_limits_cohorts_and_revocation() {
+    let accelerator_id = setup();
+    buy_seats(&purchase_request(&accelerator_id, 1, Some("spring")), "INF-seats-1");
+    fulfil_payment_at("INF-seats-1", PaymentConfirmation::default(), NOW).unwrap();
+
+    assert!(assign_seat(principal(3), "INF-seats-1", "startup-11", NOW).is_err(), "viewers cannot assign");
+    assert!(assign_seat(principal(1), "INF-seats-1", "startup-13", NOW).is_err(), "not in the cohort");
+
+    assign_seat(principal(1), "INF-seats-1", "startup-11", NOW).unwrap();
+    assert!(assign_seat(principal(1), "INF-seats-1", "startup-11", NOW).is_err(), "already seated");
+    assert!(assign_seat(principal(1), "INF-seats-1", "startup-12", NOW).is_err(), "no seats left");
+
+    // Revoking frees the seat for another founder
+    revoke_seat(principal(2), "INF-seats-1", "startup-11", NOW + DAY).unwrap();
+    assert_eq!(check_user_tier_at(&founder(11), NOW + DAY), UserTier::Free);
+    assign_seat(principal(2), "INF-seats-1", "startup-12", NOW + DAY).unwrap();
+    assert_eq!(check_user_tier_at(&founder(12), NOW + DAY), UserTier::Pro);
+
+    // Every active team member can see the usage
+    let usage = seat_usage(principal(3), &accelerator_id, NOW + DAY).unwrap();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].seats_used, 1);
+    assert_eq!(usage[0].seats_available, 0);
+    assert_eq!(usage[0].assignments.len(), 2);
+    assert!(seat_usage(principal(9), &accelerator_id, NOW).is_err());
+    println!("✅ Seat limits test passed");
+}
+
+#[test]
+fn test_failed_seat_payment_cancels_sponsorship() {
+    let accelerator_id = setup();
+    buy_seats(&purchase_request(&accelerator_id, 3, None), "INF-seats-1");
+
+    update_payment_status("INF-seats-1", PaymentStatus::Failed, None, NOW).unwrap();
+    assert_eq!(get_sponsorship("INF-seats-1").unwrap().status, SponsorshipStatus::Cancelled);
+    assert!(assign_seat(principal(1), "INF-seats-1", "startup-11", NOW).is_err());
+
+    let usage = seat_usage(principal(1), &accelerator_id, NOW).unwrap();
+    assert_eq!(usage[0].seats_available, 0);
+    println!("✅ Failed seat payment test passed");
+}
@@ -1,7 +1,7 @@
 // Startup Management Feature Tests
 // =================================
 
-use candid::{Principal, encode_one, decode_one};
+use candid::{Principal, encode_args, encode_one, decode_one};
 use pocket_ic::PocketIc;
 use std::fs;
 
@@ -20,6 +20,9 @@ const TEST_ACCELERATOR_EMAIL: &str = "test@accelerator.com";
 const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
 const INIT_CYCLES: u128 = 2_000_000_000_000;
 
+// Anonymous callers are rejected, so calls are made as this identity
+const TEST_CALLER: Principal = Principal::from_slice(&[1; 29]);
+
 // Accelerator test models (needed for setup)
 #[derive(candid::CandidType, candid::Deserialize, Clone, Debug)]
 struct AcceleratorSignUp {
@@ -34,7 +37,7 @@ fn setup() -> (PocketIc, Principal, String) {
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, INIT_CYCLES);
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
     
     // Create an accelerator first
     let signup_data = AcceleratorSignUp {
@@ -45,7 +48,7 @@ fn setup() -> (PocketIc, Principal, String) {
     let encoded = encode_one(signup_data).unwrap();
     let signup_result = pic.update_call(
         backend_canister,
-        Principal::anonymous(),
+        TEST_CALLER,
         "sign_up_accelerator",
         encoded,
     ).unwrap();
@@ -396,7 +399,7 @@ fn call_create_startup(pic: &PocketIc, canister_id: Principal, data: StartupInpu
     let encoded = encode_one(data).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "create_startup",
         encoded,
     );
@@ -416,7 +419,7 @@ fn call_create_startup(pic: &PocketIc, canister_id: Principal, data: StartupInpu
 fn call_get_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str) -> Result<Option<Startup>, String> {
     let result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "get_startup",
         encode_one(&startup_id).unwrap(),
     );
@@ -436,7 +439,7 @@ fn call_update_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str,
     let encoded = encode_one((startup_id.to_string(), updates)).unwrap();
     let result = pic.update_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "update_startup",
         encoded,
     );
@@ -456,7 +459,7 @@ fn call_update_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str,
 //     let encoded = encode_one((startup_id.to_string(), activity.activity_type.clone(), activity.description.clone(), activity.metadata.clone())).unwrap();
 //     let result = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "record_startup_activity",
 //         encoded,
 //     );
@@ -476,7 +479,7 @@ fn call_update_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str,
 //     let encoded = encode_one((accelerator_id.to_string(), Some(filter))).unwrap();
 //     let result = pic.query_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "list_startups",
 //         encoded,
 //     );
@@ -497,7 +500,7 @@ fn call_update_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str,
 //     let encoded = encode_one((accelerator_id.to_string(), data)).unwrap();
 //     let result = pic.update_call(
 //         canister_id,
-//         Principal::anonymous(),
+//         TEST_CALLER,
 //         "create_startup_cohort",
 //         encoded,
 //     );
@@ -523,7 +526,7 @@ fn call_update_startup(pic: &PocketIc, canister_id: Principal, startup_id: &str,
 fn call_get_startup_analytics(pic: &PocketIc, canister_id: Principal, accelerator_id: &str) -> Result<StartupStats, String> {
     let result = pic.query_call(
         canister_id,
-        Principal::anonymous(),
+        TEST_CALLER,
         "get_startup_stats",
         encode_one(accelerator_id).unwrap(),
     );
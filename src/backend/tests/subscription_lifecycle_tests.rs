@@ -0,0 +1,299 @@
+use backend::models::payment::{Currency, PaymentChannel, PaymentRecord, PaymentStatus};
+use backend::models::stable_string::StableString;
+use backend::models::subscription_billing::{BillingSettings, SubscriptionStatus};
+use backend::models::usage_service::UserTier;
+use backend::payments::paystack_models::{
+    ChargeAuthorizationData, ChargeAuthorizationRequest, ChargeAuthorizationResponse,
+};
+use backend::services::payment_service::{fulfil_payment_at, PaymentConfirmation};
+use backend::services::subscription_service::{
+    cancel_subscription_for, get_subscription_billing, is_in_grace_period, next_action,
+    process_due_subscriptions, resume_subscription_for, BillingAction, RenewalClient,
+};
+use backend::storage::memory::{INVOICES, PAYMENT_RECORDS, USER_SUBSCRIPTIONS};
+use std::cell::RefCell;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const HOUR: u64 = 60 * 60 * 1_000_000_000;
+const DAY: u64 = 24 * HOUR;
+const MONTH: u64 = 30 * DAY;
+const USER_ID: &str = "renewal-user";
+const AUTH_CODE: &str = "AUTH_test123";
+
+// Mock Paystack client: replays canned responses and records every request
+struct MockPaystack {
+    responses: RefCell<Vec<Result<ChargeAuthorizationResponse, String>>>,
+    requests: RefCell<Vec<ChargeAuthorizationRequest>>,
+}
+
+impl MockPaystack {
+    fn new(responses: Vec<Result<ChargeAuthorizationResponse, String>>) -> Self {
+        MockPaystack {
+            responses: RefCell::new(responses),
+            requests: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl RenewalClient for MockPaystack {
+    async fn charge_authorization(
+        &self,
+        request: ChargeAuthorizationRequest,
+    ) -> Result<ChargeAuthorizationResponse, String> {
+        self.requests.borrow_mut().push(request);
+        self.responses.borrow_mut().remove(0)
+    }
+}
+
+fn charge_success() -> Result<ChargeAuthorizationResponse, String> {
+    Ok(ChargeAuthorizationResponse {
+        status: true,
+        message: "Charge attempted".to_string(),
+        data: Some(ChargeAuthorizationData {
+            id: 42,
+            status: "success".to_string(),
+            reference: "ignored".to_string(),
+            amount: 2_900_000,
+            currency: "NGN".to_string(),
+            channel: Some("card".to_string()),
+            gateway_response: Some("Approved".to_string()),
+        }),
+    })
+}
+
+fn charge_declined() -> Result<ChargeAuthorizationResponse, String> {
+    Ok(ChargeAuthorizationResponse {
+        status: true,
+        message: "Charge attempted".to_string(),
+        data: Some(ChargeAuthorizationData {
+            id: 43,
+            status: "failed".to_string(),
+            reference: "ignored".to_string(),
+            amount: 2_900_000,
+            currency: "NGN".to_string(),
+            channel: Some("card".to_string()),
+            gateway_response: Some("Insufficient Funds".to_string()),
+        }),
+    })
+}
+
+// Minimal executor: the mock never returns Pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Store a pending monthly Pro payment and fulfil it like the webhook would
+fn subscribe(reference: &str, authorization_code: Option<&str>) {
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: USER_ID.to_string(),
+        paystack_reference: reference.to_string(),
+        amount: 2_900_000,
+        currency: Currency::NGN,
+        email: "founder@example.com".to_string(),
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        created_at: NOW,
+        updated_at: NOW,
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+
+    let confirmation = PaymentConfirmation {
+        channel: Some(PaymentChannel::Card),
+        transaction_id: Some(1),
+        provider_transaction_id: None,
+        authorization_code: authorization_code.map(|code| code.to_string()),
+    };
+    let fulfilled = fulfil_payment_at(reference, confirmation, NOW)
+    .expect("fulfilment should succeed");
+    assert!(fulfilled);
+}
+
+fn subscription_expiry() -> Option<u64> {
+    USER_SUBSCRIPTIONS.with(|subs| {
+        subs.borrow()
+            .get(&StableString::from(USER_ID.to_string()))
+            .and_then(|s| s.expires_at_ns)
+    })
+}
+
+fn invoice_count() -> u64 {
+    INVOICES.with(|invoices| invoices.borrow().len())
+}
+
+#[test]
+fn test_fulfilment_is_idempotent() {
+    subscribe("INF-test-1", Some(AUTH_CODE));
+
+    // Webhook and manual verify for the same reference
+    let again = fulfil_payment_at("INF-test-1", PaymentConfirmation::default(), NOW + HOUR).unwrap();
+
+    assert!(!again, "second fulfilment should be a no-op");
+    assert_eq!(invoice_count(), 1);
+    assert_eq!(subscription_expiry(), Some(NOW + MONTH));
+    println!("✅ Fulfilment idempotency test passed");
+}
+
+#[test]
+fn test_retried_fulfilment_does_not_extend_twice() {
+    subscribe("INF-test-1", Some(AUTH_CODE));
+
+    // As if the call failed after the upgrade, before the invoice was written
+    INVOICES.with(|invoices| invoices.borrow_mut().remove(&StableString::from("INV-INF-test-1".to_string())));
+
+    let again = fulfil_payment_at("INF-test-1", PaymentConfirmation::default(), NOW + HOUR).unwrap();
+    assert!(!again, "a saved fulfilment is not run again");
+    assert_eq!(subscription_expiry(), Some(NOW + MONTH));
+}
+
+#[test]
+fn test_successful_renewal_extends_period() {
+    subscribe("INF-test-1", Some(AUTH_CODE));
+    let client = MockPaystack::new(vec![charge_success()]);
+
+    // Inside the default 24h renewal window
+    let run_at = NOW + MONTH - 2 * HOUR;
+    let summary = block_on(process_due_subscriptions(&client, run_at));
+
+    assert_eq!(summary.renewed, 1);
+    assert_eq!(summary.failed, 0);
+
+    let requests = client.requests.borrow();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].authorization_code, AUTH_CODE);
+    assert_eq!(requests[0].amount, "2900000");
+    assert_eq!(requests[0].currency, "NGN");
+
+    // New period starts where the old one ended, not at the charge time
+    assert_eq!(subscription_expiry(), Some(NOW + 2 * MONTH));
+    assert_eq!(invoice_count(), 2);
+
+    let billing = get_subscription_billing(USER_ID).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Active);
+    assert_eq!(billing.current_period_end_ns, NOW + 2 * MONTH);
+    assert_eq!(billing.renewal_attempts, 0);
+    assert_eq!(billing.authorization_code.as_deref(), Some(AUTH_CODE));
+    println!("✅ Successful renewal test passed");
+}
+
+#[test]
+fn test_failed_renewal_uses_grace_period_then_expires() {
+    subscribe("INF-test-1", Some(AUTH_CODE));
+    let client = MockPaystack::new(vec![
+        charge_declined(),
+        Err("HTTP request failed".to_string()),
+        charge_declined(),
+        charge_declined(),
+    ]);
+
+    let first_try = NOW + MONTH - 2 * HOUR;
+    let summary = block_on(process_due_subscriptions(&client, first_try));
+    assert_eq!(summary.failed, 1);
+
+    // The paid period is still running, so the failure is only recorded
+    let billing = get_subscription_billing(USER_ID).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Active);
+    assert_eq!(billing.past_due_since_ns, None);
+    assert_eq!(billing.renewal_attempts, 1);
+    assert_eq!(billing.last_error.as_deref(), Some("Insufficient Funds"));
+
+    // Retry is not attempted before the retry interval
+    let summary = block_on(process_due_subscriptions(&client, first_try + HOUR));
+    assert_eq!(summary.failed, 0);
+    assert_eq!(client.requests.borrow().len(), 1);
+
+    // After expiry the user is still inside the 3-day grace period
+    let after_expiry = NOW + MONTH + DAY;
+    block_on(process_due_subscriptions(&client, after_expiry));
+    assert!(is_in_grace_period(USER_ID, after_expiry));
+    let billing = get_subscription_billing(USER_ID).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::PastDue);
+    assert_eq!(billing.past_due_since_ns, Some(after_expiry));
+    assert_eq!(billing.renewal_attempts, 2);
+
+    // Once the grace period is over the subscription expires
+    let after_grace = NOW + MONTH + 3 * DAY;
+    let summary = block_on(process_due_subscriptions(&client, after_grace));
+    assert_eq!(summary.expired, 1);
+    assert!(!is_in_grace_period(USER_ID, after_grace));
+
+    let billing = get_subscription_billing(USER_ID).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Expired);
+    let subscription = USER_SUBSCRIPTIONS
+        .with(|subs| subs.borrow().get(&StableString::from(USER_ID.to_string())))
+        .unwrap();
+    assert_eq!(subscription.tier, UserTier::Free);
+    assert!(!subscription.is_active);
+
+    // Failed renewal payments are recorded as such
+    let failed = PAYMENT_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, p)| p.status == PaymentStatus::Failed)
+            .count()
+    });
+    assert_eq!(failed, 2);
+    println!("✅ Grace period and expiry test passed");
+}
+
+#[test]
+fn test_cancel_and_resume() {
+    subscribe("INF-test-1", Some(AUTH_CODE));
+
+    let billing = cancel_subscription_for(USER_ID, NOW + DAY).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Cancelled);
+    assert!(cancel_subscription_for(USER_ID, NOW + DAY).is_err());
+
+    // Cancelled subscriptions are never charged
+    let client = MockPaystack::new(vec![]);
+    let summary = block_on(process_due_subscriptions(&client, NOW + MONTH - 2 * HOUR));
+    assert_eq!(summary.renewed + summary.failed, 0);
+    assert!(client.requests.borrow().is_empty());
+
+    // Resume while the period is still running
+    let billing = resume_subscription_for(USER_ID, NOW + 2 * DAY).unwrap();
+    assert_eq!(billing.status, SubscriptionStatus::Active);
+
+    // Cancel again and let the period run out
+    cancel_subscription_for(USER_ID, NOW + 3 * DAY).unwrap();
+    let summary = block_on(process_due_subscriptions(&client, NOW + MONTH));
+    assert_eq!(summary.expired, 1);
+    assert!(resume_subscription_for(USER_ID, NOW + MONTH + HOUR).is_err());
+    println!("✅ Cancel/resume test passed");
+}
+
+#[test]
+fn test_next_action_without_authorization() {
+    subscribe("INF-test-1", None);
+    let settings = BillingSettings::default();
+    let billing = get_subscription_billing(USER_ID).unwrap();
+
+    // Nothing to charge: the user has to pay manually
+    assert_eq!(next_action(&billing, &settings, NOW + MONTH - 2 * HOUR), BillingAction::None);
+    assert_eq!(next_action(&billing, &settings, NOW + MONTH), BillingAction::MarkPastDue);
+    assert_eq!(next_action(&billing, &settings, NOW + MONTH + 3 * DAY), BillingAction::Expire);
+    println!("✅ Next action test passed");
+}
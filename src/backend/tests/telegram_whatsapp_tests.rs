@@ -0,0 +1,111 @@
+use backend::models::dashboard_token::DashboardToken;
+use backend::models::platform::Platform;
+use backend::models::record::{decode_record, encode_record};
+use backend::services::account_service::UserIdentifier as AccountUserIdentifier;
+use backend::services::api_service::UserIdentifier;
+use backend::services::platform_identity_service::{
+    derived_principal, ensure_identity, get_identity, identities_for_principal, link_identity, linked_external_id,
+    list_identities, resolve_principal, unlink_identity,
+};
+use candid::Principal;
+use ic_stable_structures::Storable;
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+#[test]
+fn test_platforms_parse_and_store() {
+    assert_eq!("telegram".parse::<Platform>(), Ok(Platform::Telegram));
+    assert_eq!("whatsapp".parse::<Platform>(), Ok(Platform::WhatsApp));
+    assert_eq!((Platform::Telegram.to_string(), Platform::WhatsApp.to_string()), ("Telegram".to_string(), "WhatsApp".to_string()));
+
+    // New variants go after the existing ones, so stored keys keep their meaning
+    assert_eq!(Platform::Slack.to_bytes().as_ref(), &[1]);
+    assert_eq!(Platform::WhatsApp.to_bytes().as_ref(), &[5]);
+    assert_eq!(Platform::from_bytes(Platform::Telegram.to_bytes()), Platform::Telegram);
+    // Bytes this release doesn't know read as Unknown, which keeps its own byte
+    assert_eq!(Platform::from_bytes(vec![42].into()), Platform::Unknown);
+    assert_eq!(Platform::from_bytes(vec![].into()), Platform::Unknown);
+    assert_eq!(Platform::Unknown.to_bytes().as_ref(), &[u8::MAX]);
+    assert!("unknown".parse::<Platform>().is_err());
+
+    let token = DashboardToken {
+        token: vec![1; 32],
+        platform: Platform::WhatsApp,
+        platform_id: "254712345678".to_string(),
+        created_at: NOW,
+        expires_at: NOW + 1,
+    };
+    let decoded: DashboardToken = decode_record(&encode_record(&token)).unwrap();
+    assert_eq!((decoded.platform, decoded.platform_id.as_str()), (Platform::WhatsApp, "254712345678"));
+    println!("✅ Telegram and WhatsApp platform test passed");
+}
+
+#[test]
+fn test_identifiers_map_to_their_platform() {
+    let telegram = UserIdentifier::TelegramId("123456789".to_string());
+    let whatsapp = UserIdentifier::WhatsAppId("254712345678".to_string());
+    assert_eq!(telegram.platform_id(), Some((Platform::Telegram, "123456789")));
+    assert_eq!(whatsapp.platform_id(), Some((Platform::WhatsApp, "254712345678")));
+    let from_platform = UserIdentifier::from_platform(Platform::Telegram, "123456789".to_string());
+    assert_eq!(from_platform.as_ref().and_then(UserIdentifier::platform_id), telegram.platform_id());
+    assert!(UserIdentifier::from_platform(Platform::Unknown, "123456789".to_string()).is_none());
+    assert_eq!(
+        AccountUserIdentifier::WhatsAppId("254712345678".to_string()).platform_id(),
+        Some((Platform::WhatsApp, "254712345678"))
+    );
+
+    // Usage and analytics are keyed by user ID; a numeric Telegram ID must not share a Discord user's quota
+    assert_eq!(telegram.user_id(), "telegram:123456789");
+    assert_eq!(whatsapp.user_id(), "whatsapp:254712345678");
+    assert_eq!(UserIdentifier::DiscordId("123456789".to_string()).user_id(), "123456789");
+    println!("✅ Identifier mapping test passed");
+}
+
+#[test]
+fn test_telegram_user_links_and_unlinks() {
+    let site = principal(10);
+    let derived = derived_principal(Platform::Telegram, "123456789").unwrap();
+    assert_eq!(derived.as_slice()[0], 9);
+
+    ensure_identity(Platform::Telegram, "123456789", NOW);
+    assert_eq!(resolve_principal(Platform::Telegram, "123456789"), derived);
+
+    link_identity(Platform::Telegram, "123456789", site).unwrap();
+    assert_eq!(resolve_principal(Platform::Telegram, "123456789"), site);
+    assert_eq!(linked_external_id(site, Platform::Telegram), Some("123456789".to_string()));
+
+    unlink_identity(Platform::Telegram, "123456789").unwrap();
+    assert!(identities_for_principal(site).is_empty());
+    assert_eq!(resolve_principal(Platform::Telegram, "123456789"), derived);
+    println!("✅ Telegram linking test passed");
+}
+
+#[test]
+fn test_whatsapp_user_links_and_unlinks() {
+    let site = principal(11);
+    let derived = derived_principal(Platform::WhatsApp, "254712345678").unwrap();
+    assert_eq!(derived.as_slice()[0], 10);
+
+    ensure_identity(Platform::WhatsApp, "254712345678", NOW);
+    ensure_identity(Platform::WhatsApp, "254712345678", NOW + 1);
+    assert_eq!(get_identity(Platform::WhatsApp, "254712345678").unwrap().last_interaction, NOW + 1);
+
+    link_identity(Platform::WhatsApp, "254712345678", site).unwrap();
+    assert_eq!(resolve_principal(Platform::WhatsApp, "254712345678"), site);
+    assert_eq!(identities_for_principal(site)[0].platform, Platform::WhatsApp);
+
+    // The same number on Telegram is a different user
+    ensure_identity(Platform::Telegram, "254712345678", NOW);
+    let whatsapp_users = list_identities(Some(Platform::WhatsApp));
+    assert_eq!(whatsapp_users.iter().filter(|i| i.external_id == "254712345678").count(), 1);
+    assert!(whatsapp_users.iter().all(|i| i.platform == Platform::WhatsApp));
+
+    unlink_identity(Platform::WhatsApp, "254712345678").unwrap();
+    assert_eq!(linked_external_id(site, Platform::WhatsApp), None);
+    assert_eq!(unlink_identity(Platform::WhatsApp, "000"), Err("WhatsApp user not found".to_string()));
+    println!("✅ WhatsApp linking test passed");
+}
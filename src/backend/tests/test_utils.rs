@@ -1,4 +1,4 @@
-use candid::Principal;
+use candid::{encode_args, Principal};
 use pocket_ic::PocketIc;
 use std::fs;
 
@@ -14,7 +14,7 @@ pub fn setup() -> (PocketIc, Principal) {
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, INIT_CYCLES);
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    pic.install_canister(backend_canister, wasm, encode_args(()).unwrap(), None);
     (pic, backend_canister)
 }
 
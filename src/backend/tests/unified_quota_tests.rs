@@ -0,0 +1,130 @@
+use backend::models::merged_record::MergedRecord;
+use backend::models::record::{encode_record, MAX_RECORD_SIZE};
+use backend::models::platform::Platform;
+use backend::models::stable_string::StableString;
+use backend::models::usage_service::{UserSubscription, UserTier};
+use backend::services::identity_merge_service::{link_identity_with_records, unlink_identity_with_records};
+use backend::services::platform_identity_service::ensure_identity;
+use backend::services::pricing_services::{
+    can_make_request_at, check_user_tier_at, get_user_daily_requests_at, get_user_subscription,
+    increment_user_requests_at, quota_account,
+};
+use backend::storage::memory::USER_SUBSCRIPTIONS;
+use candid::Principal;
+
+// 22:13 UTC, so everything below happens on the same day
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+const MINUTE: u64 = 60 * 1_000_000_000;
+const DAY: u64 = 86_400 * 1_000_000_000;
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id; 29])
+}
+
+fn use_requests(user_id: &str, count: u32, now: u64) {
+    for _ in 0..count {
+        increment_user_requests_at(user_id, None, now).unwrap();
+    }
+}
+
+fn pro_subscription(user_id: &str, expires_at_ns: u64) -> UserSubscription {
+    UserSubscription {
+        user_id: user_id.to_string(),
+        tier: UserTier::Pro,
+        is_active: true,
+        started_at_ns: Some(NOW),
+        renewed_at_ns: Some(NOW),
+        expires_at_ns: Some(expires_at_ns),
+    }
+}
+
+fn subscribe(subscription: UserSubscription) {
+    USER_SUBSCRIPTIONS.with(|subs| subs.borrow_mut().insert(StableString::from(subscription.user_id.clone()), subscription));
+}
+
+#[test]
+fn test_linked_platforms_share_one_daily_counter() {
+    let site = principal(30).to_text();
+    ensure_identity(Platform::Slack, "U900", NOW);
+    ensure_identity(Platform::Discord, "900", NOW);
+    use_requests(&quota_account(Platform::Slack, "U900"), 12, NOW);
+    use_requests(&quota_account(Platform::Discord, "900"), 6, NOW);
+    use_requests(&site, 1, NOW);
+
+    // Linking halfway through the day carries the counts over
+    link_identity_with_records(Platform::Slack, "U900", principal(30)).unwrap();
+    link_identity_with_records(Platform::Discord, "900", principal(30)).unwrap();
+    assert_eq!(quota_account(Platform::Slack, "U900"), site);
+    assert_eq!(quota_account(Platform::OpenChat, "U900"), "U900", "the same ID on another platform is another user");
+    for account in [quota_account(Platform::Slack, "U900"), quota_account(Platform::Discord, "900"), site.clone()] {
+        assert_eq!(get_user_daily_requests_at(&account, NOW + MINUTE), 19);
+    }
+
+    // One request left between all three
+    use_requests(&quota_account(Platform::Discord, "900"), 1, NOW + MINUTE);
+    assert!(!can_make_request_at(&quota_account(Platform::Slack, "U900"), NOW + MINUTE));
+    assert!(increment_user_requests_at(&site, None, NOW + MINUTE).is_err());
+
+    // Unlinking gives Discord its own count back; what it used while linked stays
+    unlink_identity_with_records(Platform::Discord, "900").unwrap();
+    assert_eq!(get_user_daily_requests_at(&quota_account(Platform::Discord, "900"), NOW + 2 * MINUTE), 6);
+    assert_eq!(get_user_daily_requests_at(&quota_account(Platform::Slack, "U900"), NOW + 2 * MINUTE), 14);
+    assert!(can_make_request_at(&quota_account(Platform::Discord, "900"), NOW + 2 * MINUTE));
+
+    // Tomorrow the shared counter starts over
+    assert_eq!(get_user_daily_requests_at(&quota_account(Platform::Slack, "U900"), NOW + DAY), 0);
+    println!("✅ Shared daily counter test passed");
+}
+
+#[test]
+fn test_site_subscription_applies_to_linked_platforms() {
+    let site = principal(31);
+    ensure_identity(Platform::Slack, "U901", NOW);
+    subscribe(pro_subscription(&site.to_text(), NOW + 30 * DAY));
+    assert_eq!(check_user_tier_at(&quota_account(Platform::Slack, "U901"), NOW), UserTier::Free);
+
+    link_identity_with_records(Platform::Slack, "U901", site).unwrap();
+    assert_eq!(check_user_tier_at(&quota_account(Platform::Slack, "U901"), NOW), UserTier::Pro);
+    assert_eq!(get_user_subscription(&quota_account(Platform::Slack, "U901")).map(|s| s.user_id), Some(site.to_text()));
+
+    // Pro requests count towards the site account but are never refused
+    use_requests(&quota_account(Platform::Slack, "U901"), 25, NOW + MINUTE);
+    assert_eq!(get_user_daily_requests_at(&site.to_text(), NOW + MINUTE), 25);
+    assert!(can_make_request_at(&quota_account(Platform::Slack, "U901"), NOW + MINUTE));
+
+    unlink_identity_with_records(Platform::Slack, "U901").unwrap();
+    assert_eq!(check_user_tier_at(&quota_account(Platform::Slack, "U901"), NOW + 2 * MINUTE), UserTier::Free);
+    assert_eq!(check_user_tier_at(&site.to_text(), NOW + 2 * MINUTE), UserTier::Pro);
+    println!("✅ Site subscription test passed");
+}
+
+#[test]
+fn test_bot_subscription_moves_with_the_account() {
+    let site = principal(32);
+    ensure_identity(Platform::Telegram, "555", NOW);
+    subscribe(pro_subscription("telegram:555", NOW + 10 * DAY));
+
+    link_identity_with_records(Platform::Telegram, "555", site).unwrap();
+    assert_eq!(get_user_subscription(&site.to_text()).map(|s| s.user_id), Some(site.to_text()));
+    assert!(USER_SUBSCRIPTIONS.with(|subs| subs.borrow().get(&StableString::from("telegram:555")).is_none()));
+    assert_eq!(check_user_tier_at(&site.to_text(), NOW), UserTier::Pro);
+
+    unlink_identity_with_records(Platform::Telegram, "555").unwrap();
+    assert!(get_user_subscription(&site.to_text()).is_none());
+    assert_eq!(check_user_tier_at(&quota_account(Platform::Telegram, "telegram:555"), NOW), UserTier::Pro);
+
+    // A longer site subscription wins; the bot one waits until unlinking
+    subscribe(pro_subscription(&site.to_text(), NOW + 60 * DAY));
+    link_identity_with_records(Platform::Telegram, "555", site).unwrap();
+    assert_eq!(get_user_subscription(&quota_account(Platform::Telegram, "telegram:555")).and_then(|s| s.expires_at_ns), Some(NOW + 60 * DAY));
+    unlink_identity_with_records(Platform::Telegram, "555").unwrap();
+    assert_eq!(get_user_subscription(&quota_account(Platform::Telegram, "telegram:555")).and_then(|s| s.expires_at_ns), Some(NOW + 10 * DAY));
+
+    // The largest logged record stays within the record size limit
+    let record = MergedRecord::Subscription {
+        moved: pro_subscription(&principal(255).to_text(), NOW),
+        replaced: Some(pro_subscription(&principal(255).to_text(), NOW)),
+    };
+    assert!(encode_record(&record).len() <= MAX_RECORD_SIZE);
+    println!("✅ Bot subscription test passed");
+}
@@ -0,0 +1,204 @@
+use candid::{decode_one, encode_args, encode_one, Principal};
+use pocket_ic::PocketIc;
+use std::fs;
+
+use backend::models::chat::{ChatMessage, MessageRole};
+use backend::models::quota::QuotaPolicy;
+use backend::models::usage_service::UserTier;
+use backend::models::user::User;
+use backend::services::account_service::UserIdentifier;
+use backend::services::schema_migration_service::latest_schema_version;
+use backend::services::snapshot_service::{SnapshotCursor, SnapshotEntry, SnapshotPage};
+use backend::InitArgs;
+
+const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
+const INIT_CYCLES: u128 = 2_000_000_000_000;
+
+const ADMIN: Principal = Principal::from_slice(&[1; 29]);
+const USERS: u8 = 12;
+const MESSAGES_PER_USER: u64 = 25;
+
+fn user(id: u8) -> Principal {
+    Principal::from_slice(&[id + 10; 29])
+}
+
+fn setup() -> (PocketIc, Principal, Vec<u8>) {
+    std::env::set_var("POCKET_IC_BIN", "/usr/local/bin/pocket-ic");
+    let pic = PocketIc::new();
+    let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run 'dfx build'.");
+    let backend = install(&pic, &wasm);
+    (pic, backend, wasm)
+}
+
+fn install(pic: &PocketIc, wasm: &[u8]) -> Principal {
+    let backend = pic.create_canister();
+    pic.add_cycles(backend, INIT_CYCLES);
+    let init_args = Some(InitArgs { admins: vec![ADMIN] });
+    pic.install_canister(backend, wasm.to_vec(), encode_one(init_args).unwrap(), None);
+    backend
+}
+
+fn upgrade(pic: &PocketIc, backend: Principal, wasm: &[u8]) {
+    pic.upgrade_canister(backend, wasm.to_vec(), encode_args(()).unwrap(), None)
+        .expect("Upgrade failed");
+}
+
+fn store_messages(pic: &PocketIc, backend: Principal, owner: Principal, from: u64, to: u64) {
+    for seq in from..to {
+        let message = ChatMessage {
+            id: owner,
+            role: MessageRole::User,
+            // Large enough that all users together outgrow a single heap snapshot page many times over
+            content: format!("{}:{}", seq, "x".repeat(1200)),
+            question_asked: None,
+            timestamp: seq,
+            bot_name: None,
+        };
+        let response = pic
+            .update_call(backend, owner, "store_chat_message", encode_args((UserIdentifier::Principal(owner), message)).unwrap())
+            .expect("Failed to store chat message");
+        let result: Result<(), String> = decode_one(&response).unwrap();
+        assert!(result.is_ok(), "Storing message {} failed: {:?}", seq, result);
+    }
+}
+
+fn chat_history(pic: &PocketIc, backend: Principal, owner: Principal) -> Vec<ChatMessage> {
+    let response = pic
+        .query_call(backend, owner, "get_chat_history", encode_one(UserIdentifier::Principal(owner)).unwrap())
+        .expect("Failed to get chat history");
+    decode_one(&response).unwrap()
+}
+
+fn current_user(pic: &PocketIc, backend: Principal, caller: Principal) -> Option<User> {
+    let response = pic
+        .query_call(backend, caller, "get_current_user", encode_args(()).unwrap())
+        .expect("Failed to get current user");
+    decode_one(&response).unwrap()
+}
+
+fn export_snapshot(pic: &PocketIc, backend: Principal) -> Vec<SnapshotPage> {
+    let response = pic
+        .query_call(backend, ADMIN, "admin_list_snapshot_maps", encode_args(()).unwrap())
+        .expect("Failed to list snapshot maps");
+    let maps: Result<Vec<String>, String> = decode_one(&response).unwrap();
+
+    let mut pages = Vec::new();
+    for map in maps.unwrap() {
+        let mut cursor: Option<SnapshotCursor> = None;
+        loop {
+            let response = pic
+                .query_call(backend, ADMIN, "admin_export_snapshot", encode_args((map.clone(), cursor)).unwrap())
+                .expect("Failed to export snapshot");
+            let page: Result<SnapshotPage, String> = decode_one(&response).unwrap();
+            let page = page.unwrap();
+            cursor = page.next_cursor.clone();
+            pages.push(page);
+            if cursor.is_none() {
+                break;
+            }
+        }
+    }
+    pages
+}
+
+#[test]
+fn test_upgrades_keep_large_state() {
+    let (pic, backend, wasm) = setup();
+
+    for id in 0..USERS {
+        let response = pic
+            .update_call(backend, user(id), "register_user", encode_one(format!("User {}", id)).unwrap())
+            .expect("Failed to register user");
+        let result: Result<User, String> = decode_one(&response).unwrap();
+        assert!(result.is_ok());
+        store_messages(&pic, backend, user(id), 0, MESSAGES_PER_USER);
+    }
+
+    let policy = QuotaPolicy { daily_limit: Some(7), ..QuotaPolicy::default_for(UserTier::Free) };
+    let response = pic
+        .update_call(backend, ADMIN, "admin_set_quota_policy", encode_one(policy).unwrap())
+        .expect("Failed to set quota policy");
+    let result: Result<QuotaPolicy, String> = decode_one(&response).unwrap();
+    assert!(result.is_ok());
+
+    upgrade(&pic, backend, &wasm);
+
+    // Written between upgrades
+    store_messages(&pic, backend, user(0), MESSAGES_PER_USER, MESSAGES_PER_USER + 5);
+
+    upgrade(&pic, backend, &wasm);
+
+    for id in 0..USERS {
+        let registered = current_user(&pic, backend, user(id)).expect("User lost across upgrades");
+        assert_eq!(registered.name, format!("User {}", id));
+
+        let expected = if id == 0 { MESSAGES_PER_USER + 5 } else { MESSAGES_PER_USER };
+        let history = chat_history(&pic, backend, user(id));
+        assert_eq!(history.len() as u64, expected);
+        assert!(history.iter().all(|message| message.content.len() > 1200));
+    }
+
+    // The admin role from install and the policy set at runtime both survive
+    let response = pic
+        .query_call(backend, ADMIN, "admin_get_quota_policies", encode_args(()).unwrap())
+        .expect("Failed to get quota policies");
+    let policies: Result<Vec<QuotaPolicy>, String> = decode_one(&response).unwrap();
+    let free = policies.unwrap().into_iter().find(|policy| policy.tier == UserTier::Free);
+    assert_eq!(free.and_then(|policy| policy.daily_limit), Some(7));
+
+    let response = pic
+        .query_call(backend, ADMIN, "admin_get_schema_version", encode_args(()).unwrap())
+        .expect("Failed to get schema version");
+    let version: Result<u32, String> = decode_one(&response).unwrap();
+    assert_eq!(version, Ok(latest_schema_version()));
+    println!("✅ Upgrade test passed");
+}
+
+#[test]
+fn test_snapshot_restores_a_fresh_canister() {
+    let (pic, backend, wasm) = setup();
+
+    for id in 0..USERS {
+        let response = pic
+            .update_call(backend, user(id), "register_user", encode_one(format!("User {}", id)).unwrap())
+            .expect("Failed to register user");
+        let result: Result<User, String> = decode_one(&response).unwrap();
+        assert!(result.is_ok());
+        store_messages(&pic, backend, user(id), 0, MESSAGES_PER_USER);
+    }
+    let policy = QuotaPolicy { daily_limit: Some(7), ..QuotaPolicy::default_for(UserTier::Free) };
+    pic.update_call(backend, ADMIN, "admin_set_quota_policy", encode_one(policy).unwrap())
+        .expect("Failed to set quota policy");
+
+    let exported = export_snapshot(&pic, backend);
+
+    let restored = install(&pic, &wasm);
+    for page in &exported {
+        let response = pic
+            .update_call(restored, ADMIN, "admin_import_snapshot", encode_one(page).unwrap())
+            .expect("Failed to import snapshot");
+        let result: Result<u64, String> = decode_one(&response).unwrap();
+        assert!(result.is_ok(), "Importing a page of {} failed: {:?}", page.map, result);
+    }
+
+    // Every map of the new canister matches the original, entry for entry,
+    // and the restored audit log gains entries for importing itself and ADMINS
+    let restored_pages = export_snapshot(&pic, restored);
+    let without_audit_log = |pages: &[SnapshotPage]| -> Vec<SnapshotPage> {
+        pages.iter().filter(|page| page.map != "AUDIT_LOG").cloned().collect()
+    };
+    assert_eq!(without_audit_log(&restored_pages), without_audit_log(&exported));
+    let audit_log = |pages: &[SnapshotPage]| -> Vec<SnapshotEntry> {
+        pages.iter().filter(|page| page.map == "AUDIT_LOG").flat_map(|page| page.entries.clone()).collect()
+    };
+    let (restored_log, exported_log) = (audit_log(&restored_pages), audit_log(&exported));
+    assert_eq!(restored_log.len(), exported_log.len() + 2);
+    assert!(restored_log.starts_with(&exported_log));
+
+    for id in 0..USERS {
+        let registered = current_user(&pic, restored, user(id)).expect("User missing after restore");
+        assert_eq!(registered.name, format!("User {}", id));
+        assert_eq!(chat_history(&pic, restored, user(id)).len() as u64, MESSAGES_PER_USER);
+    }
+    println!("✅ Snapshot restore test passed");
+}
@@ -5,15 +5,37 @@ use std::time::Duration;
 
 
 use backend::models::usage_service::UsageStats; // make sure path matches your crate
+use backend::models::bot_principal::BotPermission;
+use backend::models::platform::Platform;
+use backend::{BotRegistration, InitArgs};
 
 const BACKEND_WASM: &str = "../../target/wasm32-unknown-unknown/release/backend.wasm";
 
+// The bot-facing API only serves registered bots, so calls are made as this
+// identity, registered as a playground bot by setup_backend
+const TEST_CALLER: Principal = Principal::from_slice(&[1; 29]);
+
 fn setup_backend() -> (PocketIc, Principal) {
     let pic = PocketIc::new();
     let backend_canister = pic.create_canister();
     pic.add_cycles(backend_canister, 2_000_000_000_000); // 2T cycles
     let wasm = fs::read(BACKEND_WASM).expect("Wasm file not found, run `cargo build --target wasm32-unknown-unknown`.");
-    pic.install_canister(backend_canister, wasm, vec![], None);
+    let init_args = Some(InitArgs { admins: vec![TEST_CALLER] });
+    pic.install_canister(backend_canister, wasm, encode_one(init_args).unwrap(), None);
+
+    let registration = BotRegistration {
+        principal: TEST_CALLER,
+        platform: Platform::Playground,
+        name: "Usage test bot".to_string(),
+        permissions: vec![
+            BotPermission::StoreMessages,
+            BotPermission::ReadUsage,
+            BotPermission::TrackUsage,
+            BotPermission::UpgradeTier,
+        ],
+    };
+    pic.update_call(backend_canister, TEST_CALLER, "admin_register_bot", encode_one(registration).unwrap())
+        .expect("Failed to register the test bot");
     (pic, backend_canister)
 }
 
@@ -26,7 +48,7 @@ fn test_free_tier_daily_limit() {
     for i in 0..50 {
         let result = pic.update_call(
             backend,
-            Principal::anonymous(),
+            TEST_CALLER,
             "store_api_message",
             encode_one((&user_id, format!("Request {}", i), "Response".to_string(), "GitHub Agent".to_string(), None::<String>)).unwrap(),
         );
@@ -36,7 +58,7 @@ fn test_free_tier_daily_limit() {
     // 51st request should fail
     let result = pic.update_call(
         backend,
-        Principal::anonymous(),
+        TEST_CALLER,
         "store_api_message",
         encode_one((&user_id, "Request 51".to_string(), "Response".to_string(), "GitHub Agent".to_string(), None::<String>)).unwrap(),
     );
@@ -52,7 +74,7 @@ fn test_daily_reset() {
     for i in 0..50 {
         let _ = pic.update_call(
             backend,
-            Principal::anonymous(),
+            TEST_CALLER,
             "store_api_message",
             encode_one((&user_id, format!("Req {}", i), "Resp".to_string(), "Bot".to_string(), None::<String>)).unwrap(),
         );
@@ -64,7 +86,7 @@ fn test_daily_reset() {
     // Should allow new requests
     let result = pic.update_call(
         backend,
-        Principal::anonymous(),
+        TEST_CALLER,
         "store_api_message",
         encode_one((&user_id, "After reset".to_string(), "Resp".to_string(), "Bot".to_string(), None::<String>)).unwrap(),
     );
@@ -79,7 +101,7 @@ fn test_pro_tier_unlimited() {
     // Upgrade user to Pro
     let result = pic.update_call(
         backend,
-        Principal::anonymous(),
+        TEST_CALLER,
         "api_upgrade_user_tier",
         encode_one((&user_id, backend::models::usage_service::UserTier::Pro, None::<u64>)).unwrap(),
     );
@@ -89,7 +111,7 @@ fn test_pro_tier_unlimited() {
     for i in 0..100 {
         let result = pic.update_call(
             backend,
-            Principal::anonymous(),
+            TEST_CALLER,
             "store_api_message",
             encode_one((&user_id, format!("ProReq {}", i), "Resp".to_string(), "Bot".to_string(), None::<String>)).unwrap(),
         );
@@ -106,7 +128,7 @@ fn test_usage_stats() {
     for i in 0..25 {
         let _ = pic.update_call(
             backend,
-            Principal::anonymous(),
+            TEST_CALLER,
             "store_api_message",
             encode_one((&user_id, format!("Req {}", i), "Resp".to_string(), "Bot".to_string(), None::<String>)).unwrap(),
         );
@@ -115,7 +137,7 @@ fn test_usage_stats() {
     // Query usage stats
     let result = pic.query_call(
         backend,
-        Principal::anonymous(),
+        TEST_CALLER,
         "api_get_usage_stats",
         encode_one(&user_id).unwrap(),
     ).unwrap();
@@ -0,0 +1,152 @@
+use backend::models::payment::{Currency, PaymentGateway, PaymentRecord, PaymentStatus};
+use backend::models::stable_string::StableString;
+use backend::models::webhook_event::{WebhookEventStatus, WebhookSource};
+use backend::payments::webhook_handler::{receive_webhook, reprocess_webhook_event};
+use backend::services::payment_service::get_payment;
+use backend::services::webhook_service::{begin_webhook_event, get_webhook_event, list_webhook_events, webhook_event_id};
+use backend::storage::memory::PAYMENT_RECORDS;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const NOW: u64 = 1_700_000_000 * 1_000_000_000;
+
+// Minimal executor: webhook handlers never return Pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn pending_payment(reference: &str) {
+    let payment = PaymentRecord {
+        id: reference.to_string(),
+        user_id: "user-1".to_string(),
+        paystack_reference: reference.to_string(),
+        amount: 2_900_000,
+        currency: Currency::NGN,
+        status: PaymentStatus::Pending,
+        tier: "Pro".to_string(),
+        billing_period: "monthly".to_string(),
+        ..Default::default()
+    };
+    PAYMENT_RECORDS.with(|records| {
+        records.borrow_mut().insert(StableString::from(reference.to_string()), payment);
+    });
+}
+
+/// `payment_webhook` payload: Paystack event with its data as a JSON string
+fn paystack_charge(event: &str, id: u64, reference: &str) -> String {
+    let data = format!(r#"{{"id":{},"reference":"{}","channel":"card"}}"#, id, reference);
+    serde_json::json!({ "event": event, "data": data }).to_string()
+}
+
+#[test]
+fn test_event_ids() {
+    // Paystack: event name plus object id, whatever shape the data has
+    let as_string = paystack_charge("charge.success", 42, "INF-1");
+    let as_object = r#"{"event":"charge.success","data":{"id":42,"reference":"INF-1"}}"#;
+    assert_eq!(webhook_event_id(PaymentGateway::Paystack, &as_string), "paystack:charge.success:42");
+    assert_eq!(webhook_event_id(PaymentGateway::Paystack, as_object), "paystack:charge.success:42");
+    assert_ne!(
+        webhook_event_id(PaymentGateway::Paystack, &paystack_charge("charge.failed", 42, "INF-1")),
+        "paystack:charge.success:42"
+    );
+
+    let stripe = r#"{"id":"evt_1","type":"checkout.session.completed","data":{"object":{}}}"#;
+    assert_eq!(webhook_event_id(PaymentGateway::Stripe, stripe), "stripe:evt_1");
+    let daraja = r#"{"Body":{"stkCallback":{"CheckoutRequestID":"ws_CO_1","ResultCode":0}}}"#;
+    assert_eq!(webhook_event_id(PaymentGateway::Daraja, daraja), "daraja:ws_CO_1");
+
+    // Without an id the payload hash is used
+    let hashed = webhook_event_id(PaymentGateway::Stripe, r#"{"type":"ping"}"#);
+    assert!(hashed.starts_with("stripe:sha256:"));
+    assert_eq!(hashed, webhook_event_id(PaymentGateway::Stripe, r#"{"type":"ping"}"#));
+    assert_ne!(hashed, webhook_event_id(PaymentGateway::Stripe, r#"{"type":"pong"}"#));
+    println!("✅ Webhook event id test passed");
+}
+
+#[test]
+fn test_duplicate_deliveries_are_rejected() {
+    pending_payment("INF-dup-1");
+    let payload = paystack_charge("charge.success", 1001, "INF-dup-1");
+
+    let result = block_on(receive_webhook(WebhookSource::PaystackWebhook, PaymentGateway::Paystack, &payload, NOW));
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(get_payment("INF-dup-1".to_string()).unwrap().status, PaymentStatus::Success);
+
+    let replay = block_on(receive_webhook(WebhookSource::PaystackWebhook, PaymentGateway::Paystack, &payload, NOW + 1));
+    assert!(replay.unwrap_err().contains("Duplicate webhook event"));
+
+    // The same event through the provider endpoint is a duplicate too
+    assert!(begin_webhook_event(WebhookSource::ProviderWebhook, PaymentGateway::Paystack, &payload, NOW + 2).is_err());
+
+    let event = get_webhook_event("paystack:charge.success:1001").unwrap();
+    assert_eq!(event.status, WebhookEventStatus::Processed);
+    assert_eq!(event.attempts, 1);
+    assert_eq!(event.received_at, NOW);
+    assert_eq!(event.event_type, "charge.success");
+    println!("✅ Duplicate webhook test passed");
+}
+
+#[test]
+fn test_failed_events_are_kept_and_reprocessed() {
+    // The payment record is missing, so processing fails
+    let payload = paystack_charge("charge.success", 2002, "INF-late-1");
+    let result = block_on(receive_webhook(WebhookSource::PaystackWebhook, PaymentGateway::Paystack, &payload, NOW));
+    assert!(result.is_err());
+
+    let failed = list_webhook_events(Some(WebhookEventStatus::Failed));
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].id, "paystack:charge.success:2002");
+    assert!(failed[0].error.as_deref().unwrap().contains("Payment record not found"));
+    assert_eq!(failed[0].payload.as_deref(), Some(payload.as_str()));
+
+    // An admin fixes the data and retries
+    pending_payment("INF-late-1");
+    assert!(block_on(reprocess_webhook_event("paystack:charge.success:2002", NOW + 10)).is_ok());
+
+    let event = get_webhook_event("paystack:charge.success:2002").unwrap();
+    assert_eq!(event.status, WebhookEventStatus::Processed);
+    assert_eq!(event.attempts, 2);
+    assert_eq!(event.processed_at, Some(NOW + 10));
+    assert_eq!(get_payment("INF-late-1".to_string()).unwrap().status, PaymentStatus::Success);
+    assert!(list_webhook_events(Some(WebhookEventStatus::Failed)).is_empty());
+
+    // Only failed events can be reprocessed
+    assert!(block_on(reprocess_webhook_event("paystack:charge.success:2002", NOW + 20)).is_err());
+    assert!(block_on(reprocess_webhook_event("paystack:missing", NOW + 20)).is_err());
+    println!("✅ Webhook reprocess test passed");
+}
+
+#[test]
+fn test_gateway_redelivery_retries_failed_event() {
+    let payload = r#"{"id":"evt_retry","type":"checkout.session.completed","data":{"object":{"id":"cs_1","status":"complete","payment_status":"paid","client_reference_id":"INF-stripe-1","payment_intent":"pi_1"}}}"#;
+
+    let first = block_on(receive_webhook(WebhookSource::ProviderWebhook, PaymentGateway::Stripe, payload, NOW));
+    assert!(first.is_err());
+
+    pending_payment("INF-stripe-1");
+    let second = block_on(receive_webhook(WebhookSource::ProviderWebhook, PaymentGateway::Stripe, payload, NOW + 60));
+    assert!(second.is_ok(), "{:?}", second);
+
+    let event = get_webhook_event("stripe:evt_retry").unwrap();
+    assert_eq!(event.status, WebhookEventStatus::Processed);
+    assert_eq!(event.attempts, 2);
+    assert_eq!(event.error, None);
+    assert_eq!(get_payment("INF-stripe-1".to_string()).unwrap().status, PaymentStatus::Success);
+    println!("✅ Webhook redelivery test passed");
+}
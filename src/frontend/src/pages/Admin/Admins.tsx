@@ -51,7 +51,8 @@ const AdminAdmins: React.FC = () => {
         
         setIsAddingAdmin(true);
         try {
-            const result = await actor.add_admin(newAdminPrincipal as any);
+            // No role: new admins start read-only
+            const result = await actor.add_admin(newAdminPrincipal as any, []);
             if ('Ok' in result) {
                 setNewAdminPrincipal("");
                 setShowAddAdminForm(false);
@@ -369,15 +369,18 @@ export const loginWithBotToken = async (token: string): Promise<{
             return { isValid: false, openchatId: null };
         }
 
-        if (result.platform === 'slack') {
+        // Platform is a Candid variant, e.g. { Slack: null }
+        const platform = Object.keys(result.platform)[0].toLowerCase();
+
+        if (platform === 'slack') {
             sessionStorage.setItem('slack_id', result.platform_id);
             return { isValid: true, openchatId: null, slackId: result.platform_id, platform: 'slack' };
-        } else if (result.platform === 'openchat') {
+        } else if (platform === 'openchat') {
             sessionStorage.setItem('openchat_id', result.platform_id);
             return { isValid: true, openchatId: result.platform_id, platform: 'openchat' };
         } else {
             // fallback for unknown platform
-            return { isValid: true, openchatId: result.platform_id, platform: result.platform };
+            return { isValid: true, openchatId: result.platform_id, platform };
         }
     } catch (error) {
         console.error('Token validation error:', error);
@@ -418,7 +421,7 @@ export const linkAccounts = async (openchatId: string): Promise<boolean> => {
         
         // Link the accounts - pass both the principal and openchatId
         const principal = identity.getPrincipal();
-        await actor.link_accounts(principal, openchatId);
+        await actor.link_accounts(principal, { OpenChat: null }, openchatId);
         
         // Store both the auth session and openchat_id
         sessionStorage.setItem('is_authenticated', 'true');
@@ -138,9 +138,8 @@ export const getPaymentHistory = async () => {
         }
         
         const actor = createActor(CANISTER_ID, { agent });
-        const principal = identity.getPrincipal().toString();
         
-        return await actor.payment_get_history(principal);
+        return await actor.payment_get_my_history();
     } catch (error) {
         console.error('Error fetching payment history:', error);
         return [];